@@ -0,0 +1,63 @@
+//! Times building and freeing a large tree in its boxed and arena forms.
+//!
+//! Run with `cargo bench --bench arena`. The source is the test corpus repeated many times, each
+//! copy inside a function of its own.
+
+extern crate buildlua;
+
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use buildlua::arena::Arena;
+use buildlua::parser::parse;
+
+const COPIES: usize = 500;
+const RUNS: u32 = 10;
+
+fn source() -> String {
+    let corpus = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("corpus");
+    let mut files: Vec<_> = fs::read_dir(corpus)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "lua"))
+        .collect();
+    files.sort();
+    let mut src = String::new();
+    for copy in 0..COPIES {
+        for file in &files {
+            src.push_str(&format!("local function copy{}(...)\n", copy));
+            src.push_str(&fs::read_to_string(file).unwrap());
+            src.push_str("\nend\n");
+        }
+    }
+    src
+}
+
+fn time<T>(f: impl FnOnce() -> T) -> (T, Duration) {
+    let start = Instant::now();
+    let result = f();
+    (result, start.elapsed())
+}
+
+fn main() {
+    let src = source();
+    let mut parsing = Duration::default();
+    let mut converting = Duration::default();
+    let mut freeing_boxed = Duration::default();
+    let mut freeing_arena = Duration::default();
+    for _ in 0..RUNS {
+        let (chunk, elapsed) = time(|| parse(&src).unwrap());
+        parsing += elapsed;
+        let (arena, elapsed) = time(|| Arena::from_chunk(&chunk));
+        converting += elapsed;
+        freeing_boxed += time(|| drop(chunk)).1;
+        freeing_arena += time(|| drop(arena)).1;
+    }
+    println!("source: {} bytes", src.len());
+    println!("parse to the boxed tree: {:?}", parsing / RUNS);
+    println!("parse to the arena:      {:?}", (parsing + converting) / RUNS);
+    println!("  of which converting:   {:?}", converting / RUNS);
+    println!("free the boxed tree:     {:?}", freeing_boxed / RUNS);
+    println!("free the arena:          {:?}", freeing_arena / RUNS);
+}
@@ -0,0 +1,465 @@
+-- A medium-sized module in the style of a game's inventory and crafting code, exercising most of
+-- the syntax: classes through metatables, closures, varargs, string building, numeric and generic
+-- loops, `goto`, long strings and the operators of lua 5.3.
+
+local setmetatable, getmetatable = setmetatable, getmetatable
+local pairs, ipairs, select, type = pairs, ipairs, select, type
+local tostring, tonumber, error, assert = tostring, tonumber, error, assert
+local insert, remove, concat, sort = table.insert, table.remove, table.concat, table.sort
+local floor, max, min, huge = math.floor, math.max, math.min, math.huge
+local format, rep, sub, byte = string.format, string.rep, string.sub, string.byte
+
+local M = {}
+M.VERSION = "1.4.2"
+M.MAX_STACK = 64
+M.FLAGS = { QUEST = 0x1, BOUND = 0x2, UNIQUE = 0x4, TRADEABLE = 0x8 }
+
+local HELP = [[
+Usage: inventory <command> [arguments]
+
+Commands:
+  add <item> [count]    adds items to the inventory
+  remove <item> [count] removes items from the inventory
+  craft <recipe>        crafts a recipe from the items held
+]]
+
+--[==[
+Items are tables of a name, a weight and a set of flags. Stacks hold a count of one item, up to
+`M.MAX_STACK` of it, and an inventory is a list of stacks with a limit on their total weight.
+]==]
+
+local Item = {}
+Item.__index = Item
+
+function Item.new(name, weight, flags)
+    assert(type(name) == "string", "an item needs a name")
+    local self = setmetatable({}, Item)
+    self.name = name
+    self.weight = weight or 1.0
+    self.flags = flags or 0
+    return self
+end
+
+function Item:has(flag)
+    return self.flags & flag ~= 0
+end
+
+function Item:with(flag)
+    return Item.new(self.name, self.weight, self.flags | flag)
+end
+
+function Item:without(flag)
+    return Item.new(self.name, self.weight, self.flags & ~flag)
+end
+
+function Item.__eq(a, b)
+    return a.name == b.name and a.flags == b.flags
+end
+
+function Item.__tostring(item)
+    local flags = {}
+    for name, bit in pairs(M.FLAGS) do
+        if item:has(bit) then
+            flags[#flags + 1] = name:lower()
+        end
+    end
+    sort(flags)
+    if #flags == 0 then
+        return format("%s (%.1fkg)", item.name, item.weight)
+    end
+    return format("%s (%.1fkg, %s)", item.name, item.weight, concat(flags, ", "))
+end
+
+M.Item = Item
+
+local Stack = setmetatable({}, {
+    __call = function(class, item, count)
+        return setmetatable({ item = item, count = count or 1 }, class)
+    end,
+})
+Stack.__index = Stack
+
+function Stack:weight()
+    return self.item.weight * self.count
+end
+
+function Stack:split(count)
+    count = min(count, self.count)
+    self.count = self.count - count
+    return Stack(self.item, count)
+end
+
+function Stack:merge(other)
+    if other.item ~= self.item then
+        return other
+    end
+    local room = M.MAX_STACK - self.count
+    local moved = min(room, other.count)
+    self.count = self.count + moved
+    other.count = other.count - moved
+    return other.count > 0 and other or nil
+end
+
+function Stack.__lt(a, b)
+    if a.item.name ~= b.item.name then
+        return a.item.name < b.item.name
+    end
+    return a.count > b.count
+end
+
+function Stack.__le(a, b)
+    return not (b < a)
+end
+
+function Stack.__concat(a, b)
+    return tostring(a) .. tostring(b)
+end
+
+function Stack.__tostring(stack)
+    return stack.count .. "x " .. tostring(stack.item)
+end
+
+M.Stack = Stack
+
+local Inventory = {}
+Inventory.__index = Inventory
+
+function M.inventory(limit)
+    return setmetatable({ stacks = {}, limit = limit or huge, listeners = {} }, Inventory)
+end
+
+function Inventory:on(event, listener)
+    local listeners = self.listeners[event]
+    if not listeners then
+        listeners = {}
+        self.listeners[event] = listeners
+    end
+    listeners[#listeners + 1] = listener
+    return function()
+        for i = #listeners, 1, -1 do
+            if listeners[i] == listener then
+                remove(listeners, i)
+            end
+        end
+    end
+end
+
+function Inventory:emit(event, ...)
+    local listeners = self.listeners[event]
+    if listeners == nil then return 0 end
+    local count = select("#", ...)
+    for _, listener in ipairs(listeners) do
+        listener(self, count, ...)
+    end
+    return #listeners
+end
+
+function Inventory:weight()
+    local total = 0
+    for _, stack in ipairs(self.stacks) do
+        total = total + stack:weight()
+    end
+    return total
+end
+
+function Inventory:find(name)
+    for index, stack in ipairs(self.stacks) do
+        if stack.item.name == name then
+            return stack, index
+        end
+    end
+    return nil
+end
+
+function Inventory:count(name)
+    local total = 0
+    for _, stack in ipairs(self.stacks) do
+        if stack.item.name == name then total = total + stack.count end
+    end
+    return total
+end
+
+function Inventory:add(item, count)
+    count = count or 1
+    if self:weight() + item.weight * count > self.limit then
+        return false, "too heavy"
+    end
+    local rest = Stack(item, count)
+    for _, stack in ipairs(self.stacks) do
+        rest = stack:merge(rest)
+        if not rest then goto done end
+    end
+    while rest.count > M.MAX_STACK do
+        insert(self.stacks, rest:split(M.MAX_STACK))
+    end
+    insert(self.stacks, rest)
+    ::done::
+    self:emit("add", item, count)
+    return true
+end
+
+function Inventory:remove(name, count)
+    count = count or 1
+    if self:count(name) < count then
+        return false, format("not enough %s", name)
+    end
+    local i = #self.stacks
+    repeat
+        local stack = self.stacks[i]
+        if stack.item.name == name then
+            local taken = min(stack.count, count)
+            stack.count = stack.count - taken
+            count = count - taken
+            if stack.count == 0 then remove(self.stacks, i) end
+        end
+        i = i - 1
+    until count == 0 or i < 1
+    self:emit("remove", name)
+    return true
+end
+
+function Inventory:compact()
+    sort(self.stacks)
+    local compacted = {}
+    for _, stack in ipairs(self.stacks) do
+        local last = compacted[#compacted]
+        local rest = last and last:merge(stack) or stack
+        if rest then compacted[#compacted + 1] = rest end
+    end
+    self.stacks = compacted
+end
+
+function Inventory:__len()
+    return #self.stacks
+end
+
+function Inventory:__pairs()
+    local i = 0
+    return function()
+        i = i + 1
+        local stack = self.stacks[i]
+        if stack then return i, stack end
+    end
+end
+
+M.Inventory = Inventory
+
+local recipes = {
+    plank = { wood = 1, yields = 4 },
+    stick = { plank = 2, yields = 4 },
+    ["wooden pickaxe"] = { plank = 3, stick = 2 },
+    torch = { stick = 1, coal = 1, yields = 4 },
+}
+
+local function ingredients(recipe)
+    local list = {}
+    for name, count in pairs(recipe) do
+        if name ~= "yields" then
+            list[#list + 1] = { name = name, count = count }
+        end
+    end
+    sort(list, function(a, b) return a.name < b.name end)
+    return list
+end
+
+function M.craft(inventory, name, times)
+    local recipe = recipes[name]
+    if not recipe then
+        error(format("no recipe for %q", name), 2)
+    end
+    times = times or 1
+    for _, ingredient in ipairs(ingredients(recipe)) do
+        if inventory:count(ingredient.name) < ingredient.count * times then
+            return false, "missing " .. ingredient.name
+        end
+    end
+    for _, ingredient in ipairs(ingredients(recipe)) do
+        inventory:remove(ingredient.name, ingredient.count * times)
+    end
+    return inventory:add(Item.new(name, 0.5), (recipe.yields or 1) * times)
+end
+
+local function parse_count(text)
+    local count = tonumber(text)
+    if count == nil or count < 1 or count // 1 ~= count then
+        return nil
+    end
+    return floor(count)
+end
+
+local commands = {}
+
+function commands.add(inventory, name, count)
+    return inventory:add(Item.new(name), parse_count(count or "1"))
+end
+
+function commands.remove(inventory, name, count)
+    return inventory:remove(name, parse_count(count or "1"))
+end
+
+function commands.craft(inventory, name)
+    return M.craft(inventory, name)
+end
+
+function commands.list(inventory)
+    local lines = {}
+    for i, stack in pairs(inventory) do
+        lines[i] = format("%3d. %s", i, tostring(stack))
+    end
+    return true, concat(lines, "\n")
+end
+
+local function split_words(line)
+    local words, start = {}, 1
+    for i = 1, #line + 1 do
+        local c = byte(line, i)
+        if c == nil or c == 32 or c == 9 then
+            if i > start then words[#words + 1] = sub(line, start, i - 1) end
+            start = i + 1
+        end
+    end
+    return words
+end
+
+function M.run(inventory, line)
+    local words = split_words(line)
+    local command = commands[words[1] or ""]
+    if command == nil then
+        return false, HELP
+    end
+    local ok, result = command(inventory, select(2, table.unpack(words)))
+    return ok, result
+end
+
+-- Encodes an inventory as a string of `name:count` pairs, escaping separators.
+function M.encode(inventory)
+    local parts = {}
+    for _, stack in ipairs(inventory.stacks) do
+        local name = stack.item.name:gsub("[:;\\]", function(c) return "\\" .. c end)
+        parts[#parts + 1] = name .. ":" .. stack.count
+    end
+    return concat(parts, ";")
+end
+
+function M.decode(text, limit)
+    local inventory = M.inventory(limit)
+    local name, count, escaped = {}, nil, false
+    for i = 1, #text do
+        local c = sub(text, i, i)
+        if escaped then
+            name[#name + 1] = c
+            escaped = false
+        elseif c == "\\" then
+            escaped = true
+        elseif c == ":" then
+            count = ""
+        elseif c == ";" then
+            inventory:add(Item.new(concat(name)), tonumber(count))
+            name, count = {}, nil
+        elseif count then
+            count = count .. c
+        else
+            name[#name + 1] = c
+        end
+    end
+    if #name > 0 then
+        inventory:add(Item.new(concat(name)), tonumber(count))
+    end
+    return inventory
+end
+
+-- A table of the inventory's weight in each power of two bucket, as a crude histogram.
+function M.histogram(inventory)
+    local buckets = {}
+    for _, stack in ipairs(inventory.stacks) do
+        local weight, bucket = stack:weight(), 0
+        while weight >= 2 ^ (bucket + 1) do
+            bucket = bucket + 1
+        end
+        buckets[bucket] = (buckets[bucket] or 0) + 1
+    end
+    local lines = {}
+    for bucket = 0, 16 do
+        local count = buckets[bucket]
+        if count then
+            lines[#lines + 1] = format("%6g | %s", 2 ^ bucket, rep("#", count))
+        end
+    end
+    return concat(lines, "\n")
+end
+
+function M.stats(inventory)
+    local heaviest, lightest = -huge, huge
+    local flags = 0
+    for _, stack in ipairs(inventory.stacks) do
+        heaviest = max(heaviest, stack.item.weight)
+        lightest = min(lightest, stack.item.weight)
+        flags = flags | stack.item.flags
+    end
+    return {
+        stacks = #inventory.stacks,
+        weight = inventory:weight(),
+        heaviest = heaviest,
+        lightest = lightest,
+        quest = flags & M.FLAGS.QUEST ~= 0,
+        shifted = (flags << 2) >> 1 ~ 0xff,
+    }
+end
+
+local function memoize(f)
+    local cache = setmetatable({}, { __mode = "k" })
+    return function(x, ...)
+        local cached = cache[x]
+        if cached == nil then
+            cached = f(x, ...)
+            cache[x] = cached
+        end
+        return cached
+    end
+end
+
+M.item_string = memoize(function(item) return tostring(item) end)
+
+function M.each_matching(inventory, predicate)
+    local i, stacks = 0, inventory.stacks
+    return function()
+        repeat
+            i = i + 1
+            local stack = stacks[i]
+            if stack and predicate(stack) then
+                return stack
+            end
+        until stack == nil
+    end
+end
+
+function M.transfer(from, to, name, count)
+    local ok, err = from:remove(name, count)
+    if not ok then return nil, err end
+    local stack = from:find(name) or { item = Item.new(name) }
+    ok, err = to:add(stack.item, count)
+    if not ok then
+        -- Put the items back where they came from.
+        assert(from:add(stack.item, count))
+        return nil, err
+    end
+    return true
+end
+
+do
+    local inventory <const> = M.inventory(100)
+    local self_test = {
+        function() return inventory:add(Item.new("wood", 2), 10) end,
+        function() return M.craft(inventory, "plank", 2) end,
+        function() return M.craft(inventory, "stick") end,
+        function() return inventory:count("plank") == 6 end,
+        function() return #inventory == 3 end,
+    }
+    for i, test in ipairs(self_test) do
+        if not test() then
+            error("self test " .. i .. " failed")
+        end
+    end
+end
+
+return M
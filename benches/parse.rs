@@ -0,0 +1,74 @@
+//! Times parsing and printing a medium-sized file, and parsing deeply nested expressions, as a
+//! baseline for changes to the tree and the parser.
+//!
+//! Run with `cargo bench --bench parse`. The file is `benches/fixtures/medium.lua`, a module of a
+//! few hundred lines using most of the syntax.
+
+#[macro_use]
+extern crate criterion;
+extern crate buildlua;
+
+use std::hint::black_box;
+
+use criterion::{Criterion, Throughput};
+
+use buildlua::parser::parse;
+use buildlua::print::{format, FormatOptions};
+
+const MEDIUM: &str = include_str!("fixtures/medium.lua");
+
+/// How deeply the nested expressions go. A level of parentheses around an operand takes two
+/// levels of the parser's default recursion limit of 200.
+const DEPTH: usize = 90;
+
+fn medium_file(c: &mut Criterion) {
+    let chunk = parse(MEDIUM).expect("failed to parse the fixture");
+    let options = FormatOptions { max_width: Some(80), ..FormatOptions::default() };
+    let mut group = c.benchmark_group("medium file");
+    group.throughput(Throughput::Bytes(MEDIUM.len() as u64));
+    group.bench_function("parse", |b| b.iter(|| parse(black_box(MEDIUM)).unwrap()));
+    group.bench_function("print", |b| b.iter(|| black_box(&chunk).to_string()));
+    group.bench_function("format", |b| b.iter(|| format(black_box(&chunk), &options)));
+    group.finish();
+}
+
+/// `x = 1 + (2 * (3 - ...))`, nesting `depth` operators in parentheses.
+fn parenthesized(depth: usize) -> String {
+    let operators = ["+", "*", "-", "/", "..", "//", "%", "^"];
+    let mut src = String::from("x = ");
+    for i in 0..depth {
+        src.push_str(&format!("{} {} (", i, operators[i % operators.len()]));
+    }
+    src.push('0');
+    src.push_str(&")".repeat(depth));
+    src
+}
+
+/// `x = 0 + 1 * 2 - ...`, a single long expression relying on precedence alone.
+fn flat(length: usize) -> String {
+    let operators = ["+", "*", "-", "/", "<", "and", "==", "or", "|", "&", "<<", ".."];
+    let mut src = String::from("x = 0");
+    for i in 1..length {
+        src.push_str(&format!(" {} {}", operators[i % operators.len()], i));
+    }
+    src
+}
+
+fn deep_expressions(c: &mut Criterion) {
+    let mut group = c.benchmark_group("expressions");
+    let cases = [
+        ("parenthesized", parenthesized(DEPTH)),
+        // Right associative operators nest to the right without parentheses.
+        ("exponents", format!("x = 2{}", " ^ 2".repeat(DEPTH))),
+        ("unary", format!("x = {}1", "- not #".repeat(DEPTH / 3))),
+        ("flat", flat(10 * DEPTH)),
+    ];
+    for (name, src) in &cases {
+        parse(src).unwrap_or_else(|error| panic!("failed to parse the {} case: {}", name, error));
+        group.bench_function(*name, |b| b.iter(|| parse(black_box(src)).unwrap()));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, medium_file, deep_expressions);
+criterion_main!(benches);
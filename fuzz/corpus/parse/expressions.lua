@@ -0,0 +1,2 @@
+local t = {1, 2.5, 0x1F, "two", [[long]], x = -a ^ b, ["k"] = not c; ...}
+return t.x .. t[1] // 2 | 3 & ~4 << 5 >= #t or f(t):m "s" {}
@@ -0,0 +1,7 @@
+-- A comment, and a long one.
+--[==[ spanning
+lines ]==]
+function a.b.c:d(e, ...)
+    local function f() return e end
+    return function(...) return f(), ... end
+end
@@ -0,0 +1,6 @@
+local a, b <const> = 1, 2
+for i = 1, 10, 2 do
+    if i % 3 == 0 then break elseif i > a then goto continue else b = b + i end
+    ::continue::
+end
+repeat a = a - 1 until a < 0
@@ -0,0 +1,49 @@
+//! Feeds arbitrary bytes to the parser, which has to either parse them or return an error that
+//! points into them, without panicking.
+//!
+//! Run with `cargo fuzz run parse` from the root of the repository. The inputs found so far are
+//! kept in `fuzz/corpus/parse`, which starts with a few seed files of lua source.
+
+#![no_main]
+
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate buildlua;
+
+use std::str;
+
+use buildlua::parser::{parse, parse_reader, ParseError};
+use buildlua::span::SourceError;
+
+fuzz_target!(|data: &[u8]| {
+    // Input that isn't UTF-8 can only be read, which has to fail without panicking.
+    let src = match str::from_utf8(data) {
+        Ok(src) => src,
+        Err(_) => {
+            assert!(parse_reader(data).is_err());
+            return;
+        }
+    };
+    if let Err(error) = parse(src) {
+        check_error(src, &error);
+    }
+});
+
+/// Asserts that the error could have come from parsing `src`.
+fn check_error(src: &str, error: &ParseError) {
+    if let Some(span) = error.span() {
+        assert!(span.start <= span.end, "{:?} has a backwards span", error);
+        assert!(span.end <= src.len(), "{:?} has a span past the end of the source", error);
+        assert!(src.is_char_boundary(span.start), "{:?} starts inside a character", error);
+    }
+    match *error {
+        ParseError::UnexpectedToken { ref expected, .. } |
+        ParseError::UnexpectedEof { ref expected } => {
+            assert!(!expected.is_empty(), "{:?} expects nothing", error);
+        }
+        ParseError::Lex(_) | ParseError::RecursionLimit { .. } => {}
+    }
+    // Showing the error finds its line in the source, which mustn't panic either.
+    let _ = error.to_string();
+    let _ = error.in_source(src).to_string();
+}
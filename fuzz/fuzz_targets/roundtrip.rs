@@ -0,0 +1,29 @@
+//! Parses arbitrary source and, if it parses, prints it and parses the printed source, which has
+//! to give the same tree and print the same way again.
+//!
+//! Run with `cargo fuzz run roundtrip` from the root of the repository. Most arbitrary bytes don't
+//! parse, so the seed files in `fuzz/corpus/roundtrip` are valid lua for the fuzzer to start from.
+
+#![no_main]
+
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate buildlua;
+
+use std::str;
+
+use buildlua::parser::parse;
+
+fuzz_target!(|data: &[u8]| {
+    let chunk = match str::from_utf8(data).ok().and_then(|src| parse(src).ok()) {
+        Some(chunk) => chunk,
+        None => return,
+    };
+    let printed = chunk.to_string();
+    let reparsed = parse(&printed).unwrap_or_else(|error| {
+        panic!("failed to parse the printed source: {}\n{}", error, printed)
+    });
+    // Spans are ignored when comparing trees, so only the printed layout can differ.
+    assert_eq!(chunk, reparsed, "the printed source parsed differently:\n{}", printed);
+    assert_eq!(printed, reparsed.to_string(), "printing the printed source changed it");
+});
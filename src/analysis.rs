@@ -0,0 +1,133 @@
+//! Analyses of control flow, for backends and tools that need more than the shape of the tree.
+//!
+//! A tail call is a `return` of a single function call, as described in the lua manual at
+//! http://www.lua.org/manual/5.2/manual.html#3.4.9. The called function can reuse the stack
+//! frame of the returning one, so tail calls can nest without limit. A parenthesized call isn't a
+//! tail call, as the parentheses cut its values down to one after it returns.
+//!
+//! A statement is unreachable when one before it in its block always jumps away, by `break`,
+//! `goto` or a block that always returns or jumps, such as `do return end` or an `if` whose every
+//! branch does. A label may be jumped to from elsewhere, so the statements from one on are taken
+//! to be reachable again. Loops are taken to finish, as a `break` in them continues after them.
+
+use ast::*;
+use span::{Span, Spanned};
+use visit::{self, Visitor};
+
+/// Whether the return statement is a tail call, returning exactly one function or method call.
+///
+/// # Example
+/// ```
+/// use buildlua::analysis::is_tail_call;
+/// use buildlua::parser::parse;
+///
+/// let chunk = parse("return f(x)").unwrap();
+/// assert!(is_tail_call((chunk.0).1.as_ref().unwrap()));
+/// let chunk = parse("return f(x) + 1").unwrap();
+/// assert!(!is_tail_call((chunk.0).1.as_ref().unwrap()));
+/// ```
+pub fn is_tail_call(ret: &ReturnStatement) -> bool {
+    tail_call(ret).is_some()
+}
+
+/// The call a return statement makes as a tail call, if it is one.
+fn tail_call(ret: &ReturnStatement) -> Option<&Spanned<Expression>> {
+    let list = ret.0.as_ref()?;
+    if list.1.iter().flatten().next().is_some() {
+        return None;
+    }
+    match list.0.node {
+        Expression::PrefixExpression(ref prefix) => match **prefix {
+            PrefixExpression::FunctionCall(_) => Some(&list.0),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// The spans of the calls that every tail call in the chunk makes, in source order, including
+/// those in nested functions.
+pub fn tail_calls(chunk: &Chunk) -> Vec<Span> {
+    let mut finder = TailCallFinder(Vec::new());
+    visit::walk_chunk(&mut finder, chunk);
+    finder.0
+}
+
+struct TailCallFinder(Vec<Span>);
+
+/// The spans of the statements in the chunk that can never run, as they follow a statement that
+/// always jumps away in the same block. Statements nested in unreachable ones aren't listed
+/// again, and neither are return statements, which have no spans of their own.
+///
+/// # Example
+/// ```
+/// use buildlua::analysis::find_unreachable;
+/// use buildlua::parser::parse;
+///
+/// let src = "while true do break print(1) end";
+/// let unreachable = find_unreachable(&parse(src).unwrap());
+/// assert_eq!(&src[unreachable[0].start..unreachable[0].end], "print(1)");
+/// ```
+pub fn find_unreachable(chunk: &Chunk) -> Vec<Span> {
+    let mut finder = UnreachableFinder(Vec::new());
+    visit::walk_chunk(&mut finder, chunk);
+    finder.0
+}
+
+/// Whether running the block never carries on after its end. A `goto` may land on any label in
+/// the block, so only the statements after the last one count.
+pub(crate) fn block_jumps_away(block: &Block) -> bool {
+    let statements = block.0.as_deref().unwrap_or(&[]);
+    let tail = match statements.iter().rposition(|s| matches!(s.node, Statement::Label(_))) {
+        Some(label) => &statements[label + 1..],
+        None => statements,
+    };
+    block.1.is_some() || tail.iter().any(|statement| jumps_away(&statement.node))
+}
+
+/// Whether the statement never carries on to the one after it.
+pub(crate) fn jumps_away(statement: &Statement) -> bool {
+    match *statement {
+        Statement::Break | Statement::Goto(_) => true,
+        Statement::Do(ref block) => block_jumps_away(block),
+        Statement::If { ref then, ref elseifs, else_: Some(ref else_), .. } => {
+            block_jumps_away(then) &&
+                elseifs.iter().all(|(_, block)| block_jumps_away(block)) &&
+                block_jumps_away(else_)
+        }
+        _ => false,
+    }
+}
+
+struct UnreachableFinder(Vec<Span>);
+
+impl Visitor for TailCallFinder {
+    fn visit_return_statement(&mut self, return_statement: &ReturnStatement) {
+        if let Some(call) = tail_call(return_statement) {
+            self.0.push(call.span);
+        }
+        visit::walk_return_statement(self, return_statement);
+    }
+}
+
+impl Visitor for UnreachableFinder {
+    fn visit_block(&mut self, block: &Block) {
+        let mut reachable = true;
+        for statement in block.statements() {
+            if let Statement::Label(_) = statement.node {
+                reachable = true;
+            }
+            if reachable {
+                self.visit_statement(statement);
+                reachable = !jumps_away(&statement.node);
+            } else {
+                self.0.push(statement.span);
+            }
+        }
+        if let Some(ref return_statement) = block.1 {
+            if reachable {
+                self.visit_return_statement(return_statement);
+            }
+        }
+    }
+}
@@ -0,0 +1,821 @@
+//! An arena form of the syntax tree, which is quicker to build and to free than the boxed one.
+//!
+//! The tree in `ast` gives each node an allocation of its own. Here the nodes of each kind are
+//! kept together in a vector of an `Arena` and refer to their children by `u32` indices. Lists of
+//! children are runs of one shared vector of indices, and names and strings are ranges of a
+//! buffer of text and one of bytes, so a tree takes the same few allocations however big it is.
+//!
+//! `Arena::from_chunk` converts from the boxed tree and `Arena::to_chunk` converts back. Empty
+//! lists come back as `None`, as the parser gives them.
+
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::iter;
+use core::marker::PhantomData;
+use core::ops::Index;
+
+#[cfg(not(feature = "std"))]
+use alloc::{boxed::Box, string::String, vec::Vec};
+
+use ast::{self, Attribute, BinaryOperation, Comment, StringForm, UnaryOperation};
+use span::{Span, Spanned};
+
+fn to_u32(n: usize) -> u32 {
+    assert!(n <= u32::MAX as usize, "the tree is too big for an arena");
+    n as u32
+}
+
+/// The index of a node of type `T` in its arena.
+pub struct Id<T> {
+    index: u32,
+    marker: PhantomData<T>,
+}
+
+impl<T> Id<T> {
+    fn new(index: usize) -> Id<T> {
+        Id { index: to_u32(index), marker: PhantomData }
+    }
+
+    pub fn index(self) -> usize {
+        self.index as usize
+    }
+}
+
+// These are written out rather than derived, since deriving them would require them of `T`.
+impl<T> Clone for Id<T> {
+    fn clone(&self) -> Id<T> {
+        *self
+    }
+}
+
+impl<T> Copy for Id<T> {}
+
+impl<T> PartialEq for Id<T> {
+    fn eq(&self, other: &Id<T>) -> bool {
+        self.index == other.index
+    }
+}
+
+impl<T> Eq for Id<T> {}
+
+impl<T> Hash for Id<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.index.hash(state);
+    }
+}
+
+impl<T> fmt::Debug for Id<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "#{}", self.index)
+    }
+}
+
+/// A list of nodes of type `T`, held as a run of indices in its arena.
+pub struct List<T> {
+    start: u32,
+    len: u32,
+    marker: PhantomData<T>,
+}
+
+impl<T> List<T> {
+    pub fn len(self) -> usize {
+        self.len as usize
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.len == 0
+    }
+}
+
+impl<T> Clone for List<T> {
+    fn clone(&self) -> List<T> {
+        *self
+    }
+}
+
+impl<T> Copy for List<T> {}
+
+impl<T> PartialEq for List<T> {
+    fn eq(&self, other: &List<T>) -> bool {
+        self.start == other.start && self.len == other.len
+    }
+}
+
+impl<T> Eq for List<T> {}
+
+impl<T> fmt::Debug for List<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "#[{}..{}]", self.start, self.start + self.len)
+    }
+}
+
+/// A name or the text of a number, held as a range of its arena's text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Str {
+    start: u32,
+    len: u32,
+}
+
+/// The value of a string literal, held as a range of its arena's bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Bytes {
+    start: u32,
+    len: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block {
+    pub statements: List<Statement>,
+    /// The values returned at the end of the block, if it ends with a `return`.
+    pub return_: Option<List<Expression>>,
+}
+
+/// A name declared or used by the code. Only names declared by `local` have an attribute.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Name {
+    pub text: Str,
+    pub span: Span,
+    pub attribute: Option<Attribute>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Statement {
+    Semicolon,
+    Assignment(List<Variable>, List<Expression>),
+    FunctionCall(Id<FunctionCall>),
+    Label(Str),
+    Break,
+    Goto(Str),
+    Do(Id<Block>),
+    While { exp: Id<Expression>, do_: Id<Block> },
+    Repeat { block: Id<Block>, until: Id<Expression> },
+    /// The first condition and block are the `if`, and the rest are its `elseif`s.
+    If { conditions: List<Expression>, blocks: List<Block>, else_: Option<Id<Block>> },
+    ForStepping {
+        name: Id<Name>,
+        from: Id<Expression>,
+        to: Id<Expression>,
+        step: Option<Id<Expression>>,
+        block: Id<Block>,
+    },
+    ForIn { name_list: List<Name>, in_: List<Expression>, do_: Id<Block> },
+    /// A function statement. The path holds the dotted names leading to the function, and only
+    /// its first name has a span.
+    Function { path: List<Name>, self_name: Option<Str>, body: Id<FunctionBody> },
+    LocalFunction { name: Id<Name>, body: Id<FunctionBody> },
+    LocalVariableBinding(List<Name>, List<Expression>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Variable {
+    Name(Id<Name>),
+    ArrayAccess { from: Id<PrefixExpression>, key: Id<Expression> },
+    DotAccess { from: Id<PrefixExpression>, key: Str },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expression {
+    Nil,
+    False,
+    True,
+    /// An integer literal, with its text if it isn't written plainly, as in `ast::NumberForm`.
+    Integer(i64, Option<Str>),
+    /// A float literal, with its text if it isn't written plainly.
+    Float(f64, Option<Str>),
+    String(Bytes, StringForm),
+    ExtendedArgumentAccess,
+    FunctionDefine(Id<FunctionBody>),
+    PrefixExpression(Id<PrefixExpression>),
+    TableConstructor(List<Field>),
+    BinaryOperation(BinaryOperation, Id<Expression>, Id<Expression>),
+    UnaryOperation(UnaryOperation, Id<Expression>),
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PrefixExpression {
+    Variable(Id<Variable>),
+    FunctionCall(Id<FunctionCall>),
+    Parenthesis(Id<Expression>),
+}
+
+/// A function call, which passes the called value as `self` when it has a method name.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionCall {
+    pub from: Id<PrefixExpression>,
+    pub method: Option<Str>,
+    pub arguments: FunctionArguments,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FunctionArguments {
+    Parenthesis(List<Expression>),
+    TableConstructor(List<Field>),
+    String(Bytes, StringForm),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionBody {
+    pub parameters: List<Name>,
+    /// Whether the parameters end with `...`.
+    pub varargs: bool,
+    pub block: Id<Block>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Field {
+    ExpressionForName { name: Id<Expression>, equals: Id<Expression> },
+    Equals { name: Str, equals: Id<Expression> },
+    ArrayStyle(Id<Expression>),
+}
+
+/// A whole chunk, with every node of its tree.
+#[derive(Debug, Default)]
+pub struct Arena {
+    root: Option<Id<Block>>,
+    comments: Vec<Comment>,
+    blocks: Vec<Block>,
+    statements: Vec<Spanned<Statement>>,
+    expressions: Vec<Spanned<Expression>>,
+    prefixes: Vec<PrefixExpression>,
+    variables: Vec<Variable>,
+    calls: Vec<FunctionCall>,
+    bodies: Vec<FunctionBody>,
+    fields: Vec<Field>,
+    names: Vec<Name>,
+    /// The indices making up every list, each list being one run of them.
+    children: Vec<u32>,
+    text: String,
+    bytes: Vec<u8>,
+}
+
+macro_rules! index {
+    ($($node:ty => $output:ty, $pool:ident;)*) => {$(
+        impl Index<Id<$node>> for Arena {
+            type Output = $output;
+
+            fn index(&self, id: Id<$node>) -> &$output {
+                &self.$pool[id.index()]
+            }
+        }
+    )*};
+}
+
+index! {
+    Block => Block, blocks;
+    Statement => Spanned<Statement>, statements;
+    Expression => Spanned<Expression>, expressions;
+    PrefixExpression => PrefixExpression, prefixes;
+    Variable => Variable, variables;
+    FunctionCall => FunctionCall, calls;
+    FunctionBody => FunctionBody, bodies;
+    Field => Field, fields;
+    Name => Name, names;
+}
+
+impl Arena {
+    /// Copies a chunk into a new arena.
+    pub fn from_chunk(chunk: &ast::Chunk) -> Arena {
+        let mut builder = Builder { arena: Arena::default(), scratch: Vec::new() };
+        let root = builder.block(&chunk.0);
+        builder.arena.root = Some(root);
+        builder.arena.comments = chunk.1.clone();
+        builder.arena.children.shrink_to_fit();
+        builder.arena
+    }
+
+    /// Copies the arena back into a boxed chunk.
+    pub fn to_chunk(&self) -> ast::Chunk {
+        ast::Chunk(self.block_to(self.root()), self.comments.clone())
+    }
+
+    /// The block of the whole chunk.
+    pub fn root(&self) -> Id<Block> {
+        self.root.expect("an arena is always made with a root")
+    }
+
+    pub fn comments(&self) -> &[Comment] {
+        &self.comments
+    }
+
+    pub fn text(&self, s: Str) -> &str {
+        &self.text[s.start as usize..(s.start + s.len) as usize]
+    }
+
+    pub fn bytes(&self, s: Bytes) -> &[u8] {
+        &self.bytes[s.start as usize..(s.start + s.len) as usize]
+    }
+
+    /// The nodes of a list, in order.
+    pub fn items<'a, T: 'a>(&'a self, list: List<T>) -> impl Iterator<Item = Id<T>> + 'a {
+        let start = list.start as usize;
+        self.children[start..start + list.len()].iter().map(|&index| Id::new(index as usize))
+    }
+
+    fn string(&self, s: Str) -> String {
+        self.text(s).into()
+    }
+
+    fn number_form(&self, text: Option<Str>) -> ast::NumberForm {
+        match text {
+            Some(text) => ast::NumberForm::Written(self.string(text)),
+            None => ast::NumberForm::Plain,
+        }
+    }
+
+    fn name_to(&self, id: Id<Name>) -> Spanned<String> {
+        let name = self[id];
+        Spanned::new(self.string(name.text), name.span)
+    }
+
+    fn names_to(&self, list: List<Name>) -> Option<ast::NameList> {
+        let (first, rest) = split(self.items(list).map(|id| self.name_to(id)))?;
+        Some(ast::NameList(first, rest))
+    }
+
+    fn block_to(&self, id: Id<Block>) -> ast::Block {
+        let block = &self[id];
+        let statements: Vec<_> =
+            self.items(block.statements).map(|id| self.statement_to(id)).collect();
+        let statements = if statements.is_empty() { None } else { Some(statements) };
+        let return_statement = block.return_.map(|values| {
+            Box::new(ast::ReturnStatement(self.expressions_to(values).map(Box::new)))
+        });
+        ast::Block(statements, return_statement)
+    }
+
+    fn boxed_block_to(&self, id: Id<Block>) -> Box<ast::Block> {
+        Box::new(self.block_to(id))
+    }
+
+    fn statement_to(&self, id: Id<Statement>) -> Spanned<ast::Statement> {
+        let statement = &self[id];
+        let node = match statement.node {
+            Statement::Semicolon => ast::Statement::Semicolon,
+            Statement::Assignment(variables, values) => {
+                let (first, rest) =
+                    split(self.items(variables).map(|id| self.variable_to(id)))
+                        .expect("an assignment has at least one variable");
+                let variables = ast::VariableList { first: Box::new(first), rest };
+                ast::Statement::Assignment(Box::new(variables), self.expression_list_to(values))
+            }
+            Statement::FunctionCall(call) => {
+                ast::Statement::FunctionCall(Box::new(self.call_to(call)))
+            }
+            Statement::Label(label) => ast::Statement::Label(ast::Label(self.string(label))),
+            Statement::Break => ast::Statement::Break,
+            Statement::Goto(label) => ast::Statement::Goto(ast::Label(self.string(label))),
+            Statement::Do(block) => ast::Statement::Do(self.boxed_block_to(block)),
+            Statement::While { exp, do_ } => ast::Statement::While {
+                exp: self.expression_to(exp),
+                do_: self.boxed_block_to(do_),
+            },
+            Statement::Repeat { block, until } => ast::Statement::Repeat {
+                block: self.boxed_block_to(block),
+                until: self.expression_to(until),
+            },
+            Statement::If { conditions, blocks, else_ } => {
+                let mut branches = self
+                    .items(conditions)
+                    .zip(self.items(blocks))
+                    .map(|(condition, block)| {
+                        (self.expression_to(condition), self.boxed_block_to(block))
+                    });
+                let (condition, then) = branches.next().expect("an if has at least one branch");
+                ast::Statement::If {
+                    condition,
+                    then,
+                    elseifs: branches.collect(),
+                    else_: else_.map(|block| self.boxed_block_to(block)),
+                }
+            }
+            Statement::ForStepping { name, from, to, step, block } => {
+                ast::Statement::ForStepping {
+                    name: self.name_to(name),
+                    from: self.expression_to(from),
+                    to: self.expression_to(to),
+                    step: step.map(|step| self.expression_to(step)),
+                    block: self.boxed_block_to(block),
+                }
+            }
+            Statement::ForIn { name_list, in_, do_ } => ast::Statement::ForIn {
+                name_list: Box::new(
+                    self.names_to(name_list).expect("a for loop has at least one name"),
+                ),
+                in_: self.expression_list_to(in_),
+                do_: self.boxed_block_to(do_),
+            },
+            Statement::Function { path, self_name, body } => {
+                let mut path = self.items(path);
+                let first = path.next().expect("a function name has at least one part");
+                let rest: Vec<_> = path.map(|id| self.string(self[id].text)).collect();
+                let name = ast::FunctionName {
+                    first_dot_access: self.name_to(first),
+                    rest_dot_access: if rest.is_empty() { None } else { Some(rest) },
+                    self_name: self_name.map(|name| self.string(name)),
+                };
+                ast::Statement::Function(name, Box::new(self.body_to(body)))
+            }
+            Statement::LocalFunction { name, body } => ast::Statement::LocalFunction {
+                name: self.name_to(name),
+                body: Box::new(self.body_to(body)),
+            },
+            Statement::LocalVariableBinding(names, values) => {
+                let names = self.items(names).map(|id| ast::AttributeName {
+                    name: self.name_to(id),
+                    attribute: self[id].attribute,
+                });
+                let (first, rest) = split(names).expect("a local has at least one name");
+                ast::Statement::LocalVariableBinding(
+                    ast::AttributeNameList(first, rest),
+                    self.expressions_to(values),
+                )
+            }
+        };
+        Spanned::new(node, statement.span)
+    }
+
+    fn variable_to(&self, id: Id<Variable>) -> ast::Variable {
+        match self[id] {
+            Variable::Name(name) => ast::Variable::Name(self.name_to(name)),
+            Variable::ArrayAccess { from, key } => ast::Variable::ArrayAccess {
+                from: self.prefix_to(from),
+                key: self.expression_to(key),
+            },
+            Variable::DotAccess { from, key } => {
+                ast::Variable::DotAccess { from: self.prefix_to(from), key: self.string(key) }
+            }
+        }
+    }
+
+    fn expressions_to(&self, list: List<Expression>) -> Option<ast::ExpressionList> {
+        let (first, rest) = split(self.items(list).map(|id| *self.expression_to(id)))?;
+        Some(ast::ExpressionList(Box::new(first), rest))
+    }
+
+    fn expression_list_to(&self, list: List<Expression>) -> Box<ast::ExpressionList> {
+        Box::new(self.expressions_to(list).expect("the list has at least one expression"))
+    }
+
+    fn expression_to(&self, id: Id<Expression>) -> Box<Spanned<ast::Expression>> {
+        let expression = &self[id];
+        let node = match expression.node {
+            Expression::Nil => ast::Expression::Nil,
+            Expression::False => ast::Expression::False,
+            Expression::True => ast::Expression::True,
+            Expression::Integer(n, text) => ast::Expression::Integer(n, self.number_form(text)),
+            Expression::Float(n, text) => ast::Expression::Float(n, self.number_form(text)),
+            Expression::String(s, form) => ast::Expression::String(self.bytes(s).to_vec(), form),
+            Expression::ExtendedArgumentAccess => ast::Expression::ExtendedArgumentAccess,
+            Expression::Error => ast::Expression::Error,
+            Expression::FunctionDefine(body) => ast::Expression::FunctionDefine(Box::new(
+                ast::FunctionDefine(Box::new(self.body_to(body))),
+            )),
+            Expression::PrefixExpression(prefix) => {
+                ast::Expression::PrefixExpression(self.prefix_to(prefix))
+            }
+            Expression::TableConstructor(fields) => {
+                ast::Expression::TableConstructor(Box::new(self.table_to(fields)))
+            }
+            Expression::BinaryOperation(op, lhs, rhs) => ast::Expression::BinaryOperation(
+                op,
+                self.expression_to(lhs),
+                self.expression_to(rhs),
+            ),
+            Expression::UnaryOperation(op, operand) => {
+                ast::Expression::UnaryOperation(op, self.expression_to(operand))
+            }
+        };
+        Box::new(Spanned::new(node, expression.span))
+    }
+
+    fn prefix_to(&self, id: Id<PrefixExpression>) -> Box<ast::PrefixExpression> {
+        Box::new(match self[id] {
+            PrefixExpression::Variable(variable) => {
+                ast::PrefixExpression::Variable(Box::new(self.variable_to(variable)))
+            }
+            PrefixExpression::FunctionCall(call) => {
+                ast::PrefixExpression::FunctionCall(Box::new(self.call_to(call)))
+            }
+            PrefixExpression::Parenthesis(inner) => {
+                ast::PrefixExpression::Parenthesis(self.expression_to(inner))
+            }
+        })
+    }
+
+    fn call_to(&self, id: Id<FunctionCall>) -> ast::FunctionCall {
+        let call = &self[id];
+        let arguments = match call.arguments {
+            FunctionArguments::Parenthesis(values) => {
+                ast::FunctionArguments::Parenthesis(self.expressions_to(values).map(Box::new))
+            }
+            FunctionArguments::TableConstructor(fields) => {
+                ast::FunctionArguments::TableConstructor(Box::new(self.table_to(fields)))
+            }
+            FunctionArguments::String(s, form) => {
+                ast::FunctionArguments::String(self.bytes(s).to_vec(), form)
+            }
+        };
+        let from = self.prefix_to(call.from);
+        match call.method {
+            Some(method) => {
+                ast::FunctionCall::SelfTaking(from, self.string(method), Box::new(arguments))
+            }
+            None => ast::FunctionCall::Static(from, Box::new(arguments)),
+        }
+    }
+
+    fn body_to(&self, id: Id<FunctionBody>) -> ast::FunctionBody {
+        let body = &self[id];
+        let parameters = match (self.names_to(body.parameters), body.varargs) {
+            (Some(names), false) => Some(ast::ParameterList::NameList(Box::new(names))),
+            (Some(names), true) => Some(ast::ParameterList::ExtendedArguments(Box::new(names))),
+            (None, true) => Some(ast::ParameterList::ExtendedArgumentsVoid),
+            (None, false) => None,
+        };
+        ast::FunctionBody(parameters.map(Box::new), self.boxed_block_to(body.block))
+    }
+
+    fn table_to(&self, fields: List<Field>) -> ast::TableConstructor {
+        let fields = self.items(fields).map(|id| match self[id] {
+            Field::ExpressionForName { name, equals } => ast::Field::ExpressionForName {
+                name: self.expression_to(name),
+                equals: self.expression_to(equals),
+            },
+            Field::Equals { name, equals } => {
+                ast::Field::Equals { name: self.string(name), equals: self.expression_to(equals) }
+            }
+            Field::ArrayStyle(value) => ast::Field::ArrayStyle(self.expression_to(value)),
+        });
+        let fields = split(fields).map(|(first, rest)| ast::FieldList(Box::new(first), rest));
+        ast::TableConstructor(fields.map(Box::new))
+    }
+}
+
+/// Splits the items into the first and the rest, as the boxed tree holds its lists.
+fn split<T>(mut items: impl Iterator<Item = T>) -> Option<(T, Option<Vec<T>>)> {
+    let first = items.next()?;
+    let rest: Vec<T> = items.collect();
+    Some((first, if rest.is_empty() { None } else { Some(rest) }))
+}
+
+/// The items of a boxed list, which holds its first item apart from the rest.
+fn items<'a, T>(first: &'a T, rest: &'a Option<Vec<T>>) -> impl Iterator<Item = &'a T> {
+    iter::once(first).chain(rest.iter().flatten())
+}
+
+/// Copies a boxed tree into an arena.
+struct Builder {
+    arena: Arena,
+    /// The indices of the lists being built, innermost last. A list's indices are moved to the
+    /// arena together once all of them are known, so that they end up next to each other.
+    scratch: Vec<u32>,
+}
+
+impl Builder {
+    fn list<T, U>(
+        &mut self,
+        items: impl IntoIterator<Item = U>,
+        mut push: impl FnMut(&mut Builder, U) -> Id<T>,
+    ) -> List<T> {
+        let mark = self.scratch.len();
+        for item in items {
+            let id = push(self, item);
+            self.scratch.push(id.index);
+        }
+        let start = to_u32(self.arena.children.len());
+        self.arena.children.extend(self.scratch.drain(mark..));
+        let len = to_u32(self.arena.children.len()) - start;
+        List { start, len, marker: PhantomData }
+    }
+
+    fn number_text(&mut self, form: &ast::NumberForm) -> Option<Str> {
+        match *form {
+            ast::NumberForm::Written(ref text) => Some(self.str(text)),
+            ast::NumberForm::Plain => None,
+        }
+    }
+
+    fn str(&mut self, s: &str) -> Str {
+        let start = to_u32(self.arena.text.len());
+        self.arena.text.push_str(s);
+        Str { start, len: to_u32(s.len()) }
+    }
+
+    fn bytes(&mut self, s: &[u8]) -> Bytes {
+        let start = to_u32(self.arena.bytes.len());
+        self.arena.bytes.extend_from_slice(s);
+        Bytes { start, len: to_u32(s.len()) }
+    }
+
+    fn name(&mut self, text: &str, span: Span, attribute: Option<Attribute>) -> Id<Name> {
+        let text = self.str(text);
+        self.arena.names.push(Name { text, span, attribute });
+        Id::new(self.arena.names.len() - 1)
+    }
+
+    fn names(&mut self, names: Option<&ast::NameList>) -> List<Name> {
+        let names = names.into_iter().flat_map(|names| items(&names.0, &names.1));
+        self.list(names, |builder, name| builder.name(&name.node, name.span, None))
+    }
+
+    fn block(&mut self, block: &ast::Block) -> Id<Block> {
+        let statements = self.list(block.statements(), Builder::statement);
+        let return_ = block.1.as_ref().map(|ret| self.expressions(ret.0.as_deref()));
+        self.arena.blocks.push(Block { statements, return_ });
+        Id::new(self.arena.blocks.len() - 1)
+    }
+
+    fn statement(&mut self, statement: &Spanned<ast::Statement>) -> Id<Statement> {
+        let node = match statement.node {
+            ast::Statement::Semicolon => Statement::Semicolon,
+            ast::Statement::Assignment(ref variables, ref values) => {
+                let variables =
+                    self.list(items(&*variables.first, &variables.rest), Builder::variable);
+                Statement::Assignment(variables, self.expressions(Some(values)))
+            }
+            ast::Statement::FunctionCall(ref call) => Statement::FunctionCall(self.call(call)),
+            ast::Statement::Label(ref label) => Statement::Label(self.str(&label.0)),
+            ast::Statement::Break => Statement::Break,
+            ast::Statement::Goto(ref label) => Statement::Goto(self.str(&label.0)),
+            ast::Statement::Do(ref block) => Statement::Do(self.block(block)),
+            ast::Statement::While { ref exp, ref do_ } => {
+                Statement::While { exp: self.expression(exp), do_: self.block(do_) }
+            }
+            ast::Statement::Repeat { ref block, ref until } => {
+                Statement::Repeat { block: self.block(block), until: self.expression(until) }
+            }
+            ast::Statement::If { ref condition, ref then, ref elseifs, ref else_ } => {
+                let branches = || {
+                    let elseifs = elseifs.iter().map(|(condition, block)| (condition, block));
+                    iter::once((condition, then)).chain(elseifs)
+                };
+                let conditions = self.list(branches(), |builder, (condition, _)| {
+                    builder.expression(condition)
+                });
+                let blocks = self.list(branches(), |builder, (_, block)| builder.block(block));
+                let else_ = else_.as_ref().map(|block| self.block(block));
+                Statement::If { conditions, blocks, else_ }
+            }
+            ast::Statement::ForStepping { ref name, ref from, ref to, ref step, ref block } => {
+                Statement::ForStepping {
+                    name: self.name(&name.node, name.span, None),
+                    from: self.expression(from),
+                    to: self.expression(to),
+                    step: step.as_ref().map(|step| self.expression(step)),
+                    block: self.block(block),
+                }
+            }
+            ast::Statement::ForIn { ref name_list, ref in_, ref do_ } => Statement::ForIn {
+                name_list: self.names(Some(name_list)),
+                in_: self.expressions(Some(in_)),
+                do_: self.block(do_),
+            },
+            ast::Statement::Function(ref name, ref body) => {
+                // Only the first name is spanned, so the rest are given dummy spans.
+                let first = &name.first_dot_access;
+                let rest = name.rest_dot_access.iter().flatten();
+                let parts = iter::once((&*first.node, first.span))
+                    .chain(rest.map(|part| (&**part, Span::dummy())));
+                let path = self.list(parts, |builder, (text, span)| builder.name(text, span, None));
+                let self_name = name.self_name.as_ref().map(|name| self.str(name));
+                Statement::Function { path, self_name, body: self.body(body) }
+            }
+            ast::Statement::LocalFunction { ref name, ref body } => {
+                let name = self.name(&name.node, name.span, None);
+                Statement::LocalFunction { name, body: self.body(body) }
+            }
+            ast::Statement::LocalVariableBinding(ref names, ref values) => {
+                let names = self.list(items(&names.0, &names.1), |b, name| {
+                    b.name(&name.name.node, name.name.span, name.attribute)
+                });
+                Statement::LocalVariableBinding(names, self.expressions(values.as_ref()))
+            }
+        };
+        self.arena.statements.push(Spanned::new(node, statement.span));
+        Id::new(self.arena.statements.len() - 1)
+    }
+
+    fn variable(&mut self, variable: &ast::Variable) -> Id<Variable> {
+        let variable = match *variable {
+            ast::Variable::Name(ref name) => {
+                Variable::Name(self.name(&name.node, name.span, None))
+            }
+            ast::Variable::ArrayAccess { ref from, ref key } => {
+                Variable::ArrayAccess { from: self.prefix(from), key: self.expression(key) }
+            }
+            ast::Variable::DotAccess { ref from, ref key } => {
+                Variable::DotAccess { from: self.prefix(from), key: self.str(key) }
+            }
+        };
+        self.arena.variables.push(variable);
+        Id::new(self.arena.variables.len() - 1)
+    }
+
+    fn expressions(&mut self, list: Option<&ast::ExpressionList>) -> List<Expression> {
+        let list = list.into_iter().flat_map(|list| items(&*list.0, &list.1));
+        self.list(list, Builder::expression)
+    }
+
+    fn expression(&mut self, expression: &Spanned<ast::Expression>) -> Id<Expression> {
+        let node = match expression.node {
+            ast::Expression::Nil => Expression::Nil,
+            ast::Expression::False => Expression::False,
+            ast::Expression::True => Expression::True,
+            ast::Expression::Integer(n, ref form) => Expression::Integer(n, self.number_text(form)),
+            ast::Expression::Float(n, ref form) => Expression::Float(n, self.number_text(form)),
+            ast::Expression::String(ref s, form) => Expression::String(self.bytes(s), form),
+            ast::Expression::ExtendedArgumentAccess => Expression::ExtendedArgumentAccess,
+            ast::Expression::Error => Expression::Error,
+            ast::Expression::FunctionDefine(ref define) => {
+                Expression::FunctionDefine(self.body(&define.0))
+            }
+            ast::Expression::PrefixExpression(ref prefix) => {
+                Expression::PrefixExpression(self.prefix(prefix))
+            }
+            ast::Expression::TableConstructor(ref table) => {
+                Expression::TableConstructor(self.table(table))
+            }
+            ast::Expression::BinaryOperation(op, ref lhs, ref rhs) => {
+                Expression::BinaryOperation(op, self.expression(lhs), self.expression(rhs))
+            }
+            ast::Expression::UnaryOperation(op, ref operand) => {
+                Expression::UnaryOperation(op, self.expression(operand))
+            }
+        };
+        self.arena.expressions.push(Spanned::new(node, expression.span));
+        Id::new(self.arena.expressions.len() - 1)
+    }
+
+    fn prefix(&mut self, prefix: &ast::PrefixExpression) -> Id<PrefixExpression> {
+        let prefix = match *prefix {
+            ast::PrefixExpression::Variable(ref variable) => {
+                PrefixExpression::Variable(self.variable(variable))
+            }
+            ast::PrefixExpression::FunctionCall(ref call) => {
+                PrefixExpression::FunctionCall(self.call(call))
+            }
+            ast::PrefixExpression::Parenthesis(ref inner) => {
+                PrefixExpression::Parenthesis(self.expression(inner))
+            }
+        };
+        self.arena.prefixes.push(prefix);
+        Id::new(self.arena.prefixes.len() - 1)
+    }
+
+    fn call(&mut self, call: &ast::FunctionCall) -> Id<FunctionCall> {
+        let (from, method, arguments) = match *call {
+            ast::FunctionCall::Static(ref from, ref arguments) => (from, None, &**arguments),
+            ast::FunctionCall::SelfTaking(ref from, ref method, ref arguments) => {
+                (from, Some(method), &**arguments)
+            }
+        };
+        let from = self.prefix(from);
+        let method = method.map(|method| self.str(method));
+        let arguments = match *arguments {
+            ast::FunctionArguments::Parenthesis(ref values) => {
+                FunctionArguments::Parenthesis(self.expressions(values.as_deref()))
+            }
+            ast::FunctionArguments::TableConstructor(ref table) => {
+                FunctionArguments::TableConstructor(self.table(table))
+            }
+            ast::FunctionArguments::String(ref s, form) => {
+                FunctionArguments::String(self.bytes(s), form)
+            }
+        };
+        self.arena.calls.push(FunctionCall { from, method, arguments });
+        Id::new(self.arena.calls.len() - 1)
+    }
+
+    fn body(&mut self, body: &ast::FunctionBody) -> Id<FunctionBody> {
+        let (names, varargs) = match body.0.as_deref() {
+            Some(ast::ParameterList::NameList(names)) => (Some(&**names), false),
+            Some(ast::ParameterList::ExtendedArguments(names)) => (Some(&**names), true),
+            Some(ast::ParameterList::ExtendedArgumentsVoid) => (None, true),
+            None => (None, false),
+        };
+        let parameters = self.names(names);
+        let block = self.block(&body.1);
+        self.arena.bodies.push(FunctionBody { parameters, varargs, block });
+        Id::new(self.arena.bodies.len() - 1)
+    }
+
+    fn table(&mut self, table: &ast::TableConstructor) -> List<Field> {
+        let push = |builder: &mut Builder, field: &ast::Field| {
+            let field = match *field {
+                ast::Field::ExpressionForName { ref name, ref equals } => {
+                    let name = builder.expression(name);
+                    Field::ExpressionForName { name, equals: builder.expression(equals) }
+                }
+                ast::Field::Equals { ref name, ref equals } => {
+                    Field::Equals { name: builder.str(name), equals: builder.expression(equals) }
+                }
+                ast::Field::ArrayStyle(ref value) => Field::ArrayStyle(builder.expression(value)),
+            };
+            builder.arena.fields.push(field);
+            Id::new(builder.arena.fields.len() - 1)
+        };
+        let fields = table.0.iter().flat_map(|fields| items(&*fields.0, &fields.1));
+        self.list(fields, push)
+    }
+}
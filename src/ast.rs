@@ -3,80 +3,344 @@
 //! Most of this file is based on the syntax of lua page in the lua manual.
 //!
 //! The page can be found at http://www.lua.org/manual/5.2/manual.html#9.
+//!
+//! Statements, expressions and names are wrapped in `Spanned` to record which part of the source
+//! they were parsed from. Nodes built by hand can use `Spanned::dummy`.
+//!
+//! Comments aren't part of the tree itself. They're kept in a list on the `Chunk`, and their
+//! spans tell which statements they sit next to.
+//!
+//! `Expression` and `Block` implement `Drop`, so that dropping a deeply nested tree doesn't
+//! overflow the stack. Their fields can't be moved out of, and have to be swapped out with
+//! `mem::replace` instead.
+//!
+//! The nodes are generic over a `Storage`, giving the types of the names, string literals and
+//! other text in them. The default is `Owned`, and the rest of the crate works on trees of it.
+//! `borrowed::Borrowed` is another, whose text borrows from the source the tree was parsed from.
+
+#[cfg(feature = "serde")]
+use serde::de::DeserializeOwned;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+#[cfg(not(feature = "std"))]
+use alloc::{boxed::Box, string::String, vec::Vec};
+
+use core::{fmt, iter, mem};
+
+use span::{Span, Spanned};
+
+/// How a tree holds its text.
+pub trait Storage {
+    /// An identifier, such as the name of a variable, field, method or label.
+    type Name: Clone + PartialEq + fmt::Debug + Serializable;
+    /// The bytes of a string literal.
+    type Str: Clone + PartialEq + fmt::Debug + Serializable;
+    /// The text of a comment, or of a number literal not written plainly.
+    type Text: Clone + PartialEq + fmt::Debug + Serializable;
+}
+
+/// What the text of a tree needs for the tree to be serialized, which is nothing without the
+/// `serde` feature.
+#[cfg(feature = "serde")]
+pub trait Serializable: Serialize + DeserializeOwned {}
+
+#[cfg(feature = "serde")]
+impl<T: Serialize + DeserializeOwned> Serializable for T {}
+
+/// What the text of a tree needs for the tree to be serialized, which is nothing without the
+/// `serde` feature.
+#[cfg(not(feature = "serde"))]
+pub trait Serializable {}
+
+#[cfg(not(feature = "serde"))]
+impl<T> Serializable for T {}
+
+/// Text owned by the tree, as it is when parsed with `parser::parse` or built by hand.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Owned;
+
+impl Storage for Owned {
+    type Name = String;
+    type Str = Vec<u8>;
+    type Text = String;
+}
+
+/// The largest unit of code in lua. Equates to an entire file of code, along with the comments
+/// in it in source order.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Chunk<S: Storage = Owned>(pub Block<S>, pub Vec<Comment<S>>);
+
+impl Chunk {
+    /// A chunk of the given block, without comments.
+    pub fn new(block: Block) -> Chunk {
+        Chunk(block, Vec::new())
+    }
+}
+
+impl<S: Storage> Chunk<S> {
+    /// The comments starting inside `span`. With the span between two statements, these are the
+    /// comments trailing the first statement and leading into the second.
+    pub fn comments_in(&self, span: Span) -> &[Comment<S>] {
+        let start = self.1.partition_point(|comment| comment.span.start < span.start);
+        let end = self.1.partition_point(|comment| comment.span.start < span.end);
+        &self.1[start..end.max(start)]
+    }
+}
+
+/// A comment, holding its text without the `--` and brackets.
+///
+/// Like `Spanned`, comments compare equal regardless of their spans.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Comment<S: Storage = Owned> {
+    pub text: S::Text,
+    pub kind: CommentKind,
+    /// Whether the comment follows code on the same line, rather than starting its own line.
+    pub trailing: bool,
+    pub span: Span,
+}
+
+impl<S: Storage> PartialEq for Comment<S> {
+    fn eq(&self, other: &Comment<S>) -> bool {
+        self.text == other.text && self.kind == other.kind && self.trailing == other.trailing
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum CommentKind {
+    /// A comment running to the end of the line.
+    ///
+    /// # Example
+    /// ```lua
+    /// -- abc
+    /// ```
+    Line,
+    /// A comment in long brackets with the given number of equals signs.
+    ///
+    /// # Example
+    /// ```lua
+    /// --[==[ abc ]==]
+    /// ```
+    Block(usize),
+}
 
-/// The largest unit of code in lua. Equates to an entire file of code.
-pub struct Chunk(pub Block);
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Block<S: Storage = Owned>(
+    pub Option<Vec<Spanned<Statement<S>>>>,
+    pub Option<Box<ReturnStatement<S>>>,
+);
 
-pub struct Block(pub Option<Vec<Statement>>, pub Option<Box<ReturnStatement>>);
+/// The condition and block of an `elseif` clause.
+pub type ElseIf<S = Owned> = (Box<Spanned<Expression<S>>>, Box<Block<S>>);
 
-pub enum Statement {
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Statement<S: Storage = Owned> {
     Semicolon,
-    Assignment(Box<VariableList>, Box<ExpressionList>),
-    FunctionCall(Box<FunctionCall>),
-    Label(Label),
+    Assignment(Box<VariableList<S>>, Box<ExpressionList<S>>),
+    FunctionCall(Box<FunctionCall<S>>),
+    Label(Label<S>),
     Break,
-    Goto(Label),
-    Do(Box<Block>),
-    While { exp: Box<Expression>, do_: Box<Block> },
-    Repeat { block: Box<Block>, until: Box<Expression> },
+    Goto(Label<S>),
+    Do(Box<Block<S>>),
+    While { exp: Box<Spanned<Expression<S>>>, do_: Box<Block<S>> },
+    Repeat { block: Box<Block<S>>, until: Box<Spanned<Expression<S>>> },
+    /// An if statement. Each `elseif` clause is stored in order as a condition and a block, and
+    /// the `else` block is absent if there is no `else` clause.
     If {
-        condition: Box<Expression>, then: Box<Block>,
-        elseif_condition: Option<Box<Expression>>, elsethen: Option<Box<Block>>,
-        else_: Box<Block>
+        condition: Box<Spanned<Expression<S>>>, then: Box<Block<S>>,
+        elseifs: Vec<ElseIf<S>>,
+        else_: Option<Box<Block<S>>>
     },
-    ForStepping { name: String, from: Box<Expression>, to: Box<Expression>, step: Option<Box<Expression>>, block: Box<Block> },
-    ForIn { name_list: Box<NameList>, in_: Box<ExpressionList>, do_: Box<Block> },
-    Function(FunctionName, Box<FunctionBody>),
-    LocalFunction { name: String, body: Box<FunctionBody> },
-    LocalVariableBinding(NameList, Option<ExpressionList>),
+    ForStepping {
+        name: Spanned<S::Name>, from: Box<Spanned<Expression<S>>>,
+        to: Box<Spanned<Expression<S>>>, step: Option<Box<Spanned<Expression<S>>>>,
+        block: Box<Block<S>>
+    },
+    ForIn { name_list: Box<NameList<S>>, in_: Box<ExpressionList<S>>, do_: Box<Block<S>> },
+    Function(FunctionName<S>, Box<FunctionBody<S>>),
+    LocalFunction { name: Spanned<S::Name>, body: Box<FunctionBody<S>> },
+    LocalVariableBinding(AttributeNameList<S>, Option<ExpressionList<S>>),
 }
 
-pub struct ReturnStatement(pub Box<ExpressionList>);
-pub struct Label(pub String);
+/// A return statement. The expression list is absent for a bare `return`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ReturnStatement<S: Storage = Owned>(pub Option<Box<ExpressionList<S>>>);
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Label<S: Storage = Owned>(pub S::Name);
 
-pub struct FunctionName {
-    pub first_dot_access: String,
-    pub rest_dot_access: Option<Vec<String>>,
-    pub self_name: Option<String>,
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FunctionName<S: Storage = Owned> {
+    pub first_dot_access: Spanned<S::Name>,
+    pub rest_dot_access: Option<Vec<S::Name>>,
+    pub self_name: Option<S::Name>,
 }
 
-pub struct VariableList {
-    pub first: Box<Variable>,
-    pub rest: Option<Vec<Variable>>,
+impl<S: Storage> FunctionName<S> {
+    /// The variable the function is assigned to, such as `a.b.c` for both `function a.b.c()` and
+    /// `function a.b:c()`.
+    pub fn to_variable(&self) -> Variable<S> {
+        let keys = self.rest_dot_access.iter().flatten().chain(self.self_name.as_ref());
+        keys.fold(Variable::Name(self.first_dot_access.clone()), |from, key| {
+            Variable::DotAccess {
+                from: Box::new(PrefixExpression::Variable(Box::new(from))),
+                key: key.clone(),
+            }
+        })
+    }
 }
 
-pub enum Variable {
-    Name(String),
-    ArrayAccess { from: Box<PrefixExpression>, key: Box<Expression> },
-    DotAccess { from: Box<PrefixExpression>, key: String },
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct VariableList<S: Storage = Owned> {
+    pub first: Box<Variable<S>>,
+    pub rest: Option<Vec<Variable<S>>>,
 }
 
-pub struct NameList(pub String, pub Option<Vec<String>>);
-pub struct ExpressionList(pub Box<Expression>, pub Option<Vec<Expression>>);
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Variable<S: Storage = Owned> {
+    Name(Spanned<S::Name>),
+    ArrayAccess { from: Box<PrefixExpression<S>>, key: Box<Spanned<Expression<S>>> },
+    DotAccess { from: Box<PrefixExpression<S>>, key: S::Name },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct NameList<S: Storage = Owned>(pub Spanned<S::Name>, pub Option<Vec<Spanned<S::Name>>>);
+
+impl NameList {
+    /// A list of just the one name.
+    pub fn single(name: Spanned<String>) -> NameList {
+        NameList(name, None)
+    }
+}
+
+/// The names declared by a local variable binding, each with an optional attribute.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct AttributeNameList<S: Storage = Owned>(
+    pub AttributeName<S>,
+    pub Option<Vec<AttributeName<S>>>,
+);
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct AttributeName<S: Storage = Owned> {
+    pub name: Spanned<S::Name>,
+    pub attribute: Option<Attribute>,
+}
+
+/// An attribute of a local variable, from lua 5.4.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Attribute {
+    /// A local that can't be assigned to after its declaration.
+    ///
+    /// # Example
+    /// ```lua
+    /// local x <const> = 1
+    /// ```
+    Const,
+    /// A local whose value is closed, by calling its `__close` metamethod, when it goes out of
+    /// scope.
+    ///
+    /// # Example
+    /// ```lua
+    /// local file <close> = io.open("foo")
+    /// ```
+    Close,
+}
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ExpressionList<S: Storage = Owned>(
+    pub Box<Spanned<Expression<S>>>,
+    pub Option<Vec<Spanned<Expression<S>>>>,
+);
 
-pub enum Expression {
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Expression<S: Storage = Owned> {
     Nil,
     False,
     True,
-    Number(f64),
-    String(String),
+    /// A number literal written without a fraction or exponent, such as `3`.
+    Integer(i64, NumberForm<S>),
+    /// A number literal written with a fraction or exponent, such as `3.0` or `3e2`.
+    Float(f64, NumberForm<S>),
+    /// A string literal, holding the bytes of the string it stands for: the escape sequences of
+    /// short strings are decoded, and long strings drop the newline directly after the opening
+    /// bracket. Like lua's strings, these needn't be valid UTF-8, as with `"\xff"`.
+    String(S::Str, StringForm),
     /// Reperesents the lua ... variable in a function that takes extended arguments with a ...
     /// at the end of it's argument list.
     ExtendedArgumentAccess,
-    FunctionDefine(Box<FunctionDefine>),
-    PrefixExpression(Box<PrefixExpression>),
-    TableConstructor(Box<TableConstructor>),
-    BinaryOperation(Box<Expression>, Box<Expression>),
-    UnaryOperation(Box<Expression>),
+    FunctionDefine(Box<FunctionDefine<S>>),
+    PrefixExpression(Box<PrefixExpression<S>>),
+    TableConstructor(Box<TableConstructor<S>>),
+    /// A binary operation, with the operator, the left hand side and the right hand side.
+    BinaryOperation(BinaryOperation, Box<Spanned<Expression<S>>>, Box<Spanned<Expression<S>>>),
+    /// A unary operation, with the operator and the operand.
+    UnaryOperation(UnaryOperation, Box<Spanned<Expression<S>>>),
+    /// A placeholder for a missing expression, left by `parser::parse_recovering` where the
+    /// source has an error. It has no value, and no source reads back as it.
+    Error,
 }
 
-pub enum PrefixExpression {
-    Variable(Box<Variable>),
-    FunctionCall(Box<FunctionCall>),
-    Parenthesis(Box<Expression>),
+/// How a number literal was written, so the printer can write it the same way.
+///
+/// The text is printed in place of the value, so a literal whose value is changed should have its
+/// form set back to `Plain`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum NumberForm<S: Storage = Owned> {
+    /// The way the printer writes the value, such as `255` or `3.0`, as literals built by hand
+    /// are.
+    Plain,
+    /// Some other way, such as `0xFF`, `1e3` or `.5`, with the text of the literal.
+    Written(S::Text),
 }
 
-pub enum FunctionCall {
+/// How a string literal was written.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum StringForm {
+    /// A string in the given quotes, such as `"abc"` or `'abc'`.
+    Short(Quote),
+    /// A string in long brackets with the given number of equals signs, such as `[[abc]]` for
+    /// level 0 or `[==[abc]==]` for level 2. Long strings don't have escape sequences.
+    Long(usize),
+}
+
+/// The quotes around a short string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Quote {
+    /// `"abc"`, which string literals built by hand use.
+    Double,
+    /// `'abc'`.
+    Single,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum PrefixExpression<S: Storage = Owned> {
+    Variable(Box<Variable<S>>),
+    FunctionCall(Box<FunctionCall<S>>),
+    Parenthesis(Box<Spanned<Expression<S>>>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum FunctionCall<S: Storage = Owned> {
     /// Calling the function with a . to access through tables or through the global enviroment,
     /// not passing a self argument.
     ///
@@ -85,44 +349,55 @@ pub enum FunctionCall {
     /// abcde("foo")
     /// bar.foo("foobar")
     /// ```
-    Static(Box<PrefixExpression>, Box<FunctionArguments>),
+    Static(Box<PrefixExpression<S>>, Box<FunctionArguments<S>>),
     /// Calling with a self argument, using a : access.
     ///
     /// # Example
     /// ```lua
     /// foo:bar("barfoo")
     /// ```
-    SelfTaking(Box<PrefixExpression>, String, FunctionArguments),
+    SelfTaking(Box<PrefixExpression<S>>, S::Name, Box<FunctionArguments<S>>),
 }
 
-pub enum FunctionArguments {
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum FunctionArguments<S: Storage = Owned> {
     /// Calling a function normally.
     ///
     /// # Example
     /// ```lua
     /// foo("abc")
     /// ```
-    Parenthesis(Option<Box<ExpressionList>>),
+    Parenthesis(Option<Box<ExpressionList<S>>>),
     /// Calling the function with a single table.
     ///
     /// # Example
     /// ```lua
     /// foo{a = "aaa"}
     /// ```
-    TableConstructor(Box<TableConstructor>),
+    TableConstructor(Box<TableConstructor<S>>),
     /// Calls the function with a singlur literal string.
     ///
     /// # Example
     /// ```lua
     /// foo"abc"
     /// ```
-    String(String), // This is really weird. I see the point, but why?
+    String(S::Str, StringForm), // This is really weird. I see the point, but why?
 }
 
-pub struct FunctionDefine(pub Box<FunctionBody>);
-pub struct FunctionBody(pub Option<Box<ParameterList>>, pub Box<Block>);
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FunctionDefine<S: Storage = Owned>(pub Box<FunctionBody<S>>);
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FunctionBody<S: Storage = Owned>(
+    pub Option<Box<ParameterList<S>>>,
+    pub Box<Block<S>>,
+);
 
-pub enum ParameterList {
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ParameterList<S: Storage = Owned> {
     /// A normal parameter list, without any extended arguments or special features.
     ///
     /// # Example
@@ -130,14 +405,14 @@ pub enum ParameterList {
     /// function foo(a, b)
     /// end
     /// ```
-    NameList(Box<NameList>),
+    NameList(Box<NameList<S>>),
     /// A parameter list with both arguments and an extended argument.
     ///
     /// # Example
     /// ```lua
     /// function foo(a, b, ...)
     /// ```
-    ExtendedArguments(Box<NameList>),
+    ExtendedArguments(Box<NameList<S>>),
     /// A function with an extended argument, but no regular arguments.
     ///
     /// # Example
@@ -147,28 +422,40 @@ pub enum ParameterList {
     ExtendedArgumentsVoid,
 }
 
-pub struct TableConstructor(pub Box<FieldList>);
-pub struct FieldList(pub Box<Field>, pub Option<Vec<Field>>);
+/// A table constructor. The field list is absent for an empty table, `{}`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TableConstructor<S: Storage = Owned>(pub Option<Box<FieldList<S>>>);
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FieldList<S: Storage = Owned>(pub Box<Field<S>>, pub Option<Vec<Field<S>>>);
 
-pub enum Field {
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Field<S: Storage = Owned> {
     /// A field in a table that takes an expression for the name of the field.
-    ExpressionForName { name: Box<Expression>, equals: Box<Expression> },
+    ExpressionForName {
+        name: Box<Spanned<Expression<S>>>,
+        equals: Box<Spanned<Expression<S>>>,
+    },
     /// A normal field.
     ///
     /// # Example
     /// ```lua
     /// a = { b = "bbb" }
     /// ```
-    Equals { name: String, equals: Box<Expression> },
+    Equals { name: S::Name, equals: Box<Spanned<Expression<S>>> },
     /// A field in the form of an array.
     ///
     /// # Example
     /// ```lua
     /// a = { "bbb" }
     /// ```
-    ArrayStyle(Box<Expression>),
+    ArrayStyle(Box<Spanned<Expression<S>>>),
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum BinaryOperation {
     /// + sigil.
     Plus,
@@ -178,6 +465,8 @@ pub enum BinaryOperation {
     Times,
     /// / sigil.
     Devide,
+    /// // sigil.
+    FloorDivide,
     /// ^ sigil.
     Exponent,
     /// % sigil.
@@ -196,14 +485,326 @@ pub enum BinaryOperation {
     Equal,
     /// ~= sigil.
     NotEqual,
+    /// & sigil.
+    BitwiseAnd,
+    /// | sigil.
+    BitwiseOr,
+    /// ~ sigil, when used as a binary operation.
+    BitwiseXor,
+    /// << sigil.
+    ShiftLeft,
+    /// >> sigil.
+    ShiftRight,
     And,
     Or,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum UnaryOperation {
     /// - sigil, when used as a unary operation.
     Negate,
     Not,
     /// # sigil.
     Length,
+    /// ~ sigil, when used as a unary operation.
+    BitwiseNot,
+}
+
+impl BinaryOperation {
+    /// The left and right binding powers of the operator, as in the reference implementation of
+    /// lua at http://www.lua.org/manual/5.3/manual.html#3.4.8.
+    ///
+    /// An operator takes the operand after the operator before it when its left power is higher
+    /// than that operator's right power. Operators with a lower right power than left power are
+    /// right associative.
+    pub fn binding_power(&self) -> (u8, u8) {
+        match *self {
+            BinaryOperation::Or => (1, 1),
+            BinaryOperation::And => (2, 2),
+            BinaryOperation::LessThan |
+            BinaryOperation::LessThanOrEqual |
+            BinaryOperation::GreaterThan |
+            BinaryOperation::GreaterThanOrEqual |
+            BinaryOperation::Equal |
+            BinaryOperation::NotEqual => (3, 3),
+            BinaryOperation::BitwiseOr => (4, 4),
+            BinaryOperation::BitwiseXor => (5, 5),
+            BinaryOperation::BitwiseAnd => (6, 6),
+            BinaryOperation::ShiftLeft | BinaryOperation::ShiftRight => (7, 7),
+            BinaryOperation::Concatanate => (9, 8),
+            BinaryOperation::Plus | BinaryOperation::Minus => (10, 10),
+            BinaryOperation::Times |
+            BinaryOperation::Devide |
+            BinaryOperation::FloorDivide |
+            BinaryOperation::Modulo => (11, 11),
+            BinaryOperation::Exponent => (14, 13),
+        }
+    }
+}
+
+/// The precedence of a binary operator, from 1 for `or` up to 12 for `^`, following the list at
+/// http://www.lua.org/manual/5.2/manual.html#3.4.7 with the operators of lua 5.3 slotted in as in
+/// http://www.lua.org/manual/5.3/manual.html#3.4.8. Unary operators come between `^` and the other
+/// binary operators.
+///
+/// An operand needs parentheses when its operator has a lower precedence than the operator it's
+/// the operand of, or the same precedence on the opposite side to the associativity.
+pub fn precedence(op: BinaryOperation) -> u8 {
+    match op {
+        BinaryOperation::Or => 1,
+        BinaryOperation::And => 2,
+        BinaryOperation::LessThan |
+        BinaryOperation::LessThanOrEqual |
+        BinaryOperation::GreaterThan |
+        BinaryOperation::GreaterThanOrEqual |
+        BinaryOperation::Equal |
+        BinaryOperation::NotEqual => 3,
+        BinaryOperation::BitwiseOr => 4,
+        BinaryOperation::BitwiseXor => 5,
+        BinaryOperation::BitwiseAnd => 6,
+        BinaryOperation::ShiftLeft | BinaryOperation::ShiftRight => 7,
+        BinaryOperation::Concatanate => 8,
+        BinaryOperation::Plus | BinaryOperation::Minus => 9,
+        BinaryOperation::Times |
+        BinaryOperation::Devide |
+        BinaryOperation::FloorDivide |
+        BinaryOperation::Modulo => 10,
+        BinaryOperation::Exponent => 12,
+    }
+}
+
+/// The precedence of every unary operator, see `precedence`.
+pub const UNARY_PRECEDENCE: u8 = 11;
+
+/// Whether `a op b op c` means `a op (b op c)`, which is so for `..` and `^`.
+pub fn is_right_associative(op: BinaryOperation) -> bool {
+    let (left, right) = op.binding_power();
+    right < left
+}
+
+impl UnaryOperation {
+    /// The binding power of every unary operator, which takes all binary operators but `^` into
+    /// its operand.
+    pub const BINDING_POWER: u8 = 12;
+}
+
+// Dropping a tree the default way recurses once for every level of nesting, which overflows the
+// stack on something like a concatenation of thousands of strings. These take the nested nodes
+// out first and drop them one at a time instead. The nodes are moved out of their boxes, leaving
+// cheap placeholders behind, so nothing new is allocated.
+//
+// Expressions nested in operators, parentheses, calls, indexing and table constructors, and blocks
+// nested in statements, are taken apart this way. Functions nested in expressions still recurse,
+// once per function, as do chains of calls and indexing like `a.b.c`, once per link.
+
+impl<S: Storage> Drop for Expression<S> {
+    fn drop(&mut self) {
+        let mut nested = Vec::new();
+        self.take_nested(&mut nested);
+        while let Some(mut expression) = nested.pop() {
+            expression.take_nested(&mut nested);
+        }
+    }
+}
+
+/// Moves an expression out to `nested`, unless it can't hold any others.
+fn take_expression<S: Storage>(
+    expression: &mut Spanned<Expression<S>>,
+    nested: &mut Vec<Expression<S>>,
+) {
+    let is_leaf = matches!(
+        expression.node,
+        Expression::Nil | Expression::False | Expression::True | Expression::Integer(..) |
+            Expression::Float(..) | Expression::String(..) | Expression::ExtendedArgumentAccess |
+            Expression::Error
+    );
+    if !is_leaf {
+        nested.push(mem::replace(&mut expression.node, Expression::Nil));
+    }
+}
+
+fn take_expression_list<S: Storage>(list: &mut ExpressionList<S>, nested: &mut Vec<Expression<S>>) {
+    take_expression(&mut list.0, nested);
+    for expression in list.1.iter_mut().flatten() {
+        take_expression(expression, nested);
+    }
+}
+
+fn take_table<S: Storage>(table: &mut TableConstructor<S>, nested: &mut Vec<Expression<S>>) {
+    let fields = match table.0 {
+        Some(ref mut fields) => fields,
+        None => return,
+    };
+    for field in iter::once(&mut *fields.0).chain(fields.1.iter_mut().flatten()) {
+        match *field {
+            Field::ExpressionForName { ref mut name, ref mut equals } => {
+                take_expression(name, nested);
+                take_expression(equals, nested);
+            }
+            Field::Equals { ref mut equals, .. } => take_expression(equals, nested),
+            Field::ArrayStyle(ref mut value) => take_expression(value, nested),
+        }
+    }
+}
+
+fn take_prefix<S: Storage>(prefix: &mut PrefixExpression<S>, nested: &mut Vec<Expression<S>>) {
+    match *prefix {
+        PrefixExpression::Parenthesis(ref mut inner) => take_expression(inner, nested),
+        PrefixExpression::Variable(ref mut variable) => match **variable {
+            Variable::Name(_) => {}
+            Variable::ArrayAccess { ref mut from, ref mut key } => {
+                take_expression(key, nested);
+                take_prefix(from, nested);
+            }
+            Variable::DotAccess { ref mut from, .. } => take_prefix(from, nested),
+        },
+        PrefixExpression::FunctionCall(ref mut call) => {
+            let (from, arguments) = match **call {
+                FunctionCall::Static(ref mut from, ref mut arguments) |
+                FunctionCall::SelfTaking(ref mut from, _, ref mut arguments) => (from, arguments),
+            };
+            match **arguments {
+                FunctionArguments::Parenthesis(Some(ref mut list)) => {
+                    take_expression_list(list, nested)
+                }
+                FunctionArguments::TableConstructor(ref mut table) => take_table(table, nested),
+                FunctionArguments::Parenthesis(None) | FunctionArguments::String(..) => {}
+            }
+            take_prefix(from, nested);
+        }
+    }
+}
+
+impl<S: Storage> Expression<S> {
+    /// Moves the expressions inside this one to `nested`.
+    fn take_nested(&mut self, nested: &mut Vec<Expression<S>>) {
+        match *self {
+            Expression::BinaryOperation(_, ref mut lhs, ref mut rhs) => {
+                take_expression(lhs, nested);
+                take_expression(rhs, nested);
+            }
+            Expression::UnaryOperation(_, ref mut operand) => take_expression(operand, nested),
+            Expression::PrefixExpression(ref mut prefix) => take_prefix(prefix, nested),
+            Expression::TableConstructor(ref mut table) => take_table(table, nested),
+            _ => {}
+        }
+    }
+}
+
+/// An integer literal.
+impl From<i64> for Expression {
+    fn from(n: i64) -> Self {
+        Expression::Integer(n, NumberForm::Plain)
+    }
+}
+
+/// A float literal, which is written with a fraction even when it's whole.
+impl From<f64> for Expression {
+    fn from(n: f64) -> Self {
+        Expression::Float(n, NumberForm::Plain)
+    }
+}
+
+/// `true` or `false`.
+impl From<bool> for Expression {
+    fn from(value: bool) -> Self {
+        if value { Expression::True } else { Expression::False }
+    }
+}
+
+/// A string literal holding exactly the given string, never a name.
+impl<'a> From<&'a str> for Expression {
+    fn from(s: &'a str) -> Self {
+        Expression::String(s.as_bytes().to_vec(), StringForm::Short(Quote::Double))
+    }
+}
+
+/// A string literal holding exactly the given string, never a name.
+impl From<String> for Expression {
+    fn from(s: String) -> Self {
+        Expression::String(s.into_bytes(), StringForm::Short(Quote::Double))
+    }
+}
+
+/// A string literal holding exactly the given bytes, which needn't be valid UTF-8.
+impl From<Vec<u8>> for Expression {
+    fn from(s: Vec<u8>) -> Self {
+        Expression::String(s, StringForm::Short(Quote::Double))
+    }
+}
+
+impl<S: Storage> Drop for Block<S> {
+    fn drop(&mut self) {
+        let mut nested = Vec::new();
+        self.take_nested(&mut nested);
+        while let Some(mut block) = nested.pop() {
+            block.take_nested(&mut nested);
+        }
+    }
+}
+
+impl Block {
+    /// A block with no statements and no return statement.
+    pub fn empty() -> Block {
+        Block(None, None)
+    }
+
+    /// A block of the given statements, without a return statement. No statements leaves the
+    /// list out, as the parser does for an empty block.
+    pub fn from_statements(statements: Vec<Spanned<Statement>>) -> Block {
+        Block(if statements.is_empty() { None } else { Some(statements) }, None)
+    }
+}
+
+impl<S: Storage> Block<S> {
+
+    /// The statements of the block in order, not counting its return statement.
+    pub fn statements(&self) -> impl Iterator<Item = &Spanned<Statement<S>>> {
+        self.0.iter().flatten()
+    }
+
+    /// The statements of the block in order, not counting its return statement.
+    pub fn statements_mut(&mut self) -> impl Iterator<Item = &mut Spanned<Statement<S>>> {
+        self.0.iter_mut().flatten()
+    }
+
+    /// Whether the block has neither statements nor a return statement.
+    pub fn is_empty(&self) -> bool {
+        self.0.as_ref().is_none_or(Vec::is_empty) && self.1.is_none()
+    }
+
+    /// Moves the blocks of the statements in this one to `nested`.
+    fn take_nested(&mut self, nested: &mut Vec<Block<S>>) {
+        let statements = match self.0 {
+            Some(ref mut statements) => statements,
+            None => return,
+        };
+        let mut take = |block: &mut Block<S>| {
+            if block.0.is_some() {
+                nested.push(mem::replace(block, Block(None, None)));
+            }
+        };
+        for statement in statements {
+            match statement.node {
+                Statement::Do(ref mut block) |
+                Statement::While { do_: ref mut block, .. } |
+                Statement::Repeat { ref mut block, .. } |
+                Statement::ForStepping { ref mut block, .. } |
+                Statement::ForIn { do_: ref mut block, .. } => take(block),
+                Statement::If { ref mut then, ref mut elseifs, ref mut else_, .. } => {
+                    take(then);
+                    for &mut (_, ref mut block) in elseifs {
+                        take(block);
+                    }
+                    if let Some(ref mut block) = *else_ {
+                        take(block);
+                    }
+                }
+                Statement::Function(_, ref mut body) |
+                Statement::LocalFunction { ref mut body, .. } => take(&mut body.1),
+                _ => {}
+            }
+        }
+    }
 }
@@ -0,0 +1,63 @@
+//! Parses a lua file and prints its syntax tree.
+//!
+//! Usage: `buildlua [--json] <file>`. The tree is printed in its `Debug` form, or as JSON with
+//! `--json` if the binary was built with the `json` feature. A file that fails to parse exits
+//! with status 1.
+
+extern crate buildlua;
+#[cfg(feature = "json")]
+extern crate serde_json;
+
+use std::env;
+use std::fmt;
+use std::fs;
+use std::process;
+
+use buildlua::ast::Chunk;
+use buildlua::parser::parse;
+use buildlua::span::SourceError;
+
+fn main() {
+    let mut json = false;
+    let mut path = None;
+    for argument in env::args().skip(1) {
+        match &*argument {
+            "--json" => json = true,
+            flag if flag.starts_with("--") => usage(),
+            _ if path.is_none() => path = Some(argument),
+            _ => usage(),
+        }
+    }
+    let path = path.unwrap_or_else(|| usage());
+    let source = fs::read_to_string(&path)
+        .unwrap_or_else(|err| fail(format_args!("{}: {}", path, err)));
+    // Parse errors lead with their line and column, as in `file:line:column`.
+    let chunk = parse(&source)
+        .unwrap_or_else(|err| fail(format_args!("{}:{}", path, err.in_source(&source))));
+    if json {
+        print_json(&chunk);
+    } else {
+        println!("{:#?}", chunk);
+    }
+}
+
+fn usage() -> ! {
+    eprintln!("usage: buildlua [--json] <file>");
+    process::exit(2)
+}
+
+fn fail(message: fmt::Arguments) -> ! {
+    eprintln!("error: {}", message);
+    process::exit(1)
+}
+
+#[cfg(feature = "json")]
+fn print_json(chunk: &Chunk) {
+    println!("{}", serde_json::to_string_pretty(chunk).expect("failed to write the tree as JSON"));
+}
+
+#[cfg(not(feature = "json"))]
+fn print_json(_: &Chunk) {
+    eprintln!("error: printing JSON needs buildlua to be built with the json feature");
+    process::exit(2)
+}
@@ -0,0 +1,138 @@
+//! A form of the syntax tree whose names and strings borrow from the source it was parsed from.
+//!
+//! The tree in `ast` owns a `String` for every name, string literal, number written in some other
+//! way than the plain one, and comment. In the `Borrowed` storage the tree holds `Cow`s of them
+//! instead, borrowing the text from the source wherever the source holds it as is. That's every
+//! name, number and comment, and every string literal without escape sequences or `\r`s, so a
+//! tree kept around for long takes hardly any memory for text, and copying a tree copies none of
+//! it.
+//!
+//! `BorrowedChunk::parse` builds the tree straight from tokens borrowing from the source, without
+//! copying the text, and `BorrowedChunk::to_chunk` converts it to the owned tree.
+//!
+//! Apart from their text, the nodes are those of `ast`, and are documented there.
+
+use std::borrow::Cow;
+use std::marker::PhantomData;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use ast::{self, Owned, Storage};
+use convert::Convert;
+use lexer::FromSource;
+use parser::{parse_stored, ParseError, ParserConfig, Store};
+
+/// Text borrowed from the source, or owned when the source doesn't hold it as is.
+pub type Text<'a> = Cow<'a, str>;
+
+/// The bytes of a string literal, borrowed from the source like `Text`.
+pub type Bytes<'a> = Cow<'a, [u8]>;
+
+/// Text borrowed from a source living for `'a`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Borrowed<'a>(PhantomData<&'a str>);
+
+impl<'a> Storage for Borrowed<'a> {
+    type Name = Text<'a>;
+    type Str = Bytes<'a>;
+    type Text = Text<'a>;
+}
+
+impl<'a> FromSource<'a> for Borrowed<'a> {
+    fn name(name: &'a str) -> Text<'a> {
+        Cow::Borrowed(name)
+    }
+
+    fn string(s: Bytes<'a>) -> Bytes<'a> {
+        s
+    }
+
+    fn text(text: &'a str) -> Text<'a> {
+        Cow::Borrowed(text)
+    }
+}
+
+impl<'a> Store<'a> for Borrowed<'a> {
+    type Storage = Borrowed<'a>;
+
+    fn name(&mut self, name: Text<'a>) -> Text<'a> {
+        name
+    }
+
+    fn string(&mut self, s: Bytes<'a>) -> Bytes<'a> {
+        s
+    }
+
+    fn text(&mut self, text: Text<'a>) -> Text<'a> {
+        text
+    }
+}
+
+/// A whole chunk, with the comments in it in source order.
+pub type BorrowedChunk<'a> = ast::Chunk<Borrowed<'a>>;
+pub type Comment<'a> = ast::Comment<Borrowed<'a>>;
+pub type Block<'a> = ast::Block<Borrowed<'a>>;
+pub type Statement<'a> = ast::Statement<Borrowed<'a>>;
+pub type ReturnStatement<'a> = ast::ReturnStatement<Borrowed<'a>>;
+pub type Label<'a> = ast::Label<Borrowed<'a>>;
+pub type FunctionName<'a> = ast::FunctionName<Borrowed<'a>>;
+pub type VariableList<'a> = ast::VariableList<Borrowed<'a>>;
+pub type Variable<'a> = ast::Variable<Borrowed<'a>>;
+pub type NameList<'a> = ast::NameList<Borrowed<'a>>;
+pub type AttributeNameList<'a> = ast::AttributeNameList<Borrowed<'a>>;
+pub type AttributeName<'a> = ast::AttributeName<Borrowed<'a>>;
+pub type ExpressionList<'a> = ast::ExpressionList<Borrowed<'a>>;
+pub type Expression<'a> = ast::Expression<Borrowed<'a>>;
+pub type NumberForm<'a> = ast::NumberForm<Borrowed<'a>>;
+pub type PrefixExpression<'a> = ast::PrefixExpression<Borrowed<'a>>;
+pub type FunctionCall<'a> = ast::FunctionCall<Borrowed<'a>>;
+pub type FunctionArguments<'a> = ast::FunctionArguments<Borrowed<'a>>;
+pub type FunctionDefine<'a> = ast::FunctionDefine<Borrowed<'a>>;
+pub type FunctionBody<'a> = ast::FunctionBody<Borrowed<'a>>;
+pub type ParameterList<'a> = ast::ParameterList<Borrowed<'a>>;
+pub type TableConstructor<'a> = ast::TableConstructor<Borrowed<'a>>;
+pub type FieldList<'a> = ast::FieldList<Borrowed<'a>>;
+pub type Field<'a> = ast::Field<Borrowed<'a>>;
+
+/// Copies the text of a borrowed tree.
+struct Copier;
+
+impl<'a> Convert<Borrowed<'a>, Owned> for Copier {
+    fn name(&self, name: &Text<'a>) -> String {
+        name.to_string()
+    }
+
+    fn string(&self, s: &Bytes<'a>) -> Vec<u8> {
+        s.to_vec()
+    }
+
+    fn text(&self, text: &Text<'a>) -> String {
+        text.to_string()
+    }
+}
+
+impl<'a> BorrowedChunk<'a> {
+    /// Parses the source into a borrowed tree.
+    ///
+    /// # Example
+    /// ```
+    /// use std::borrow::Cow;
+    ///
+    /// use buildlua::ast::Label;
+    /// use buildlua::borrowed::{BorrowedChunk, Statement};
+    ///
+    /// let chunk = BorrowedChunk::parse("goto done").unwrap();
+    /// let statement = &chunk.0.0.as_ref().unwrap()[0];
+    /// assert!(matches!(statement.node, Statement::Goto(Label(Cow::Borrowed("done")))));
+    /// ```
+    pub fn parse(src: &'a str) -> Result<BorrowedChunk<'a>, ParseError> {
+        parse_stored(src, &ParserConfig::default(), Borrowed(PhantomData))
+    }
+
+    /// Converts to the owned tree, copying the text.
+    pub fn to_chunk(&self) -> ast::Chunk {
+        Copier.chunk(self)
+    }
+}
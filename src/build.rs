@@ -0,0 +1,353 @@
+//! Shorthands for building the abstract syntax tree by hand, such as when generating code.
+//!
+//! Nodes built here are given dummy spans. Lists that lua requires to be non-empty, such as the
+//! expressions of an assignment, panic when given empty.
+//!
+//! # Example
+//! ```rust
+//! use buildlua::ast::BinaryOperation;
+//! use buildlua::build::{expr, stmt, var, BlockBuilder};
+//!
+//! let block = BlockBuilder::new()
+//!     .push(stmt::assign(vec![var::name("x")], vec![
+//!         expr::binop(BinaryOperation::Plus, expr::integer(1), expr::integer(2)),
+//!     ]))
+//!     .build();
+//! assert_eq!(block.to_string(), "x = 1 + 2");
+//! ```
+
+use std::mem;
+
+use ast::*;
+use span::Spanned;
+
+/// Splits a list into the first item and the rest, the way the tree stores lists.
+fn split<T>(items: Vec<T>) -> Option<(T, Option<Vec<T>>)> {
+    let mut items = items.into_iter();
+    let first = items.next()?;
+    let rest: Vec<T> = items.collect();
+    Some((first, if rest.is_empty() { None } else { Some(rest) }))
+}
+
+fn expression_list(expressions: Vec<Spanned<Expression>>) -> Option<ExpressionList> {
+    split(expressions).map(|(first, rest)| ExpressionList(Box::new(first), rest))
+}
+
+fn name_list(names: &[&str]) -> Option<NameList> {
+    let names = names.iter().map(|name| Spanned::dummy(name.to_string())).collect();
+    split(names).map(|(first, rest)| NameList(first, rest))
+}
+
+/// Turns an expression into a prefix expression, wrapping it in parentheses unless it already is
+/// one.
+fn prefix(mut expression: Spanned<Expression>) -> PrefixExpression {
+    // Expressions implement `Drop`, so the prefix expression is swapped out rather than moved.
+    if let Expression::PrefixExpression(ref mut prefix) = expression.node {
+        let placeholder = PrefixExpression::Parenthesis(Box::new(expr::nil()));
+        return mem::replace(&mut **prefix, placeholder);
+    }
+    PrefixExpression::Parenthesis(Box::new(expression))
+}
+
+/// Builds a function body taking the named parameters.
+pub fn function_body(parameters: &[&str], block: Block) -> FunctionBody {
+    let parameters = name_list(parameters).map(|names| ParameterList::NameList(Box::new(names)));
+    FunctionBody(parameters.map(Box::new), Box::new(block))
+}
+
+/// Places that can be assigned to.
+pub mod var {
+    use super::*;
+
+    pub fn name(name: &str) -> Variable {
+        Variable::Name(Spanned::dummy(name.to_string()))
+    }
+
+    /// `from[key]`.
+    pub fn index(from: Spanned<Expression>, key: Spanned<Expression>) -> Variable {
+        Variable::ArrayAccess { from: Box::new(prefix(from)), key: Box::new(key) }
+    }
+
+    /// `from.key`.
+    pub fn field(from: Spanned<Expression>, key: &str) -> Variable {
+        Variable::DotAccess { from: Box::new(prefix(from)), key: key.to_string() }
+    }
+}
+
+/// Expressions.
+pub mod expr {
+    use super::*;
+
+    fn spanned(expression: Expression) -> Spanned<Expression> {
+        Spanned::dummy(expression)
+    }
+
+    pub fn nil() -> Spanned<Expression> {
+        spanned(Expression::Nil)
+    }
+
+    pub fn boolean(value: bool) -> Spanned<Expression> {
+        spanned(Expression::from(value))
+    }
+
+    pub fn integer(n: i64) -> Spanned<Expression> {
+        spanned(Expression::from(n))
+    }
+
+    pub fn float(n: f64) -> Spanned<Expression> {
+        spanned(Expression::from(n))
+    }
+
+    /// A string literal holding exactly `s`.
+    pub fn string(s: &str) -> Spanned<Expression> {
+        spanned(Expression::from(s))
+    }
+
+    /// The `...` of a function taking extended arguments.
+    pub fn varargs() -> Spanned<Expression> {
+        spanned(Expression::ExtendedArgumentAccess)
+    }
+
+    /// A variable read as an expression.
+    pub fn var(variable: Variable) -> Spanned<Expression> {
+        let prefix = PrefixExpression::Variable(Box::new(variable));
+        spanned(Expression::PrefixExpression(Box::new(prefix)))
+    }
+
+    /// A variable read by name.
+    pub fn name(name: &str) -> Spanned<Expression> {
+        var(var::name(name))
+    }
+
+    /// `from[key]`.
+    pub fn index(from: Spanned<Expression>, key: Spanned<Expression>) -> Spanned<Expression> {
+        var(var::index(from, key))
+    }
+
+    /// `from.key`.
+    pub fn field(from: Spanned<Expression>, key: &str) -> Spanned<Expression> {
+        var(var::field(from, key))
+    }
+
+    fn call_expression(call: FunctionCall) -> Spanned<Expression> {
+        let prefix = PrefixExpression::FunctionCall(Box::new(call));
+        spanned(Expression::PrefixExpression(Box::new(prefix)))
+    }
+
+    fn arguments(arguments: Vec<Spanned<Expression>>) -> FunctionArguments {
+        FunctionArguments::Parenthesis(expression_list(arguments).map(Box::new))
+    }
+
+    /// `function(arguments)`.
+    pub fn call(
+        function: Spanned<Expression>,
+        arguments: Vec<Spanned<Expression>>,
+    ) -> Spanned<Expression> {
+        let arguments = Box::new(self::arguments(arguments));
+        call_expression(FunctionCall::Static(Box::new(prefix(function)), arguments))
+    }
+
+    /// `object:method(arguments)`.
+    pub fn method_call(
+        object: Spanned<Expression>,
+        method: &str,
+        arguments: Vec<Spanned<Expression>>,
+    ) -> Spanned<Expression> {
+        let arguments = Box::new(self::arguments(arguments));
+        let from = Box::new(prefix(object));
+        call_expression(FunctionCall::SelfTaking(from, method.to_string(), arguments))
+    }
+
+    pub fn binop(
+        op: BinaryOperation,
+        lhs: Spanned<Expression>,
+        rhs: Spanned<Expression>,
+    ) -> Spanned<Expression> {
+        spanned(Expression::BinaryOperation(op, Box::new(lhs), Box::new(rhs)))
+    }
+
+    pub fn unop(op: UnaryOperation, operand: Spanned<Expression>) -> Spanned<Expression> {
+        spanned(Expression::UnaryOperation(op, Box::new(operand)))
+    }
+
+    /// An anonymous function taking the named parameters.
+    pub fn function(parameters: &[&str], block: Block) -> Spanned<Expression> {
+        let define = FunctionDefine(Box::new(function_body(parameters, block)));
+        spanned(Expression::FunctionDefine(Box::new(define)))
+    }
+
+    /// A table constructor with the given fields.
+    pub fn table(fields: Vec<Field>) -> Spanned<Expression> {
+        let fields = split(fields).map(|(first, rest)| Box::new(FieldList(Box::new(first), rest)));
+        spanned(Expression::TableConstructor(Box::new(TableConstructor(fields))))
+    }
+
+    /// A table constructor listing its values in order, like `{1, 2, 3}`.
+    pub fn array(values: Vec<Spanned<Expression>>) -> Spanned<Expression> {
+        table(values.into_iter().map(|value| Field::ArrayStyle(Box::new(value))).collect())
+    }
+}
+
+/// Statements.
+pub mod stmt {
+    use super::*;
+
+    fn spanned(statement: Statement) -> Spanned<Statement> {
+        Spanned::dummy(statement)
+    }
+
+    /// `variables = expressions`.
+    pub fn assign(
+        variables: Vec<Variable>,
+        expressions: Vec<Spanned<Expression>>,
+    ) -> Spanned<Statement> {
+        let (first, rest) = split(variables).expect("an assignment needs a variable");
+        let expressions = expression_list(expressions).expect("an assignment needs an expression");
+        let variables = VariableList { first: Box::new(first), rest };
+        spanned(Statement::Assignment(Box::new(variables), Box::new(expressions)))
+    }
+
+    /// `local names = expressions`, leaving out the `=` when there are no expressions.
+    pub fn local(names: &[&str], expressions: Vec<Spanned<Expression>>) -> Spanned<Statement> {
+        let names: Vec<_> = names
+            .iter()
+            .map(|name| AttributeName { name: Spanned::dummy(name.to_string()), attribute: None })
+            .collect();
+        let (first, rest) = split(names).expect("a local binding needs a name");
+        let names = AttributeNameList(first, rest);
+        spanned(Statement::LocalVariableBinding(names, expression_list(expressions)))
+    }
+
+    /// A function call as a statement.
+    ///
+    /// # Panics
+    /// If the expression isn't a function call, such as one made by `expr::call`.
+    pub fn call(call: Spanned<Expression>) -> Spanned<Statement> {
+        match prefix(call) {
+            PrefixExpression::FunctionCall(call) => spanned(Statement::FunctionCall(call)),
+            _ => panic!("stmt::call needs a function call expression"),
+        }
+    }
+
+    pub fn do_(block: Block) -> Spanned<Statement> {
+        spanned(Statement::Do(Box::new(block)))
+    }
+
+    pub fn while_(condition: Spanned<Expression>, block: Block) -> Spanned<Statement> {
+        spanned(Statement::While { exp: Box::new(condition), do_: Box::new(block) })
+    }
+
+    pub fn repeat(block: Block, until: Spanned<Expression>) -> Spanned<Statement> {
+        spanned(Statement::Repeat { block: Box::new(block), until: Box::new(until) })
+    }
+
+    /// An if statement, with an `else` block if one is given.
+    pub fn if_(
+        condition: Spanned<Expression>,
+        then: Block,
+        else_: Option<Block>,
+    ) -> Spanned<Statement> {
+        spanned(Statement::If {
+            condition: Box::new(condition),
+            then: Box::new(then),
+            elseifs: Vec::new(),
+            else_: else_.map(Box::new),
+        })
+    }
+
+    /// `for name = from, to, step do block end`.
+    pub fn for_range(
+        name: &str,
+        from: Spanned<Expression>,
+        to: Spanned<Expression>,
+        step: Option<Spanned<Expression>>,
+        block: Block,
+    ) -> Spanned<Statement> {
+        spanned(Statement::ForStepping {
+            name: Spanned::dummy(name.to_string()),
+            from: Box::new(from),
+            to: Box::new(to),
+            step: step.map(Box::new),
+            block: Box::new(block),
+        })
+    }
+
+    /// `for names in expressions do block end`.
+    pub fn for_in(
+        names: &[&str],
+        expressions: Vec<Spanned<Expression>>,
+        block: Block,
+    ) -> Spanned<Statement> {
+        spanned(Statement::ForIn {
+            name_list: Box::new(name_list(names).expect("a for loop needs a name")),
+            in_: Box::new(expression_list(expressions).expect("a for loop needs an expression")),
+            do_: Box::new(block),
+        })
+    }
+
+    /// `function name(parameters) block end`. The name can be dotted, like `a.b.c`, and can end
+    /// in a method name, like `a.b:c`.
+    pub fn function(name: &str, parameters: &[&str], block: Block) -> Spanned<Statement> {
+        let (path, self_name) = match name.find(':') {
+            Some(colon) => (&name[..colon], Some(name[colon + 1..].to_string())),
+            None => (name, None),
+        };
+        let mut path = path.split('.').map(|part| part.to_string());
+        let first = path.next().unwrap_or_default();
+        let rest: Vec<String> = path.collect();
+        let name = FunctionName {
+            first_dot_access: Spanned::dummy(first),
+            rest_dot_access: if rest.is_empty() { None } else { Some(rest) },
+            self_name,
+        };
+        spanned(Statement::Function(name, Box::new(function_body(parameters, block))))
+    }
+
+    /// `local function name(parameters) block end`.
+    pub fn local_function(name: &str, parameters: &[&str], block: Block) -> Spanned<Statement> {
+        spanned(Statement::LocalFunction {
+            name: Spanned::dummy(name.to_string()),
+            body: Box::new(function_body(parameters, block)),
+        })
+    }
+
+    pub fn break_() -> Spanned<Statement> {
+        spanned(Statement::Break)
+    }
+}
+
+/// Builds a block one statement at a time.
+#[derive(Debug, Clone, Default)]
+pub struct BlockBuilder {
+    statements: Vec<Spanned<Statement>>,
+    return_statement: Option<ReturnStatement>,
+}
+
+impl BlockBuilder {
+    pub fn new() -> BlockBuilder {
+        BlockBuilder::default()
+    }
+
+    /// Adds a statement to the end of the block.
+    pub fn push(mut self, statement: Spanned<Statement>) -> BlockBuilder {
+        self.statements.push(statement);
+        self
+    }
+
+    /// Ends the block with a return statement, replacing any set before.
+    pub fn ret(mut self, expressions: Vec<Spanned<Expression>>) -> BlockBuilder {
+        self.return_statement = Some(ReturnStatement(expression_list(expressions).map(Box::new)));
+        self
+    }
+
+    pub fn build(self) -> Block {
+        let mut block = Block::from_statements(self.statements);
+        block.1 = self.return_statement.map(Box::new);
+        block
+    }
+
+    /// Builds the block as a whole chunk, without comments.
+    pub fn chunk(self) -> Chunk {
+        Chunk::new(self.build())
+    }
+}
@@ -0,0 +1,266 @@
+//! Converting a tree from one storage to another, node by node.
+
+use ast::*;
+use span::Spanned;
+
+fn boxed<T, U>(node: &T, f: impl FnOnce(&T) -> U) -> Box<U> {
+    Box::new(f(node))
+}
+
+fn list<T, U>(items: &Option<Vec<T>>, f: impl FnMut(&T) -> U) -> Option<Vec<U>> {
+    items.as_ref().map(|items| items.iter().map(f).collect())
+}
+
+fn spanned<T, U>(node: &Spanned<T>, f: impl FnOnce(&T) -> U) -> Spanned<U> {
+    Spanned::new(f(&node.node), node.span)
+}
+
+/// Converts the text of a tree in storage `S` to storage `T`, and so whole trees.
+pub(crate) trait Convert<S: Storage, T: Storage> {
+    fn name(&self, name: &S::Name) -> T::Name;
+    fn string(&self, s: &S::Str) -> T::Str;
+    fn text(&self, text: &S::Text) -> T::Text;
+
+    fn chunk(&self, chunk: &Chunk<S>) -> Chunk<T> {
+        let comments = chunk.1.iter().map(|comment| self.comment(comment)).collect();
+        Chunk(self.block(&chunk.0), comments)
+    }
+
+    fn comment(&self, comment: &Comment<S>) -> Comment<T> {
+        Comment {
+            text: self.text(&comment.text),
+            kind: comment.kind,
+            trailing: comment.trailing,
+            span: comment.span,
+        }
+    }
+
+    fn spanned_name(&self, name: &Spanned<S::Name>) -> Spanned<T::Name> {
+        spanned(name, |name| self.name(name))
+    }
+
+    fn block(&self, block: &Block<S>) -> Block<T> {
+        let statements = list(&block.0, |statement| spanned(statement, |s| self.statement(s)));
+        let ret = block.1.as_ref().map(|ret| {
+            let values = ret.0.as_ref().map(|list| boxed(&**list, |l| self.expressions(l)));
+            Box::new(ReturnStatement(values))
+        });
+        Block(statements, ret)
+    }
+
+    fn statement(&self, statement: &Statement<S>) -> Statement<T> {
+        match *statement {
+            Statement::Semicolon => Statement::Semicolon,
+            Statement::Assignment(ref variables, ref expressions) => Statement::Assignment(
+                Box::new(VariableList {
+                    first: boxed(&*variables.first, |v| self.variable(v)),
+                    rest: list(&variables.rest, |v| self.variable(v)),
+                }),
+                boxed(&**expressions, |e| self.expressions(e)),
+            ),
+            Statement::FunctionCall(ref call) => {
+                Statement::FunctionCall(boxed(&**call, |c| self.call(c)))
+            }
+            Statement::Label(ref label) => Statement::Label(Label(self.name(&label.0))),
+            Statement::Break => Statement::Break,
+            Statement::Goto(ref label) => Statement::Goto(Label(self.name(&label.0))),
+            Statement::Do(ref block) => Statement::Do(boxed(&**block, |b| self.block(b))),
+            Statement::While { ref exp, ref do_ } => Statement::While {
+                exp: boxed(&**exp, |e| self.expression(e)),
+                do_: boxed(&**do_, |b| self.block(b)),
+            },
+            Statement::Repeat { ref block, ref until } => Statement::Repeat {
+                block: boxed(&**block, |b| self.block(b)),
+                until: boxed(&**until, |e| self.expression(e)),
+            },
+            Statement::If { ref condition, ref then, ref elseifs, ref else_ } => Statement::If {
+                condition: boxed(&**condition, |e| self.expression(e)),
+                then: boxed(&**then, |b| self.block(b)),
+                elseifs: elseifs
+                    .iter()
+                    .map(|(condition, block)| {
+                        let condition = boxed(&**condition, |e| self.expression(e));
+                        (condition, boxed(&**block, |b| self.block(b)))
+                    })
+                    .collect(),
+                else_: else_.as_ref().map(|block| boxed(&**block, |b| self.block(b))),
+            },
+            Statement::ForStepping { ref name, ref from, ref to, ref step, ref block } => {
+                Statement::ForStepping {
+                    name: self.spanned_name(name),
+                    from: boxed(&**from, |e| self.expression(e)),
+                    to: boxed(&**to, |e| self.expression(e)),
+                    step: step.as_ref().map(|step| boxed(&**step, |e| self.expression(e))),
+                    block: boxed(&**block, |b| self.block(b)),
+                }
+            }
+            Statement::ForIn { ref name_list, ref in_, ref do_ } => Statement::ForIn {
+                name_list: boxed(&**name_list, |n| self.names(n)),
+                in_: boxed(&**in_, |e| self.expressions(e)),
+                do_: boxed(&**do_, |b| self.block(b)),
+            },
+            Statement::Function(ref name, ref body) => Statement::Function(
+                FunctionName {
+                    first_dot_access: self.spanned_name(&name.first_dot_access),
+                    rest_dot_access: list(&name.rest_dot_access, |key| self.name(key)),
+                    self_name: name.self_name.as_ref().map(|key| self.name(key)),
+                },
+                boxed(&**body, |b| self.body(b)),
+            ),
+            Statement::LocalFunction { ref name, ref body } => Statement::LocalFunction {
+                name: self.spanned_name(name),
+                body: boxed(&**body, |b| self.body(b)),
+            },
+            Statement::LocalVariableBinding(ref names, ref expressions) => {
+                let name = |name: &AttributeName<S>| AttributeName {
+                    name: self.spanned_name(&name.name),
+                    attribute: name.attribute,
+                };
+                Statement::LocalVariableBinding(
+                    AttributeNameList(name(&names.0), list(&names.1, name)),
+                    expressions.as_ref().map(|e| self.expressions(e)),
+                )
+            }
+        }
+    }
+
+    fn names(&self, names: &NameList<S>) -> NameList<T> {
+        NameList(self.spanned_name(&names.0), list(&names.1, |name| self.spanned_name(name)))
+    }
+
+    fn expressions(&self, expressions: &ExpressionList<S>) -> ExpressionList<T> {
+        ExpressionList(
+            boxed(&*expressions.0, |e| self.expression(e)),
+            list(&expressions.1, |e| self.expression(e)),
+        )
+    }
+
+    fn expression(&self, expression: &Spanned<Expression<S>>) -> Spanned<Expression<T>> {
+        spanned(expression, |expression| match *expression {
+            Expression::Nil => Expression::Nil,
+            Expression::False => Expression::False,
+            Expression::True => Expression::True,
+            Expression::Integer(n, ref form) => Expression::Integer(n, self.number(form)),
+            Expression::Float(n, ref form) => Expression::Float(n, self.number(form)),
+            Expression::String(ref s, form) => Expression::String(self.string(s), form),
+            Expression::ExtendedArgumentAccess => Expression::ExtendedArgumentAccess,
+            Expression::FunctionDefine(ref define) => Expression::FunctionDefine(Box::new(
+                FunctionDefine(boxed(&*define.0, |b| self.body(b))),
+            )),
+            Expression::PrefixExpression(ref prefix) => {
+                Expression::PrefixExpression(boxed(&**prefix, |p| self.prefix(p)))
+            }
+            Expression::TableConstructor(ref table) => {
+                Expression::TableConstructor(boxed(&**table, |t| self.table(t)))
+            }
+            Expression::BinaryOperation(op, ref lhs, ref rhs) => Expression::BinaryOperation(
+                op,
+                boxed(&**lhs, |e| self.expression(e)),
+                boxed(&**rhs, |e| self.expression(e)),
+            ),
+            Expression::UnaryOperation(op, ref operand) => {
+                Expression::UnaryOperation(op, boxed(&**operand, |e| self.expression(e)))
+            }
+            Expression::Error => Expression::Error,
+        })
+    }
+
+    fn number(&self, form: &NumberForm<S>) -> NumberForm<T> {
+        match *form {
+            NumberForm::Plain => NumberForm::Plain,
+            NumberForm::Written(ref text) => NumberForm::Written(self.text(text)),
+        }
+    }
+
+    fn prefix(&self, prefix: &PrefixExpression<S>) -> PrefixExpression<T> {
+        match *prefix {
+            PrefixExpression::Variable(ref variable) => {
+                PrefixExpression::Variable(boxed(&**variable, |v| self.variable(v)))
+            }
+            PrefixExpression::FunctionCall(ref call) => {
+                PrefixExpression::FunctionCall(boxed(&**call, |c| self.call(c)))
+            }
+            PrefixExpression::Parenthesis(ref expression) => {
+                PrefixExpression::Parenthesis(boxed(&**expression, |e| self.expression(e)))
+            }
+        }
+    }
+
+    fn variable(&self, variable: &Variable<S>) -> Variable<T> {
+        match *variable {
+            Variable::Name(ref name) => Variable::Name(self.spanned_name(name)),
+            Variable::ArrayAccess { ref from, ref key } => Variable::ArrayAccess {
+                from: boxed(&**from, |p| self.prefix(p)),
+                key: boxed(&**key, |e| self.expression(e)),
+            },
+            Variable::DotAccess { ref from, ref key } => Variable::DotAccess {
+                from: boxed(&**from, |p| self.prefix(p)),
+                key: self.name(key),
+            },
+        }
+    }
+
+    fn call(&self, call: &FunctionCall<S>) -> FunctionCall<T> {
+        match *call {
+            FunctionCall::Static(ref from, ref arguments) => FunctionCall::Static(
+                boxed(&**from, |p| self.prefix(p)),
+                boxed(&**arguments, |a| self.arguments(a)),
+            ),
+            FunctionCall::SelfTaking(ref from, ref method, ref arguments) => {
+                FunctionCall::SelfTaking(
+                    boxed(&**from, |p| self.prefix(p)),
+                    self.name(method),
+                    boxed(&**arguments, |a| self.arguments(a)),
+                )
+            }
+        }
+    }
+
+    fn arguments(&self, arguments: &FunctionArguments<S>) -> FunctionArguments<T> {
+        match *arguments {
+            FunctionArguments::Parenthesis(ref expressions) => FunctionArguments::Parenthesis(
+                expressions.as_ref().map(|list| boxed(&**list, |l| self.expressions(l))),
+            ),
+            FunctionArguments::TableConstructor(ref table) => {
+                FunctionArguments::TableConstructor(boxed(&**table, |t| self.table(t)))
+            }
+            FunctionArguments::String(ref s, form) => {
+                FunctionArguments::String(self.string(s), form)
+            }
+        }
+    }
+
+    fn body(&self, body: &FunctionBody<S>) -> FunctionBody<T> {
+        let parameters = body.0.as_ref().map(|parameters| {
+            Box::new(match **parameters {
+                ParameterList::NameList(ref names) => {
+                    ParameterList::NameList(boxed(&**names, |n| self.names(n)))
+                }
+                ParameterList::ExtendedArguments(ref names) => {
+                    ParameterList::ExtendedArguments(boxed(&**names, |n| self.names(n)))
+                }
+                ParameterList::ExtendedArgumentsVoid => ParameterList::ExtendedArgumentsVoid,
+            })
+        });
+        FunctionBody(parameters, boxed(&*body.1, |b| self.block(b)))
+    }
+
+    fn table(&self, table: &TableConstructor<S>) -> TableConstructor<T> {
+        let field = |field: &Field<S>| match *field {
+            Field::ExpressionForName { ref name, ref equals } => Field::ExpressionForName {
+                name: boxed(&**name, |e| self.expression(e)),
+                equals: boxed(&**equals, |e| self.expression(e)),
+            },
+            Field::Equals { ref name, ref equals } => Field::Equals {
+                name: self.name(name),
+                equals: boxed(&**equals, |e| self.expression(e)),
+            },
+            Field::ArrayStyle(ref expression) => {
+                Field::ArrayStyle(boxed(&**expression, |e| self.expression(e)))
+            }
+        };
+        TableConstructor(table.0.as_ref().map(|fields| {
+            Box::new(FieldList(boxed(&*fields.0, field), list(&fields.1, field)))
+        }))
+    }
+}
@@ -0,0 +1,482 @@
+//! Structural comparison of syntax trees, telling where two trees differ rather than only
+//! whether they do.
+//!
+//! Like `==`, the comparison ignores spans. Once two nodes are found to differ, what is beneath
+//! them isn't compared, so each difference is the outermost one on its path.
+
+use std::fmt::{self, Debug, Display};
+use std::iter;
+use std::mem;
+
+use ast::*;
+use span::Spanned;
+
+/// A place where two trees differ.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Difference {
+    /// The way down from the chunk to the differing nodes, such as
+    /// `["statement 2", "condition", "operator"]`.
+    pub path: Vec<String>,
+    /// The node in the first tree, written as lua source where it can be.
+    pub left: String,
+    /// The node in the second tree.
+    pub right: String,
+}
+
+/// Writes the difference as its path and the two nodes, such as
+/// `statement 2, condition, operator: Plus vs Minus`.
+impl Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.path.is_empty() {
+            f.write_str("chunk")?;
+        }
+        write!(f, "{}: {} vs {}", self.path.join(", "), self.left, self.right)
+    }
+}
+
+/// Finds where two chunks differ, in source order. The list is empty if the chunks are equal.
+///
+/// Statements, values and other lists are counted from 1 in the path, so the first statement is
+/// `statement 1`. A node that only one tree has is shown as `nothing` in the other.
+pub fn diff(a: &Chunk, b: &Chunk) -> Vec<Difference> {
+    let mut differ = Differ { path: Vec::new(), differences: Vec::new() };
+    differ.block(&a.0, &b.0);
+    let show = |comment: &Comment| format!("{:?}", comment.text);
+    differ.list("comment", &a.1, &b.1, |differ, a, b| differ.leaf(a, b), show);
+    differ.differences
+}
+
+const NOTHING: &str = "nothing";
+
+struct Differ {
+    path: Vec<String>,
+    differences: Vec<Difference>,
+}
+
+fn expressions(list: &ExpressionList) -> Vec<&Spanned<Expression>> {
+    iter::once(&*list.0).chain(list.1.iter().flatten()).collect()
+}
+
+/// The expressions of a list that may be absent, which has none.
+fn optional_expressions(list: Option<&ExpressionList>) -> Vec<&Spanned<Expression>> {
+    list.map_or(Vec::new(), expressions)
+}
+
+/// The names of a list, in order.
+fn name_list(list: &NameList) -> Vec<String> {
+    iter::once(&list.0).chain(list.1.iter().flatten()).map(|name| name.node.clone()).collect()
+}
+
+fn show_prefix(prefix: &PrefixExpression) -> String {
+    Expression::PrefixExpression(Box::new(prefix.clone())).to_string()
+}
+
+fn show_variable(variable: &Variable) -> String {
+    show_prefix(&PrefixExpression::Variable(Box::new(variable.clone())))
+}
+
+fn show_call(call: &FunctionCall) -> String {
+    show_prefix(&PrefixExpression::FunctionCall(Box::new(call.clone())))
+}
+
+/// Writes arguments as they would follow the function they're passed to.
+fn show_arguments(arguments: &FunctionArguments) -> String {
+    let function = PrefixExpression::Variable(Box::new(Variable::Name(Spanned::dummy("f".into()))));
+    let call = FunctionCall::Static(Box::new(function), Box::new(arguments.clone()));
+    show_call(&call)[1..].to_string()
+}
+
+fn show_field(field: &Field) -> String {
+    let table = TableConstructor(Some(Box::new(FieldList(Box::new(field.clone()), None))));
+    let shown = Expression::TableConstructor(Box::new(table)).to_string();
+    shown.trim_start_matches("{ ").trim_end_matches(" }").to_string()
+}
+
+fn show_function_name(name: &FunctionName) -> String {
+    let mut shown = name.first_dot_access.node.clone();
+    for part in name.rest_dot_access.iter().flatten() {
+        shown = shown + "." + part;
+    }
+    if let Some(ref method) = name.self_name {
+        shown = shown + ":" + method;
+    }
+    shown
+}
+
+fn same_variant<T>(a: &T, b: &T) -> bool {
+    mem::discriminant(a) == mem::discriminant(b)
+}
+
+impl Differ {
+    /// Compares nodes found by following `label` from the current nodes.
+    fn at<F: FnOnce(&mut Differ)>(&mut self, label: &str, compare: F) {
+        self.path.push(label.to_string());
+        compare(self);
+        self.path.pop();
+    }
+
+    fn report(&mut self, left: String, right: String) {
+        self.differences.push(Difference { path: self.path.clone(), left, right });
+    }
+
+    /// Compares nodes that aren't looked inside, reporting them with `Debug`.
+    fn leaf<T: PartialEq + Debug>(&mut self, a: &T, b: &T) {
+        if a != b {
+            self.report(format!("{:?}", a), format!("{:?}", b));
+        }
+    }
+
+    /// Compares names, reporting them as they're written.
+    fn name(&mut self, a: &str, b: &str) {
+        if a != b {
+            self.report(a.to_string(), b.to_string());
+        }
+    }
+
+    /// Compares two lists item by item, labelling each with its position after `label`.
+    fn list<T, C, S>(&mut self, label: &str, a: &[T], b: &[T], mut compare: C, show: S)
+    where
+        C: FnMut(&mut Differ, &T, &T),
+        S: Fn(&T) -> String,
+    {
+        for i in 0..a.len().max(b.len()) {
+            self.at(&format!("{} {}", label, i + 1), |differ| match (a.get(i), b.get(i)) {
+                (Some(a), Some(b)) => compare(differ, a, b),
+                (Some(a), None) => differ.report(show(a), NOTHING.to_string()),
+                (None, Some(b)) => differ.report(NOTHING.to_string(), show(b)),
+                (None, None) => unreachable!(),
+            });
+        }
+    }
+
+    fn optional<T, C, S>(&mut self, a: Option<&T>, b: Option<&T>, compare: C, show: S)
+    where
+        C: FnOnce(&mut Differ, &T, &T),
+        S: Fn(&T) -> String,
+    {
+        match (a, b) {
+            (Some(a), Some(b)) => compare(self, a, b),
+            (Some(a), None) => self.report(show(a), NOTHING.to_string()),
+            (None, Some(b)) => self.report(NOTHING.to_string(), show(b)),
+            (None, None) => {}
+        }
+    }
+
+    fn block(&mut self, a: &Block, b: &Block) {
+        let (a_statements, b_statements) = (a.0.as_deref(), b.0.as_deref());
+        self.list(
+            "statement",
+            a_statements.unwrap_or(&[]),
+            b_statements.unwrap_or(&[]),
+            |differ, a, b| differ.statement(&a.node, &b.node),
+            |statement| statement.node.to_string(),
+        );
+        self.at("return", |differ| {
+            differ.optional(
+                a.1.as_deref(),
+                b.1.as_deref(),
+                |differ, a, b| {
+                    let a = optional_expressions(a.0.as_deref());
+                    let b = optional_expressions(b.0.as_deref());
+                    differ.expression_list(&a, &b)
+                },
+                |r| Block(None, Some(Box::new(r.clone()))).to_string(),
+            )
+        });
+    }
+
+    fn expression_list(&mut self, a: &[&Spanned<Expression>], b: &[&Spanned<Expression>]) {
+        self.list(
+            "value",
+            a,
+            b,
+            |differ, a, b| differ.expression(&a.node, &b.node),
+            |e| e.node.to_string(),
+        );
+    }
+
+    fn statement(&mut self, a: &Statement, b: &Statement) {
+        use ast::Statement::*;
+        if !same_variant(a, b) {
+            return self.report(a.to_string(), b.to_string());
+        }
+        match (a, b) {
+            (Assignment(a_targets, a_values), Assignment(b_targets, b_values)) => {
+                let targets = |list: &VariableList| {
+                    iter::once(&*list.first).chain(list.rest.iter().flatten()).cloned().collect()
+                };
+                let (a_targets, b_targets): (Vec<_>, Vec<_>) =
+                    (targets(a_targets), targets(b_targets));
+                self.list("target", &a_targets, &b_targets, Differ::variable, show_variable);
+                self.expression_list(&expressions(a_values), &expressions(b_values));
+            }
+            (FunctionCall(a), FunctionCall(b)) => self.call(a, b),
+            (Label(a), Label(b)) | (Goto(a), Goto(b)) => {
+                self.at("label", |differ| differ.name(&a.0, &b.0))
+            }
+            (Do(a), Do(b)) => self.at("block", |differ| differ.block(a, b)),
+            (While { exp: a_exp, do_: a_do }, While { exp: b_exp, do_: b_do }) => {
+                self.at("condition", |differ| differ.expression(&a_exp.node, &b_exp.node));
+                self.at("block", |differ| differ.block(a_do, b_do));
+            }
+            (
+                Repeat { block: a_block, until: a_until },
+                Repeat { block: b_block, until: b_until },
+            ) => {
+                self.at("block", |differ| differ.block(a_block, b_block));
+                self.at("condition", |differ| differ.expression(&a_until.node, &b_until.node));
+            }
+            (
+                If { condition: a_condition, then: a_then, elseifs: a_elseifs, else_: a_else },
+                If { condition: b_condition, then: b_then, elseifs: b_elseifs, else_: b_else },
+            ) => {
+                self.at("condition", |differ| {
+                    differ.expression(&a_condition.node, &b_condition.node)
+                });
+                self.at("then", |differ| differ.block(a_then, b_then));
+                self.list(
+                    "elseif",
+                    a_elseifs,
+                    b_elseifs,
+                    |differ, (a_condition, a_block), (b_condition, b_block)| {
+                        differ.at("condition", |differ| {
+                            differ.expression(&a_condition.node, &b_condition.node)
+                        });
+                        differ.at("block", |differ| differ.block(a_block, b_block));
+                    },
+                    |(condition, _)| format!("elseif {} then", condition.node),
+                );
+                self.at("else", |differ| {
+                    differ.optional(
+                        a_else.as_deref(),
+                        b_else.as_deref(),
+                        Differ::block,
+                        |block| block.to_string(),
+                    )
+                });
+            }
+            (
+                ForStepping { name: a_name, from: a_from, to: a_to, step: a_step, block: a_block },
+                ForStepping { name: b_name, from: b_from, to: b_to, step: b_step, block: b_block },
+            ) => {
+                self.at("variable", |differ| differ.name(&a_name.node, &b_name.node));
+                self.at("start", |differ| differ.expression(&a_from.node, &b_from.node));
+                self.at("limit", |differ| differ.expression(&a_to.node, &b_to.node));
+                self.at("step", |differ| {
+                    differ.optional(
+                        a_step.as_deref(),
+                        b_step.as_deref(),
+                        |differ, a, b| differ.expression(&a.node, &b.node),
+                        |step| step.node.to_string(),
+                    )
+                });
+                self.at("block", |differ| differ.block(a_block, b_block));
+            }
+            (
+                ForIn { name_list: a_names, in_: a_values, do_: a_do },
+                ForIn { name_list: b_names, in_: b_values, do_: b_do },
+            ) => {
+                self.names(a_names, b_names);
+                self.expression_list(&expressions(a_values), &expressions(b_values));
+                self.at("block", |differ| differ.block(a_do, b_do));
+            }
+            (Function(a_name, a_body), Function(b_name, b_body)) => {
+                self.at("name", |differ| {
+                    differ.name(&show_function_name(a_name), &show_function_name(b_name))
+                });
+                self.at("body", |differ| differ.function_body(a_body, b_body));
+            }
+            (
+                LocalFunction { name: a_name, body: a_body },
+                LocalFunction { name: b_name, body: b_body },
+            ) => {
+                self.at("name", |differ| differ.name(&a_name.node, &b_name.node));
+                self.at("body", |differ| differ.function_body(a_body, b_body));
+            }
+            (LocalVariableBinding(a_names, a_values), LocalVariableBinding(b_names, b_values)) => {
+                let names = |list: &AttributeNameList| {
+                    iter::once(&list.0).chain(list.1.iter().flatten()).cloned().collect()
+                };
+                let (a_names, b_names): (Vec<_>, Vec<_>) = (names(a_names), names(b_names));
+                let show = |name: &AttributeName| match name.attribute {
+                    Some(attribute) => format!("{} <{:?}>", name.name.node, attribute),
+                    None => name.name.node.clone(),
+                };
+                self.list(
+                    "name",
+                    &a_names,
+                    &b_names,
+                    |differ, a, b| {
+                        differ.name(&a.name.node, &b.name.node);
+                        if a.name.node == b.name.node {
+                            let (a, b) = (&a.attribute, &b.attribute);
+                            differ.at("attribute", |differ| differ.leaf(a, b));
+                        }
+                    },
+                    show,
+                );
+                let a_values = optional_expressions(a_values.as_ref());
+                let b_values = optional_expressions(b_values.as_ref());
+                self.expression_list(&a_values, &b_values);
+            }
+            _ => {}
+        }
+    }
+
+    fn names(&mut self, a: &NameList, b: &NameList) {
+        let (a, b) = (name_list(a), name_list(b));
+        self.list("name", &a, &b, |differ, a, b| differ.name(a, b), String::clone);
+    }
+
+    fn function_body(&mut self, a: &FunctionBody, b: &FunctionBody) {
+        let parameters = |body: &FunctionBody| -> (Vec<String>, bool) {
+            let (names, varargs) = match body.0.as_deref() {
+                Some(ParameterList::NameList(names)) => (Some(&**names), false),
+                Some(ParameterList::ExtendedArguments(names)) => (Some(&**names), true),
+                Some(ParameterList::ExtendedArgumentsVoid) => (None, true),
+                None => (None, false),
+            };
+            let names = names.map_or(Vec::new(), name_list);
+            (names, varargs)
+        };
+        let ((a_names, a_varargs), (b_names, b_varargs)) = (parameters(a), parameters(b));
+        self.list("parameter", &a_names, &b_names, |differ, a, b| differ.name(a, b), String::clone);
+        if a_varargs != b_varargs {
+            let show = |varargs| if varargs { "..." } else { NOTHING }.to_string();
+            self.at("varargs", |differ| differ.report(show(a_varargs), show(b_varargs)));
+        }
+        self.block(&a.1, &b.1);
+    }
+
+    fn expression(&mut self, a: &Expression, b: &Expression) {
+        use ast::Expression::*;
+        if !same_variant(a, b) {
+            return self.report(a.to_string(), b.to_string());
+        }
+        match (a, b) {
+            (FunctionDefine(a), FunctionDefine(b)) => {
+                self.at("body", |differ| differ.function_body(&a.0, &b.0))
+            }
+            (PrefixExpression(a), PrefixExpression(b)) => self.prefix(a, b),
+            (TableConstructor(a), TableConstructor(b)) => self.table(a, b),
+            (BinaryOperation(a_op, a_left, a_right), BinaryOperation(b_op, b_left, b_right)) => {
+                self.at("operator", |differ| differ.leaf(a_op, b_op));
+                self.at("left", |differ| differ.expression(&a_left.node, &b_left.node));
+                self.at("right", |differ| differ.expression(&a_right.node, &b_right.node));
+            }
+            (UnaryOperation(a_op, a_operand), UnaryOperation(b_op, b_operand)) => {
+                self.at("operator", |differ| differ.leaf(a_op, b_op));
+                self.at("operand", |differ| differ.expression(&a_operand.node, &b_operand.node));
+            }
+            // Literals have nothing inside them to compare.
+            _ if a != b => self.report(a.to_string(), b.to_string()),
+            _ => {}
+        }
+    }
+
+    fn prefix(&mut self, a: &PrefixExpression, b: &PrefixExpression) {
+        use ast::PrefixExpression::*;
+        match (a, b) {
+            (Variable(a), Variable(b)) => self.variable(a, b),
+            (FunctionCall(a), FunctionCall(b)) => self.call(a, b),
+            (Parenthesis(a), Parenthesis(b)) => {
+                self.at("parenthesized", |differ| differ.expression(&a.node, &b.node))
+            }
+            _ => self.report(show_prefix(a), show_prefix(b)),
+        }
+    }
+
+    fn variable(&mut self, a: &Variable, b: &Variable) {
+        use ast::Variable::*;
+        match (a, b) {
+            (Name(a_name), Name(b_name)) => self.name(&a_name.node, &b_name.node),
+            (
+                ArrayAccess { from: a_from, key: a_key },
+                ArrayAccess { from: b_from, key: b_key },
+            ) => {
+                self.at("object", |differ| differ.prefix(a_from, b_from));
+                self.at("key", |differ| differ.expression(&a_key.node, &b_key.node));
+            }
+            (DotAccess { from: a_from, key: a_key }, DotAccess { from: b_from, key: b_key }) => {
+                self.at("object", |differ| differ.prefix(a_from, b_from));
+                self.at("field", |differ| differ.name(a_key, b_key));
+            }
+            _ => self.report(show_variable(a), show_variable(b)),
+        }
+    }
+
+    fn call(&mut self, a: &FunctionCall, b: &FunctionCall) {
+        use ast::FunctionCall::*;
+        match (a, b) {
+            (Static(a_function, a_arguments), Static(b_function, b_arguments)) => {
+                self.at("function", |differ| differ.prefix(a_function, b_function));
+                self.arguments(a_arguments, b_arguments);
+            }
+            (
+                SelfTaking(a_object, a_method, a_arguments),
+                SelfTaking(b_object, b_method, b_arguments),
+            ) => {
+                self.at("object", |differ| differ.prefix(a_object, b_object));
+                self.at("method", |differ| differ.name(a_method, b_method));
+                self.arguments(a_arguments, b_arguments);
+            }
+            _ => self.report(show_call(a), show_call(b)),
+        }
+    }
+
+    fn arguments(&mut self, a: &FunctionArguments, b: &FunctionArguments) {
+        use ast::FunctionArguments::*;
+        match (a, b) {
+            (Parenthesis(a_list), Parenthesis(b_list)) => {
+                self.list(
+                    "argument",
+                    &optional_expressions(a_list.as_deref()),
+                    &optional_expressions(b_list.as_deref()),
+                    |differ, a, b| differ.expression(&a.node, &b.node),
+                    |e| e.node.to_string(),
+                );
+            }
+            (TableConstructor(a), TableConstructor(b)) => {
+                self.at("argument 1", |differ| differ.table(a, b))
+            }
+            _ if a != b => self.report(show_arguments(a), show_arguments(b)),
+            _ => {}
+        }
+    }
+
+    fn table(&mut self, a: &TableConstructor, b: &TableConstructor) {
+        let fields = |table: &TableConstructor| -> Vec<Field> {
+            match table.0 {
+                Some(ref fields) => {
+                    iter::once(&*fields.0).chain(fields.1.iter().flatten()).cloned().collect()
+                }
+                None => Vec::new(),
+            }
+        };
+        self.list("field", &fields(a), &fields(b), Differ::field, show_field);
+    }
+
+    fn field(&mut self, a: &Field, b: &Field) {
+        use ast::Field::*;
+        match (a, b) {
+            (
+                ExpressionForName { name: a_name, equals: a_value },
+                ExpressionForName { name: b_name, equals: b_value },
+            ) => {
+                self.at("key", |differ| differ.expression(&a_name.node, &b_name.node));
+                self.at("value", |differ| differ.expression(&a_value.node, &b_value.node));
+            }
+            (
+                Equals { name: a_name, equals: a_value },
+                Equals { name: b_name, equals: b_value },
+            ) => {
+                self.at("key", |differ| differ.name(a_name, b_name));
+                self.at("value", |differ| differ.expression(&a_value.node, &b_value.node));
+            }
+            (ArrayStyle(a), ArrayStyle(b)) => {
+                self.at("value", |differ| differ.expression(&a.node, &b.node))
+            }
+            _ => self.report(show_field(a), show_field(b)),
+        }
+    }
+}
@@ -0,0 +1,380 @@
+//! Exports of the syntax tree in other notations, for looking at its shape.
+//!
+//! `to_dot` writes the tree in the DOT language of graphviz, described at
+//! https://graphviz.org/doc/info/lang.html, so it can be drawn with `dot -Tsvg`. `to_sexpr` writes
+//! it as an s-expression on one line, which is compact enough to compare in tests, and
+//! `to_sexpr_pretty` writes the same s-expression over several lines, for golden files of the
+//! parser's output.
+//!
+//! Every node is named by its kind, such as `while` or `call`, along with the value it holds if
+//! it's a literal, a name or an operator. Literals are written as lua source, so a string keeps its
+//! quotes.
+
+use std::fmt::Write;
+use std::iter;
+
+use ast::*;
+use span::Spanned;
+
+/// Writes the chunk as a graphviz digraph, with a box for each node of the tree and an edge from
+/// each node to the nodes beneath it, in order.
+///
+/// Operators are labelled with their sigil, such as `+` or `..`, and other nodes with their kind
+/// and value, such as `num 1` or `name x`.
+///
+/// # Example
+/// ```
+/// use buildlua::export::to_dot;
+/// use buildlua::parser::parse;
+///
+/// let dot = to_dot(&parse("return 1 + 2").unwrap());
+/// assert!(dot.contains("[label=\"+\"]"));
+/// ```
+pub fn to_dot(chunk: &Chunk) -> String {
+    let mut out = String::from("digraph ast {\n    node [shape=box];\n");
+    let mut count = 0;
+    write_dot(&block(&chunk.0), &mut count, &mut out);
+    out.push_str("}\n");
+    out
+}
+
+/// Writes the node and the nodes beneath it, numbering them from `count`, and returns the number
+/// of the node.
+fn write_dot(node: &Node, count: &mut usize, out: &mut String) -> usize {
+    let id = *count;
+    *count += 1;
+    let label = match (node.kind, &node.value) {
+        ("binop", Some(sigil)) | ("unop", Some(sigil)) => sigil.clone(),
+        (kind, Some(value)) => format!("{} {}", kind, value),
+        (kind, None) => kind.to_string(),
+    };
+    // Writing to a string can't fail.
+    let _ = writeln!(out, "    n{} [label=\"{}\"];", id, escape_label(&label));
+    for child in &node.children {
+        let child = write_dot(child, count, out);
+        let _ = writeln!(out, "    n{} -> n{};", id, child);
+    }
+    id
+}
+
+/// Writes the chunk as an s-expression, with each node in parentheses after its kind and value.
+/// Nodes with neither a value nor nodes beneath them, such as `nil` or `break`, are written without
+/// parentheses.
+///
+/// # Example
+/// ```
+/// use buildlua::export::to_sexpr;
+/// use buildlua::parser::parse;
+///
+/// let sexpr = to_sexpr(&parse("return 1 + 2").unwrap());
+/// assert_eq!(sexpr, "(block (return (binop + (num 1) (num 2))))");
+/// ```
+pub fn to_sexpr(chunk: &Chunk) -> String {
+    let mut out = String::new();
+    write_sexpr(&block(&chunk.0), &mut out);
+    out
+}
+
+/// Writes the chunk as the s-expression of `to_sexpr`, broken over lines so that each line fits in
+/// 80 columns where it can. A node too wide for its line has each node beneath it on a line of its
+/// own, indented by two spaces more than it. The output ends with a newline.
+///
+/// Spans and layout aren't written, so the output only changes when the tree does, which makes it
+/// fit for checking the trees the parser builds against files of the expected output.
+///
+/// # Example
+/// ```
+/// use buildlua::export::to_sexpr_pretty;
+/// use buildlua::parser::parse;
+///
+/// let sexpr = to_sexpr_pretty(&parse("return 1 + 2").unwrap());
+/// assert_eq!(sexpr, "(block (return (binop + (num 1) (num 2))))\n");
+/// ```
+pub fn to_sexpr_pretty(chunk: &Chunk) -> String {
+    let mut out = String::new();
+    write_pretty(&block(&chunk.0), 0, &mut out);
+    out.push('\n');
+    out
+}
+
+/// The column past which `to_sexpr_pretty` breaks a node over lines.
+const PRETTY_WIDTH: usize = 80;
+
+fn write_pretty(node: &Node, indent: usize, out: &mut String) {
+    let mut flat = String::new();
+    write_sexpr(node, &mut flat);
+    if node.children.is_empty() || indent + flat.len() <= PRETTY_WIDTH {
+        out.push_str(&flat);
+        return;
+    }
+    out.push('(');
+    out.push_str(node.kind);
+    if let Some(ref value) = node.value {
+        out.push(' ');
+        out.push_str(value);
+    }
+    for child in &node.children {
+        out.push('\n');
+        out.extend(iter::repeat_n(' ', indent + 2));
+        write_pretty(child, indent + 2, out);
+    }
+    out.push(')');
+}
+
+fn write_sexpr(node: &Node, out: &mut String) {
+    if node.value.is_none() && node.children.is_empty() {
+        out.push_str(node.kind);
+        return;
+    }
+    out.push('(');
+    out.push_str(node.kind);
+    if let Some(ref value) = node.value {
+        out.push(' ');
+        out.push_str(value);
+    }
+    for child in &node.children {
+        out.push(' ');
+        write_sexpr(child, out);
+    }
+    out.push(')');
+}
+
+/// Escapes a label for a quoted DOT string.
+fn escape_label(label: &str) -> String {
+    let mut escaped = String::with_capacity(label.len());
+    for c in label.chars() {
+        match c {
+            '"' | '\\' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' => escaped.push_str("\\n"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// A node of the tree as it's exported.
+struct Node {
+    kind: &'static str,
+    value: Option<String>,
+    children: Vec<Node>,
+}
+
+impl Node {
+    fn new(kind: &'static str, children: Vec<Node>) -> Node {
+        Node { kind, value: None, children }
+    }
+
+    fn leaf(kind: &'static str) -> Node {
+        Node::new(kind, Vec::new())
+    }
+
+    fn with_value(kind: &'static str, value: String, children: Vec<Node>) -> Node {
+        Node { kind, value: Some(value), children }
+    }
+}
+
+fn block(block: &Block) -> Node {
+    let mut children: Vec<Node> = block.statements().map(statement).collect();
+    if let Some(ref return_statement) = block.1 {
+        let values = return_statement.0.as_ref().map_or(Vec::new(), |list| expressions(list));
+        children.push(Node::new("return", values));
+    }
+    Node::new("block", children)
+}
+
+fn statement(statement: &Spanned<Statement>) -> Node {
+    match statement.node {
+        Statement::Semicolon => Node::leaf("semicolon"),
+        Statement::Assignment(ref targets, ref values) => {
+            let targets = iter::once(&*targets.first).chain(targets.rest.iter().flatten());
+            Node::new("assign", vec![
+                Node::new("targets", targets.map(variable).collect()),
+                Node::new("values", expressions(values)),
+            ])
+        }
+        Statement::FunctionCall(ref call) => function_call(call),
+        Statement::Label(ref label) => Node::with_value("label", label.0.clone(), Vec::new()),
+        Statement::Break => Node::leaf("break"),
+        Statement::Goto(ref label) => Node::with_value("goto", label.0.clone(), Vec::new()),
+        Statement::Do(ref body) => Node::new("do", vec![block(body)]),
+        Statement::While { ref exp, ref do_ } => {
+            Node::new("while", vec![expression(exp), block(do_)])
+        }
+        Statement::Repeat { block: ref body, ref until } => {
+            Node::new("repeat", vec![block(body), expression(until)])
+        }
+        Statement::If { ref condition, ref then, ref elseifs, ref else_ } => {
+            let mut children = vec![expression(condition), block(then)];
+            for (condition, body) in elseifs {
+                children.push(Node::new("elseif", vec![expression(condition), block(body)]));
+            }
+            if let Some(ref body) = *else_ {
+                children.push(Node::new("else", vec![block(body)]));
+            }
+            Node::new("if", children)
+        }
+        Statement::ForStepping { ref name, ref from, ref to, ref step, block: ref body } => {
+            let mut children = vec![expression(from), expression(to)];
+            children.extend(step.iter().map(|step| expression(step)));
+            children.push(block(body));
+            Node::with_value("for", name.node.clone(), children)
+        }
+        Statement::ForIn { ref name_list, ref in_, ref do_ } => {
+            Node::new("for-in", vec![
+                Node::new("names", names(name_list)),
+                Node::new("values", expressions(in_)),
+                block(do_),
+            ])
+        }
+        Statement::Function(ref name, ref body) => {
+            let mut written = name.first_dot_access.node.clone();
+            for key in name.rest_dot_access.iter().flatten() {
+                written.push('.');
+                written.push_str(key);
+            }
+            if let Some(ref self_name) = name.self_name {
+                written.push(':');
+                written.push_str(self_name);
+            }
+            Node::with_value("function", written, function_body(body))
+        }
+        Statement::LocalFunction { ref name, ref body } => {
+            Node::with_value("local-function", name.node.clone(), function_body(body))
+        }
+        Statement::LocalVariableBinding(ref list, ref values) => {
+            let declared = iter::once(&list.0).chain(list.1.iter().flatten()).map(|name| {
+                let mut written = name.name.node.clone();
+                match name.attribute {
+                    Some(Attribute::Const) => written.push_str(" <const>"),
+                    Some(Attribute::Close) => written.push_str(" <close>"),
+                    None => {}
+                }
+                Node::with_value("name", written, Vec::new())
+            });
+            let mut children = vec![Node::new("names", declared.collect())];
+            if let Some(ref values) = *values {
+                children.push(Node::new("values", expressions(values)));
+            }
+            Node::new("local", children)
+        }
+    }
+}
+
+fn expressions(list: &ExpressionList) -> Vec<Node> {
+    iter::once(&*list.0).chain(list.1.iter().flatten()).map(expression).collect()
+}
+
+fn names(list: &NameList) -> Vec<Node> {
+    iter::once(&list.0)
+        .chain(list.1.iter().flatten())
+        .map(|name| Node::with_value("name", name.node.clone(), Vec::new()))
+        .collect()
+}
+
+/// The parameters and block of a function.
+fn function_body(body: &FunctionBody) -> Vec<Node> {
+    let parameters = match body.0.as_deref() {
+        Some(ParameterList::NameList(list)) => names(list),
+        Some(ParameterList::ExtendedArguments(list)) => {
+            let mut parameters = names(list);
+            parameters.push(Node::leaf("..."));
+            parameters
+        }
+        Some(ParameterList::ExtendedArgumentsVoid) => vec![Node::leaf("...")],
+        None => Vec::new(),
+    };
+    vec![Node::new("parameters", parameters), block(&body.1)]
+}
+
+fn expression(spanned: &Spanned<Expression>) -> Node {
+    match spanned.node {
+        Expression::Nil => Node::leaf("nil"),
+        Expression::False => Node::leaf("false"),
+        Expression::True => Node::leaf("true"),
+        Expression::Integer(..) | Expression::Float(..) => {
+            Node::with_value("num", spanned.node.to_string(), Vec::new())
+        }
+        Expression::String(..) => Node::with_value("str", spanned.node.to_string(), Vec::new()),
+        Expression::ExtendedArgumentAccess => Node::leaf("..."),
+        Expression::Error => Node::leaf("error"),
+        Expression::FunctionDefine(ref define) => Node::new("function", function_body(&define.0)),
+        Expression::PrefixExpression(ref inner) => prefix(inner),
+        Expression::TableConstructor(ref table) => Node::new("table", fields(table)),
+        Expression::BinaryOperation(op, ref left, ref right) => {
+            Node::with_value("binop", op.to_string(), vec![expression(left), expression(right)])
+        }
+        Expression::UnaryOperation(op, ref operand) => {
+            Node::with_value("unop", op.to_string(), vec![expression(operand)])
+        }
+    }
+}
+
+fn prefix(prefix: &PrefixExpression) -> Node {
+    match *prefix {
+        PrefixExpression::Variable(ref inner) => variable(inner),
+        PrefixExpression::FunctionCall(ref call) => function_call(call),
+        PrefixExpression::Parenthesis(ref inner) => Node::new("paren", vec![expression(inner)]),
+    }
+}
+
+fn variable(variable: &Variable) -> Node {
+    match *variable {
+        Variable::Name(ref name) => Node::with_value("name", name.node.clone(), Vec::new()),
+        Variable::ArrayAccess { ref from, ref key } => {
+            Node::new("index", vec![prefix(from), expression(key)])
+        }
+        Variable::DotAccess { ref from, ref key } => {
+            Node::with_value("dot", key.clone(), vec![prefix(from)])
+        }
+    }
+}
+
+fn function_call(call: &FunctionCall) -> Node {
+    match *call {
+        FunctionCall::Static(ref function, ref args) => {
+            let mut children = vec![prefix(function)];
+            children.extend(arguments(args));
+            Node::new("call", children)
+        }
+        FunctionCall::SelfTaking(ref object, ref method, ref args) => {
+            let mut children = vec![prefix(object)];
+            children.extend(arguments(args));
+            Node::with_value("method", method.clone(), children)
+        }
+    }
+}
+
+fn arguments(args: &FunctionArguments) -> Vec<Node> {
+    match *args {
+        FunctionArguments::Parenthesis(ref list) => {
+            list.as_ref().map_or(Vec::new(), |list| expressions(list))
+        }
+        FunctionArguments::TableConstructor(ref table) => vec![Node::new("table", fields(table))],
+        FunctionArguments::String(ref s, form) => {
+            let written = Expression::String(s.clone(), form).to_string();
+            vec![Node::with_value("str", written, Vec::new())]
+        }
+    }
+}
+
+fn fields(table: &TableConstructor) -> Vec<Node> {
+    let list = match table.0 {
+        Some(ref list) => list,
+        None => return Vec::new(),
+    };
+    let fields = iter::once(&*list.0).chain(list.1.iter().flatten());
+    fields
+        .map(|field| match *field {
+            Field::ExpressionForName { ref name, ref equals } => {
+                Node::new("field", vec![expression(name), expression(equals)])
+            }
+            Field::Equals { ref name, ref equals } => {
+                Node::with_value("field", name.clone(), vec![expression(equals)])
+            }
+            Field::ArrayStyle(ref value) => expression(value),
+        })
+        .collect()
+}
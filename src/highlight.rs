@@ -0,0 +1,174 @@
+//! Classification of the tokens of lua source for syntax highlighting, as editors do with the
+//! semantic tokens of the language server protocol.
+//!
+//! Keywords, numbers, strings and comments are told apart by the lexer alone. A name is classified
+//! by what it refers to, as worked out by `resolve::resolve_names`: a use of a local has the kind
+//! of its declaration, so a parameter stays a parameter wherever it's read. Source with errors is
+//! parsed with `parser::parse_recovering`, so the parts of it that do parse are still classified.
+//! Operators and other punctuation aren't classified.
+
+use std::collections::HashMap;
+use std::iter;
+
+use ast::*;
+use lexer::{Lexer, Token};
+use parser::parse_recovering;
+use resolve::{resolve_names, Binding};
+use span::{Span, Spanned};
+use visit::{self, Visitor};
+
+/// What a token is, for choosing how to highlight it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SemanticKind {
+    /// A reserved word, including `nil`, `true` and `false`, or a local's attribute such as
+    /// `const`.
+    Keyword,
+    /// The name of a function declared by a function statement, or a local holding a function it
+    /// was declared with.
+    Function,
+    /// A parameter of a function, including the hidden `self` of a method.
+    Parameter,
+    Local,
+    Global,
+    /// The key of a field, such as `b` in `a.b` or `{b = 1}`, or the name of a method.
+    Field,
+    /// The name of a label, where it's defined or in a `goto`.
+    Label,
+    Number,
+    String,
+    Comment,
+}
+
+/// A classified token, with the span of the source it takes up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SemanticToken {
+    pub span: Span,
+    pub kind: SemanticKind,
+}
+
+/// Classifies the tokens and comments of `src`, in the order they appear in it.
+///
+/// # Example
+/// ```
+/// use buildlua::highlight::{semantic_tokens, SemanticKind};
+///
+/// let kinds: Vec<_> = semantic_tokens("local x = 1").iter().map(|token| token.kind).collect();
+/// assert_eq!(kinds, [SemanticKind::Keyword, SemanticKind::Local, SemanticKind::Number]);
+/// ```
+pub fn semantic_tokens(src: &str) -> Vec<SemanticToken> {
+    let (chunk, _) = parse_recovering(src);
+    let resolution = resolve_names(&chunk);
+    let mut declarations = Declarations(HashMap::new());
+    visit::walk_chunk(&mut declarations, &chunk);
+    let declarations = declarations.0;
+
+    let mut lexer = Lexer::new(src);
+    let mut classified = Vec::new();
+    let mut previous = None;
+    // Like parsing, classifying stops at a malformed token.
+    while let Some(Ok(token)) = lexer.next_spanned() {
+        let kind = match token.node {
+            Token::Name(_) => Some(match declarations.get(&token.span) {
+                Some(&kind) => kind,
+                None => match resolution.binding(token.span) {
+                    Some(Binding::Local(declared)) | Some(Binding::Upvalue(declared)) => {
+                        declarations.get(&declared).cloned().unwrap_or(SemanticKind::Local)
+                    }
+                    Some(Binding::Global) => SemanticKind::Global,
+                    // Names that aren't variables are told apart by what comes before them.
+                    None => match previous {
+                        Some(Token::DoubleColon) | Some(Token::Goto) => SemanticKind::Label,
+                        Some(Token::Less) => SemanticKind::Keyword,
+                        _ => SemanticKind::Field,
+                    },
+                },
+            }),
+            Token::Integer(_) | Token::Float(_) => Some(SemanticKind::Number),
+            Token::String(..) => Some(SemanticKind::String),
+            ref token if is_keyword(token) => Some(SemanticKind::Keyword),
+            _ => None,
+        };
+        if let Some(kind) = kind {
+            classified.push(SemanticToken { span: token.span, kind });
+        }
+        previous = Some(token.node);
+    }
+    let comments = chunk.1.iter().map(|comment| SemanticToken {
+        span: comment.span,
+        kind: SemanticKind::Comment,
+    });
+    classified.extend(comments);
+    classified.sort_by_key(|token| token.span.start);
+    classified
+}
+
+fn is_keyword(token: &Token) -> bool {
+    matches!(
+        *token,
+        Token::And | Token::Break | Token::Do | Token::Else | Token::Elseif | Token::End |
+            Token::False | Token::For | Token::Function | Token::Goto | Token::If | Token::In |
+            Token::Local | Token::Nil | Token::Not | Token::Or | Token::Repeat | Token::Return |
+            Token::Then | Token::True | Token::Until | Token::While
+    )
+}
+
+/// The kinds of the names declaring locals and functions, keyed by the span of the name. The
+/// hidden `self` of a method is keyed by the span of the function statement, as in
+/// `resolve::Binding`.
+struct Declarations(HashMap<Span, SemanticKind>);
+
+impl Declarations {
+    fn declare(&mut self, name: &Spanned<String>, kind: SemanticKind) {
+        self.0.insert(name.span, kind);
+    }
+}
+
+impl Visitor for Declarations {
+    fn visit_statement(&mut self, statement: &Spanned<Statement>) {
+        match statement.node {
+            Statement::LocalVariableBinding(ref names, ref values) => {
+                let names = iter::once(&names.0).chain(names.1.iter().flatten());
+                let values: Vec<_> = match *values {
+                    Some(ref list) => iter::once(&*list.0).chain(list.1.iter().flatten()).collect(),
+                    None => Vec::new(),
+                };
+                for (i, name) in names.enumerate() {
+                    let function = values
+                        .get(i)
+                        .is_some_and(|value| matches!(value.node, Expression::FunctionDefine(_)));
+                    let kind = if function { SemanticKind::Function } else { SemanticKind::Local };
+                    self.declare(&name.name, kind);
+                }
+            }
+            Statement::LocalFunction { ref name, .. } => self.declare(name, SemanticKind::Function),
+            Statement::ForStepping { ref name, .. } => self.declare(name, SemanticKind::Local),
+            Statement::ForIn { ref name_list, .. } => {
+                for name in iter::once(&name_list.0).chain(name_list.1.iter().flatten()) {
+                    self.declare(name, SemanticKind::Local);
+                }
+            }
+            Statement::Function(ref name, _) => {
+                if name.rest_dot_access.is_none() && name.self_name.is_none() {
+                    self.declare(&name.first_dot_access, SemanticKind::Function);
+                }
+                if name.self_name.is_some() {
+                    self.0.insert(statement.span, SemanticKind::Parameter);
+                }
+            }
+            _ => {}
+        }
+        visit::walk_statement(self, statement);
+    }
+
+    fn visit_function_body(&mut self, body: &FunctionBody) {
+        match body.0.as_deref() {
+            Some(ParameterList::NameList(list)) | Some(ParameterList::ExtendedArguments(list)) => {
+                for name in iter::once(&list.0).chain(list.1.iter().flatten()) {
+                    self.declare(name, SemanticKind::Parameter);
+                }
+            }
+            Some(ParameterList::ExtendedArgumentsVoid) | None => {}
+        }
+        visit::walk_function_body(self, body);
+    }
+}
@@ -0,0 +1,141 @@
+//! Interning of identifiers, giving each distinct name a `Symbol` that compares in constant time.
+//!
+//! A `StringInterner` keeps one copy of every name interned in it, however many times the name is
+//! used, and can be shared by the parses of many files. `parser::parse_interned` parses a chunk
+//! while interning every identifier in it, into a tree in the `Interned` storage, which holds the
+//! symbol of each name in place of its text. `StringInterner::resolve_chunk` gives the names back.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use ast::{Chunk, Owned, Storage};
+use convert::Convert;
+use parser::Store;
+
+/// An interned name, standing for its text in the interner it came from.
+///
+/// Symbols of the same interner are equal exactly when their names are, and are ordered by when
+/// their names were first interned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Symbol(u32);
+
+/// Names held as the symbols of an interner. Strings and other text are owned, as in `Owned`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Interned;
+
+impl Storage for Interned {
+    type Name = Symbol;
+    type Str = Vec<u8>;
+    type Text = String;
+}
+
+/// A whole chunk whose names are symbols.
+pub type InternedChunk = Chunk<Interned>;
+
+impl Symbol {
+    /// How many names were interned before this one.
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// Names interned so far, each stored once.
+#[derive(Debug, Clone, Default)]
+pub struct StringInterner {
+    symbols: HashMap<Arc<str>, Symbol>,
+    names: Vec<Arc<str>>,
+}
+
+impl StringInterner {
+    pub fn new() -> StringInterner {
+        StringInterner::default()
+    }
+
+    /// The symbol of a name, interning it if it hasn't been yet.
+    ///
+    /// # Example
+    /// ```
+    /// use buildlua::intern::StringInterner;
+    ///
+    /// let mut interner = StringInterner::new();
+    /// let first = interner.intern("self");
+    /// assert_eq!(interner.intern("self"), first);
+    /// assert_ne!(interner.intern("other"), first);
+    /// assert_eq!(interner.resolve(first), "self");
+    /// ```
+    pub fn intern(&mut self, name: &str) -> Symbol {
+        if let Some(&symbol) = self.symbols.get(name) {
+            return symbol;
+        }
+        assert!(self.names.len() < u32::MAX as usize, "too many names for an interner");
+        let symbol = Symbol(self.names.len() as u32);
+        let name: Arc<str> = Arc::from(name);
+        self.names.push(name.clone());
+        self.symbols.insert(name, symbol);
+        symbol
+    }
+
+    /// The symbol of a name, if it has been interned.
+    pub fn get(&self, name: &str) -> Option<Symbol> {
+        self.symbols.get(name).copied()
+    }
+
+    /// The name a symbol stands for.
+    ///
+    /// Panics if the symbol came from another interner holding more names.
+    pub fn resolve(&self, symbol: Symbol) -> &str {
+        &self.names[symbol.index()]
+    }
+
+    /// How many distinct names have been interned.
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    /// The tree with the names its symbols stand for, as `parser::parse` would give it.
+    ///
+    /// Panics if the symbols came from another interner holding more names.
+    pub fn resolve_chunk(&self, chunk: &InternedChunk) -> Chunk {
+        self.chunk(chunk)
+    }
+}
+
+impl<'a> Store<'a> for &mut StringInterner {
+    type Storage = Interned;
+
+    fn name(&mut self, name: Cow<'a, str>) -> Symbol {
+        self.intern(&name)
+    }
+
+    fn string(&mut self, s: Cow<'a, [u8]>) -> Vec<u8> {
+        s.into_owned()
+    }
+
+    fn text(&mut self, text: Cow<'a, str>) -> String {
+        text.into_owned()
+    }
+}
+
+impl Convert<Interned, Owned> for StringInterner {
+    fn name(&self, name: &Symbol) -> String {
+        self.resolve(*name).to_string()
+    }
+
+    fn string(&self, s: &Vec<u8>) -> Vec<u8> {
+        s.clone()
+    }
+
+    fn text(&self, text: &String) -> String {
+        text.clone()
+    }
+}
@@ -0,0 +1,1505 @@
+//! A tree-walking interpreter, running a chunk straight from its syntax tree.
+//!
+//! Values and the operators on them follow the lua manual at
+//! http://www.lua.org/manual/5.2/manual.html#2.1, except that numbers are integers or floats as in
+//! http://www.lua.org/manual/5.3/manual.html#3.4.1: integers wrap around on overflow, and an
+//! operation mixing the two works on floats. Only part of the language is covered: there are no
+//! coroutines, and of the metamethods only `__index` and `__newindex` are used, as described in
+//! http://www.lua.org/manual/5.2/manual.html#2.4. An interpreter starts out with only
+//! `setmetatable` and `getmetatable` as globals, and `stdlib` installs the rest of the standard
+//! library.
+
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::iter;
+use std::mem;
+use std::rc::Rc;
+use std::str;
+
+use ast::*;
+use lexer::{Lexer, Token};
+use opt::fold::{float_modulo, floor_divide, integer_modulo, shift_left};
+use span::{Span, Spanned};
+
+/// The deepest calls can nest before failing, rather than overflowing the native stack.
+///
+/// Debug builds use much more stack per call, so reaching the limit in one can take more than
+/// the 2MB a spawned thread gets by default.
+pub const CALL_LIMIT: usize = 200;
+
+/// How many `__index` or `__newindex` metamethods one access may go through, as a chain longer
+/// than this is probably a loop.
+const METAMETHOD_CHAIN_LIMIT: usize = 100;
+
+/// Runs a chunk in a fresh interpreter, giving the values it returns.
+pub fn eval(chunk: &Chunk) -> Result<Vec<Value>, RuntimeError> {
+    Interpreter::new().eval(chunk)
+}
+
+/// A lua value. Tables and functions are shared, and compare equal only to themselves.
+///
+/// Integers and floats are both numbers, and equal when they have the same value.
+#[derive(Clone)]
+pub enum Value {
+    Nil,
+    Bool(bool),
+    Integer(i64),
+    Number(f64),
+    /// A string, as the bytes it holds, which needn't be valid UTF-8, as in `"\xff"`.
+    Str(Rc<[u8]>),
+    Table(Rc<RefCell<Table>>),
+    Function(Rc<Function>),
+}
+
+impl Value {
+    /// The name of the value's type, as lua's `type` function gives it.
+    pub fn type_name(&self) -> &'static str {
+        match *self {
+            Value::Nil => "nil",
+            Value::Bool(_) => "boolean",
+            Value::Integer(_) | Value::Number(_) => "number",
+            Value::Str(_) => "string",
+            Value::Table(_) => "table",
+            Value::Function(_) => "function",
+        }
+    }
+
+    /// Whether the value counts as true in a condition, which everything but `nil` and `false`
+    /// does.
+    pub fn is_truthy(&self) -> bool {
+        !matches!(*self, Value::Nil | Value::Bool(false))
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Value {
+        Value::Bool(b)
+    }
+}
+
+impl From<i64> for Value {
+    fn from(n: i64) -> Value {
+        Value::Integer(n)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(n: f64) -> Value {
+        Value::Number(n)
+    }
+}
+
+impl<'a> From<&'a str> for Value {
+    fn from(s: &'a str) -> Value {
+        Value::Str(s.as_bytes().into())
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Value {
+        Value::Str(s.into_bytes().into())
+    }
+}
+
+impl<'a> From<&'a [u8]> for Value {
+    fn from(s: &'a [u8]) -> Value {
+        Value::Str(s.into())
+    }
+}
+
+impl From<Vec<u8>> for Value {
+    fn from(s: Vec<u8>) -> Value {
+        Value::Str(s.into())
+    }
+}
+
+impl From<Table> for Value {
+    fn from(table: Table) -> Value {
+        Value::Table(Rc::new(RefCell::new(table)))
+    }
+}
+
+impl From<Function> for Value {
+    fn from(function: Function) -> Value {
+        Value::Function(Rc::new(function))
+    }
+}
+
+/// Raw equality, without metamethods.
+impl PartialEq for Value {
+    fn eq(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::Nil, Value::Nil) => true,
+            (Value::Bool(a), Value::Bool(b)) => a == b,
+            (Value::Integer(a), Value::Integer(b)) => a == b,
+            (Value::Number(a), Value::Number(b)) => a == b,
+            (&Value::Integer(a), &Value::Number(b)) | (&Value::Number(b), &Value::Integer(a)) => {
+                compare_mixed(a, b) == Some(Ordering::Equal)
+            }
+            (Value::Str(a), Value::Str(b)) => a == b,
+            (Value::Table(a), Value::Table(b)) => Rc::ptr_eq(a, b),
+            (Value::Function(a), Value::Function(b)) => Rc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Debug for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Value::Nil => f.write_str("Nil"),
+            Value::Bool(b) => f.debug_tuple("Bool").field(&b).finish(),
+            Value::Integer(n) => f.debug_tuple("Integer").field(&n).finish(),
+            Value::Number(n) => f.debug_tuple("Number").field(&n).finish(),
+            Value::Str(ref s) => f.debug_tuple("Str").field(&String::from_utf8_lossy(s)).finish(),
+            Value::Table(ref t) => write!(f, "Table({:p})", Rc::as_ptr(t)),
+            Value::Function(ref function) => write!(f, "Function({:p})", Rc::as_ptr(function)),
+        }
+    }
+}
+
+/// Formats the value as lua's `tostring` does, except that bytes of a string that aren't valid
+/// UTF-8 are replaced with U+FFFD.
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Value::Nil => f.write_str("nil"),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Integer(n) => write!(f, "{}", n),
+            Value::Number(n) => f.write_str(&format_number(n)),
+            Value::Str(ref s) => f.write_str(&String::from_utf8_lossy(s)),
+            Value::Table(ref t) => write!(f, "table: {:p}", Rc::as_ptr(t)),
+            Value::Function(ref function) => write!(f, "function: {:p}", Rc::as_ptr(function)),
+        }
+    }
+}
+
+/// A table, mapping every value but `nil` and NaN to a value. A float key with an integer value
+/// is the same key as that integer, and is stored as it.
+///
+/// Keys from `1` up to a border are kept in an array, and the rest in a hash map. `next` goes
+/// through the array in order and then the other keys in the order they were added.
+#[derive(Clone, Default)]
+pub struct Table {
+    /// The values of keys `1` to `array.len()`. The last of them is never `nil`.
+    array: Vec<Value>,
+    /// Where each key of the hash part is in `entries`.
+    slots: HashMap<Key, usize>,
+    /// The keys and values of the hash part, in the order they were added. A removed key is left
+    /// with a `nil` value until a new key is added, so `next` can still go on from it.
+    entries: Vec<(Value, Value)>,
+    /// How many entries have a `nil` value.
+    removed: usize,
+    metatable: Option<Rc<RefCell<Table>>>,
+}
+
+impl Table {
+    pub fn new() -> Table {
+        Table::default()
+    }
+
+    pub fn metatable(&self) -> Option<Rc<RefCell<Table>>> {
+        self.metatable.clone()
+    }
+
+    pub fn set_metatable(&mut self, metatable: Option<Rc<RefCell<Table>>>) {
+        self.metatable = metatable;
+    }
+
+    /// The value at `key`, which is `nil` if there isn't one.
+    pub fn get(&self, key: &Value) -> Value {
+        if let Some(index) = array_index(key) {
+            if index <= self.array.len() {
+                return self.array[index - 1].clone();
+            }
+        }
+        match self.slots.get(&Key(key.clone())) {
+            Some(&slot) => self.entries[slot].1.clone(),
+            None => Value::Nil,
+        }
+    }
+
+    /// Sets the value at `key`, removing it if the value is `nil`.
+    ///
+    /// # Panics
+    ///
+    /// If the key is `nil` or NaN.
+    pub fn set(&mut self, key: Value, value: Value) {
+        assert!(check_key(&key).is_ok(), "{} is not a valid table key", key);
+        let key = match key {
+            Value::Number(n) => float_to_integer(n).map_or(key, Value::Integer),
+            key => key,
+        };
+        if let Some(index) = array_index(&key) {
+            if index <= self.array.len() {
+                self.array[index - 1] = value;
+                while let Some(Value::Nil) = self.array.last() {
+                    self.array.pop();
+                }
+                return;
+            }
+            if index == self.array.len() + 1 && value != Value::Nil {
+                self.array.push(value);
+                self.remove_hash(&key);
+                // The keys after the new one may continue the sequence.
+                loop {
+                    match self.remove_hash(&Value::Integer(self.array.len() as i64 + 1)) {
+                        Value::Nil => break,
+                        value => self.array.push(value),
+                    }
+                }
+                return;
+            }
+        }
+        self.set_hash(key, value);
+    }
+
+    fn set_hash(&mut self, key: Value, value: Value) {
+        let key = Key(key);
+        if let Some(&slot) = self.slots.get(&key) {
+            let old = &mut self.entries[slot].1;
+            match (*old == Value::Nil, value == Value::Nil) {
+                (true, false) => self.removed -= 1,
+                (false, true) => self.removed += 1,
+                _ => {}
+            }
+            *old = value;
+        } else if value != Value::Nil {
+            if self.removed > self.entries.len() / 2 {
+                self.compact();
+            }
+            self.slots.insert(key.clone(), self.entries.len());
+            self.entries.push((key.0, value));
+        }
+    }
+
+    /// Takes a key out of the hash part, giving its value. Unlike setting it to `nil`, this
+    /// forgets the key, so it is only done while adding a key, when `next` can't go on anyway.
+    fn remove_hash(&mut self, key: &Value) -> Value {
+        match self.slots.remove(&Key(key.clone())) {
+            Some(slot) => {
+                let value = mem::replace(&mut self.entries[slot].1, Value::Nil);
+                if value != Value::Nil {
+                    self.removed += 1;
+                }
+                value
+            }
+            None => Value::Nil,
+        }
+    }
+
+    /// Drops the entries of removed keys.
+    fn compact(&mut self) {
+        self.entries.retain(|(_, value)| *value != Value::Nil);
+        self.slots = self
+            .entries
+            .iter()
+            .enumerate()
+            .map(|(slot, (key, _))| (Key(key.clone()), slot))
+            .collect();
+        self.removed = 0;
+    }
+
+    /// The key and value after `key`, or the first of them if `key` is `nil`, as lua's `next`
+    /// gives them. Keys may be removed while going through the table, but not added.
+    pub fn next(&self, key: &Value) -> Result<Option<(Value, Value)>, &'static str> {
+        // Positions count through the array and then the entries.
+        let start = match *key {
+            Value::Nil => 0,
+            _ => match self.slots.get(&Key(key.clone())) {
+                Some(&slot) => self.array.len() + slot + 1,
+                // Removing the last keys of the array shortens it, leaving keys past its end.
+                None => match array_index(key) {
+                    Some(index) => index.min(self.array.len()),
+                    None => return Err("invalid key to 'next'"),
+                },
+            },
+        };
+        let len = self.array.len();
+        for (i, value) in self.array.iter().enumerate().skip(start) {
+            if *value != Value::Nil {
+                return Ok(Some((Value::Integer(i as i64 + 1), value.clone())));
+            }
+        }
+        for (key, value) in &self.entries[start.saturating_sub(len)..] {
+            if *value != Value::Nil {
+                return Ok(Some((key.clone(), value.clone())));
+            }
+        }
+        Ok(None)
+    }
+
+    /// A border of the table, which is what the length operator gives: a key whose value isn't
+    /// `nil` while the value of the next key is, or zero if the value at `1` is `nil`.
+    pub fn border(&self) -> usize {
+        self.array.len()
+    }
+}
+
+// The metatable is written by address, as it may be the table itself.
+impl fmt::Debug for Table {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let hash: Vec<_> = self.entries.iter().filter(|(_, value)| *value != Value::Nil).collect();
+        f.debug_struct("Table")
+            .field("array", &self.array)
+            .field("hash", &hash)
+            .field("metatable", &self.metatable.as_ref().map(Rc::as_ptr))
+            .finish()
+    }
+}
+
+/// The position of a key in the array part, if it is a positive integer.
+fn array_index(key: &Value) -> Option<usize> {
+    let n = match *key {
+        Value::Integer(n) => n,
+        Value::Number(n) => float_to_integer(n)?,
+        _ => return None,
+    };
+    if n >= 1 { usize::try_from(n).ok() } else { None }
+}
+
+pub(crate) fn check_key(key: &Value) -> Result<(), &'static str> {
+    match *key {
+        Value::Nil => Err("table index is nil"),
+        Value::Number(n) if n.is_nan() => Err("table index is NaN"),
+        _ => Ok(()),
+    }
+}
+
+/// A table key, hashed so that keys that are raw equal collide.
+#[derive(Debug, Clone, PartialEq)]
+struct Key(Value);
+
+// Tables never hold NaN keys, the only values that aren't equal to themselves.
+impl Eq for Key {}
+
+impl Hash for Key {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match self.0 {
+            Value::Nil => {}
+            Value::Bool(b) => b.hash(state),
+            Value::Integer(n) => n.hash(state),
+            // Floats equal to an integer have to hash as it, which covers negative zero too.
+            Value::Number(n) => match float_to_integer(n) {
+                Some(n) => n.hash(state),
+                None => n.to_bits().hash(state),
+            },
+            Value::Str(ref s) => s.hash(state),
+            Value::Table(ref t) => Rc::as_ptr(t).hash(state),
+            Value::Function(ref function) => Rc::as_ptr(function).hash(state),
+        }
+    }
+}
+
+/// A function written in rust, called with the interpreter, its arguments and the span of the
+/// call, to raise errors at.
+pub type NativeFunction =
+    dyn Fn(&mut Interpreter, Vec<Value>, Span) -> Result<Vec<Value>, RuntimeError>;
+
+/// A function, either defined in lua along with the locals it closes over, or written in rust.
+pub struct Function(FunctionKind);
+
+enum FunctionKind {
+    Lua {
+        body: Rc<FunctionBody>,
+        /// Whether the function was defined with a colon, taking `self` before its parameters.
+        method: bool,
+        /// The locals visible where the function was defined, shared with the scopes declaring
+        /// them.
+        upvalues: Vec<Local>,
+    },
+    Native(Box<NativeFunction>),
+}
+
+impl Function {
+    pub fn native<F>(function: F) -> Function
+    where
+        F: Fn(&mut Interpreter, Vec<Value>, Span) -> Result<Vec<Value>, RuntimeError> + 'static,
+    {
+        Function(FunctionKind::Native(Box::new(function)))
+    }
+}
+
+impl fmt::Debug for Function {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Function({:p})", self)
+    }
+}
+
+/// An error raised while running a chunk, such as from doing arithmetic on a table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuntimeError {
+    pub message: String,
+    /// The statement or expression raising the error.
+    pub span: Span,
+}
+
+impl RuntimeError {
+    pub fn new<S: Into<String>>(message: S, span: Span) -> RuntimeError {
+        RuntimeError { message: message.into(), span }
+    }
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at byte {}", self.message, self.span.start)
+    }
+}
+
+impl Error for RuntimeError {}
+
+/// A local variable, as its name and a cell shared with any closures capturing it.
+type Local = (Rc<str>, Rc<RefCell<Value>>);
+
+/// The state of a running function.
+struct Frame {
+    /// The locals in scope, innermost last. A name declared twice refers to the later local.
+    locals: Vec<Local>,
+    varargs: Vec<Value>,
+}
+
+impl Frame {
+    fn lookup(&self, name: &str) -> Option<&Rc<RefCell<Value>>> {
+        self.locals.iter().rev().find(|(local, _)| &**local == name).map(|(_, cell)| cell)
+    }
+
+    fn declare(&mut self, name: &str, value: Value) {
+        self.locals.push((name.into(), Rc::new(RefCell::new(value))));
+    }
+}
+
+/// How running a statement ended.
+enum Flow {
+    Normal,
+    Break(Span),
+    Goto(String, Span),
+    Return(Vec<Value>),
+}
+
+/// Somewhere an assignment can store a value.
+enum Place {
+    Local(Rc<RefCell<Value>>),
+    Global(Rc<str>),
+    Field(Value, Value),
+}
+
+/// Runs chunks, keeping their globals between runs.
+pub struct Interpreter {
+    globals: Rc<RefCell<Table>>,
+    /// The metatable shared by every string, which lets methods be called on them.
+    string_metatable: Option<Rc<RefCell<Table>>>,
+    /// Shared copies of the function bodies closures have been made from this run, keyed by the
+    /// address of the original body.
+    bodies: HashMap<*const FunctionBody, Rc<FunctionBody>>,
+    depth: usize,
+}
+
+impl Default for Interpreter {
+    fn default() -> Interpreter {
+        Interpreter::new()
+    }
+}
+
+impl Interpreter {
+    pub fn new() -> Interpreter {
+        let mut interpreter = Interpreter {
+            globals: Rc::default(),
+            string_metatable: None,
+            bodies: HashMap::new(),
+            depth: 0,
+        };
+        interpreter.set_global("setmetatable", Function::native(setmetatable).into());
+        interpreter.set_global("getmetatable", Function::native(getmetatable).into());
+        interpreter
+    }
+
+    /// The value of a global variable.
+    pub fn global(&self, name: &str) -> Value {
+        self.globals.borrow().get(&Value::from(name))
+    }
+
+    pub fn set_global(&mut self, name: &str, value: Value) {
+        self.globals.borrow_mut().set(Value::from(name), value);
+    }
+
+    /// The table of global variables, which `_G` refers to.
+    pub fn globals(&self) -> Rc<RefCell<Table>> {
+        self.globals.clone()
+    }
+
+    pub fn set_string_metatable(&mut self, metatable: Option<Rc<RefCell<Table>>>) {
+        self.string_metatable = metatable;
+    }
+
+    /// The metatable of a table or string, if it has one.
+    pub fn metatable(&self, value: &Value) -> Option<Rc<RefCell<Table>>> {
+        match *value {
+            Value::Table(ref table) => table.borrow().metatable(),
+            Value::Str(_) => self.string_metatable.clone(),
+            _ => None,
+        }
+    }
+
+    /// The field `event` of the value's metatable, or `nil` if it has none.
+    pub(crate) fn metamethod(&self, value: &Value, event: &str) -> Value {
+        match self.metatable(value) {
+            Some(metatable) => metatable.borrow().get(&Value::from(event)),
+            None => Value::Nil,
+        }
+    }
+
+    /// Runs a chunk, giving the values it returns.
+    pub fn eval(&mut self, chunk: &Chunk) -> Result<Vec<Value>, RuntimeError> {
+        let mut frame = Frame { locals: Vec::new(), varargs: Vec::new() };
+        let result = self.body(&chunk.0, &mut frame);
+        // The bodies are keyed by address, which doesn't outlive the chunk.
+        self.bodies.clear();
+        result
+    }
+
+    /// Calls a value, which has to be a function, giving the values it returns.
+    pub fn call_value(
+        &mut self,
+        function: &Value,
+        arguments: Vec<Value>,
+        span: Span,
+    ) -> Result<Vec<Value>, RuntimeError> {
+        let function = match *function {
+            Value::Function(ref function) => function.clone(),
+            ref other => {
+                let message = format!("attempt to call a {} value", other.type_name());
+                return Err(RuntimeError::new(message, span));
+            }
+        };
+        if self.depth >= CALL_LIMIT {
+            return Err(RuntimeError::new("stack overflow", span));
+        }
+        self.depth += 1;
+        let result = match function.0 {
+            FunctionKind::Lua { ref body, method, ref upvalues } => {
+                self.function(body, method, upvalues, arguments)
+            }
+            FunctionKind::Native(ref native) => native(self, arguments, span),
+        };
+        self.depth -= 1;
+        result
+    }
+
+    fn function(
+        &mut self,
+        body: &FunctionBody,
+        method: bool,
+        upvalues: &[Local],
+        arguments: Vec<Value>,
+    ) -> Result<Vec<Value>, RuntimeError> {
+        let mut frame = Frame { locals: upvalues.to_vec(), varargs: Vec::new() };
+        let mut arguments = arguments.into_iter();
+        if method {
+            frame.declare("self", arguments.next().unwrap_or(Value::Nil));
+        }
+        let (names, varargs) = match body.0.as_deref() {
+            Some(ParameterList::NameList(names)) => (Some(names), false),
+            Some(ParameterList::ExtendedArguments(names)) => (Some(names), true),
+            Some(ParameterList::ExtendedArgumentsVoid) => (None, true),
+            None => (None, false),
+        };
+        if let Some(names) = names {
+            for name in iter::once(&names.0).chain(names.1.iter().flatten()) {
+                frame.declare(&name.node, arguments.next().unwrap_or(Value::Nil));
+            }
+        }
+        if varargs {
+            frame.varargs = arguments.collect();
+        }
+        self.body(&body.1, &mut frame)
+    }
+
+    /// Runs the block of a function, giving the values it returns.
+    fn body(&mut self, block: &Block, frame: &mut Frame) -> Result<Vec<Value>, RuntimeError> {
+        match self.statements(block, frame)? {
+            Flow::Normal => Ok(Vec::new()),
+            Flow::Return(values) => Ok(values),
+            Flow::Break(span) => Err(RuntimeError::new("break outside a loop", span)),
+            Flow::Goto(label, span) => {
+                Err(RuntimeError::new(format!("no visible label '{}' for goto", label), span))
+            }
+        }
+    }
+
+    /// Runs a block in a scope of its own.
+    fn block(&mut self, block: &Block, frame: &mut Frame) -> Result<Flow, RuntimeError> {
+        let mark = frame.locals.len();
+        let flow = self.statements(block, frame)?;
+        frame.locals.truncate(mark);
+        Ok(flow)
+    }
+
+    /// Runs the statements of a block in the current scope, so its locals stay declared.
+    fn statements(&mut self, block: &Block, frame: &mut Frame) -> Result<Flow, RuntimeError> {
+        let statements = block.0.as_deref().unwrap_or(&[]);
+        // How many locals were declared when each label was reached, as a goto jumping back to a
+        // label leaves the scope of the locals declared after it.
+        let mut labels: Vec<(usize, usize)> = Vec::new();
+        let mut i = 0;
+        while i < statements.len() {
+            if let Statement::Label(_) = statements[i].node {
+                match labels.iter_mut().find(|(label, _)| *label == i) {
+                    Some(label) => label.1 = frame.locals.len(),
+                    None => labels.push((i, frame.locals.len())),
+                }
+            }
+            match self.statement(&statements[i], frame)? {
+                Flow::Normal => i += 1,
+                Flow::Goto(label, span) => {
+                    let target = statements.iter().position(|statement| match statement.node {
+                        Statement::Label(Label(ref name)) => *name == label,
+                        _ => false,
+                    });
+                    match target {
+                        Some(target) => {
+                            if let Some(&(_, mark)) = labels.iter().find(|(i, _)| *i == target) {
+                                frame.locals.truncate(mark);
+                            }
+                            i = target;
+                        }
+                        None => return Ok(Flow::Goto(label, span)),
+                    }
+                }
+                flow => return Ok(flow),
+            }
+        }
+        match block.1 {
+            Some(ref statement) => match statement.0 {
+                Some(ref expressions) => {
+                    Ok(Flow::Return(self.expression_list(expressions, frame)?))
+                }
+                None => Ok(Flow::Return(Vec::new())),
+            },
+            None => Ok(Flow::Normal),
+        }
+    }
+
+    fn statement(
+        &mut self,
+        statement: &Spanned<Statement>,
+        frame: &mut Frame,
+    ) -> Result<Flow, RuntimeError> {
+        let span = statement.span;
+        match statement.node {
+            Statement::Semicolon | Statement::Label(_) => {}
+            Statement::Assignment(ref variables, ref expressions) => {
+                let rest = variables.rest.as_deref().unwrap_or(&[]);
+                let mut places = Vec::with_capacity(1 + rest.len());
+                for variable in iter::once(&*variables.first).chain(rest) {
+                    places.push(self.place(variable, span, frame)?);
+                }
+                let mut values = self.expression_list(expressions, frame)?.into_iter();
+                for place in places {
+                    self.assign(place, values.next().unwrap_or(Value::Nil), span)?;
+                }
+            }
+            Statement::FunctionCall(ref call) => {
+                self.call(call, span, frame)?;
+            }
+            Statement::Break => return Ok(Flow::Break(span)),
+            Statement::Goto(Label(ref label)) => return Ok(Flow::Goto(label.clone(), span)),
+            Statement::Do(ref block) => return self.block(block, frame),
+            Statement::While { ref exp, ref do_ } => {
+                while self.expression(exp, frame)?.is_truthy() {
+                    match self.block(do_, frame)? {
+                        Flow::Normal => {}
+                        Flow::Break(_) => break,
+                        flow => return Ok(flow),
+                    }
+                }
+            }
+            Statement::Repeat { ref block, ref until } => loop {
+                // The condition is in the scope of the block.
+                let mark = frame.locals.len();
+                let flow = self.statements(block, frame)?;
+                let done = match flow {
+                    Flow::Normal => self.expression(until, frame)?.is_truthy(),
+                    _ => true,
+                };
+                // The block's locals go out of scope however it's left.
+                frame.locals.truncate(mark);
+                match flow {
+                    Flow::Normal if !done => {}
+                    Flow::Normal | Flow::Break(_) => break,
+                    flow => return Ok(flow),
+                }
+            },
+            Statement::If { ref condition, ref then, ref elseifs, ref else_ } => {
+                if self.expression(condition, frame)?.is_truthy() {
+                    return self.block(then, frame);
+                }
+                for (condition, block) in elseifs {
+                    if self.expression(condition, frame)?.is_truthy() {
+                        return self.block(block, frame);
+                    }
+                }
+                if let Some(ref block) = *else_ {
+                    return self.block(block, frame);
+                }
+            }
+            Statement::ForStepping { ref name, ref from, ref to, ref step, ref block } => {
+                let from = self.for_number(from, "initial value", frame)?;
+                let to = self.for_number(to, "limit", frame)?;
+                let step = match *step {
+                    Some(ref step) => self.for_number(step, "step", frame)?,
+                    None => Value::Integer(1),
+                };
+                if step == Value::Integer(0) {
+                    return Err(RuntimeError::new("'for' step is zero", span));
+                }
+                // The loop counts in integers if it starts and steps by them, stopping rather
+                // than wrapping around.
+                let values: Box<dyn Iterator<Item = Value>> = match (from, step) {
+                    (Value::Integer(from), Value::Integer(step)) => {
+                        let limit = match to {
+                            Value::Integer(limit) => Some(limit),
+                            _ => integer_limit(to_number(&to).unwrap(), step),
+                        };
+                        let within = move |i: &i64| match limit {
+                            Some(limit) if step > 0 => *i <= limit,
+                            Some(limit) => *i >= limit,
+                            None => false,
+                        };
+                        let first = Some(from).filter(within);
+                        let counter =
+                            iter::successors(first, move |i| i.checked_add(step).filter(within));
+                        Box::new(counter.map(Value::Integer))
+                    }
+                    (from, step) => {
+                        let (from, step) = (to_number(&from).unwrap(), to_number(&step).unwrap());
+                        let to = to_number(&to).unwrap();
+                        let within = move |i: &f64| if step > 0.0 { *i <= to } else { *i >= to };
+                        let first = Some(from).filter(within);
+                        let counter =
+                            iter::successors(first, move |i| Some(i + step).filter(within));
+                        Box::new(counter.map(Value::Number))
+                    }
+                };
+                for value in values {
+                    // Every iteration has a new local, so closures capture each value.
+                    let mark = frame.locals.len();
+                    frame.declare(&name.node, value);
+                    let flow = self.block(block, frame)?;
+                    frame.locals.truncate(mark);
+                    match flow {
+                        Flow::Normal => {}
+                        Flow::Break(_) => break,
+                        flow => return Ok(flow),
+                    }
+                }
+            }
+            Statement::ForIn { ref name_list, ref in_, ref do_ } => {
+                let mut values = self.expression_list(in_, frame)?.into_iter();
+                let function = values.next().unwrap_or(Value::Nil);
+                let state = values.next().unwrap_or(Value::Nil);
+                let mut control = values.next().unwrap_or(Value::Nil);
+                loop {
+                    let arguments = vec![state.clone(), control.clone()];
+                    let mut values = self.call_value(&function, arguments, span)?.into_iter();
+                    control = values.next().unwrap_or(Value::Nil);
+                    if control == Value::Nil {
+                        break;
+                    }
+                    let mark = frame.locals.len();
+                    frame.declare(&name_list.0.node, control.clone());
+                    for name in name_list.1.iter().flatten() {
+                        frame.declare(&name.node, values.next().unwrap_or(Value::Nil));
+                    }
+                    let flow = self.block(do_, frame)?;
+                    frame.locals.truncate(mark);
+                    match flow {
+                        Flow::Normal => {}
+                        Flow::Break(_) => break,
+                        flow => return Ok(flow),
+                    }
+                }
+            }
+            Statement::Function(ref name, ref body) => {
+                let function = self.closure(body, name.self_name.is_some(), frame);
+                let mut keys = name
+                    .rest_dot_access
+                    .iter()
+                    .flatten()
+                    .chain(name.self_name.iter())
+                    .map(|key| Value::from(key.as_str()));
+                let place = match keys.next() {
+                    None => self.name_place(&name.first_dot_access.node, frame),
+                    Some(first) => {
+                        let mut object = self.name(&name.first_dot_access.node, frame);
+                        let mut key = first;
+                        for next in keys {
+                            object = self.index(&object, &key, span)?;
+                            key = next;
+                        }
+                        Place::Field(object, key)
+                    }
+                };
+                self.assign(place, function, span)?;
+            }
+            Statement::LocalFunction { ref name, ref body } => {
+                // The function can see its own local, so it can recurse.
+                frame.declare(&name.node, Value::Nil);
+                let function = self.closure(body, false, frame);
+                *frame.lookup(&name.node).unwrap().borrow_mut() = function;
+            }
+            Statement::LocalVariableBinding(ref names, ref expressions) => {
+                let mut values = match *expressions {
+                    Some(ref expressions) => self.expression_list(expressions, frame)?,
+                    None => Vec::new(),
+                }
+                .into_iter();
+                for name in iter::once(&names.0).chain(names.1.iter().flatten()) {
+                    frame.declare(&name.name.node, values.next().unwrap_or(Value::Nil));
+                }
+            }
+        }
+        Ok(Flow::Normal)
+    }
+
+    fn for_number(
+        &mut self,
+        expression: &Spanned<Expression>,
+        what: &str,
+        frame: &mut Frame,
+    ) -> Result<Value, RuntimeError> {
+        let value = self.expression(expression, frame)?;
+        to_numeric(&value).ok_or_else(|| {
+            RuntimeError::new(format!("'for' {} must be a number", what), expression.span)
+        })
+    }
+
+    fn closure(&mut self, body: &FunctionBody, method: bool, frame: &Frame) -> Value {
+        let body = self
+            .bodies
+            .entry(body as *const FunctionBody)
+            .or_insert_with(|| Rc::new(body.clone()))
+            .clone();
+        Function(FunctionKind::Lua { body, method, upvalues: frame.locals.clone() }).into()
+    }
+
+    fn name(&self, name: &str, frame: &Frame) -> Value {
+        match frame.lookup(name) {
+            Some(cell) => cell.borrow().clone(),
+            None => self.global(name),
+        }
+    }
+
+    fn name_place(&self, name: &str, frame: &Frame) -> Place {
+        match frame.lookup(name) {
+            Some(cell) => Place::Local(cell.clone()),
+            None => Place::Global(name.into()),
+        }
+    }
+
+    fn place(
+        &mut self,
+        variable: &Variable,
+        span: Span,
+        frame: &mut Frame,
+    ) -> Result<Place, RuntimeError> {
+        Ok(match *variable {
+            Variable::Name(ref name) => self.name_place(&name.node, frame),
+            Variable::ArrayAccess { ref from, ref key } => {
+                let object = self.prefix(from, span, frame)?;
+                Place::Field(object, self.expression(key, frame)?)
+            }
+            Variable::DotAccess { ref from, ref key } => {
+                Place::Field(self.prefix(from, span, frame)?, Value::from(key.as_str()))
+            }
+        })
+    }
+
+    fn assign(&mut self, place: Place, value: Value, span: Span) -> Result<(), RuntimeError> {
+        match place {
+            Place::Local(cell) => *cell.borrow_mut() = value,
+            Place::Global(name) => self.globals.borrow_mut().set(Value::from(&*name), value),
+            Place::Field(object, key) => self.set_index(&object, key, value, span)?,
+        }
+        Ok(())
+    }
+
+    /// Reads `object[key]`, going through `__index` metamethods for keys a table doesn't have.
+    fn index(&mut self, object: &Value, key: &Value, span: Span) -> Result<Value, RuntimeError> {
+        let mut object = object.clone();
+        for _ in 0..METAMETHOD_CHAIN_LIMIT {
+            if let Value::Table(ref table) = object {
+                let value = table.borrow().get(key);
+                if value != Value::Nil {
+                    return Ok(value);
+                }
+            }
+            let handler = match self.metamethod(&object, "__index") {
+                Value::Nil if matches!(object, Value::Table(_)) => return Ok(Value::Nil),
+                Value::Nil => return Err(index_error(&object, span)),
+                handler => handler,
+            };
+            if let Value::Function(_) = handler {
+                let values = self.call_value(&handler, vec![object, key.clone()], span)?;
+                return Ok(values.into_iter().next().unwrap_or(Value::Nil));
+            }
+            object = handler;
+        }
+        Err(RuntimeError::new("'__index' chain too long; possibly a loop", span))
+    }
+
+    /// Sets `object[key]`, going through `__newindex` metamethods for keys a table doesn't have.
+    fn set_index(
+        &mut self,
+        object: &Value,
+        key: Value,
+        value: Value,
+        span: Span,
+    ) -> Result<(), RuntimeError> {
+        let mut object = object.clone();
+        for _ in 0..METAMETHOD_CHAIN_LIMIT {
+            let handler = self.metamethod(&object, "__newindex");
+            if let Value::Table(ref table) = object {
+                // The metamethod is only used for keys the table doesn't have.
+                if handler == Value::Nil || table.borrow().get(&key) != Value::Nil {
+                    check_key(&key).map_err(|message| RuntimeError::new(message, span))?;
+                    table.borrow_mut().set(key, value);
+                    return Ok(());
+                }
+            } else if handler == Value::Nil {
+                return Err(index_error(&object, span));
+            }
+            if let Value::Function(_) = handler {
+                self.call_value(&handler, vec![object, key, value], span)?;
+                return Ok(());
+            }
+            object = handler;
+        }
+        Err(RuntimeError::new("'__newindex' chain too long; possibly a loop", span))
+    }
+
+    /// Evaluates an expression to a single value, the first if it gives several.
+    fn expression(
+        &mut self,
+        expression: &Spanned<Expression>,
+        frame: &mut Frame,
+    ) -> Result<Value, RuntimeError> {
+        let span = expression.span;
+        Ok(match expression.node {
+            Expression::Error => {
+                return Err(RuntimeError::new("attempt to evaluate a missing expression", span));
+            }
+            Expression::Nil => Value::Nil,
+            Expression::False => Value::Bool(false),
+            Expression::True => Value::Bool(true),
+            Expression::Integer(n, _) => Value::Integer(n),
+            Expression::Float(n, _) => Value::Number(n),
+            Expression::String(ref s, _) => Value::from(&s[..]),
+            Expression::ExtendedArgumentAccess => {
+                frame.varargs.first().cloned().unwrap_or(Value::Nil)
+            }
+            Expression::FunctionDefine(ref define) => self.closure(&define.0, false, frame),
+            Expression::PrefixExpression(ref prefix) => self.prefix(prefix, span, frame)?,
+            Expression::TableConstructor(ref table) => self.table(table, frame)?,
+            Expression::BinaryOperation(BinaryOperation::And, ref left, ref right) => {
+                let left = self.expression(left, frame)?;
+                if left.is_truthy() { self.expression(right, frame)? } else { left }
+            }
+            Expression::BinaryOperation(BinaryOperation::Or, ref left, ref right) => {
+                let left = self.expression(left, frame)?;
+                if left.is_truthy() { left } else { self.expression(right, frame)? }
+            }
+            Expression::BinaryOperation(op, ref left, ref right) => {
+                let left = self.expression(left, frame)?;
+                let right = self.expression(right, frame)?;
+                binary(op, left, right, span)?
+            }
+            Expression::UnaryOperation(op, ref operand) => {
+                let operand = self.expression(operand, frame)?;
+                unary(op, operand, span)?
+            }
+        })
+    }
+
+    /// Evaluates an expression to all of its values, of which only calls and `...` can give
+    /// other than one.
+    fn expression_values(
+        &mut self,
+        expression: &Spanned<Expression>,
+        frame: &mut Frame,
+    ) -> Result<Vec<Value>, RuntimeError> {
+        match expression.node {
+            Expression::ExtendedArgumentAccess => Ok(frame.varargs.clone()),
+            Expression::PrefixExpression(ref prefix) => match **prefix {
+                PrefixExpression::FunctionCall(ref call) => self.call(call, expression.span, frame),
+                _ => Ok(vec![self.expression(expression, frame)?]),
+            },
+            _ => Ok(vec![self.expression(expression, frame)?]),
+        }
+    }
+
+    /// Evaluates a list of expressions, where only the last can give other than one value.
+    fn expression_list(
+        &mut self,
+        list: &ExpressionList,
+        frame: &mut Frame,
+    ) -> Result<Vec<Value>, RuntimeError> {
+        let rest = list.1.as_deref().unwrap_or(&[]);
+        let mut values = Vec::with_capacity(1 + rest.len());
+        let mut last = &*list.0;
+        for expression in rest {
+            values.push(self.expression(last, frame)?);
+            last = expression;
+        }
+        values.extend(self.expression_values(last, frame)?);
+        Ok(values)
+    }
+
+    fn prefix(
+        &mut self,
+        prefix: &PrefixExpression,
+        span: Span,
+        frame: &mut Frame,
+    ) -> Result<Value, RuntimeError> {
+        match *prefix {
+            PrefixExpression::Variable(ref variable) => match **variable {
+                Variable::Name(ref name) => Ok(self.name(&name.node, frame)),
+                Variable::ArrayAccess { ref from, ref key } => {
+                    let object = self.prefix(from, span, frame)?;
+                    let key = self.expression(key, frame)?;
+                    self.index(&object, &key, span)
+                }
+                Variable::DotAccess { ref from, ref key } => {
+                    let object = self.prefix(from, span, frame)?;
+                    self.index(&object, &Value::from(key.as_str()), span)
+                }
+            },
+            PrefixExpression::FunctionCall(ref call) => {
+                Ok(self.call(call, span, frame)?.into_iter().next().unwrap_or(Value::Nil))
+            }
+            PrefixExpression::Parenthesis(ref expression) => self.expression(expression, frame),
+        }
+    }
+
+    fn call(
+        &mut self,
+        call: &FunctionCall,
+        span: Span,
+        frame: &mut Frame,
+    ) -> Result<Vec<Value>, RuntimeError> {
+        match *call {
+            FunctionCall::Static(ref function, ref arguments) => {
+                let function = self.prefix(function, span, frame)?;
+                let arguments = self.arguments(arguments, frame)?;
+                self.call_value(&function, arguments, span)
+            }
+            FunctionCall::SelfTaking(ref object, ref name, ref arguments) => {
+                let object = self.prefix(object, span, frame)?;
+                let function = self.index(&object, &Value::from(name.as_str()), span)?;
+                let mut values = vec![object];
+                values.extend(self.arguments(arguments, frame)?);
+                self.call_value(&function, values, span)
+            }
+        }
+    }
+
+    fn arguments(
+        &mut self,
+        arguments: &FunctionArguments,
+        frame: &mut Frame,
+    ) -> Result<Vec<Value>, RuntimeError> {
+        match *arguments {
+            FunctionArguments::Parenthesis(None) => Ok(Vec::new()),
+            FunctionArguments::Parenthesis(Some(ref list)) => self.expression_list(list, frame),
+            FunctionArguments::TableConstructor(ref table) => Ok(vec![self.table(table, frame)?]),
+            FunctionArguments::String(ref s, _) => Ok(vec![Value::from(&s[..])]),
+        }
+    }
+
+    /// Builds a table, setting its fields in the order they're written. Array-style fields take
+    /// the integer keys from 1 up, and the last field gives every value if it's array-style. The
+    /// lua manual leaves the order undefined, which only matters when a key is given twice.
+    fn table(
+        &mut self,
+        table: &TableConstructor,
+        frame: &mut Frame,
+    ) -> Result<Value, RuntimeError> {
+        let mut result = Table::new();
+        if let Some(ref fields) = table.0 {
+            let rest = fields.1.as_deref().unwrap_or(&[]);
+            let mut next = 1;
+            for (i, field) in iter::once(&*fields.0).chain(rest).enumerate() {
+                match *field {
+                    Field::ExpressionForName { ref name, ref equals } => {
+                        let key = self.expression(name, frame)?;
+                        check_key(&key).map_err(|message| RuntimeError::new(message, name.span))?;
+                        let value = self.expression(equals, frame)?;
+                        result.set(key, value);
+                    }
+                    Field::Equals { ref name, ref equals } => {
+                        let value = self.expression(equals, frame)?;
+                        result.set(Value::from(name.as_str()), value);
+                    }
+                    // Only the last field gives all of its values.
+                    Field::ArrayStyle(ref value) if i == rest.len() => {
+                        for value in self.expression_values(value, frame)? {
+                            result.set(Value::Integer(next), value);
+                            next += 1;
+                        }
+                    }
+                    Field::ArrayStyle(ref value) => {
+                        let value = self.expression(value, frame)?;
+                        result.set(Value::Integer(next), value);
+                        next += 1;
+                    }
+                }
+            }
+        }
+        Ok(Value::from(result))
+    }
+}
+
+fn index_error(object: &Value, span: Span) -> RuntimeError {
+    RuntimeError::new(format!("attempt to index a {} value", object.type_name()), span)
+}
+
+/// The error for the argument at `position`, counted from 1, being wrong.
+pub(crate) fn bad_argument(
+    position: usize,
+    function: &str,
+    message: &str,
+    span: Span,
+) -> RuntimeError {
+    let message = format!("bad argument #{} to '{}' ({})", position, function, message);
+    RuntimeError::new(message, span)
+}
+
+fn setmetatable(
+    interpreter: &mut Interpreter,
+    arguments: Vec<Value>,
+    span: Span,
+) -> Result<Vec<Value>, RuntimeError> {
+    let mut arguments = arguments.into_iter();
+    let table = match arguments.next() {
+        Some(Value::Table(table)) => table,
+        other => {
+            let got = other.as_ref().map_or("no value", Value::type_name);
+            let message = format!("table expected, got {}", got);
+            return Err(bad_argument(1, "setmetatable", &message, span));
+        }
+    };
+    let metatable = match arguments.next() {
+        Some(Value::Table(metatable)) => Some(metatable),
+        Some(Value::Nil) => None,
+        _ => return Err(bad_argument(2, "setmetatable", "nil or table expected", span)),
+    };
+    let object = Value::Table(table.clone());
+    if interpreter.metamethod(&object, "__metatable") != Value::Nil {
+        return Err(RuntimeError::new("cannot change a protected metatable", span));
+    }
+    table.borrow_mut().set_metatable(metatable);
+    Ok(vec![object])
+}
+
+/// Gives the metatable of a value, or its `__metatable` field if that is set.
+fn getmetatable(
+    interpreter: &mut Interpreter,
+    arguments: Vec<Value>,
+    _: Span,
+) -> Result<Vec<Value>, RuntimeError> {
+    let metatable = arguments.first().and_then(|value| interpreter.metatable(value));
+    Ok(vec![match metatable {
+        Some(metatable) => match metatable.borrow().get(&Value::from("__metatable")) {
+            Value::Nil => Value::Table(metatable.clone()),
+            protected => protected,
+        },
+        None => Value::Nil,
+    }])
+}
+
+fn binary(
+    op: BinaryOperation,
+    left: Value,
+    right: Value,
+    span: Span,
+) -> Result<Value, RuntimeError> {
+    use ast::BinaryOperation::*;
+    Ok(match op {
+        Equal => Value::Bool(left == right),
+        NotEqual => Value::Bool(left != right),
+        LessThan => Value::Bool(compare(&left, &right, span)? == Some(Ordering::Less)),
+        LessThanOrEqual => Value::Bool(compare(&left, &right, span)?.is_some_and(Ordering::is_le)),
+        GreaterThan => Value::Bool(compare(&left, &right, span)? == Some(Ordering::Greater)),
+        GreaterThanOrEqual => {
+            Value::Bool(compare(&left, &right, span)?.is_some_and(Ordering::is_ge))
+        }
+        Concatanate => match (concat_piece(&left), concat_piece(&right)) {
+            (Some(mut left), Some(right)) => {
+                left.extend_from_slice(&right);
+                Value::from(left)
+            }
+            (None, _) => return Err(concat_error(&left, span)),
+            (_, None) => return Err(concat_error(&right, span)),
+        },
+        BitwiseAnd | BitwiseOr | BitwiseXor | ShiftLeft | ShiftRight => {
+            let (a, b) = (to_integer(&left, span)?, to_integer(&right, span)?);
+            Value::Integer(match op {
+                BitwiseAnd => a & b,
+                BitwiseOr => a | b,
+                BitwiseXor => a ^ b,
+                ShiftLeft => shift_left(a, b),
+                _ => shift_left(a, b.saturating_neg()),
+            })
+        }
+        _ => match (arithmetic_number(&left, span)?, arithmetic_number(&right, span)?) {
+            // Division and exponentiation always work on floats.
+            (Value::Integer(a), Value::Integer(b)) if op != Devide && op != Exponent => {
+                Value::Integer(match op {
+                    Plus => a.wrapping_add(b),
+                    Minus => a.wrapping_sub(b),
+                    Times => a.wrapping_mul(b),
+                    FloorDivide if b == 0 => {
+                        return Err(RuntimeError::new("attempt to perform 'n//0'", span));
+                    }
+                    FloorDivide => floor_divide(a, b),
+                    Modulo if b == 0 => {
+                        return Err(RuntimeError::new("attempt to perform 'n%0'", span));
+                    }
+                    Modulo => integer_modulo(a, b),
+                    _ => unreachable!("{:?} is not an arithmetic operator", op),
+                })
+            }
+            (a, b) => {
+                let (a, b) = (to_number(&a).unwrap(), to_number(&b).unwrap());
+                Value::Number(match op {
+                    Plus => a + b,
+                    Minus => a - b,
+                    Times => a * b,
+                    Devide => a / b,
+                    FloorDivide => (a / b).floor(),
+                    Modulo => float_modulo(a, b),
+                    Exponent => a.powf(b),
+                    _ => unreachable!("{:?} is not an arithmetic operator", op),
+                })
+            }
+        },
+    })
+}
+
+fn unary(op: UnaryOperation, operand: Value, span: Span) -> Result<Value, RuntimeError> {
+    Ok(match op {
+        UnaryOperation::Negate => match arithmetic_number(&operand, span)? {
+            Value::Integer(n) => Value::Integer(n.wrapping_neg()),
+            n => Value::Number(-to_number(&n).unwrap()),
+        },
+        UnaryOperation::Not => Value::Bool(!operand.is_truthy()),
+        UnaryOperation::Length => match operand {
+            Value::Str(ref s) => Value::Integer(s.len() as i64),
+            Value::Table(ref table) => Value::Integer(table.borrow().border() as i64),
+            ref other => {
+                let message = format!("attempt to get length of a {} value", other.type_name());
+                return Err(RuntimeError::new(message, span));
+            }
+        },
+        UnaryOperation::BitwiseNot => Value::Integer(!to_integer(&operand, span)?),
+    })
+}
+
+/// Orders two numbers or two strings, where NaN isn't ordered with anything.
+pub(crate) fn compare(
+    left: &Value,
+    right: &Value,
+    span: Span,
+) -> Result<Option<Ordering>, RuntimeError> {
+    match (left, right) {
+        (Value::Integer(a), Value::Integer(b)) => Ok(Some(a.cmp(b))),
+        (Value::Number(a), Value::Number(b)) => Ok(a.partial_cmp(b)),
+        (&Value::Integer(a), &Value::Number(b)) => Ok(compare_mixed(a, b)),
+        (&Value::Number(a), &Value::Integer(b)) => Ok(compare_mixed(b, a).map(Ordering::reverse)),
+        (Value::Str(a), Value::Str(b)) => Ok(Some(a.cmp(b))),
+        _ if left.type_name() == right.type_name() => {
+            let message = format!("attempt to compare two {} values", left.type_name());
+            Err(RuntimeError::new(message, span))
+        }
+        _ => {
+            let message =
+                format!("attempt to compare {} with {}", left.type_name(), right.type_name());
+            Err(RuntimeError::new(message, span))
+        }
+    }
+}
+
+/// Orders an integer and a float by their exact values, which converting either to the other's
+/// type could round.
+pub(crate) fn compare_mixed(a: i64, b: f64) -> Option<Ordering> {
+    if b.is_nan() {
+        None
+    } else if b >= 2f64.powi(63) {
+        Some(Ordering::Less)
+    } else if b < -(2f64.powi(63)) {
+        Some(Ordering::Greater)
+    } else {
+        let floor = b.floor();
+        let fraction = if b > floor { Ordering::Less } else { Ordering::Equal };
+        Some(a.cmp(&(floor as i64)).then(fraction))
+    }
+}
+
+/// Converts a value to a float the way arithmetic on floats does, reading strings as numerals.
+pub(crate) fn to_number(value: &Value) -> Option<f64> {
+    match to_numeric(value)? {
+        Value::Integer(n) => Some(n as f64),
+        Value::Number(n) => Some(n),
+        _ => None,
+    }
+}
+
+/// Converts a value to an integer or a float the way arithmetic does, reading strings as
+/// numerals.
+pub(crate) fn to_numeric(value: &Value) -> Option<Value> {
+    match *value {
+        Value::Integer(_) | Value::Number(_) => Some(value.clone()),
+        Value::Str(ref s) => str::from_utf8(s).ok().and_then(parse_number),
+        _ => None,
+    }
+}
+
+/// The integer a float is equal to, if there is one.
+pub(crate) fn float_to_integer(n: f64) -> Option<i64> {
+    let in_range = n >= -(2f64.powi(63)) && n < 2f64.powi(63);
+    if n.fract() == 0.0 && in_range { Some(n as i64) } else { None }
+}
+
+/// The last value an integer loop stepping by `step` can reach without passing a float limit, or
+/// `None` if it can't start.
+fn integer_limit(limit: f64, step: i64) -> Option<i64> {
+    if limit.is_nan() {
+        return None;
+    }
+    let rounded = if step > 0 { limit.floor() } else { limit.ceil() };
+    Some(float_to_integer(rounded).unwrap_or(if limit > 0.0 { i64::MAX } else { i64::MIN }))
+}
+
+fn arithmetic_number(value: &Value, span: Span) -> Result<Value, RuntimeError> {
+    to_numeric(value).ok_or_else(|| {
+        let message = format!("attempt to perform arithmetic on a {} value", value.type_name());
+        RuntimeError::new(message, span)
+    })
+}
+
+/// Converts an operand of a bitwise operator to an integer, which it has to be equal to.
+pub(crate) fn to_integer(value: &Value, span: Span) -> Result<i64, RuntimeError> {
+    match to_numeric(value) {
+        Some(Value::Integer(n)) => Ok(n),
+        Some(Value::Number(n)) if float_to_integer(n).is_some() => Ok(n as i64),
+        Some(_) => Err(RuntimeError::new("number has no integer representation", span)),
+        None => {
+            let message =
+                format!("attempt to perform bitwise operation on a {} value", value.type_name());
+            Err(RuntimeError::new(message, span))
+        }
+    }
+}
+
+/// Reads a string as a lua numeral, allowing a sign and surrounding whitespace.
+fn parse_number(s: &str) -> Option<Value> {
+    let s = s.trim();
+    let (negative, digits) = match s.as_bytes().first() {
+        Some(b'-') => (true, &s[1..]),
+        Some(b'+') => (false, &s[1..]),
+        _ => (false, s),
+    };
+    let n = match Lexer::new(digits).next_spanned()? {
+        Ok(Spanned { node: Token::Integer(n), span }) if span == Span::new(0, digits.len()) => {
+            Value::Integer(if negative { n.wrapping_neg() } else { n })
+        }
+        Ok(Spanned { node: Token::Float(n), span }) if span == Span::new(0, digits.len()) => {
+            Value::Number(if negative { -n } else { n })
+        }
+        _ => return None,
+    };
+    Some(n)
+}
+
+fn concat_piece(value: &Value) -> Option<Vec<u8>> {
+    match *value {
+        Value::Str(ref s) => Some(s.to_vec()),
+        Value::Integer(n) => Some(n.to_string().into_bytes()),
+        Value::Number(n) => Some(format_number(n).into_bytes()),
+        _ => None,
+    }
+}
+
+fn concat_error(value: &Value, span: Span) -> RuntimeError {
+    RuntimeError::new(format!("attempt to concatenate a {} value", value.type_name()), span)
+}
+
+/// Formats a float as lua does, using `printf`'s `%.14g` and adding `.0` if that looks like an
+/// integer.
+fn format_number(n: f64) -> String {
+    let mut s = format_general(n, 14);
+    if s.bytes().all(|b| b == b'-' || b.is_ascii_digit()) {
+        s.push_str(".0");
+    }
+    s
+}
+
+/// Formats a number like `printf`'s `%g` with the given precision, in fixed or scientific
+/// notation by its exponent and without trailing zeros.
+pub(crate) fn format_general(n: f64, precision: usize) -> String {
+    if let Some(s) = non_finite(n) {
+        return s;
+    }
+    let precision = precision.max(1);
+    // Rounding to the significant digits first gives the exponent `%g` decides by.
+    let scientific = format!("{:.*e}", precision - 1, n);
+    let (mantissa, exponent) = scientific.split_once('e').unwrap();
+    let exponent: i32 = exponent.parse().unwrap();
+    if (-4..precision as i32).contains(&exponent) {
+        let fixed = format!("{:.*}", (precision as i32 - 1 - exponent) as usize, n);
+        trim_fraction(&fixed).to_string()
+    } else {
+        with_exponent(trim_fraction(mantissa), exponent)
+    }
+}
+
+/// Formats a number like `printf`'s `%e`, with `precision` digits after the point.
+pub(crate) fn format_scientific(n: f64, precision: usize) -> String {
+    if let Some(s) = non_finite(n) {
+        return s;
+    }
+    let scientific = format!("{:.*e}", precision, n);
+    let (mantissa, exponent) = scientific.split_once('e').unwrap();
+    with_exponent(mantissa, exponent.parse().unwrap())
+}
+
+/// Formats a number like `printf`'s `%f`, with `precision` digits after the point.
+pub(crate) fn format_fixed(n: f64, precision: usize) -> String {
+    non_finite(n).unwrap_or_else(|| format!("{:.*}", precision, n))
+}
+
+/// How `printf` writes infinities and NaN.
+fn non_finite(n: f64) -> Option<String> {
+    if n.is_nan() {
+        Some(if n.is_sign_negative() { "-nan" } else { "nan" }.to_string())
+    } else if n.is_infinite() {
+        Some(if n > 0.0 { "inf" } else { "-inf" }.to_string())
+    } else {
+        None
+    }
+}
+
+/// Writes an exponent as `printf` does, with a sign and at least two digits.
+fn with_exponent(mantissa: &str, exponent: i32) -> String {
+    let sign = if exponent < 0 { '-' } else { '+' };
+    format!("{}e{}{:02}", mantissa, sign, exponent.abs())
+}
+
+/// Drops the trailing zeros of a fraction, and the point if nothing is left after it.
+fn trim_fraction(s: &str) -> &str {
+    if s.contains('.') { s.trim_end_matches('0').trim_end_matches('.') } else { s }
+}
@@ -0,0 +1,881 @@
+//! The lexer of buildlua, turning lua source code into a stream of tokens.
+//!
+//! Most of this file is based on the lexical conventions page in the lua manual.
+//!
+//! The page can be found at http://www.lua.org/manual/5.2/manual.html#3.1.
+//!
+//! Like the syntax tree, tokens are generic over the `Storage` of their names and strings. The
+//! lexer gives `Owned` tokens, while the parser reads tokens whose text borrows from the source.
+
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+
+use ast::{Comment, CommentKind, Owned, Quote, Storage, StringForm};
+use span::{SourceError, Span, Spanned};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token<S: Storage = Owned> {
+    And,
+    Break,
+    Do,
+    Else,
+    Elseif,
+    End,
+    False,
+    For,
+    Function,
+    Goto,
+    If,
+    In,
+    Local,
+    Nil,
+    Not,
+    Or,
+    Repeat,
+    Return,
+    Then,
+    True,
+    Until,
+    While,
+    /// An identifier, such as the name of a variable.
+    Name(S::Name),
+    /// A number without a fraction or exponent.
+    Integer(i64),
+    /// A number with a fraction or exponent, or an integer too large to fit in an `i64`.
+    Float(f64),
+    /// A string literal, as the bytes it stands for. Escape sequences in short strings are decoded,
+    /// and long strings drop the newline directly after the opening bracket.
+    String(S::Str, StringForm),
+    /// + sigil.
+    Plus,
+    /// - sigil.
+    Minus,
+    /// * sigil.
+    Star,
+    /// / sigil.
+    Slash,
+    /// // sigil.
+    DoubleSlash,
+    /// % sigil.
+    Percent,
+    /// ^ sigil.
+    Caret,
+    /// # sigil.
+    Hash,
+    /// & sigil.
+    Ampersand,
+    /// | sigil.
+    Pipe,
+    /// ~ sigil, both binary xor and unary not.
+    Tilde,
+    /// << sigil.
+    ShiftLeft,
+    /// >> sigil.
+    ShiftRight,
+    /// == sigil.
+    EqualEqual,
+    /// ~= sigil.
+    TildeEqual,
+    /// <= sigil.
+    LessEqual,
+    /// >= sigil.
+    GreaterEqual,
+    /// < sigil.
+    Less,
+    /// > sigil.
+    Greater,
+    /// = sigil.
+    Equal,
+    /// ( sigil.
+    LeftParen,
+    /// ) sigil.
+    RightParen,
+    /// { sigil.
+    LeftBrace,
+    /// } sigil.
+    RightBrace,
+    /// [ sigil.
+    LeftBracket,
+    /// ] sigil.
+    RightBracket,
+    /// :: sigil.
+    DoubleColon,
+    /// ; sigil.
+    Semicolon,
+    /// : sigil.
+    Colon,
+    /// , sigil.
+    Comma,
+    /// . sigil.
+    Dot,
+    /// .. sigil.
+    DoubleDot,
+    /// ... sigil.
+    TripleDot,
+}
+
+impl Token {
+    /// Returns the keyword token spelled by `name`, if `name` is a reserved word.
+    pub fn keyword(name: &str) -> Option<Token> {
+        keyword(name)
+    }
+}
+
+/// The keyword token spelled by `name`, in any storage.
+fn keyword<S: Storage>(name: &str) -> Option<Token<S>> {
+    Some(match name {
+        "and" => Token::And,
+        "break" => Token::Break,
+        "do" => Token::Do,
+        "else" => Token::Else,
+        "elseif" => Token::Elseif,
+        "end" => Token::End,
+        "false" => Token::False,
+        "for" => Token::For,
+        "function" => Token::Function,
+        "goto" => Token::Goto,
+        "if" => Token::If,
+        "in" => Token::In,
+        "local" => Token::Local,
+        "nil" => Token::Nil,
+        "not" => Token::Not,
+        "or" => Token::Or,
+        "repeat" => Token::Repeat,
+        "return" => Token::Return,
+        "then" => Token::Then,
+        "true" => Token::True,
+        "until" => Token::Until,
+        "while" => Token::While,
+        _ => return None,
+    })
+}
+
+impl<S: Storage> Token<S> {
+    /// A short description of the token for error messages, such as `'end'` or `name`.
+    pub fn describe(&self) -> &'static str {
+        match *self {
+            Token::And => "'and'",
+            Token::Break => "'break'",
+            Token::Do => "'do'",
+            Token::Else => "'else'",
+            Token::Elseif => "'elseif'",
+            Token::End => "'end'",
+            Token::False => "'false'",
+            Token::For => "'for'",
+            Token::Function => "'function'",
+            Token::Goto => "'goto'",
+            Token::If => "'if'",
+            Token::In => "'in'",
+            Token::Local => "'local'",
+            Token::Nil => "'nil'",
+            Token::Not => "'not'",
+            Token::Or => "'or'",
+            Token::Repeat => "'repeat'",
+            Token::Return => "'return'",
+            Token::Then => "'then'",
+            Token::True => "'true'",
+            Token::Until => "'until'",
+            Token::While => "'while'",
+            Token::Name(_) => "name",
+            Token::Integer(_) | Token::Float(_) => "number",
+            Token::String(..) => "string",
+            Token::Plus => "'+'",
+            Token::Minus => "'-'",
+            Token::Star => "'*'",
+            Token::Slash => "'/'",
+            Token::DoubleSlash => "'//'",
+            Token::Percent => "'%'",
+            Token::Caret => "'^'",
+            Token::Hash => "'#'",
+            Token::Ampersand => "'&'",
+            Token::Pipe => "'|'",
+            Token::Tilde => "'~'",
+            Token::ShiftLeft => "'<<'",
+            Token::ShiftRight => "'>>'",
+            Token::EqualEqual => "'=='",
+            Token::TildeEqual => "'~='",
+            Token::LessEqual => "'<='",
+            Token::GreaterEqual => "'>='",
+            Token::Less => "'<'",
+            Token::Greater => "'>'",
+            Token::Equal => "'='",
+            Token::LeftParen => "'('",
+            Token::RightParen => "')'",
+            Token::LeftBrace => "'{'",
+            Token::RightBrace => "'}'",
+            Token::LeftBracket => "'['",
+            Token::RightBracket => "']'",
+            Token::DoubleColon => "'::'",
+            Token::Semicolon => "';'",
+            Token::Colon => "':'",
+            Token::Comma => "','",
+            Token::Dot => "'.'",
+            Token::DoubleDot => "'..'",
+            Token::TripleDot => "'...'",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LexErrorKind {
+    /// A character that can't start any token.
+    UnexpectedCharacter(char),
+    /// A string literal without a closing quote before the end of the line, or a long string
+    /// without its closing bracket.
+    UnterminatedString,
+    /// A block comment without its closing bracket.
+    UnterminatedComment,
+    /// A number literal that couldn't be read, such as `3x`.
+    MalformedNumber,
+    /// An escape sequence in a string that lua doesn't have, such as `\q` or `\300`.
+    InvalidEscape,
+}
+
+/// An error found while lexing, along with the span of the offending input.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LexError {
+    pub kind: LexErrorKind,
+    pub span: Span,
+}
+
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.write_message(f)?;
+        write!(f, " at byte {}", self.span.start)
+    }
+}
+
+impl SourceError for LexError {
+    fn span(&self) -> Option<Span> {
+        Some(self.span)
+    }
+
+    fn write_message(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            LexErrorKind::UnexpectedCharacter(c) => write!(f, "unexpected character {:?}", c),
+            LexErrorKind::UnterminatedString => write!(f, "unterminated string"),
+            LexErrorKind::UnterminatedComment => write!(f, "unterminated comment"),
+            LexErrorKind::MalformedNumber => write!(f, "malformed number"),
+            LexErrorKind::InvalidEscape => write!(f, "invalid escape sequence"),
+        }
+    }
+}
+
+impl Error for LexError {}
+
+/// A storage the lexer can give tokens in, making their text from the source.
+pub trait FromSource<'a>: Storage {
+    fn name(name: &'a str) -> Self::Name;
+    /// A string literal, borrowed from the source when it's written there as is.
+    fn string(s: Cow<'a, [u8]>) -> Self::Str;
+    fn text(text: &'a str) -> Self::Text;
+}
+
+impl<'a> FromSource<'a> for Owned {
+    fn name(name: &'a str) -> String {
+        name.to_string()
+    }
+
+    fn string(s: Cow<'a, [u8]>) -> Vec<u8> {
+        s.into_owned()
+    }
+
+    fn text(text: &'a str) -> String {
+        text.to_string()
+    }
+}
+
+/// The token starting at byte `start` of the source, which has to have been lexed without error
+/// before.
+pub(crate) fn token_at(source: &str, start: usize) -> Token {
+    let mut lexer = Lexer::new(source);
+    lexer.position = start;
+    match lexer.next_spanned() {
+        Some(Ok(token)) => token.node,
+        _ => panic!("no token at byte {}", start),
+    }
+}
+
+/// Turns a string of lua source code into tokens.
+///
+/// Whitespace is skipped, and comments are collected on the side to be taken with
+/// `into_comments`. After an error the lexer moves past the offending input, so iteration can
+/// continue.
+pub struct Lexer<'a, S: Storage = Owned> {
+    source: &'a str,
+    position: usize,
+    comments: Vec<Comment<S>>,
+    /// Whether a token has been read since the last newline, making a comment trailing.
+    line_has_token: bool,
+}
+
+impl<'a> Lexer<'a> {
+    /// Makes a lexer for the source, skipping a byte order mark at its start and a first line
+    /// starting with `#!` like the lua interpreter does, so scripts can be run as programs. Spans
+    /// still count from the start of the source.
+    pub fn new(source: &'a str) -> Lexer<'a> {
+        Lexer::from_source(source)
+    }
+}
+
+impl<'a, S: FromSource<'a>> Lexer<'a, S> {
+    /// Makes a lexer giving tokens in any storage, such as `borrowed::Borrowed` for tokens whose
+    /// text borrows from the source. Otherwise the same as `new`.
+    pub fn from_source(source: &'a str) -> Lexer<'a, S> {
+        let start = if source.starts_with('\u{FEFF}') { '\u{FEFF}'.len_utf8() } else { 0 };
+        let position = if source[start..].starts_with("#!") {
+            source[start..].find(['\n', '\r']).map_or(source.len(), |end| start + end)
+        } else {
+            start
+        };
+        Lexer { source, position, comments: Vec::new(), line_has_token: false }
+    }
+
+    /// The comments read so far, in the order they appear in the source.
+    pub fn into_comments(self) -> Vec<Comment<S>> {
+        self.comments
+    }
+
+    fn peek_byte(&self, offset: usize) -> Option<u8> {
+        self.source.as_bytes().get(self.position + offset).cloned()
+    }
+
+    /// An error for the input from `start` up to the current position.
+    fn error(&self, kind: LexErrorKind, start: usize) -> LexError {
+        LexError { kind, span: Span::new(start, self.position) }
+    }
+
+    /// Skips whitespace and comments, stopping at the start of the next token.
+    fn skip_trivia(&mut self) -> Result<(), LexError> {
+        while let Some(byte) = self.peek_byte(0) {
+            match byte {
+                b'\n' | b'\r' => {
+                    self.line_has_token = false;
+                    self.position += 1;
+                }
+                b' ' | b'\t' | 0x0b | 0x0c => self.position += 1,
+                b'-' if self.peek_byte(1) == Some(b'-') => self.comment()?,
+                _ => break,
+            }
+        }
+        Ok(())
+    }
+
+    /// The level of the long bracket opening at the current position, such as 2 for `[==[`.
+    fn long_bracket_level(&self) -> Option<usize> {
+        let rest = &self.source.as_bytes()[self.position..];
+        if rest.first() != Some(&b'[') {
+            return None;
+        }
+        let level = rest[1..].iter().take_while(|&&b| b == b'=').count();
+        if rest.get(level + 1) == Some(&b'[') { Some(level) } else { None }
+    }
+
+    /// Reads from an opening long bracket of `level` to the matching closing bracket, returning
+    /// what's between them. `kind` is the error if the closing bracket is missing.
+    fn long_bracket(&mut self, level: usize, kind: LexErrorKind) -> Result<&'a str, LexError> {
+        let start = self.position;
+        self.position += level + 2;
+        let close = format!("]{}]", "=".repeat(level));
+        match self.source[self.position..].find(&close) {
+            Some(end) => {
+                let contents = &self.source[self.position..self.position + end];
+                self.position += end + close.len();
+                Ok(contents)
+            }
+            None => {
+                self.position = self.source.len();
+                Err(self.error(kind, start))
+            }
+        }
+    }
+
+    /// Reads a comment, up to but not including the newline ending a line comment.
+    fn comment(&mut self) -> Result<(), LexError> {
+        let start = self.position;
+        self.position += 2;
+        let (text, kind) = if let Some(level) = self.long_bracket_level() {
+            match self.long_bracket(level, LexErrorKind::UnterminatedComment) {
+                Ok(text) => (text, CommentKind::Block(level)),
+                Err(_) => return Err(self.error(LexErrorKind::UnterminatedComment, start)),
+            }
+        } else {
+            let rest = &self.source[self.position..];
+            // A line comment ends at any line break, be it `\n`, `\r\n` or a lone `\r`.
+            let text = &rest[..rest.find(['\n', '\r']).unwrap_or(rest.len())];
+            self.position += text.len();
+            (text, CommentKind::Line)
+        };
+        self.comments.push(Comment {
+            text: S::text(text),
+            kind,
+            trailing: self.line_has_token,
+            span: Span::new(start, self.position),
+        });
+        Ok(())
+    }
+
+    fn lex_name(&mut self) -> Token<S> {
+        let start = self.position;
+        while let Some(byte) = self.peek_byte(0) {
+            if byte.is_ascii_alphanumeric() || byte == b'_' {
+                self.position += 1;
+            } else {
+                break;
+            }
+        }
+        let name = &self.source[start..self.position];
+        keyword(name).unwrap_or_else(|| Token::Name(S::name(name)))
+    }
+
+    fn skip_digits(&mut self) {
+        while self.peek_byte(0).is_some_and(|b| b.is_ascii_digit()) {
+            self.position += 1;
+        }
+    }
+
+    /// Fails with a malformed number if the number just read runs on into more letters, digits or
+    /// dots.
+    fn end_number(&mut self, start: usize) -> Result<(), LexError> {
+        // Lua reads a number as far as it looks like one, so `3x` is a bad number, not `3` and `x`.
+        let is_number_byte = |b: u8| b.is_ascii_alphanumeric() || b == b'_' || b == b'.';
+        if self.peek_byte(0).is_some_and(is_number_byte) {
+            while self.peek_byte(0).is_some_and(is_number_byte) {
+                self.position += 1;
+            }
+            return Err(self.error(LexErrorKind::MalformedNumber, start));
+        }
+        Ok(())
+    }
+
+    fn lex_number(&mut self) -> Result<Token<S>, LexError> {
+        let rest = &self.source[self.position..];
+        if rest.starts_with("0x") || rest.starts_with("0X") {
+            return self.lex_hex_number();
+        }
+        let start = self.position;
+        let mut is_float = false;
+        self.skip_digits();
+        if self.peek_byte(0) == Some(b'.') {
+            is_float = true;
+            self.position += 1;
+            self.skip_digits();
+        }
+        if let Some(b'e') | Some(b'E') = self.peek_byte(0) {
+            is_float = true;
+            self.position += 1;
+            if let Some(b'+') | Some(b'-') = self.peek_byte(0) {
+                self.position += 1;
+            }
+            self.skip_digits();
+        }
+        self.end_number(start)?;
+        let text = &self.source[start..self.position];
+        if !is_float {
+            // Like lua, integers that overflow are read as floats instead.
+            if let Ok(n) = text.parse() {
+                return Ok(Token::Integer(n));
+            }
+        }
+        text.parse()
+            .map(Token::Float)
+            .map_err(|_| self.error(LexErrorKind::MalformedNumber, start))
+    }
+
+    /// Lexes a hexadecimal number such as `0xff`, `0x.8` or `0x1p4`, where the exponent after `p`
+    /// is a power of two written in decimal.
+    fn lex_hex_number(&mut self) -> Result<Token<S>, LexError> {
+        let start = self.position;
+        self.position += 2;
+        // Hexadecimal integers wrap around on overflow, so the integer value is kept separately
+        // from the float value.
+        let mut integer: i64 = 0;
+        let mut float = 0.0;
+        let mut exponent: i64 = 0;
+        let mut has_digits = false;
+        let mut is_float = false;
+        while let Some(digit) = self.peek_byte(0).and_then(|b| (b as char).to_digit(16)) {
+            integer = integer.wrapping_mul(16).wrapping_add(digit as i64);
+            float = float * 16.0 + digit as f64;
+            has_digits = true;
+            self.position += 1;
+        }
+        if self.peek_byte(0) == Some(b'.') {
+            is_float = true;
+            self.position += 1;
+            while let Some(digit) = self.peek_byte(0).and_then(|b| (b as char).to_digit(16)) {
+                float = float * 16.0 + digit as f64;
+                exponent -= 4;
+                has_digits = true;
+                self.position += 1;
+            }
+        }
+        if !has_digits {
+            self.end_number(start)?;
+            return Err(self.error(LexErrorKind::MalformedNumber, start));
+        }
+        if let Some(b'p') | Some(b'P') = self.peek_byte(0) {
+            is_float = true;
+            self.position += 1;
+            let negative = self.peek_byte(0) == Some(b'-');
+            if let Some(b'+') | Some(b'-') = self.peek_byte(0) {
+                self.position += 1;
+            }
+            let digits_start = self.position;
+            self.skip_digits();
+            let written: i64 = match self.source[digits_start..self.position].parse() {
+                Ok(written) => written,
+                Err(_) => {
+                    self.end_number(start)?;
+                    return Err(self.error(LexErrorKind::MalformedNumber, start));
+                }
+            };
+            exponent += if negative { -written } else { written };
+        }
+        self.end_number(start)?;
+        if is_float {
+            let exponent = exponent.clamp(i32::MIN as i64, i32::MAX as i64) as i32;
+            Ok(Token::Float(float * 2f64.powi(exponent)))
+        } else {
+            Ok(Token::Integer(integer))
+        }
+    }
+
+    /// Lexes a short string, decoding the escape sequences described at
+    /// http://www.lua.org/manual/5.2/manual.html#3.1.
+    ///
+    /// The bytes are only copied out of the source from the first escape sequence on, so a string
+    /// without any borrows them.
+    fn lex_string(&mut self, quote: u8) -> Result<Token<S>, LexError> {
+        let start = self.position;
+        self.position += 1;
+        let mut bytes: Option<Vec<u8>> = None;
+        // The first invalid escape, which is reported once the rest of the string is read past.
+        let mut invalid = None;
+        loop {
+            match self.peek_byte(0) {
+                Some(byte) if byte == quote => break,
+                Some(b'\\') => {
+                    let copied = &self.source.as_bytes()[start + 1..self.position];
+                    let bytes = bytes.get_or_insert_with(|| copied.to_vec());
+                    self.position += 1;
+                    if let Err(err) = self.escape(bytes) {
+                        invalid = invalid.or(Some(err));
+                    }
+                }
+                Some(b'\n') | Some(b'\r') | None => {
+                    return Err(self.error(LexErrorKind::UnterminatedString, start));
+                }
+                Some(byte) => {
+                    if let Some(ref mut bytes) = bytes {
+                        bytes.push(byte);
+                    }
+                    self.position += 1;
+                }
+            }
+        }
+        self.position += 1;
+        if let Some(err) = invalid {
+            return Err(err);
+        }
+        let quote = if quote == b'\'' { Quote::Single } else { Quote::Double };
+        let bytes = match bytes {
+            Some(bytes) => Cow::Owned(bytes),
+            None => Cow::Borrowed(&self.source.as_bytes()[start + 1..self.position - 1]),
+        };
+        Ok(Token::String(S::string(bytes), StringForm::Short(quote)))
+    }
+
+    /// Decodes the escape sequence after a backslash, which has already been read past.
+    fn escape(&mut self, bytes: &mut Vec<u8>) -> Result<(), LexError> {
+        let start = self.position - 1;
+        // A backslash at the end of the input leaves the string unterminated.
+        let Some(byte) = self.peek_byte(0) else { return Ok(()) };
+        self.position += 1;
+        let decoded = match byte {
+            b'a' => 0x07,
+            b'b' => 0x08,
+            b'f' => 0x0c,
+            b'n' => b'\n',
+            b'r' => b'\r',
+            b't' => b'\t',
+            b'v' => 0x0b,
+            b'\\' | b'"' | b'\'' => byte,
+            b'\n' | b'\r' => {
+                // An escaped line break, which may be written as a pair of different bytes.
+                if let Some(next @ (b'\n' | b'\r')) = self.peek_byte(0) {
+                    if next != byte {
+                        self.position += 1;
+                    }
+                }
+                b'\n'
+            }
+            b'z' => {
+                while let Some(b' ' | b'\t' | b'\n' | b'\r' | 0x0b | 0x0c) = self.peek_byte(0) {
+                    self.position += 1;
+                }
+                return Ok(());
+            }
+            b'x' => match (self.peek_byte(0), self.peek_byte(1)) {
+                (Some(high), Some(low)) if high.is_ascii_hexdigit() && low.is_ascii_hexdigit() => {
+                    self.position += 2;
+                    let digits = &self.source[self.position - 2..self.position];
+                    u8::from_str_radix(digits, 16).unwrap()
+                }
+                _ => return Err(self.error(LexErrorKind::InvalidEscape, start)),
+            },
+            b'0'..=b'9' => {
+                let mut value = u32::from(byte - b'0');
+                for _ in 0..2 {
+                    match self.peek_byte(0) {
+                        Some(digit @ b'0'..=b'9') => {
+                            value = value * 10 + u32::from(digit - b'0');
+                            self.position += 1;
+                        }
+                        _ => break,
+                    }
+                }
+                if value > 255 {
+                    return Err(self.error(LexErrorKind::InvalidEscape, start));
+                }
+                value as u8
+            }
+            b'u' => {
+                let code = self.unicode_escape();
+                let code = code.ok_or_else(|| self.error(LexErrorKind::InvalidEscape, start))?;
+                encode_utf8(code, bytes);
+                return Ok(());
+            }
+            _ => return Err(self.error(LexErrorKind::InvalidEscape, start)),
+        };
+        bytes.push(decoded);
+        Ok(())
+    }
+
+    /// Reads the braced code point of a `\u{XXX}` escape, from lua 5.4 at
+    /// http://www.lua.org/manual/5.4/manual.html#3.1. Any code point below 2^31 is valid, including
+    /// surrogates and ones past `10FFFF`, which no `char` holds.
+    fn unicode_escape(&mut self) -> Option<u32> {
+        if self.peek_byte(0) != Some(b'{') {
+            return None;
+        }
+        let digits_start = self.position + 1;
+        let digits = self.source.as_bytes()[digits_start..]
+            .iter()
+            .take_while(|byte| byte.is_ascii_hexdigit())
+            .count();
+        self.position = digits_start + digits;
+        if digits == 0 || self.peek_byte(0) != Some(b'}') {
+            return None;
+        }
+        self.position += 1;
+        let code = u32::from_str_radix(&self.source[digits_start..digits_start + digits], 16);
+        code.ok().filter(|&code| code < 1 << 31)
+    }
+
+    /// Lexes a string in long brackets, such as `[[abc]]` or `[==[abc]==]`. As in lua, every line
+    /// break in it is read as `\n`, be it `\r\n`, `\n\r` or a lone `\r`, so the string is only
+    /// borrowed from the source if it has no `\r`.
+    fn lex_long_string(&mut self, level: usize) -> Result<Token<S>, LexError> {
+        let contents = self.long_bracket(level, LexErrorKind::UnterminatedString)?;
+        // A newline directly after the opening bracket isn't part of the string.
+        let contents = contents
+            .strip_prefix("\r\n")
+            .or_else(|| contents.strip_prefix("\n\r"))
+            .or_else(|| contents.strip_prefix('\n'))
+            .or_else(|| contents.strip_prefix('\r'))
+            .unwrap_or(contents);
+        let contents = if contents.contains('\r') {
+            Cow::Owned(normalize_line_breaks(contents))
+        } else {
+            Cow::Borrowed(contents.as_bytes())
+        };
+        Ok(Token::String(S::string(contents), StringForm::Long(level)))
+    }
+
+    /// Lexes the next token along with its span.
+    pub fn next_spanned(&mut self) -> Option<Result<Spanned<Token<S>>, LexError>> {
+        if let Err(err) = self.skip_trivia() {
+            return Some(Err(err));
+        }
+        let byte = self.peek_byte(0)?;
+        let start = self.position;
+        let token = match byte {
+            b'a'..=b'z' | b'A'..=b'Z' | b'_' => Ok(self.lex_name()),
+            b'0'..=b'9' => self.lex_number(),
+            b'.' if self.peek_byte(1).is_some_and(|b| b.is_ascii_digit()) => self.lex_number(),
+            b'"' | b'\'' => self.lex_string(byte),
+            b'[' if self.long_bracket_level().is_some() => {
+                let level = self.long_bracket_level().unwrap();
+                self.lex_long_string(level)
+            }
+            _ => self.lex_sigil(),
+        };
+        self.line_has_token = true;
+        Some(token.map(|token| Spanned::new(token, Span::new(start, self.position))))
+    }
+
+    /// Lexes a token of punctuation, trying the longest sigils first.
+    fn lex_sigil(&mut self) -> Result<Token<S>, LexError> {
+        let rest = &self.source[self.position..];
+        for len in (1..=3).rev() {
+            if let Some(token) = rest.get(..len).and_then(sigil) {
+                self.position += len;
+                return Ok(token);
+            }
+        }
+        let c = rest.chars().next().expect("lex_sigil called at the end of the source");
+        let start = self.position;
+        self.position += c.len_utf8();
+        Err(self.error(LexErrorKind::UnexpectedCharacter(c), start))
+    }
+}
+
+/// Turns every line break in `s` into `\n`, taking `\r\n` and `\n\r` as one line break each.
+fn normalize_line_breaks(s: &str) -> Vec<u8> {
+    let mut normalized = Vec::with_capacity(s.len());
+    let mut bytes = s.bytes().peekable();
+    while let Some(byte) = bytes.next() {
+        match byte {
+            b'\r' | b'\n' => {
+                let pair = if byte == b'\r' { b'\n' } else { b'\r' };
+                bytes.next_if_eq(&pair);
+                normalized.push(b'\n');
+            }
+            byte => normalized.push(byte),
+        }
+    }
+    normalized
+}
+
+/// Encodes a code point below 2^31 as lua does, with the UTF-8 scheme extended to six bytes for
+/// the code points past `10FFFF`, and without rejecting surrogates.
+fn encode_utf8(mut code: u32, bytes: &mut Vec<u8>) {
+    if code < 0x80 {
+        bytes.push(code as u8);
+        return;
+    }
+    let mut continuation = Vec::with_capacity(5);
+    // The most the first byte can hold, which shrinks by a bit for every byte after it.
+    let mut first_max = 0x3f;
+    loop {
+        continuation.push(0x80 | (code & 0x3f) as u8);
+        code >>= 6;
+        first_max >>= 1;
+        if code <= first_max {
+            break;
+        }
+    }
+    bytes.push((!first_max << 1) as u8 | code as u8);
+    bytes.extend(continuation.iter().rev());
+}
+
+/// The token of punctuation spelled by `text`, if there is one.
+fn sigil<S: Storage>(text: &str) -> Option<Token<S>> {
+    Some(match text {
+        "..." => Token::TripleDot,
+        ".." => Token::DoubleDot,
+        "==" => Token::EqualEqual,
+        "~=" => Token::TildeEqual,
+        "<=" => Token::LessEqual,
+        ">=" => Token::GreaterEqual,
+        "::" => Token::DoubleColon,
+        "<<" => Token::ShiftLeft,
+        "//" => Token::DoubleSlash,
+        ">>" => Token::ShiftRight,
+        "+" => Token::Plus,
+        "-" => Token::Minus,
+        "*" => Token::Star,
+        "/" => Token::Slash,
+        "%" => Token::Percent,
+        "^" => Token::Caret,
+        "#" => Token::Hash,
+        "&" => Token::Ampersand,
+        "|" => Token::Pipe,
+        "~" => Token::Tilde,
+        "<" => Token::Less,
+        ">" => Token::Greater,
+        "=" => Token::Equal,
+        "(" => Token::LeftParen,
+        ")" => Token::RightParen,
+        "{" => Token::LeftBrace,
+        "}" => Token::RightBrace,
+        "[" => Token::LeftBracket,
+        "]" => Token::RightBracket,
+        ";" => Token::Semicolon,
+        ":" => Token::Colon,
+        "," => Token::Comma,
+        "." => Token::Dot,
+        _ => return None,
+    })
+}
+
+impl<'a, S: FromSource<'a>> Iterator for Lexer<'a, S> {
+    type Item = Result<Token<S>, LexError>;
+
+    fn next(&mut self) -> Option<Result<Token<S>, LexError>> {
+        self.next_spanned().map(|result| result.map(|token| token.node))
+    }
+}
+
+/// A lexer that can look ahead at tokens before taking them, for parsers built on top of it.
+///
+/// Tokens come with their spans, and an error is given in its place in the stream like a token.
+pub struct TokenStream<'a> {
+    lexer: Lexer<'a>,
+    /// Tokens lexed by looking ahead but not taken yet.
+    buffer: VecDeque<Result<Spanned<Token>, LexError>>,
+}
+
+impl<'a> TokenStream<'a> {
+    pub fn new(source: &'a str) -> TokenStream<'a> {
+        TokenStream::from(Lexer::new(source))
+    }
+
+    /// The next token, without taking it.
+    pub fn peek(&mut self) -> Option<&Result<Spanned<Token>, LexError>> {
+        self.peek_nth(0)
+    }
+
+    /// The token `n` places after the next one without taking any, so `peek_nth(0)` is the same
+    /// as `peek`.
+    pub fn peek_nth(&mut self, n: usize) -> Option<&Result<Spanned<Token>, LexError>> {
+        while self.buffer.len() <= n {
+            self.buffer.push_back(self.lexer.next_spanned()?);
+        }
+        self.buffer.get(n)
+    }
+
+    /// Takes the next token if it was lexed without error and `predicate` holds for it.
+    pub fn next_if<F>(&mut self, predicate: F) -> Option<Spanned<Token>>
+    where
+        F: FnOnce(&Token) -> bool,
+    {
+        match self.peek() {
+            Some(Ok(token)) if predicate(&token.node) => {}
+            _ => return None,
+        }
+        self.buffer.pop_front().and_then(Result::ok)
+    }
+
+    /// The comments read so far, including those before tokens that were only looked at.
+    pub fn into_comments(self) -> Vec<Comment> {
+        self.lexer.into_comments()
+    }
+}
+
+impl<'a> From<Lexer<'a>> for TokenStream<'a> {
+    fn from(lexer: Lexer<'a>) -> TokenStream<'a> {
+        TokenStream { lexer, buffer: VecDeque::new() }
+    }
+}
+
+impl<'a> Iterator for TokenStream<'a> {
+    type Item = Result<Spanned<Token>, LexError>;
+
+    fn next(&mut self) -> Option<Result<Spanned<Token>, LexError>> {
+        self.buffer.pop_front().or_else(|| self.lexer.next_spanned())
+    }
+}
@@ -1 +1,56 @@
+// Without the standard library only the syntax tree is available, with its strings, boxes and
+// vectors taken from `alloc`.
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(not(feature = "std"))]
+extern crate alloc;
+#[cfg(feature = "std")]
+extern crate core;
+#[cfg(feature = "rayon")]
+extern crate rayon;
+#[cfg(feature = "serde")]
+extern crate serde;
+
+#[cfg(feature = "std")]
+pub mod analysis;
+pub mod arena;
 pub mod ast;
+#[cfg(feature = "std")]
+pub mod borrowed;
+#[cfg(feature = "std")]
+pub mod build;
+#[cfg(feature = "std")]
+mod convert;
+#[cfg(feature = "std")]
+pub mod diff;
+#[cfg(feature = "std")]
+pub mod export;
+#[cfg(feature = "std")]
+pub mod highlight;
+#[cfg(feature = "std")]
+pub mod interp;
+#[cfg(feature = "std")]
+pub mod intern;
+#[cfg(feature = "std")]
+pub mod lexer;
+#[cfg(feature = "std")]
+pub mod lint;
+#[cfg(feature = "std")]
+pub mod opt;
+#[cfg(feature = "std")]
+pub mod parser;
+#[cfg(feature = "std")]
+pub mod print;
+#[cfg(feature = "std")]
+pub mod refactor;
+#[cfg(feature = "std")]
+pub mod resolve;
+pub mod span;
+#[cfg(feature = "std")]
+pub mod stats;
+#[cfg(feature = "std")]
+pub mod stdlib;
+#[cfg(feature = "std")]
+pub mod validate;
+#[cfg(feature = "std")]
+pub mod visit;
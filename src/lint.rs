@@ -0,0 +1,166 @@
+//! Lints, finding code that is valid lua but likely to be a mistake.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::iter;
+
+use ast::*;
+use resolve::{collect_names, resolve_names, Binding};
+use span::{Span, Spanned};
+use visit::{self, Visitor};
+
+/// How an unused local was declared.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LocalKind {
+    /// A `local` statement.
+    Variable,
+    /// A `local function` statement.
+    Function,
+    /// A parameter of a function.
+    Parameter,
+}
+
+/// A local whose value is never read. Assigning to a local doesn't count as using it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnusedLocal {
+    pub name: String,
+    /// The span of the name declaring the local.
+    pub span: Span,
+    pub kind: LocalKind,
+}
+
+impl fmt::Display for UnusedLocal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = match self.kind {
+            LocalKind::Variable => "local variable",
+            LocalKind::Function => "local function",
+            LocalKind::Parameter => "parameter",
+        };
+        write!(f, "unused {} {:?} at byte {}", kind, self.name, self.span.start)
+    }
+}
+
+/// Finds the locals and parameters in the chunk that are never read, in source order. Locals
+/// named `_` are meant to be unused, so they are left out.
+///
+/// Like name resolution, this tells nodes apart by their spans, so the chunk should have been
+/// parsed rather than built by hand.
+///
+/// # Example
+/// ```lua
+/// local function f(a, b)
+///     local c = 1
+///     c = 2
+///     return a
+/// end
+/// ```
+/// has an unused local function `f`, parameter `b` and local variable `c`.
+pub fn find_unused_locals(chunk: &Chunk) -> Vec<UnusedLocal> {
+    let mut finder = LocalFinder { declared: Vec::new() };
+    visit::walk_chunk(&mut finder, chunk);
+    let writes = assigned_names(chunk);
+    let read: HashSet<Span> = resolve_names(chunk)
+        .bindings
+        .into_iter()
+        .filter(|(use_span, _)| !writes.contains(use_span))
+        .filter_map(|(_, binding)| match binding {
+            Binding::Local(declared) | Binding::Upvalue(declared) => Some(declared),
+            Binding::Global => None,
+        })
+        .collect();
+    finder
+        .declared
+        .into_iter()
+        .filter(|local| local.name != "_" && !read.contains(&local.span))
+        .collect()
+}
+
+/// Finds the globals the chunk reads that aren't in `known`, such as misspelt names, in source
+/// order. Globals the chunk assigns to are only reported where they are read.
+///
+/// Like name resolution, this tells nodes apart by their spans, so the chunk should have been
+/// parsed rather than built by hand.
+pub fn find_undefined_globals(chunk: &Chunk, known: &HashSet<String>) -> Vec<(String, Span)> {
+    let resolution = resolve_names(chunk);
+    let writes = assigned_names(chunk);
+    collect_names(chunk)
+        .into_iter()
+        .filter(|&(ref name, span)| {
+            resolution.binding(span) == Some(Binding::Global) &&
+                !writes.contains(&span) &&
+                !known.contains(name)
+        })
+        .collect()
+}
+
+/// The spans of the names the chunk assigns to, by assignments and `function` statements.
+fn assigned_names(chunk: &Chunk) -> HashSet<Span> {
+    let mut assigned = AssignedNames(HashSet::new());
+    visit::walk_chunk(&mut assigned, chunk);
+    assigned.0
+}
+
+struct AssignedNames(HashSet<Span>);
+
+impl Visitor for AssignedNames {
+    fn visit_statement(&mut self, statement: &Spanned<Statement>) {
+        match statement.node {
+            Statement::Assignment(ref variables, _) => {
+                let rest = variables.rest.iter().flatten();
+                for variable in iter::once(&*variables.first).chain(rest) {
+                    if let Variable::Name(ref name) = *variable {
+                        self.0.insert(name.span);
+                    }
+                }
+            }
+            // A dotted name only reads its first part.
+            Statement::Function(ref name, _)
+                if name.rest_dot_access.is_none() && name.self_name.is_none() =>
+            {
+                self.0.insert(name.first_dot_access.span);
+            }
+            _ => {}
+        }
+        visit::walk_statement(self, statement);
+    }
+}
+
+struct LocalFinder {
+    declared: Vec<UnusedLocal>,
+}
+
+impl LocalFinder {
+    fn declare(&mut self, name: &Spanned<String>, kind: LocalKind) {
+        self.declared.push(UnusedLocal { name: name.node.clone(), span: name.span, kind });
+    }
+}
+
+impl Visitor for LocalFinder {
+    fn visit_statement(&mut self, statement: &Spanned<Statement>) {
+        match statement.node {
+            Statement::LocalVariableBinding(ref names, _) => {
+                self.declare(&names.0.name, LocalKind::Variable);
+                for name in names.1.iter().flatten() {
+                    self.declare(&name.name, LocalKind::Variable);
+                }
+            }
+            Statement::LocalFunction { ref name, .. } => self.declare(name, LocalKind::Function),
+            _ => {}
+        }
+        visit::walk_statement(self, statement);
+    }
+
+    fn visit_function_body(&mut self, body: &FunctionBody) {
+        match body.0.as_deref() {
+            Some(ParameterList::NameList(names)) |
+            Some(ParameterList::ExtendedArguments(names)) => {
+                self.declare(&names.0, LocalKind::Parameter);
+                for name in names.1.iter().flatten() {
+                    self.declare(name, LocalKind::Parameter);
+                }
+            }
+            Some(ParameterList::ExtendedArgumentsVoid) | None => {}
+        }
+        visit::walk_function_body(self, body);
+    }
+}
@@ -0,0 +1,132 @@
+//! Dead code elimination, removing statements that can never run.
+//!
+//! Statements after a `break`, `goto` or a block that always jumps away are removed up to the
+//! next label that a `goto` jumps to, as described in `analysis`. Branches of an `if` whose
+//! condition is a literal are resolved, so running `fold_constants` first removes more of them.
+//! A label that no `goto` names doesn't make the code after it reachable, as nothing can jump to
+//! it, so it's removed along with that code.
+
+use std::collections::HashSet;
+
+use analysis::jumps_away;
+use ast::*;
+use span::Spanned;
+use visit::{self, Visitor, VisitorMut};
+
+/// Removes the statements in the chunk that can never run, and the branches of `if` statements
+/// whose conditions are literals that never take them.
+///
+/// # Example
+/// ```lua
+/// while true do
+///     if false then print(1) elseif x then print(2) end
+///     break
+///     print(3)
+/// end
+/// ```
+/// becomes
+/// ```lua
+/// while true do
+///     if x then print(2) end
+///     break
+/// end
+/// ```
+pub fn eliminate_dead_code(chunk: &mut Chunk) {
+    // Removing a `goto` can leave its label unused, freeing the code after that too.
+    loop {
+        let mut gotos = GotoNames::default();
+        visit::walk_chunk(&mut gotos, chunk);
+        let count = gotos.0.len();
+        visit::walk_chunk_mut(&mut Eliminator { targets: gotos.0 }, chunk);
+        let mut remaining = GotoNames::default();
+        visit::walk_chunk(&mut remaining, chunk);
+        if remaining.0.len() == count {
+            break;
+        }
+    }
+}
+
+/// The names of the labels some `goto` in the chunk jumps to.
+#[derive(Default)]
+struct GotoNames(HashSet<String>);
+
+impl Visitor for GotoNames {
+    fn visit_statement(&mut self, statement: &Spanned<Statement>) {
+        if let Statement::Goto(ref label) = statement.node {
+            self.0.insert(label.0.clone());
+        }
+        visit::walk_statement(self, statement);
+    }
+}
+
+struct Eliminator {
+    targets: HashSet<String>,
+}
+
+/// Whether a condition is a literal that is always true or always false.
+fn literal_truth(expression: &Expression) -> Option<bool> {
+    match *expression {
+        Expression::Nil | Expression::False => Some(false),
+        Expression::True | Expression::Integer(..) | Expression::Float(..) => Some(true),
+        Expression::String(..) => Some(true),
+        Expression::PrefixExpression(ref prefix) => match **prefix {
+            PrefixExpression::Parenthesis(ref inner) => literal_truth(&inner.node),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Drops the branches of an `if` statement that are never taken. A branch that is always taken
+/// becomes the `else`, and an `if` left with only that becomes a `do` block, or nothing if it has
+/// no branches at all.
+fn resolve_branches(statement: Statement) -> Option<Statement> {
+    let (condition, then, elseifs, mut else_) = match statement {
+        Statement::If { condition, then, elseifs, else_ } => (condition, then, elseifs, else_),
+        statement => return Some(statement),
+    };
+    let mut kept = Vec::new();
+    for (condition, block) in Some((condition, then)).into_iter().chain(elseifs) {
+        match literal_truth(&condition.node) {
+            Some(false) => {}
+            Some(true) => {
+                else_ = Some(block);
+                break;
+            }
+            None => kept.push((condition, block)),
+        }
+    }
+    if kept.is_empty() {
+        return else_.map(Statement::Do);
+    }
+    let (condition, then) = kept.remove(0);
+    Some(Statement::If { condition, then, elseifs: kept, else_ })
+}
+
+impl VisitorMut for Eliminator {
+    fn visit_block_mut(&mut self, block: &mut Block) {
+        visit::walk_block_mut(self, block);
+        let statements = block.0.take().unwrap_or_default();
+        let mut live = Vec::with_capacity(statements.len());
+        let mut reachable = true;
+        for statement in statements {
+            let Spanned { node, span } = statement;
+            if let Statement::Label(ref label) = node {
+                reachable = reachable || self.targets.contains(&label.0);
+            }
+            if !reachable {
+                continue;
+            }
+            if let Some(node) = resolve_branches(node) {
+                reachable = !jumps_away(&node);
+                live.push(Spanned { node, span });
+            }
+        }
+        if !reachable {
+            block.1 = None;
+        }
+        if !live.is_empty() {
+            block.0 = Some(live);
+        }
+    }
+}
@@ -0,0 +1,546 @@
+//! Desugaring, rewriting statements in terms of simpler ones for backends that lack them.
+//!
+//! Both kinds of `for` loop are rewritten following their definitions in the lua manual at
+//! http://www.lua.org/manual/5.2/manual.html#3.3.5, except that a numeric loop counts in floats
+//! unless it starts and steps by integers, as in lua 5.3, and that it follows lua 5.4 in raising
+//! an error for a step of zero, rather than looping forever, and in never wrapping an integer
+//! counter around.
+//!
+//! A function statement is rewritten into the assignment it stands for, as described at
+//! http://www.lua.org/manual/5.2/manual.html#3.4.10, with the `self` parameter of a method made
+//! explicit.
+
+use std::collections::HashSet;
+use std::iter;
+use std::mem;
+
+use ast::*;
+use build::{expr, stmt, var, BlockBuilder};
+use span::{Span, Spanned};
+use visit::{self, Visitor, VisitorMut};
+
+/// Rewrites every numeric `for` loop in the chunk into a `while` loop over hidden locals.
+///
+/// # Example
+/// ```lua
+/// for i = 1, n do print(i) end
+/// ```
+/// becomes
+/// ```lua
+/// do
+///     local for_var, for_limit, for_step = 1, n, 1
+///     if type(for_limit) ~= "number" then
+///         for_limit = type(for_limit) == "string" and tonumber(for_limit)
+///             or error("'for' limit must be a number")
+///     end
+///     if math.type(for_limit) == "float" then
+///         for_limit = math.floor(for_limit)
+///         if math.type(for_limit) == "float" then
+///             if for_limit > 0 then
+///                 for_limit = math.maxinteger
+///             elseif for_limit < 0 then
+///                 for_limit = math.mininteger
+///             end
+///         end
+///     end
+///     while for_var <= for_limit do
+///         local i = for_var
+///         do print(i) end
+///         if 0 <= for_limit - for_var and for_limit - for_var < for_step then break end
+///         for_var = for_var + for_step
+///     end
+/// end
+/// ```
+/// Like lua 5.4, a loop counting in integers rounds a float limit to an integer first, and stops
+/// before a step would take the counter past the limit, so it never wraps around at the bounds of
+/// the integers.
+///
+/// Only what isn't known from the source is checked as the loop runs. A start, limit or step that
+/// isn't a number literal is converted from a string like the above, a step that isn't is checked
+/// for zero and has its direction tested, and when the start or the step isn't, another hidden
+/// local `for_integer` records whether the loop counts in integers, a loop in floats making its
+/// counter one. These checks call `type`, `tonumber`, `error` and the `math` library. The hidden
+/// locals are given other names if the chunk already uses these.
+pub fn desugar_numeric_for(chunk: &mut Chunk) {
+    let [var, limit, step, integer] = hidden_names(chunk, ["var", "limit", "step", "integer"]);
+    visit::walk_chunk_mut(&mut NumericForDesugarer { var, limit, step, integer }, chunk);
+}
+
+/// Rewrites every generic `for` loop in the chunk into a `while` loop calling its iterator
+/// function explicitly.
+///
+/// # Example
+/// ```lua
+/// for k, v in pairs(t) do print(k, v) end
+/// ```
+/// becomes
+/// ```lua
+/// do
+///     local for_f, for_s, for_control = pairs(t)
+///     while true do
+///         local k, v = for_f(for_s, for_control)
+///         if k == nil then break end
+///         for_control = k
+///         print(k, v)
+///     end
+/// end
+/// ```
+/// The hidden locals are given other names if the chunk already uses these.
+pub fn desugar_generic_for(chunk: &mut Chunk) {
+    let [function, state, control] = hidden_names(chunk, ["f", "s", "control"]);
+    visit::walk_chunk_mut(&mut GenericForDesugarer { function, state, control }, chunk);
+}
+
+/// Rewrites every function statement in the chunk into an assignment of a function expression,
+/// adding the `self` parameter a method takes.
+///
+/// # Example
+/// ```lua
+/// function a.b:c(d) return self, d end
+/// ```
+/// becomes
+/// ```lua
+/// a.b.c = function(self, d) return self, d end
+/// ```
+/// A `local function` statement is left alone, as the local it declares is in scope inside the
+/// function, which it isn't when assigning a function expression to a local.
+pub fn desugar_function_statements(chunk: &mut Chunk) {
+    visit::walk_chunk_mut(&mut FunctionStatementDesugarer, chunk);
+}
+
+/// Names for hidden locals, made of `for` and the given suffixes, with a number after the `for`
+/// if the chunk already uses any of them.
+fn hidden_names<const N: usize>(chunk: &Chunk, suffixes: [&str; N]) -> [String; N] {
+    let mut used = UsedNames::default();
+    visit::walk_chunk(&mut used, chunk);
+    let mut n = 0;
+    loop {
+        let prefix = if n == 0 { "for".to_string() } else { format!("for{}", n) };
+        let names = suffixes.map(|suffix| format!("{}_{}", prefix, suffix));
+        if !names.iter().any(|name| used.0.contains(name)) {
+            return names;
+        }
+        n += 1;
+    }
+}
+
+/// The names read, assigned or declared anywhere in a chunk, which the hidden locals mustn't
+/// shadow. A declared name counts even if it's never read, as it would shadow a hidden local of
+/// the same name in its scope.
+#[derive(Default)]
+struct UsedNames(HashSet<String>);
+
+impl UsedNames {
+    fn insert_names(&mut self, names: &NameList) {
+        self.0.insert(names.0.node.clone());
+        for name in names.1.iter().flatten() {
+            self.0.insert(name.node.clone());
+        }
+    }
+}
+
+impl Visitor for UsedNames {
+    fn visit_statement(&mut self, statement: &Spanned<Statement>) {
+        match statement.node {
+            Statement::Function(ref name, _) => {
+                self.0.insert(name.first_dot_access.node.clone());
+            }
+            Statement::ForStepping { ref name, .. } | Statement::LocalFunction { ref name, .. } => {
+                self.0.insert(name.node.clone());
+            }
+            Statement::ForIn { ref name_list, .. } => self.insert_names(name_list),
+            Statement::LocalVariableBinding(ref names, _) => {
+                self.0.insert(names.0.name.node.clone());
+                for name in names.1.iter().flatten() {
+                    self.0.insert(name.name.node.clone());
+                }
+            }
+            _ => {}
+        }
+        visit::walk_statement(self, statement);
+    }
+
+    fn visit_function_body(&mut self, body: &FunctionBody) {
+        match body.0.as_deref() {
+            Some(&ParameterList::NameList(ref names))
+            | Some(&ParameterList::ExtendedArguments(ref names)) => self.insert_names(names),
+            _ => {}
+        }
+        visit::walk_function_body(self, body);
+    }
+
+    fn visit_variable(&mut self, variable: &Variable) {
+        if let Variable::Name(ref name) = *variable {
+            self.0.insert(name.node.clone());
+        }
+        visit::walk_variable(self, variable);
+    }
+}
+
+struct NumericForDesugarer {
+    var: String,
+    limit: String,
+    step: String,
+    integer: String,
+}
+
+/// What kind of number a number literal, or a negated one, is.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Literal {
+    Integer,
+    Float,
+}
+
+fn number_literal(expression: &Expression) -> Option<Literal> {
+    match *expression {
+        Expression::Integer(..) => Some(Literal::Integer),
+        Expression::Float(..) => Some(Literal::Float),
+        Expression::UnaryOperation(UnaryOperation::Negate, ref operand) => {
+            number_literal(&operand.node)
+        }
+        _ => None,
+    }
+}
+
+/// The sign of a step written as a nonzero number literal.
+fn literal_sign(step: &Expression) -> Option<i8> {
+    match *step {
+        Expression::Integer(n, _) if n != 0 => Some(n.signum() as i8),
+        Expression::Float(n, _) if n > 0.0 => Some(1),
+        Expression::Float(n, _) if n < 0.0 => Some(-1),
+        Expression::UnaryOperation(UnaryOperation::Negate, ref operand) => {
+            literal_sign(&operand.node).map(|sign| -sign)
+        }
+        _ => None,
+    }
+}
+
+/// `math.name(arguments)`.
+fn math(name: &str, arguments: Vec<Spanned<Expression>>) -> Spanned<Expression> {
+    expr::call(expr::field(expr::name("math"), name), arguments)
+}
+
+/// `math.type(name) == kind`.
+fn has_type(name: &str, kind: &str) -> Spanned<Expression> {
+    let math_type = math("type", vec![expr::name(name)]);
+    expr::binop(BinaryOperation::Equal, math_type, expr::string(kind))
+}
+
+/// `error(message)` as a statement.
+fn error(message: &str) -> Spanned<Statement> {
+    stmt::call(expr::call(expr::name("error"), vec![expr::string(message)]))
+}
+
+/// A block of the one statement.
+fn block(statement: Spanned<Statement>) -> Block {
+    BlockBuilder::new().push(statement).build()
+}
+
+/// `condition and rest`, or just `rest` without a condition.
+fn and(condition: Option<Spanned<Expression>>, rest: Spanned<Expression>) -> Spanned<Expression> {
+    match condition {
+        Some(condition) => expr::binop(BinaryOperation::And, condition, rest),
+        None => rest,
+    }
+}
+
+impl NumericForDesugarer {
+    /// Converts the value of a hidden local from a string to a number as the loop does, raising
+    /// lua's error if it can't be.
+    fn to_number(&self, name: &str, what: &str) -> Spanned<Statement> {
+        use ast::BinaryOperation::*;
+        let type_of = || expr::call(expr::name("type"), vec![expr::name(name)]);
+        let converted = expr::binop(
+            And,
+            expr::binop(Equal, type_of(), expr::string("string")),
+            expr::call(expr::name("tonumber"), vec![expr::name(name)]),
+        );
+        let message = format!("'for' {} must be a number", what);
+        let error = expr::call(expr::name("error"), vec![expr::string(&message)]);
+        stmt::if_(
+            expr::binop(NotEqual, type_of(), expr::string("number")),
+            block(stmt::assign(vec![var::name(name)], vec![expr::binop(Or, converted, error)])),
+            None,
+        )
+    }
+
+    /// Rounds a float limit of a loop counting in integers to the last integer the loop can
+    /// reach, clipping one too large or small for an integer to the largest or smallest.
+    fn integer_limit(
+        &self,
+        integer: Option<Spanned<Expression>>,
+        sign: Option<i8>,
+    ) -> Spanned<Statement> {
+        use ast::BinaryOperation::*;
+        let limit = &*self.limit;
+        let floor = || math("floor", vec![expr::name(limit)]);
+        let ceil = || math("ceil", vec![expr::name(limit)]);
+        let rounded = match sign {
+            Some(1) => floor(),
+            Some(_) => ceil(),
+            None => expr::binop(
+                Or,
+                expr::binop(
+                    And,
+                    expr::binop(GreaterThan, expr::name(&self.step), expr::integer(0)),
+                    floor(),
+                ),
+                ceil(),
+            ),
+        };
+        let set = |value| block(stmt::assign(vec![var::name(limit)], vec![value]));
+        // A NaN limit is left as it is, so the loop doesn't run.
+        let clip = Statement::If {
+            condition: Box::new(expr::binop(GreaterThan, expr::name(limit), expr::integer(0))),
+            then: Box::new(set(expr::field(expr::name("math"), "maxinteger"))),
+            elseifs: vec![(
+                Box::new(expr::binop(LessThan, expr::name(limit), expr::integer(0))),
+                Box::new(set(expr::field(expr::name("math"), "mininteger"))),
+            )],
+            else_: None,
+        };
+        let round = BlockBuilder::new()
+            .push(stmt::assign(vec![var::name(limit)], vec![rounded]))
+            .push(stmt::if_(has_type(limit, "float"), block(Spanned::dummy(clip)), None))
+            .build();
+        stmt::if_(and(integer, has_type(limit, "float")), round, None)
+    }
+
+    fn desugar(
+        &self,
+        name: &str,
+        from: Spanned<Expression>,
+        to: Spanned<Expression>,
+        step: Option<Spanned<Expression>>,
+        block: Block,
+    ) -> Statement {
+        use ast::BinaryOperation::*;
+        let (from_kind, limit_kind) = (number_literal(&from.node), number_literal(&to.node));
+        let (step_kind, sign) = match step {
+            Some(ref step) => (number_literal(&step.node), literal_sign(&step.node)),
+            None => (Some(Literal::Integer), Some(1)),
+        };
+        // Whether the loop counts in integers, if that's known before it runs.
+        let integer = match (from_kind, step_kind) {
+            (Some(Literal::Integer), Some(Literal::Integer)) => Some(true),
+            (Some(Literal::Float), _) | (_, Some(Literal::Float)) => Some(false),
+            _ => None,
+        };
+        let is_integer = || match integer {
+            None => Some(expr::name(&self.integer)),
+            _ => None,
+        };
+        let step = step.unwrap_or_else(|| expr::integer(1));
+        let (var, limit) = (&*self.var, &*self.limit);
+        let by_sign = |upwards: Spanned<Expression>, downwards: Spanned<Expression>| match sign {
+            Some(1) => upwards,
+            Some(_) => downwards,
+            None => expr::binop(
+                Or,
+                expr::binop(
+                    And,
+                    expr::binop(GreaterThan, expr::name(&self.step), expr::integer(0)),
+                    upwards,
+                ),
+                expr::binop(
+                    And,
+                    expr::binop(LessThan, expr::name(&self.step), expr::integer(0)),
+                    downwards,
+                ),
+            ),
+        };
+
+        let hidden = [&*self.var, &*self.limit, &*self.step];
+        let mut outer = BlockBuilder::new().push(stmt::local(&hidden, vec![from, to, step]));
+        let checks = [
+            (from_kind, var, "initial value"),
+            (limit_kind, limit, "limit"),
+            (step_kind, &*self.step, "step"),
+        ];
+        for &(kind, name, what) in &checks {
+            if kind.is_none() {
+                outer = outer.push(self.to_number(name, what));
+            }
+        }
+        if sign.is_none() {
+            outer = outer.push(stmt::if_(
+                expr::binop(Equal, expr::name(&self.step), expr::integer(0)),
+                self::block(error("'for' step is zero")),
+                None,
+            ));
+        }
+        if integer.is_none() {
+            let both = expr::binop(And, has_type(var, "integer"), has_type(&self.step, "integer"));
+            outer = outer.push(stmt::local(&[&self.integer], vec![both]));
+        }
+        if integer != Some(false) && limit_kind != Some(Literal::Integer) {
+            outer = outer.push(self.integer_limit(is_integer(), sign));
+        }
+        // A loop in floats counts from a float, even when it starts from an integer.
+        let to_float = || {
+            let float = expr::binop(Times, expr::name(var), expr::float(1.0));
+            stmt::assign(vec![var::name(var)], vec![float])
+        };
+        match integer {
+            Some(false) if from_kind != Some(Literal::Float) => outer = outer.push(to_float()),
+            None => {
+                let is_float = expr::unop(UnaryOperation::Not, expr::name(&self.integer));
+                outer = outer.push(stmt::if_(is_float, self::block(to_float()), None));
+            }
+            _ => {}
+        }
+
+        let mut body = BlockBuilder::new().push(stmt::local(&[name], vec![expr::name(var)]));
+        // The block goes in a scope of its own, so its locals don't shadow the hidden ones.
+        if !block.is_empty() {
+            body = body.push(stmt::do_(block));
+        }
+        if integer != Some(false) {
+            // An integer counter stops before a step would take it past the limit, so it never
+            // wraps around. The distance wraps around instead when it's too large for an integer,
+            // which is then further than any step.
+            let left = || expr::binop(Minus, expr::name(limit), expr::name(var));
+            let last = by_sign(
+                expr::binop(
+                    And,
+                    expr::binop(LessThanOrEqual, expr::integer(0), left()),
+                    expr::binop(LessThan, left(), expr::name(&self.step)),
+                ),
+                expr::binop(
+                    And,
+                    expr::binop(LessThan, expr::name(&self.step), left()),
+                    expr::binop(LessThanOrEqual, left(), expr::integer(0)),
+                ),
+            );
+            body = body.push(stmt::if_(and(is_integer(), last), self::block(stmt::break_()), None));
+        }
+        let body = body
+            .push(stmt::assign(
+                vec![var::name(var)],
+                vec![expr::binop(Plus, expr::name(var), expr::name(&self.step))],
+            ))
+            .build();
+        let condition = by_sign(
+            expr::binop(LessThanOrEqual, expr::name(var), expr::name(limit)),
+            expr::binop(GreaterThanOrEqual, expr::name(var), expr::name(limit)),
+        );
+        Statement::Do(Box::new(outer.push(stmt::while_(condition, body)).build()))
+    }
+}
+
+impl VisitorMut for NumericForDesugarer {
+    fn visit_statement_mut(&mut self, statement: &mut Spanned<Statement>) {
+        // Loops nested in this one are desugared first.
+        visit::walk_statement_mut(self, statement);
+        if let Statement::ForStepping { .. } = statement.node {
+            if let Statement::ForStepping { name, from, to, step, block } =
+                mem::replace(&mut statement.node, Statement::Semicolon)
+            {
+                let step = step.map(|step| *step);
+                statement.node = self.desugar(&name.node, *from, *to, step, *block);
+            }
+        }
+    }
+}
+
+struct GenericForDesugarer {
+    function: String,
+    state: String,
+    control: String,
+}
+
+impl GenericForDesugarer {
+    fn desugar(
+        &self,
+        names: &NameList,
+        expressions: ExpressionList,
+        mut block: Block,
+    ) -> Statement {
+        let names: Vec<&str> = iter::once(&names.0)
+            .chain(names.1.iter().flatten())
+            .map(|name| &*name.node)
+            .collect();
+        let call = expr::call(
+            expr::name(&self.function),
+            vec![expr::name(&self.state), expr::name(&self.control)],
+        );
+        let first = expr::name(names[0]);
+        let mut statements = vec![
+            stmt::local(&names, vec![call]),
+            stmt::if_(
+                expr::binop(BinaryOperation::Equal, first.clone(), expr::nil()),
+                BlockBuilder::new().push(stmt::break_()).build(),
+                None,
+            ),
+            stmt::assign(vec![var::name(&self.control)], vec![first]),
+        ];
+        // The loop's block ends the body, so its statements can go in directly.
+        statements.extend(mem::take(&mut block.0).into_iter().flatten());
+        let body = Block(Some(statements), mem::take(&mut block.1));
+        let hidden = [&*self.function, &*self.state, &*self.control];
+        let expressions = iter::once(*expressions.0).chain(expressions.1.into_iter().flatten());
+        let outer = BlockBuilder::new()
+            .push(stmt::local(&hidden, expressions.collect()))
+            .push(stmt::while_(expr::boolean(true), body));
+        Statement::Do(Box::new(outer.build()))
+    }
+}
+
+impl VisitorMut for GenericForDesugarer {
+    fn visit_statement_mut(&mut self, statement: &mut Spanned<Statement>) {
+        visit::walk_statement_mut(self, statement);
+        if let Statement::ForIn { .. } = statement.node {
+            if let Statement::ForIn { name_list, in_, do_ } =
+                mem::replace(&mut statement.node, Statement::Semicolon)
+            {
+                statement.node = self.desugar(&name_list, *in_, *do_);
+            }
+        }
+    }
+}
+
+struct FunctionStatementDesugarer;
+
+/// Adds `self` before the other parameters of a method's body.
+fn add_self(body: &mut FunctionBody, span: Span) {
+    let name = Spanned::new("self".to_string(), span);
+    let parameters = match body.0.take().map(|parameters| *parameters) {
+        None => ParameterList::NameList(Box::new(NameList::single(name))),
+        Some(ParameterList::ExtendedArgumentsVoid) => {
+            ParameterList::ExtendedArguments(Box::new(NameList::single(name)))
+        }
+        Some(ParameterList::NameList(mut list)) => {
+            prepend(&mut list, name);
+            ParameterList::NameList(list)
+        }
+        Some(ParameterList::ExtendedArguments(mut list)) => {
+            prepend(&mut list, name);
+            ParameterList::ExtendedArguments(list)
+        }
+    };
+    body.0 = Some(Box::new(parameters));
+}
+
+fn prepend(list: &mut NameList, name: Spanned<String>) {
+    let first = mem::replace(&mut list.0, name);
+    list.1.get_or_insert_with(Vec::new).insert(0, first);
+}
+
+impl VisitorMut for FunctionStatementDesugarer {
+    fn visit_statement_mut(&mut self, statement: &mut Spanned<Statement>) {
+        visit::walk_statement_mut(self, statement);
+        if let Statement::Function(..) = statement.node {
+            if let Statement::Function(name, mut body) =
+                mem::replace(&mut statement.node, Statement::Semicolon)
+            {
+                if name.self_name.is_some() {
+                    add_self(&mut body, name.first_dot_access.span);
+                }
+                let define = Expression::FunctionDefine(Box::new(FunctionDefine(body)));
+                let targets = VariableList { first: Box::new(name.to_variable()), rest: None };
+                let values = ExpressionList(Box::new(Spanned::new(define, statement.span)), None);
+                statement.node = Statement::Assignment(Box::new(targets), Box::new(values));
+            }
+        }
+    }
+}
@@ -0,0 +1,68 @@
+//! Flattening, inlining `do` blocks whose scope makes no difference into the block around them.
+//!
+//! A `do` block only matters for the locals and labels it declares, which go out of scope at its
+//! `end`, and for a `return` statement, which has to end a block. Without any of these its
+//! statements run the same in the enclosing block, as generated code often leaves them.
+
+use std::mem;
+
+use ast::*;
+use span::Spanned;
+use visit::{self, VisitorMut};
+
+/// Inlines every `do` block in the chunk that declares no locals or labels and doesn't return,
+/// dropping empty ones.
+///
+/// # Example
+/// ```lua
+/// do
+///     print(1)
+///     do local x = 2 print(x) end
+/// end
+/// ```
+/// becomes
+/// ```lua
+/// print(1)
+/// do local x = 2 print(x) end
+/// ```
+pub fn flatten_blocks(chunk: &mut Chunk) {
+    visit::walk_chunk_mut(&mut Flattener, chunk);
+}
+
+struct Flattener;
+
+/// Whether a `do` block's statements can go in the enclosing block without changing what they do.
+fn is_flattenable(block: &Block) -> bool {
+    block.1.is_none() &&
+        block.statements().all(|statement| {
+            !matches!(
+                statement.node,
+                Statement::LocalVariableBinding(..) |
+                    Statement::LocalFunction { .. } |
+                    Statement::Label(_)
+            )
+        })
+}
+
+impl VisitorMut for Flattener {
+    fn visit_block_mut(&mut self, block: &mut Block) {
+        // Nested blocks are flattened first, so a chain of them is inlined all the way.
+        visit::walk_block_mut(self, block);
+        let statements = match block.0.take() {
+            Some(statements) => statements,
+            None => return,
+        };
+        let mut flattened: Vec<Spanned<Statement>> = Vec::with_capacity(statements.len());
+        for mut statement in statements {
+            match statement.node {
+                Statement::Do(ref mut inner) if is_flattenable(inner) => {
+                    flattened.extend(mem::take(&mut inner.0).into_iter().flatten());
+                }
+                _ => flattened.push(statement),
+            }
+        }
+        if !flattened.is_empty() {
+            block.0 = Some(flattened);
+        }
+    }
+}
@@ -0,0 +1,269 @@
+//! Constant folding, evaluating operations on literals ahead of time.
+//!
+//! Operations follow the arithmetic of lua 5.3 at
+//! http://www.lua.org/manual/5.3/manual.html#3.4.1: integers wrap around on overflow, `/` and `^`
+//! always give floats, and the other arithmetic operators give integers only for integer operands.
+//! Operations that would raise an error at runtime, such as dividing an integer by zero or adding
+//! a string, are left for the runtime to raise. Division by zero is left alone for floats too.
+
+use std::cmp::Ordering;
+
+use ast::*;
+use interp;
+use span::Spanned;
+use visit::{self, VisitorMut};
+
+/// Replaces every operation on constants in the chunk with its result.
+///
+/// # Example
+/// ```lua
+/// x = 2 * (3 + 4)
+/// ```
+/// becomes
+/// ```lua
+/// x = 14
+/// ```
+pub fn fold_constants(chunk: &mut Chunk) {
+    visit::walk_chunk_mut(&mut Folder, chunk);
+}
+
+struct Folder;
+
+impl VisitorMut for Folder {
+    fn visit_expression_mut(&mut self, expression: &mut Spanned<Expression>) {
+        visit::walk_expression_mut(self, expression);
+        if let Some(folded) = fold(&expression.node) {
+            expression.node = folded;
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Constant<'a> {
+    Nil,
+    Boolean(bool),
+    Integer(i64),
+    Float(f64),
+    String(&'a [u8]),
+}
+
+impl<'a> Constant<'a> {
+    fn of(expression: &'a Expression) -> Option<Constant<'a>> {
+        match *expression {
+            Expression::Nil => Some(Constant::Nil),
+            Expression::False => Some(Constant::Boolean(false)),
+            Expression::True => Some(Constant::Boolean(true)),
+            Expression::Integer(n, _) => Some(Constant::Integer(n)),
+            Expression::Float(n, _) => Some(Constant::Float(n)),
+            Expression::String(ref s, _) => Some(Constant::String(s)),
+            Expression::PrefixExpression(ref prefix) => match **prefix {
+                PrefixExpression::Parenthesis(ref inner) => Constant::of(&inner.node),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn into_expression(self) -> Expression {
+        match self {
+            Constant::Nil => Expression::Nil,
+            Constant::Boolean(false) => Expression::False,
+            Constant::Boolean(true) => Expression::True,
+            Constant::Integer(n) => Expression::from(n),
+            Constant::Float(n) => Expression::from(n),
+            Constant::String(s) => Expression::from(s.to_vec()),
+        }
+    }
+
+    fn is_truthy(self) -> bool {
+        !matches!(self, Constant::Nil | Constant::Boolean(false))
+    }
+
+    fn to_float(self) -> Option<f64> {
+        match self {
+            Constant::Integer(n) => Some(n as f64),
+            Constant::Float(n) => Some(n),
+            _ => None,
+        }
+    }
+}
+
+/// The result of a constant operation, which may be a string made by concatenation.
+enum Folded<'a> {
+    Constant(Constant<'a>),
+    String(Vec<u8>),
+}
+
+impl<'a> Folded<'a> {
+    fn into_expression(self) -> Expression {
+        match self {
+            Folded::Constant(constant) => constant.into_expression(),
+            Folded::String(s) => Expression::from(s),
+        }
+    }
+}
+
+/// Evaluates an operation whose operands are constants, giving `None` for anything else.
+fn fold(expression: &Expression) -> Option<Expression> {
+    match *expression {
+        Expression::BinaryOperation(ref op, ref lhs, ref rhs) => {
+            let left = Constant::of(&lhs.node)?;
+            // `and` and `or` only need their left hand side to be known.
+            match *op {
+                BinaryOperation::And if !left.is_truthy() => return Some(lhs.node.clone()),
+                BinaryOperation::And => return Some(single_value(&rhs.node)),
+                BinaryOperation::Or if left.is_truthy() => return Some(lhs.node.clone()),
+                BinaryOperation::Or => return Some(single_value(&rhs.node)),
+                _ => {}
+            }
+            let right = Constant::of(&rhs.node)?;
+            binary(op, left, right).map(Folded::into_expression)
+        }
+        Expression::UnaryOperation(ref op, ref operand) => {
+            let operand = Constant::of(&operand.node)?;
+            unary(op, operand).map(Constant::into_expression)
+        }
+        _ => None,
+    }
+}
+
+/// The expression adjusted to a single value, as it would be as an operand. Calls and `...` are
+/// parenthesized so they don't expand to several values in place of the operation.
+fn single_value(expression: &Expression) -> Expression {
+    let expands = match *expression {
+        Expression::ExtendedArgumentAccess => true,
+        Expression::PrefixExpression(ref prefix) => {
+            matches!(**prefix, PrefixExpression::FunctionCall(_))
+        }
+        _ => false,
+    };
+    if expands {
+        let inner = Box::new(Spanned::dummy(expression.clone()));
+        Expression::PrefixExpression(Box::new(PrefixExpression::Parenthesis(inner)))
+    } else {
+        expression.clone()
+    }
+}
+
+/// Lua's floored division on integers, rounding towards negative infinity.
+pub(crate) fn floor_divide(a: i64, b: i64) -> i64 {
+    let q = a.wrapping_div(b);
+    if a.wrapping_rem(b) != 0 && (a ^ b) < 0 { q - 1 } else { q }
+}
+
+/// Lua's floored modulo on integers, taking the sign of the divisor.
+pub(crate) fn integer_modulo(a: i64, b: i64) -> i64 {
+    let r = a.wrapping_rem(b);
+    if r != 0 && (r ^ b) < 0 { r + b } else { r }
+}
+
+pub(crate) fn float_modulo(a: f64, b: f64) -> f64 {
+    let r = a % b;
+    if r != 0.0 && (r < 0.0) != (b < 0.0) { r + b } else { r }
+}
+
+/// Lua's logical shift, where shifting by 64 or more bits gives zero and a negative shift goes the
+/// other way.
+pub(crate) fn shift_left(a: i64, b: i64) -> i64 {
+    if b <= -64 || b >= 64 {
+        0
+    } else if b >= 0 {
+        ((a as u64) << b) as i64
+    } else {
+        ((a as u64) >> -b) as i64
+    }
+}
+
+fn binary<'a>(op: &BinaryOperation, left: Constant<'a>, right: Constant<'a>) -> Option<Folded<'a>> {
+    use self::Constant::{Float, Integer};
+
+    let constant = match (op, left, right) {
+        (BinaryOperation::Plus, Integer(a), Integer(b)) => Integer(a.wrapping_add(b)),
+        (BinaryOperation::Minus, Integer(a), Integer(b)) => Integer(a.wrapping_sub(b)),
+        (BinaryOperation::Times, Integer(a), Integer(b)) => Integer(a.wrapping_mul(b)),
+        (BinaryOperation::FloorDivide, Integer(_), Integer(0)) |
+        (BinaryOperation::Modulo, Integer(_), Integer(0)) => return None,
+        (BinaryOperation::FloorDivide, Integer(a), Integer(b)) => Integer(floor_divide(a, b)),
+        (BinaryOperation::Modulo, Integer(a), Integer(b)) => Integer(integer_modulo(a, b)),
+        (BinaryOperation::BitwiseAnd, Integer(a), Integer(b)) => Integer(a & b),
+        (BinaryOperation::BitwiseOr, Integer(a), Integer(b)) => Integer(a | b),
+        (BinaryOperation::BitwiseXor, Integer(a), Integer(b)) => Integer(a ^ b),
+        (BinaryOperation::ShiftLeft, Integer(a), Integer(b)) => Integer(shift_left(a, b)),
+        (BinaryOperation::ShiftRight, Integer(a), Integer(b)) => {
+            Integer(shift_left(a, b.checked_neg()?))
+        }
+        (BinaryOperation::Concatanate, _, _) => {
+            let piece = |constant| match constant {
+                Constant::String(s) => Some(s.to_vec()),
+                Integer(n) => Some(n.to_string().into_bytes()),
+                _ => None,
+            };
+            return Some(Folded::String([piece(left)?, piece(right)?].concat()));
+        }
+        (BinaryOperation::Equal, _, _) => Constant::Boolean(equal(left, right)),
+        (BinaryOperation::NotEqual, _, _) => Constant::Boolean(!equal(left, right)),
+        (BinaryOperation::LessThan, _, _) => Constant::Boolean(less(left, right, false)?),
+        (BinaryOperation::LessThanOrEqual, _, _) => Constant::Boolean(less(left, right, true)?),
+        (BinaryOperation::GreaterThan, _, _) => Constant::Boolean(less(right, left, false)?),
+        (BinaryOperation::GreaterThanOrEqual, _, _) => {
+            Constant::Boolean(less(right, left, true)?)
+        }
+        _ => {
+            let (a, b) = (left.to_float()?, right.to_float()?);
+            match *op {
+                BinaryOperation::Plus => Float(a + b),
+                BinaryOperation::Minus => Float(a - b),
+                BinaryOperation::Times => Float(a * b),
+                BinaryOperation::Exponent => Float(a.powf(b)),
+                _ if b == 0.0 => return None,
+                BinaryOperation::Devide => Float(a / b),
+                BinaryOperation::FloorDivide => Float((a / b).floor()),
+                BinaryOperation::Modulo => Float(float_modulo(a, b)),
+                _ => return None,
+            }
+        }
+    };
+    Some(Folded::Constant(constant))
+}
+
+/// Orders two numbers by their exact values as the interpreter does, or gives `None` if either
+/// isn't a number. The inner `None` is for NaN, which isn't ordered with anything.
+fn compare_numbers(left: Constant, right: Constant) -> Option<Option<Ordering>> {
+    Some(match (left, right) {
+        (Constant::Integer(a), Constant::Integer(b)) => Some(a.cmp(&b)),
+        (Constant::Float(a), Constant::Float(b)) => a.partial_cmp(&b),
+        (Constant::Integer(a), Constant::Float(b)) => interp::compare_mixed(a, b),
+        (Constant::Float(a), Constant::Integer(b)) => {
+            interp::compare_mixed(b, a).map(Ordering::reverse)
+        }
+        _ => return None,
+    })
+}
+
+fn equal(left: Constant, right: Constant) -> bool {
+    match compare_numbers(left, right) {
+        Some(ordering) => ordering == Some(Ordering::Equal),
+        None => left == right,
+    }
+}
+
+/// Whether `left` is less than `right`, or equal too if `or_equal` is set. Only numbers and
+/// strings can be ordered.
+fn less(left: Constant, right: Constant, or_equal: bool) -> Option<bool> {
+    if let (Constant::String(a), Constant::String(b)) = (left, right) {
+        return Some(if or_equal { a <= b } else { a < b });
+    }
+    let ordering = compare_numbers(left, right)?;
+    Some(ordering.is_some_and(|ordering| ordering.is_lt() || or_equal && ordering.is_eq()))
+}
+
+fn unary<'a>(op: &UnaryOperation, operand: Constant<'a>) -> Option<Constant<'a>> {
+    Some(match (op, operand) {
+        (UnaryOperation::Not, _) => Constant::Boolean(!operand.is_truthy()),
+        (UnaryOperation::Negate, Constant::Integer(n)) => Constant::Integer(n.wrapping_neg()),
+        (UnaryOperation::Negate, Constant::Float(n)) => Constant::Float(-n),
+        (UnaryOperation::Length, Constant::String(s)) => Constant::Integer(s.len() as i64),
+        (UnaryOperation::BitwiseNot, Constant::Integer(n)) => Constant::Integer(!n),
+        _ => return None,
+    })
+}
@@ -0,0 +1,15 @@
+//! Passes rewriting the abstract syntax tree into an equivalent but simpler one.
+
+pub mod dead_code;
+pub mod desugar;
+pub mod flatten;
+pub mod fold;
+pub mod propagate;
+pub mod simplify;
+
+pub use self::dead_code::eliminate_dead_code;
+pub use self::desugar::{desugar_function_statements, desugar_generic_for, desugar_numeric_for};
+pub use self::flatten::flatten_blocks;
+pub use self::fold::fold_constants;
+pub use self::propagate::propagate_constants;
+pub use self::simplify::simplify;
@@ -0,0 +1,140 @@
+//! Constant propagation, replacing reads of locals that always hold a literal with the literal.
+//!
+//! A local qualifies when its declaration gives it a literal, such as `local x = 5`, and nothing
+//! assigns to it afterwards, including closures capturing it. Names are resolved with
+//! `resolve_names`, so shadowing locals are told apart and the chunk should have been parsed
+//! rather than built by hand.
+
+use std::collections::{HashMap, HashSet};
+use std::iter;
+
+use ast::*;
+use resolve::{resolve_names, Binding, Resolution};
+use span::{Span, Spanned};
+use visit::{self, Visitor, VisitorMut};
+
+/// Replaces every read of a local that is declared with a literal and never assigned with that
+/// literal. The declarations are left in place.
+///
+/// # Example
+/// ```lua
+/// local x = 5
+/// y = x + 1
+/// ```
+/// becomes
+/// ```lua
+/// local x = 5
+/// y = 5 + 1
+/// ```
+pub fn propagate_constants(chunk: &mut Chunk) {
+    let resolution = resolve_names(chunk);
+    let mut finder = ConstantFinder {
+        resolution: &resolution,
+        constants: HashMap::new(),
+        assigned: HashSet::new(),
+    };
+    visit::walk_chunk(&mut finder, chunk);
+    let mut constants = finder.constants;
+    for span in &finder.assigned {
+        constants.remove(span);
+    }
+    if !constants.is_empty() {
+        visit::walk_chunk_mut(&mut Propagator { resolution: &resolution, constants }, chunk);
+    }
+}
+
+fn is_literal(expression: &Expression) -> bool {
+    matches!(
+        *expression,
+        Expression::Nil |
+            Expression::False |
+            Expression::True |
+            Expression::Integer(..) |
+            Expression::Float(..) |
+            Expression::String(..)
+    )
+}
+
+/// Whether a `function` statement assigns to its name, as `function f() end` does, rather than
+/// only reading it to set a field, as `function f.g() end` does.
+fn is_plain_name(name: &FunctionName) -> bool {
+    name.rest_dot_access.iter().flatten().next().is_none() && name.self_name.is_none()
+}
+
+/// The local a name refers to, as the span declaring it.
+fn declaration(resolution: &Resolution, name: &Spanned<String>) -> Option<Span> {
+    match resolution.binding(name.span)? {
+        Binding::Local(span) | Binding::Upvalue(span) => Some(span),
+        Binding::Global => None,
+    }
+}
+
+/// Finds the locals declared with a literal, and the locals assigned to anywhere.
+struct ConstantFinder<'a> {
+    resolution: &'a Resolution,
+    /// The literal of each local declared with one, keyed by the span declaring it.
+    constants: HashMap<Span, Expression>,
+    assigned: HashSet<Span>,
+}
+
+impl<'a> ConstantFinder<'a> {
+    fn assign(&mut self, name: &Spanned<String>) {
+        if let Some(span) = declaration(self.resolution, name) {
+            self.assigned.insert(span);
+        }
+    }
+}
+
+impl<'a> Visitor for ConstantFinder<'a> {
+    fn visit_statement(&mut self, statement: &Spanned<Statement>) {
+        match statement.node {
+            Statement::LocalVariableBinding(ref names, Some(ref values)) => {
+                let names = iter::once(&names.0).chain(names.1.iter().flatten());
+                let values = iter::once(&*values.0).chain(values.1.iter().flatten());
+                for (name, value) in names.zip(values) {
+                    // A to-be-closed local is closed when it goes out of scope, not just read.
+                    if is_literal(&value.node) && name.attribute != Some(Attribute::Close) {
+                        self.constants.insert(name.name.span, value.node.clone());
+                    }
+                }
+            }
+            Statement::Assignment(ref targets, _) => {
+                for target in iter::once(&*targets.first).chain(targets.rest.iter().flatten()) {
+                    if let Variable::Name(ref name) = *target {
+                        self.assign(name);
+                    }
+                }
+            }
+            Statement::Function(ref name, _) if is_plain_name(name) => {
+                self.assign(&name.first_dot_access);
+            }
+            _ => {}
+        }
+        visit::walk_statement(self, statement);
+    }
+}
+
+struct Propagator<'a> {
+    resolution: &'a Resolution,
+    constants: HashMap<Span, Expression>,
+}
+
+impl<'a> VisitorMut for Propagator<'a> {
+    fn visit_expression_mut(&mut self, expression: &mut Spanned<Expression>) {
+        let constant = match expression.node {
+            Expression::PrefixExpression(ref prefix) => match **prefix {
+                PrefixExpression::Variable(ref variable) => match **variable {
+                    Variable::Name(ref name) => declaration(self.resolution, name)
+                        .and_then(|span| self.constants.get(&span)),
+                    _ => None,
+                },
+                _ => None,
+            },
+            _ => None,
+        };
+        match constant {
+            Some(constant) => expression.node = constant.clone(),
+            None => visit::walk_expression_mut(self, expression),
+        }
+    }
+}
@@ -0,0 +1,147 @@
+//! Algebraic simplification, dropping the identities that generated code tends to leave behind.
+//!
+//! Lua's operators can't be simplified as freely as arithmetic on paper. `x + 0` turns a string
+//! into a number, calls a metamethod of a table and fails on anything else, and `x * 0` isn't even
+//! zero for infinity or NaN. So an identity is only dropped where the other operand is known from
+//! its form to be a number, or a boolean for the logical operators, and only literals are ever
+//! dropped, so no operand with side effects goes.
+
+use std::mem;
+
+use ast::*;
+use span::Spanned;
+use visit::{self, VisitorMut};
+
+/// Simplifies the identities in the chunk whose operands are known well enough:
+///
+/// - `x + 0`, `0 + x` and `x - 0` become `x` for integers `x`. Adding zero to the float `-0.0`
+///   gives `0.0`, but subtracting it doesn't, so `x - 0` is simplified for floats too.
+/// - `x * 1` and `1 * x` become `x` for numbers `x`.
+/// - `x and true` and `x or false` become `x` for booleans `x`, such as comparisons.
+/// - `#"literal"` becomes the length of the string.
+///
+/// # Example
+/// ```lua
+/// y = #"abc" * 1 + 0
+/// ```
+/// becomes
+/// ```lua
+/// y = 3
+/// ```
+pub fn simplify(chunk: &mut Chunk) {
+    visit::walk_chunk_mut(&mut Simplifier, chunk);
+}
+
+struct Simplifier;
+
+/// What the value of an expression is known to be from its form alone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Known {
+    Integer,
+    Number,
+    Boolean,
+}
+
+fn known(expression: &Expression) -> Option<Known> {
+    use ast::BinaryOperation::*;
+    match *expression {
+        Expression::Integer(..) => Some(Known::Integer),
+        Expression::Float(..) => Some(Known::Number),
+        Expression::True | Expression::False => Some(Known::Boolean),
+        Expression::PrefixExpression(ref prefix) => match **prefix {
+            PrefixExpression::Parenthesis(ref inner) => known(&inner.node),
+            _ => None,
+        },
+        Expression::UnaryOperation(op, ref operand) => match (op, known(&operand.node)) {
+            (UnaryOperation::Not, _) => Some(Known::Boolean),
+            (UnaryOperation::Negate, Some(Known::Boolean)) => None,
+            (UnaryOperation::Negate, known) => known,
+            (UnaryOperation::Length, _) => match operand.node {
+                Expression::String(..) => Some(Known::Integer),
+                _ => None,
+            },
+            (UnaryOperation::BitwiseNot, Some(Known::Integer)) |
+            (UnaryOperation::BitwiseNot, Some(Known::Number)) => Some(Known::Integer),
+            (UnaryOperation::BitwiseNot, _) => None,
+        },
+        Expression::BinaryOperation(op, ref lhs, ref rhs) => {
+            let (left, right) = (known(&lhs.node), known(&rhs.node));
+            let numbers = |known| matches!(known, Some(Known::Integer) | Some(Known::Number));
+            match op {
+                // Comparisons always give booleans, even through metamethods.
+                Equal | NotEqual | LessThan | LessThanOrEqual | GreaterThan |
+                GreaterThanOrEqual => Some(Known::Boolean),
+                And | Or if left == Some(Known::Boolean) && right == Some(Known::Boolean) => {
+                    Some(Known::Boolean)
+                }
+                Plus | Minus | Times | FloorDivide | Modulo
+                    if left == Some(Known::Integer) && right == Some(Known::Integer) =>
+                {
+                    Some(Known::Integer)
+                }
+                Plus | Minus | Times | Devide | FloorDivide | Modulo | Exponent
+                    if numbers(left) && numbers(right) =>
+                {
+                    Some(Known::Number)
+                }
+                BitwiseAnd | BitwiseOr | BitwiseXor | ShiftLeft | ShiftRight
+                    if numbers(left) && numbers(right) =>
+                {
+                    Some(Known::Integer)
+                }
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+fn is_integer(expression: &Expression, n: i64) -> bool {
+    matches!(*expression, Expression::Integer(value, _) if value == n)
+}
+
+/// Which operand of a binary operation it simplifies to.
+#[derive(Clone, Copy)]
+enum Side {
+    Left,
+    Right,
+}
+
+/// The operand a binary operation is the identity on, if it can be simplified to it.
+fn identity(op: BinaryOperation, lhs: &Expression, rhs: &Expression) -> Option<Side> {
+    use ast::BinaryOperation::*;
+    let (left, right) = (known(lhs), known(rhs));
+    let number = |known| matches!(known, Some(Known::Integer) | Some(Known::Number));
+    match op {
+        Plus if is_integer(rhs, 0) && left == Some(Known::Integer) => Some(Side::Left),
+        Plus if is_integer(lhs, 0) && right == Some(Known::Integer) => Some(Side::Right),
+        Minus if is_integer(rhs, 0) && number(left) => Some(Side::Left),
+        Times if is_integer(rhs, 1) && number(left) => Some(Side::Left),
+        Times if is_integer(lhs, 1) && number(right) => Some(Side::Right),
+        And if *rhs == Expression::True && left == Some(Known::Boolean) => Some(Side::Left),
+        Or if *rhs == Expression::False && left == Some(Known::Boolean) => Some(Side::Left),
+        _ => None,
+    }
+}
+
+impl VisitorMut for Simplifier {
+    fn visit_expression_mut(&mut self, expression: &mut Spanned<Expression>) {
+        // Operands are simplified first, so identities nested in each other all go.
+        visit::walk_expression_mut(self, expression);
+        let simplified = match expression.node {
+            Expression::BinaryOperation(op, ref mut lhs, ref mut rhs) => {
+                match identity(op, &lhs.node, &rhs.node) {
+                    Some(Side::Left) => mem::replace(&mut lhs.node, Expression::Nil),
+                    Some(Side::Right) => mem::replace(&mut rhs.node, Expression::Nil),
+                    None => return,
+                }
+            }
+            Expression::UnaryOperation(UnaryOperation::Length, ref operand) => match operand.node {
+                Expression::String(ref s, _) => Expression::from(s.len() as i64),
+                _ => return,
+            },
+            _ => return,
+        };
+        expression.node = simplified;
+    }
+}
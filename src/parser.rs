@@ -0,0 +1,1130 @@
+//! A recursive descent parser turning lua source code into the abstract syntax tree.
+//!
+//! The parser follows the complete syntax of lua page in the lua manual.
+//!
+//! The page can be found at http://www.lua.org/manual/5.2/manual.html#9.
+//!
+//! The bitwise and floor division operators of lua 5.3 are also accepted, at the precedence given
+//! in http://www.lua.org/manual/5.3/manual.html#3.4.8, as are the local attributes of lua 5.4.
+//! Expressions are parsed by precedence climbing over the binding powers of the operators.
+//!
+//! `parse` stops at the first error, while `parse_recovering` goes on past errors to give as much
+//! of the tree as it can, for tools such as editors working on source that is being written.
+//!
+//! With the `rayon` feature, `parse_many` parses a batch of files in parallel.
+//!
+//! `parse_interned` also interns the identifiers of the source, for tools comparing many names.
+//!
+//! The parser reads tokens borrowing their text from the source, and can build the tree in any
+//! `Store`, which makes the text of the tree from that of the tokens. Only the owned tree copies
+//! the text, as `borrowed::BorrowedChunk::parse` keeps borrowing it.
+
+use std::borrow::Cow;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Read};
+use std::str::FromStr;
+
+use ast::*;
+use borrowed::Borrowed;
+use intern::{InternedChunk, StringInterner};
+use lexer::{self, LexError, Lexer, Token};
+use span::{SourceError, Span, Spanned};
+
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+#[cfg(feature = "rayon")]
+use rayon::ThreadPoolBuilder;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+    /// The source couldn't be split into tokens.
+    Lex(LexError),
+    /// A token appeared where it isn't allowed by the grammar.
+    UnexpectedToken {
+        found: Token,
+        /// Descriptions of what could have appeared instead, such as `name` or `'end'`.
+        expected: Vec<&'static str>,
+        span: Span,
+    },
+    /// The source ended in the middle of a construct.
+    UnexpectedEof { expected: Vec<&'static str> },
+    /// Blocks or expressions were nested deeper than `ParserConfig::recursion_limit`.
+    RecursionLimit { limit: usize, span: Span },
+}
+
+/// Writes a list of alternatives such as `a, b or c`.
+fn write_alternatives(f: &mut fmt::Formatter, alternatives: &[&str]) -> fmt::Result {
+    for (i, alternative) in alternatives.iter().enumerate() {
+        if i > 0 {
+            f.write_str(if i + 1 == alternatives.len() { " or " } else { ", " })?;
+        }
+        f.write_str(alternative)?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ParseError::Lex(ref err) => err.fmt(f),
+            ParseError::UnexpectedToken { ref found, ref expected, span } => {
+                write!(f, "unexpected {} at byte {}, expected ", found.describe(), span.start)?;
+                write_alternatives(f, expected)
+            }
+            ParseError::UnexpectedEof { ref expected } => {
+                f.write_str("unexpected end of input, expected ")?;
+                write_alternatives(f, expected)
+            }
+            ParseError::RecursionLimit { limit, span } => {
+                write!(f, "nesting at byte {} is deeper than the limit of {}", span.start, limit)
+            }
+        }
+    }
+}
+
+impl SourceError for ParseError {
+    fn span(&self) -> Option<Span> {
+        match *self {
+            ParseError::Lex(ref err) => Some(err.span),
+            ParseError::UnexpectedToken { span, .. } | ParseError::RecursionLimit { span, .. } => {
+                Some(span)
+            }
+            ParseError::UnexpectedEof { .. } => None,
+        }
+    }
+
+    fn write_message(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ParseError::Lex(ref err) => err.write_message(f),
+            ParseError::UnexpectedToken { ref found, ref expected, .. } => {
+                write!(f, "unexpected {}, expected ", found.describe())?;
+                write_alternatives(f, expected)
+            }
+            ParseError::UnexpectedEof { ref expected } => {
+                f.write_str("unexpected end of input, expected ")?;
+                write_alternatives(f, expected)
+            }
+            ParseError::RecursionLimit { limit, .. } => {
+                write!(f, "nesting is deeper than the limit of {}", limit)
+            }
+        }
+    }
+}
+
+impl Error for ParseError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            ParseError::Lex(ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<LexError> for ParseError {
+    fn from(err: LexError) -> ParseError {
+        ParseError::Lex(err)
+    }
+}
+
+/// An error parsing source code read from a reader, which may have failed before parsing began.
+///
+/// This is separate from `ParseError` because `io::Error` can be neither cloned nor compared.
+#[derive(Debug)]
+pub enum ReadError {
+    /// The source couldn't be read, or wasn't valid UTF-8.
+    Io(io::Error),
+    /// The source was read but couldn't be parsed.
+    Parse(ParseError),
+}
+
+impl fmt::Display for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ReadError::Io(ref err) => write!(f, "couldn't read the source: {}", err),
+            ReadError::Parse(ref err) => err.fmt(f),
+        }
+    }
+}
+
+impl Error for ReadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            ReadError::Io(ref err) => Some(err),
+            ReadError::Parse(ref err) => Some(err),
+        }
+    }
+}
+
+impl From<io::Error> for ReadError {
+    fn from(err: io::Error) -> ReadError {
+        ReadError::Io(err)
+    }
+}
+
+impl From<ParseError> for ReadError {
+    fn from(err: ParseError) -> ReadError {
+        ReadError::Parse(err)
+    }
+}
+
+/// Options changing how source code is parsed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParserConfig {
+    /// How deeply blocks and expressions may be nested. Each level of nesting takes a level of
+    /// recursion in the parser, so this keeps pathological input such as thousands of nested
+    /// parentheses from overflowing the stack. The default fits in the stack of a main thread, but
+    /// threads with smaller stacks may need a lower limit.
+    pub recursion_limit: usize,
+}
+
+impl Default for ParserConfig {
+    /// The same limit of 200 levels as the reference implementation of lua.
+    fn default() -> ParserConfig {
+        ParserConfig { recursion_limit: 200 }
+    }
+}
+
+/// Parses an entire file of lua source code.
+pub fn parse(src: &str) -> Result<Chunk, ParseError> {
+    parse_with(src, &ParserConfig::default())
+}
+
+/// Parses an entire file of lua source code with the given options.
+pub fn parse_with(src: &str, config: &ParserConfig) -> Result<Chunk, ParseError> {
+    parse_stored(src, config, Owned)
+}
+
+/// Makes the text of a tree from the text of the tokens it's parsed from, which borrows from the
+/// source.
+pub(crate) trait Store<'a> {
+    type Storage: Storage;
+
+    fn name(&mut self, name: Cow<'a, str>) -> <Self::Storage as Storage>::Name;
+    fn string(&mut self, s: Cow<'a, [u8]>) -> <Self::Storage as Storage>::Str;
+    fn text(&mut self, text: Cow<'a, str>) -> <Self::Storage as Storage>::Text;
+}
+
+impl<'a> Store<'a> for Owned {
+    type Storage = Owned;
+
+    fn name(&mut self, name: Cow<'a, str>) -> String {
+        name.into_owned()
+    }
+
+    fn string(&mut self, s: Cow<'a, [u8]>) -> Vec<u8> {
+        s.into_owned()
+    }
+
+    fn text(&mut self, text: Cow<'a, str>) -> String {
+        text.into_owned()
+    }
+}
+
+/// A token whose text borrows from the source, as the parser reads them.
+type SourceToken<'a> = Token<Borrowed<'a>>;
+
+/// Parses an entire file of lua source code with the given options, into a tree whose text is
+/// made by `store`.
+pub(crate) fn parse_stored<'a, S: Store<'a>>(
+    src: &'a str,
+    config: &ParserConfig,
+    store: S,
+) -> Result<Chunk<S::Storage>, ParseError> {
+    let mut lexer = Lexer::<Borrowed>::from_source(src);
+    let mut tokens = Vec::new();
+    while let Some(token) = lexer.next_spanned() {
+        tokens.push(token?);
+    }
+    let mut parser = Parser::new(src, tokens, config, false, store);
+    let block = parser.block()?;
+    if parser.peek().is_some() {
+        return Err(parser.unexpected(&["end of input"]));
+    }
+    let comments = parser.comments(lexer.into_comments());
+    Ok(Chunk(block, comments))
+}
+
+/// Parses an entire file of lua source code with the given options, interning every identifier
+/// in it. The tree holds the symbols in place of the names. The interner can be shared with the
+/// parses of other files, so that the same name gets the same symbol in all of them. The names of
+/// a file that fails to parse may be left interned.
+///
+/// # Example
+/// ```
+/// use buildlua::ast::{Label, Statement};
+/// use buildlua::intern::StringInterner;
+/// use buildlua::parser::{parse_interned, ParserConfig};
+///
+/// let mut interner = StringInterner::new();
+/// let src = "goto done ::done::";
+/// let chunk = parse_interned(src, &ParserConfig::default(), &mut interner).unwrap();
+/// let labels: Vec<_> = chunk.0.statements().map(|statement| match statement.node {
+///     Statement::Goto(Label(symbol)) | Statement::Label(Label(symbol)) => symbol,
+///     _ => unreachable!(),
+/// }).collect();
+/// assert_eq!(labels[0], labels[1]);
+/// assert_eq!(interner.resolve(labels[0]), "done");
+/// ```
+pub fn parse_interned(
+    src: &str,
+    config: &ParserConfig,
+    interner: &mut StringInterner,
+) -> Result<InternedChunk, ParseError> {
+    parse_stored(src, config, interner)
+}
+
+/// Parses many files at once on a pool of threads, taking each as its name and source. The
+/// results are in the same order as the files, paired with their names.
+///
+/// The threads get stacks as large as a main thread's, which the default recursion limit of the
+/// parser needs.
+///
+/// # Example
+/// ```
+/// use buildlua::parser::parse_many;
+///
+/// let parsed = parse_many(&[("a.lua", "return 1"), ("b.lua", "return +")]);
+/// assert_eq!(parsed[0].0, "a.lua");
+/// assert!(parsed[0].1.is_ok());
+/// assert!(parsed[1].1.is_err());
+/// ```
+#[cfg(feature = "rayon")]
+pub fn parse_many(sources: &[(&str, &str)]) -> Vec<(String, Result<Chunk, ParseError>)> {
+    let parse_all = || {
+        sources.par_iter().map(|&(name, src)| (name.to_string(), parse(src))).collect()
+    };
+    match ThreadPoolBuilder::new().stack_size(8 << 20).build() {
+        Ok(pool) => pool.install(parse_all),
+        // Without a pool of its own, the files are parsed on the global one.
+        Err(_) => parse_all(),
+    }
+}
+
+/// Parses an entire file of lua source code, going on past errors to give a tree of as much of it
+/// as can be parsed along with every error found.
+///
+/// A missing expression is left as `Expression::Error`, and a missing token such as `then` or
+/// `end` is treated as if it were there. A statement that can't be parsed otherwise is skipped up
+/// to the start of the next one, as are `end` and the other words that close blocks where there is
+/// no block to close. Splitting the source into tokens stops at the first malformed one, such as
+/// an unfinished string, leaving the tokens before it to be parsed.
+///
+/// The errors are of the same kinds as `parse` gives, in the order of where they are in the
+/// source. When there are none, the tree is the one `parse` gives.
+///
+/// # Example
+/// ```
+/// use buildlua::parser::parse_recovering;
+///
+/// let (chunk, errors) = parse_recovering("x = \nlocal y = 1");
+/// assert_eq!(errors.len(), 1);
+/// assert_eq!(chunk.0.statements().count(), 2);
+/// ```
+pub fn parse_recovering(src: &str) -> (Chunk, Vec<ParseError>) {
+    let mut lexer = Lexer::<Borrowed>::from_source(src);
+    let mut tokens = Vec::new();
+    let mut errors = Vec::new();
+    while let Some(token) = lexer.next_spanned() {
+        match token {
+            Ok(token) => tokens.push(token),
+            Err(error) => {
+                errors.push(ParseError::Lex(error));
+                break;
+            }
+        }
+    }
+    let mut parser = Parser::new(src, tokens, &ParserConfig::default(), true, Owned);
+    let block = parser.block().unwrap_or_else(|error| {
+        parser.errors.push(error);
+        Block(None, None)
+    });
+    // Only a return at the end of the chunk can leave tokens after it.
+    if parser.peek().is_some() {
+        let error = parser.unexpected(&["end of input"]);
+        parser.errors.push(error);
+    }
+    // A lexing error comes after every token that was parsed.
+    parser.errors.extend(errors);
+    let comments = parser.comments(lexer.into_comments());
+    (Chunk(block, comments), parser.errors)
+}
+
+/// Reads the whole of a reader and parses it as a file of lua source code.
+pub fn parse_reader<R: Read>(reader: R) -> Result<Chunk, ReadError> {
+    parse_reader_with(reader, &ParserConfig::default())
+}
+
+/// Reads the whole of a reader and parses it as a file of lua source code with the given options.
+pub fn parse_reader_with<R: Read>(
+    mut reader: R,
+    config: &ParserConfig,
+) -> Result<Chunk, ReadError> {
+    let mut src = String::new();
+    reader.read_to_string(&mut src)?;
+    Ok(parse_with(&src, config)?)
+}
+
+fn binary<S: Storage>(
+    op: BinaryOperation,
+    lhs: Spanned<Expression<S>>,
+    rhs: Spanned<Expression<S>>,
+) -> Spanned<Expression<S>> {
+    let span = lhs.span.to(rhs.span);
+    Spanned::new(Expression::BinaryOperation(op, Box::new(lhs), Box::new(rhs)), span)
+}
+
+fn binary_operator<S: Storage>(token: &Token<S>) -> Option<BinaryOperation> {
+    Some(match *token {
+        Token::Or => BinaryOperation::Or,
+        Token::And => BinaryOperation::And,
+        Token::Less => BinaryOperation::LessThan,
+        Token::LessEqual => BinaryOperation::LessThanOrEqual,
+        Token::Greater => BinaryOperation::GreaterThan,
+        Token::GreaterEqual => BinaryOperation::GreaterThanOrEqual,
+        Token::EqualEqual => BinaryOperation::Equal,
+        Token::TildeEqual => BinaryOperation::NotEqual,
+        Token::Pipe => BinaryOperation::BitwiseOr,
+        Token::Tilde => BinaryOperation::BitwiseXor,
+        Token::Ampersand => BinaryOperation::BitwiseAnd,
+        Token::ShiftLeft => BinaryOperation::ShiftLeft,
+        Token::ShiftRight => BinaryOperation::ShiftRight,
+        Token::DoubleDot => BinaryOperation::Concatanate,
+        Token::Plus => BinaryOperation::Plus,
+        Token::Minus => BinaryOperation::Minus,
+        Token::Star => BinaryOperation::Times,
+        Token::Slash => BinaryOperation::Devide,
+        Token::DoubleSlash => BinaryOperation::FloorDivide,
+        Token::Percent => BinaryOperation::Modulo,
+        Token::Caret => BinaryOperation::Exponent,
+        _ => return None,
+    })
+}
+
+fn unary_operator<S: Storage>(token: &Token<S>) -> Option<UnaryOperation> {
+    Some(match *token {
+        Token::Not => UnaryOperation::Not,
+        Token::Hash => UnaryOperation::Length,
+        Token::Minus => UnaryOperation::Negate,
+        Token::Tilde => UnaryOperation::BitwiseNot,
+        _ => return None,
+    })
+}
+
+/// The error for parsing an operator from a string that isn't one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownOperator(pub String);
+
+impl fmt::Display for UnknownOperator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?} isn't an operator", self.0)
+    }
+}
+
+impl Error for UnknownOperator {}
+
+/// The token making up all of `s`, if it's exactly one token with nothing around it.
+fn single_token(s: &str) -> Option<Token> {
+    let token = Lexer::new(s).next_spanned()?.ok()?;
+    if token.span == Span::new(0, s.len()) { Some(token.node) } else { None }
+}
+
+/// Reads an operator such as `~=` or `and`, as written in the source.
+impl FromStr for BinaryOperation {
+    type Err = UnknownOperator;
+
+    fn from_str(s: &str) -> Result<BinaryOperation, UnknownOperator> {
+        single_token(s)
+            .as_ref()
+            .and_then(binary_operator)
+            .ok_or_else(|| UnknownOperator(s.to_string()))
+    }
+}
+
+/// Reads an operator such as `#` or `not`, as written in the source.
+impl FromStr for UnaryOperation {
+    type Err = UnknownOperator;
+
+    fn from_str(s: &str) -> Result<UnaryOperation, UnknownOperator> {
+        single_token(s)
+            .as_ref()
+            .and_then(unary_operator)
+            .ok_or_else(|| UnknownOperator(s.to_string()))
+    }
+}
+
+struct Parser<'a, S> {
+    /// The source being parsed, for the text of number literals.
+    source: &'a str,
+    tokens: Vec<Spanned<SourceToken<'a>>>,
+    store: S,
+    position: usize,
+    /// How many levels of nesting are being parsed.
+    depth: usize,
+    limit: usize,
+    /// Whether errors are collected in `errors` and parsing goes on past them, rather than
+    /// stopping at the first.
+    recovering: bool,
+    errors: Vec<ParseError>,
+}
+
+impl<'a, S: Store<'a>> Parser<'a, S> {
+    fn new(
+        source: &'a str,
+        tokens: Vec<Spanned<SourceToken<'a>>>,
+        config: &ParserConfig,
+        recovering: bool,
+        store: S,
+    ) -> Parser<'a, S> {
+        Parser {
+            source,
+            tokens,
+            store,
+            position: 0,
+            depth: 0,
+            limit: config.recursion_limit,
+            recovering,
+            errors: Vec::new(),
+        }
+    }
+
+    fn peek(&self) -> Option<&SourceToken<'a>> {
+        self.tokens.get(self.position).map(|token| &token.node)
+    }
+
+    fn peek_nth(&self, n: usize) -> Option<&SourceToken<'a>> {
+        self.tokens.get(self.position + n).map(|token| &token.node)
+    }
+
+    fn next(&mut self) -> Option<SourceToken<'a>> {
+        let token = self.tokens.get(self.position).map(|token| token.node.clone());
+        if token.is_some() {
+            self.position += 1;
+        }
+        token
+    }
+
+    /// The byte offset where the next token starts.
+    fn start(&self) -> usize {
+        match self.tokens.get(self.position) {
+            Some(token) => token.span.start,
+            None => self.tokens.last().map_or(0, |token| token.span.end),
+        }
+    }
+
+    /// How the number literal at the current token is written, given the literal of its value.
+    fn number_form(&mut self, plain: Expression) -> NumberForm<S::Storage> {
+        let span = self.tokens[self.position].span;
+        let text = &self.source[span.start..span.end];
+        if plain.to_string() == text {
+            NumberForm::Plain
+        } else {
+            NumberForm::Written(self.store.text(Cow::Borrowed(text)))
+        }
+    }
+
+    /// The comments of the source, with their text made by the store.
+    fn comments(&mut self, comments: Vec<Comment<Borrowed<'a>>>) -> Vec<Comment<S::Storage>> {
+        let store = &mut self.store;
+        let comment = |comment: Comment<Borrowed<'a>>| Comment {
+            text: store.text(comment.text),
+            kind: comment.kind,
+            trailing: comment.trailing,
+            span: comment.span,
+        };
+        comments.into_iter().map(comment).collect()
+    }
+
+    /// The span from `start` to the end of the last token read.
+    fn span_from(&self, start: usize) -> Span {
+        let end = match self.position {
+            0 => start,
+            position => self.tokens[position - 1].span.end,
+        };
+        Span::new(start, end)
+    }
+
+    fn check(&self, token: &SourceToken<'a>) -> bool {
+        self.peek() == Some(token)
+    }
+
+    /// Consumes the next token if it is `token`.
+    fn eat(&mut self, token: &SourceToken<'a>) -> bool {
+        if self.check(token) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Consumes the next token, failing if it isn't `token`. When recovering, a missing token is
+    /// reported and treated as if it were there instead.
+    fn expect(&mut self, token: &SourceToken<'a>) -> Result<(), ParseError> {
+        if self.eat(token) {
+            return Ok(());
+        }
+        let error = self.unexpected(&[token.describe()]);
+        if !self.recovering {
+            return Err(error);
+        }
+        self.errors.push(error);
+        Ok(())
+    }
+
+    fn expect_name(&mut self) -> Result<Spanned<<S::Storage as Storage>::Name>, ParseError> {
+        let start = self.start();
+        match self.peek() {
+            Some(&Token::Name(_)) => {}
+            _ => return Err(self.unexpected(&["name"])),
+        }
+        match self.next() {
+            Some(Token::Name(name)) => {
+                let name = self.store.name(name);
+                Ok(Spanned::new(name, self.span_from(start)))
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// The error for the token about to be read not being any of `expected`.
+    fn unexpected(&self, expected: &[&'static str]) -> ParseError {
+        let expected = expected.to_vec();
+        match self.tokens.get(self.position) {
+            // Errors are rare enough that the token is lexed again, as an owned one.
+            Some(token) => ParseError::UnexpectedToken {
+                found: lexer::token_at(self.source, token.span.start),
+                expected,
+                span: token.span,
+            },
+            None => ParseError::UnexpectedEof { expected },
+        }
+    }
+
+    /// Starts parsing a nested block or expression, failing if that goes over the limit.
+    fn enter(&mut self) -> Result<(), ParseError> {
+        self.depth += 1;
+        if self.depth > self.limit {
+            let start = self.start();
+            let span = self.tokens.get(self.position).map_or(Span::new(start, start), |t| t.span);
+            return Err(ParseError::RecursionLimit { limit: self.limit, span });
+        }
+        Ok(())
+    }
+
+    fn leave(&mut self) {
+        self.depth -= 1;
+    }
+
+    fn block_ends(&self) -> bool {
+        matches!(
+            self.peek(),
+            None | Some(&Token::End) | Some(&Token::Else) | Some(&Token::Elseif) |
+                Some(&Token::Until)
+        )
+    }
+
+    /// Whether the next token can only start a statement, which recovering skips to after an
+    /// error. A name is counted, though it may also go on an expression, as most statements start
+    /// with one.
+    fn statement_starts(&self) -> bool {
+        matches!(
+            self.peek(),
+            Some(&Token::Semicolon) | Some(&Token::DoubleColon) | Some(&Token::Break) |
+                Some(&Token::Goto) | Some(&Token::Do) | Some(&Token::While) |
+                Some(&Token::Repeat) | Some(&Token::If) | Some(&Token::For) |
+                Some(&Token::Function) | Some(&Token::Local) | Some(&Token::Return) |
+                Some(&Token::Name(_))
+        )
+    }
+
+    /// Reports an error in a statement that started at `position` and `depth` when recovering,
+    /// skipping to the start of the next statement. Otherwise gives the error back.
+    fn recover(
+        &mut self,
+        error: ParseError,
+        position: usize,
+        depth: usize,
+    ) -> Result<(), ParseError> {
+        if !self.recovering {
+            return Err(error);
+        }
+        self.errors.push(error);
+        // Failing inside nested blocks or expressions leaves them without being left.
+        self.depth = depth;
+        // The statement has to give up at least a token, so parsing can't get stuck on it.
+        if self.position == position {
+            self.position += 1;
+        }
+        while !self.block_ends() && !self.statement_starts() {
+            self.position += 1;
+        }
+        Ok(())
+    }
+
+    fn block(&mut self) -> Result<Block<S::Storage>, ParseError> {
+        self.enter()?;
+        let mut statements = Vec::new();
+        let mut return_statement = None;
+        loop {
+            if self.block_ends() {
+                // Outside of any block, a word closing one can only be a mistake.
+                if !self.recovering || self.depth > 1 || self.peek().is_none() {
+                    break;
+                }
+                let error = self.unexpected(&["statement"]);
+                self.errors.push(error);
+                self.position += 1;
+                continue;
+            }
+            let (position, depth) = (self.position, self.depth);
+            if self.check(&Token::Return) {
+                match self.return_statement() {
+                    Ok(statement) => {
+                        return_statement = Some(Box::new(statement));
+                        break;
+                    }
+                    Err(error) => self.recover(error, position, depth)?,
+                }
+                continue;
+            }
+            let start = self.start();
+            match self.statement() {
+                Ok(statement) => statements.push(Spanned::new(statement, self.span_from(start))),
+                Err(error) => self.recover(error, position, depth)?,
+            }
+        }
+        let statements = if statements.is_empty() { None } else { Some(statements) };
+        self.leave();
+        Ok(Block(statements, return_statement))
+    }
+
+    fn return_statement(&mut self) -> Result<ReturnStatement<S::Storage>, ParseError> {
+        self.expect(&Token::Return)?;
+        let expressions = if self.block_ends() || self.check(&Token::Semicolon) {
+            None
+        } else {
+            Some(Box::new(self.expression_list()?))
+        };
+        self.eat(&Token::Semicolon);
+        if !self.block_ends() {
+            return Err(self.unexpected(&["end of block"]));
+        }
+        Ok(ReturnStatement(expressions))
+    }
+
+    fn statement(&mut self) -> Result<Statement<S::Storage>, ParseError> {
+        match self.peek() {
+            Some(&Token::Semicolon) => {
+                self.next();
+                Ok(Statement::Semicolon)
+            }
+            Some(&Token::DoubleColon) => {
+                self.next();
+                let name = self.expect_name()?;
+                self.expect(&Token::DoubleColon)?;
+                Ok(Statement::Label(Label(name.node)))
+            }
+            Some(&Token::Break) => {
+                self.next();
+                Ok(Statement::Break)
+            }
+            Some(&Token::Goto) => {
+                self.next();
+                Ok(Statement::Goto(Label(self.expect_name()?.node)))
+            }
+            Some(&Token::Do) => {
+                self.next();
+                let block = self.block()?;
+                self.expect(&Token::End)?;
+                Ok(Statement::Do(Box::new(block)))
+            }
+            Some(&Token::While) => {
+                self.next();
+                let exp = self.expression()?;
+                self.expect(&Token::Do)?;
+                let block = self.block()?;
+                self.expect(&Token::End)?;
+                Ok(Statement::While { exp: Box::new(exp), do_: Box::new(block) })
+            }
+            Some(&Token::Repeat) => {
+                self.next();
+                let block = self.block()?;
+                self.expect(&Token::Until)?;
+                let until = self.expression()?;
+                Ok(Statement::Repeat { block: Box::new(block), until: Box::new(until) })
+            }
+            Some(&Token::If) => self.if_statement(),
+            Some(&Token::For) => self.for_statement(),
+            Some(&Token::Function) => {
+                self.next();
+                let name = self.function_name()?;
+                let body = self.function_body()?;
+                Ok(Statement::Function(name, Box::new(body)))
+            }
+            Some(&Token::Local) => {
+                self.next();
+                if self.eat(&Token::Function) {
+                    let name = self.expect_name()?;
+                    let body = self.function_body()?;
+                    return Ok(Statement::LocalFunction { name, body: Box::new(body) });
+                }
+                let names = self.attribute_name_list()?;
+                let expressions =
+                    if self.eat(&Token::Equal) { Some(self.expression_list()?) } else { None };
+                Ok(Statement::LocalVariableBinding(names, expressions))
+            }
+            Some(&Token::Name(_)) | Some(&Token::LeftParen) => self.expression_statement(),
+            _ => Err(self.unexpected(&["statement"])),
+        }
+    }
+
+    fn if_statement(&mut self) -> Result<Statement<S::Storage>, ParseError> {
+        self.expect(&Token::If)?;
+        let condition = self.expression()?;
+        self.expect(&Token::Then)?;
+        let then = self.block()?;
+        let mut elseifs = Vec::new();
+        while self.eat(&Token::Elseif) {
+            let condition = self.expression()?;
+            self.expect(&Token::Then)?;
+            elseifs.push((Box::new(condition), Box::new(self.block()?)));
+        }
+        let else_ = if self.eat(&Token::Else) { Some(Box::new(self.block()?)) } else { None };
+        self.expect(&Token::End)?;
+        Ok(Statement::If {
+            condition: Box::new(condition),
+            then: Box::new(then),
+            elseifs,
+            else_,
+        })
+    }
+
+    fn for_statement(&mut self) -> Result<Statement<S::Storage>, ParseError> {
+        self.expect(&Token::For)?;
+        let name = self.expect_name()?;
+        if self.eat(&Token::Equal) {
+            let from = self.expression()?;
+            self.expect(&Token::Comma)?;
+            let to = self.expression()?;
+            let step =
+                if self.eat(&Token::Comma) { Some(Box::new(self.expression()?)) } else { None };
+            self.expect(&Token::Do)?;
+            let block = self.block()?;
+            self.expect(&Token::End)?;
+            return Ok(Statement::ForStepping {
+                name,
+                from: Box::new(from),
+                to: Box::new(to),
+                step,
+                block: Box::new(block),
+            });
+        }
+        let mut rest = Vec::new();
+        while self.eat(&Token::Comma) {
+            rest.push(self.expect_name()?);
+        }
+        let name_list = NameList(name, if rest.is_empty() { None } else { Some(rest) });
+        self.expect(&Token::In)?;
+        let in_ = self.expression_list()?;
+        self.expect(&Token::Do)?;
+        let block = self.block()?;
+        self.expect(&Token::End)?;
+        Ok(Statement::ForIn {
+            name_list: Box::new(name_list),
+            in_: Box::new(in_),
+            do_: Box::new(block),
+        })
+    }
+
+    /// Parses either an assignment or a function call, which both start with a prefix expression.
+    fn expression_statement(&mut self) -> Result<Statement<S::Storage>, ParseError> {
+        let prefix = self.prefix_expression()?.node;
+        if self.check(&Token::Equal) || self.check(&Token::Comma) {
+            let first = Box::new(self.assignment_target(prefix)?);
+            let mut rest = Vec::new();
+            while self.eat(&Token::Comma) {
+                let prefix = self.prefix_expression()?.node;
+                rest.push(self.assignment_target(prefix)?);
+            }
+            self.expect(&Token::Equal)?;
+            let rest = if rest.is_empty() { None } else { Some(rest) };
+            let variables = VariableList { first, rest };
+            let expressions = self.expression_list()?;
+            return Ok(Statement::Assignment(Box::new(variables), Box::new(expressions)));
+        }
+        match prefix {
+            PrefixExpression::FunctionCall(call) => Ok(Statement::FunctionCall(call)),
+            _ => Err(self.unexpected(&["'='", "function arguments"])),
+        }
+    }
+
+    fn assignment_target(
+        &self,
+        prefix: PrefixExpression<S::Storage>,
+    ) -> Result<Variable<S::Storage>, ParseError> {
+        match prefix {
+            PrefixExpression::Variable(variable) => Ok(*variable),
+            _ => Err(self.unexpected(&["variable"])),
+        }
+    }
+
+    fn function_name(&mut self) -> Result<FunctionName<S::Storage>, ParseError> {
+        let first_dot_access = self.expect_name()?;
+        let mut rest = Vec::new();
+        while self.eat(&Token::Dot) {
+            rest.push(self.expect_name()?.node);
+        }
+        let self_name =
+            if self.eat(&Token::Colon) { Some(self.expect_name()?.node) } else { None };
+        Ok(FunctionName {
+            first_dot_access,
+            rest_dot_access: if rest.is_empty() { None } else { Some(rest) },
+            self_name,
+        })
+    }
+
+    fn function_body(&mut self) -> Result<FunctionBody<S::Storage>, ParseError> {
+        self.expect(&Token::LeftParen)?;
+        let parameters = if self.eat(&Token::TripleDot) {
+            Some(ParameterList::ExtendedArgumentsVoid)
+        } else if self.check(&Token::RightParen) {
+            None
+        } else {
+            let first = self.expect_name()?;
+            let mut rest = Vec::new();
+            let mut extended = false;
+            while self.eat(&Token::Comma) {
+                if self.eat(&Token::TripleDot) {
+                    extended = true;
+                    break;
+                }
+                rest.push(self.expect_name()?);
+            }
+            let names = Box::new(NameList(first, if rest.is_empty() { None } else { Some(rest) }));
+            if extended {
+                Some(ParameterList::ExtendedArguments(names))
+            } else {
+                Some(ParameterList::NameList(names))
+            }
+        };
+        self.expect(&Token::RightParen)?;
+        let block = self.block()?;
+        self.expect(&Token::End)?;
+        Ok(FunctionBody(parameters.map(Box::new), Box::new(block)))
+    }
+
+    fn attribute_name_list(&mut self) -> Result<AttributeNameList<S::Storage>, ParseError> {
+        let first = self.attribute_name()?;
+        let mut rest = Vec::new();
+        while self.eat(&Token::Comma) {
+            rest.push(self.attribute_name()?);
+        }
+        Ok(AttributeNameList(first, if rest.is_empty() { None } else { Some(rest) }))
+    }
+
+    /// Parses a name optionally followed by an attribute, as in `x <const>`.
+    fn attribute_name(&mut self) -> Result<AttributeName<S::Storage>, ParseError> {
+        let name = self.expect_name()?;
+        if !self.eat(&Token::Less) {
+            return Ok(AttributeName { name, attribute: None });
+        }
+        let attribute = match self.peek() {
+            Some(Token::Name(attribute)) if attribute == "const" => Attribute::Const,
+            Some(Token::Name(attribute)) if attribute == "close" => Attribute::Close,
+            _ => return Err(self.unexpected(&["'const'", "'close'"])),
+        };
+        self.next();
+        self.expect(&Token::Greater)?;
+        Ok(AttributeName { name, attribute: Some(attribute) })
+    }
+
+    fn expression_list(&mut self) -> Result<ExpressionList<S::Storage>, ParseError> {
+        let first = self.expression()?;
+        let mut rest = Vec::new();
+        while self.eat(&Token::Comma) {
+            rest.push(self.expression()?);
+        }
+        Ok(ExpressionList(Box::new(first), if rest.is_empty() { None } else { Some(rest) }))
+    }
+
+    fn expression(&mut self) -> Result<Spanned<Expression<S::Storage>>, ParseError> {
+        self.subexpression(0)
+    }
+
+    /// Parses an expression, stopping at the first binary operator whose left binding power
+    /// isn't above `limit`.
+    ///
+    /// Each operator takes the expression parsed so far as its left hand side, and whatever binds
+    /// tighter than its right binding power as its right hand side, so higher powers group first
+    /// and right associative operators group from the right.
+    fn subexpression(&mut self, limit: u8) -> Result<Spanned<Expression<S::Storage>>, ParseError> {
+        self.enter()?;
+        let start = self.start();
+        let mut lhs = match self.peek().and_then(unary_operator) {
+            Some(op) => {
+                self.next();
+                let operand = self.subexpression(UnaryOperation::BINDING_POWER)?;
+                let unary = Expression::UnaryOperation(op, Box::new(operand));
+                Spanned::new(unary, self.span_from(start))
+            }
+            None => self.simple_expression()?,
+        };
+        while let Some(op) = self.peek().and_then(binary_operator) {
+            let (left, right) = op.binding_power();
+            if left <= limit {
+                break;
+            }
+            self.next();
+            let rhs = self.subexpression(right)?;
+            lhs = binary(op, lhs, rhs);
+        }
+        self.leave();
+        Ok(lhs)
+    }
+
+    fn simple_expression(&mut self) -> Result<Spanned<Expression<S::Storage>>, ParseError> {
+        let start = self.start();
+        let expression = match self.peek() {
+            Some(&Token::Nil) => Expression::Nil,
+            Some(&Token::False) => Expression::False,
+            Some(&Token::True) => Expression::True,
+            Some(&Token::Integer(n)) => Expression::Integer(n, self.number_form(n.into())),
+            Some(&Token::Float(n)) => Expression::Float(n, self.number_form(n.into())),
+            Some(Token::String(s, form)) => {
+                let (s, form) = (s.clone(), *form);
+                Expression::String(self.store.string(s), form)
+            }
+            Some(&Token::TripleDot) => Expression::ExtendedArgumentAccess,
+            Some(&Token::Function) => {
+                self.next();
+                let body = self.function_body()?;
+                let define = Expression::FunctionDefine(Box::new(FunctionDefine(Box::new(body))));
+                return Ok(Spanned::new(define, self.span_from(start)));
+            }
+            Some(&Token::LeftBrace) => {
+                let table = Expression::TableConstructor(Box::new(self.table_constructor()?));
+                return Ok(Spanned::new(table, self.span_from(start)));
+            }
+            Some(&Token::Name(_)) | Some(&Token::LeftParen) => {
+                let prefix = self.prefix_expression()?;
+                let expression = Expression::PrefixExpression(Box::new(prefix.node));
+                return Ok(Spanned::new(expression, prefix.span));
+            }
+            _ if self.recovering => {
+                // The missing expression takes up no source, leaving the token for what follows.
+                let error = self.unexpected(&["expression"]);
+                self.errors.push(error);
+                return Ok(Spanned::new(Expression::Error, Span::new(start, start)));
+            }
+            _ => return Err(self.unexpected(&["expression"])),
+        };
+        self.next();
+        Ok(Spanned::new(expression, self.span_from(start)))
+    }
+
+    /// Parses a name or parenthesized expression followed by any number of indexes and calls.
+    fn prefix_expression(&mut self) -> Result<Spanned<PrefixExpression<S::Storage>>, ParseError> {
+        let start = self.start();
+        let mut prefix = match self.peek() {
+            Some(&Token::Name(_)) => {
+                PrefixExpression::Variable(Box::new(Variable::Name(self.expect_name()?)))
+            }
+            Some(&Token::LeftParen) => {
+                self.next();
+                let expression = self.expression()?;
+                self.expect(&Token::RightParen)?;
+                PrefixExpression::Parenthesis(Box::new(expression))
+            }
+            _ => return Err(self.unexpected(&["name", "'('"])),
+        };
+        loop {
+            prefix = match self.peek() {
+                Some(&Token::Dot) => {
+                    self.next();
+                    let key = self.expect_name()?.node;
+                    let from = Box::new(prefix);
+                    PrefixExpression::Variable(Box::new(Variable::DotAccess { from, key }))
+                }
+                Some(&Token::LeftBracket) => {
+                    self.next();
+                    let key = Box::new(self.expression()?);
+                    self.expect(&Token::RightBracket)?;
+                    let from = Box::new(prefix);
+                    PrefixExpression::Variable(Box::new(Variable::ArrayAccess { from, key }))
+                }
+                Some(&Token::Colon) => {
+                    self.next();
+                    let name = self.expect_name()?.node;
+                    let arguments = Box::new(self.function_arguments()?);
+                    let call = FunctionCall::SelfTaking(Box::new(prefix), name, arguments);
+                    PrefixExpression::FunctionCall(Box::new(call))
+                }
+                Some(&Token::LeftParen) | Some(&Token::LeftBrace) | Some(&Token::String(..)) => {
+                    let arguments = self.function_arguments()?;
+                    let call = FunctionCall::Static(Box::new(prefix), Box::new(arguments));
+                    PrefixExpression::FunctionCall(Box::new(call))
+                }
+                _ => return Ok(Spanned::new(prefix, self.span_from(start))),
+            };
+        }
+    }
+
+    fn function_arguments(&mut self) -> Result<FunctionArguments<S::Storage>, ParseError> {
+        match self.peek() {
+            Some(&Token::LeftParen) => {
+                self.next();
+                if self.eat(&Token::RightParen) {
+                    return Ok(FunctionArguments::Parenthesis(None));
+                }
+                let arguments = self.expression_list()?;
+                self.expect(&Token::RightParen)?;
+                Ok(FunctionArguments::Parenthesis(Some(Box::new(arguments))))
+            }
+            Some(&Token::LeftBrace) => {
+                Ok(FunctionArguments::TableConstructor(Box::new(self.table_constructor()?)))
+            }
+            Some(&Token::String(..)) => match self.next() {
+                Some(Token::String(s, form)) => {
+                    Ok(FunctionArguments::String(self.store.string(s), form))
+                }
+                _ => unreachable!(),
+            },
+            _ => Err(self.unexpected(&["function arguments"])),
+        }
+    }
+
+    fn table_constructor(&mut self) -> Result<TableConstructor<S::Storage>, ParseError> {
+        self.expect(&Token::LeftBrace)?;
+        let mut fields = Vec::new();
+        while !self.check(&Token::RightBrace) {
+            fields.push(self.field()?);
+            if !self.eat(&Token::Comma) && !self.eat(&Token::Semicolon) {
+                break;
+            }
+        }
+        self.expect(&Token::RightBrace)?;
+        let mut fields = fields.into_iter();
+        Ok(TableConstructor(fields.next().map(|first| {
+            let rest: Vec<_> = fields.collect();
+            Box::new(FieldList(Box::new(first), if rest.is_empty() { None } else { Some(rest) }))
+        })))
+    }
+
+    fn field(&mut self) -> Result<Field<S::Storage>, ParseError> {
+        if self.eat(&Token::LeftBracket) {
+            let name = self.expression()?;
+            self.expect(&Token::RightBracket)?;
+            self.expect(&Token::Equal)?;
+            let equals = self.expression()?;
+            return Ok(Field::ExpressionForName { name: Box::new(name), equals: Box::new(equals) });
+        }
+        if let (Some(&Token::Name(_)), Some(&Token::Equal)) = (self.peek(), self.peek_nth(1)) {
+            let name = self.expect_name()?.node;
+            self.next();
+            let equals = self.expression()?;
+            return Ok(Field::Equals { name, equals: Box::new(equals) });
+        }
+        Ok(Field::ArrayStyle(Box::new(self.expression()?)))
+    }
+}
@@ -0,0 +1,993 @@
+//! A pretty printer turning the abstract syntax tree back into lua source code.
+//!
+//! The output is indented with four spaces per block, or as set in `FormatOptions` when printed
+//! with `format`, which can also break long argument lists and tables across lines. Either way it
+//! can be parsed again to get the same program back. Printing a `Chunk` also
+//! writes its comments, placed by their spans before the statement they lead into or after the
+//! line they trail.
+//!
+//! `minify` prints without indentation, comments or any whitespace that isn't needed to keep
+//! tokens apart.
+
+use std::fmt::{self, Display, Write};
+use std::iter;
+use std::str;
+
+use ast::*;
+use interp::format_general;
+use span::{Span, Spanned};
+
+/// How blocks are indented.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Indent {
+    /// This many spaces per level of nesting.
+    Spaces(usize),
+    /// One tab per level of nesting.
+    Tab,
+}
+
+/// Which quotes short strings are written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuoteStyle {
+    /// The quotes each string was written in.
+    Preserve,
+    /// Double quotes, escaping any in the string.
+    AlwaysDouble,
+    /// Single quotes, escaping any in the string.
+    AlwaysSingle,
+}
+
+/// Which statements are followed by semicolons.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SemicolonStyle {
+    /// The semicolons that were written, each on a line of its own.
+    Preserve,
+    /// No semicolons, except where one is needed to keep a statement starting with a parenthesis
+    /// from continuing the one before it.
+    Remove,
+    /// A semicolon after every statement that doesn't end with a block, and after return
+    /// statements. Statements ending with `end`, such as `if` and `while`, and labels are left
+    /// alone.
+    Add,
+}
+
+/// How floats are written, unless they keep the form they were written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FloatFormat {
+    /// The fewest digits that read back as the same float.
+    Shortest,
+    /// At most this many significant digits, like `printf`'s `%g`. Floats needing more digits
+    /// than this read back as a float near them.
+    Significant(usize),
+}
+
+/// Options changing how source code is printed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormatOptions {
+    pub indent: Indent,
+    /// How many characters long lines may be before the argument lists and table constructors on
+    /// them are broken up, with an item on each line, or `None` to leave them on one line. Lines
+    /// can still be longer where there's nothing to break, such as in a long string.
+    pub max_width: Option<usize>,
+    pub quote_style: QuoteStyle,
+    pub semicolons: SemicolonStyle,
+    pub float_format: FloatFormat,
+}
+
+impl Default for FormatOptions {
+    /// Four spaces of indentation, no line breaking, strings and semicolons as they were written,
+    /// and the shortest floats that read back the same, as `Display` prints.
+    fn default() -> FormatOptions {
+        FormatOptions {
+            indent: Indent::Spaces(4),
+            max_width: None,
+            quote_style: QuoteStyle::Preserve,
+            semicolons: SemicolonStyle::Preserve,
+            float_format: FloatFormat::Shortest,
+        }
+    }
+}
+
+/// Prints the chunk with its comments like `Display` does, but with the given options.
+pub fn format(chunk: &Chunk, options: &FormatOptions) -> String {
+    let mut out = String::new();
+    // Writing to a string can't fail.
+    write_chunk(chunk, options, &mut out).unwrap();
+    out
+}
+
+fn write_chunk<W: Write>(chunk: &Chunk, options: &FormatOptions, out: W) -> fmt::Result {
+    let mut printer = Printer::new(out);
+    printer.unit = match options.indent {
+        Indent::Spaces(n) => " ".repeat(n),
+        Indent::Tab => "\t".to_string(),
+    };
+    printer.max_width = options.max_width;
+    printer.quote_style = options.quote_style;
+    printer.semicolons = options.semicolons;
+    printer.float_format = options.float_format;
+    printer.comments = &chunk.1;
+    printer.statements(&chunk.0)?;
+    printer.comments_before(usize::MAX)?;
+    if !printer.fresh {
+        printer.out.write_char('\n')?;
+    }
+    Ok(())
+}
+
+impl Display for Chunk {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_chunk(self, &FormatOptions::default(), f)
+    }
+}
+
+/// Prints the chunk as compactly as possible, leaving out comments, optional semicolons and every
+/// space and newline lua doesn't need to tell the tokens apart.
+pub fn minify(chunk: &Chunk) -> String {
+    let mut printer = Printer::new(String::new());
+    printer.out.compact = true;
+    // Writing to a string can't fail.
+    printer.statements(&chunk.0).unwrap();
+    printer.out.inner
+}
+
+impl Display for Block {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        Printer::new(f).statements(self)
+    }
+}
+
+impl Display for Statement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        Printer::new(f).statement(self)
+    }
+}
+
+impl Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        Printer::new(f).expression(self)
+    }
+}
+
+/// Writes the operator as it appears in the source, such as `..` or `and`.
+impl Display for BinaryOperation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(binary_sigil(*self))
+    }
+}
+
+/// Writes the operator as it appears in the source, such as `#` or `not`.
+impl Display for UnaryOperation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(unary_sigil(*self))
+    }
+}
+
+fn binary_sigil(op: BinaryOperation) -> &'static str {
+    match op {
+        BinaryOperation::Plus => "+",
+        BinaryOperation::Minus => "-",
+        BinaryOperation::Times => "*",
+        BinaryOperation::Devide => "/",
+        BinaryOperation::FloorDivide => "//",
+        BinaryOperation::Exponent => "^",
+        BinaryOperation::Modulo => "%",
+        BinaryOperation::Concatanate => "..",
+        BinaryOperation::LessThan => "<",
+        BinaryOperation::LessThanOrEqual => "<=",
+        BinaryOperation::GreaterThan => ">",
+        BinaryOperation::GreaterThanOrEqual => ">=",
+        BinaryOperation::Equal => "==",
+        BinaryOperation::NotEqual => "~=",
+        BinaryOperation::BitwiseAnd => "&",
+        BinaryOperation::BitwiseOr => "|",
+        BinaryOperation::BitwiseXor => "~",
+        BinaryOperation::ShiftLeft => "<<",
+        BinaryOperation::ShiftRight => ">>",
+        BinaryOperation::And => "and",
+        BinaryOperation::Or => "or",
+    }
+}
+
+fn unary_sigil(op: UnaryOperation) -> &'static str {
+    match op {
+        UnaryOperation::Negate => "-",
+        UnaryOperation::Not => "not",
+        UnaryOperation::Length => "#",
+        UnaryOperation::BitwiseNot => "~",
+    }
+}
+
+/// Whether the expression is printed starting with a unary operator, which takes any operator of
+/// higher precedence following it into its operand.
+fn is_unary(expression: &Expression) -> bool {
+    match *expression {
+        Expression::UnaryOperation(..) => true,
+        Expression::Integer(n, _) => n < 0 && n != i64::MIN,
+        Expression::Float(n, _) => n.is_sign_negative() && !n.is_nan(),
+        _ => false,
+    }
+}
+
+/// Whether the printed form of the statement ends with an expression, which a following opening
+/// parenthesis would turn into a call.
+fn ends_with_expression(statement: &Statement) -> bool {
+    match *statement {
+        Statement::Assignment(..) | Statement::FunctionCall(_) | Statement::Repeat { .. } => true,
+        Statement::LocalVariableBinding(_, ref expressions) => expressions.is_some(),
+        _ => false,
+    }
+}
+
+/// Whether the statement ends with a block, or is a label or a semicolon, which semicolons
+/// aren't added after.
+fn ends_with_block(statement: &Statement) -> bool {
+    matches!(
+        *statement,
+        Statement::Semicolon |
+            Statement::Label(_) |
+            Statement::Do(_) |
+            Statement::While { .. } |
+            Statement::If { .. } |
+            Statement::ForStepping { .. } |
+            Statement::ForIn { .. } |
+            Statement::Function(..) |
+            Statement::LocalFunction { .. }
+    )
+}
+
+/// Whether the printed form of the statement starts with an opening parenthesis, which would make
+/// it continue a function call on the previous line.
+fn starts_with_parenthesis(statement: &Statement) -> bool {
+    match *statement {
+        Statement::Assignment(ref variables, _) => {
+            variable_starts_with_parenthesis(&variables.first)
+        }
+        Statement::FunctionCall(ref call) => call_starts_with_parenthesis(call),
+        _ => false,
+    }
+}
+
+fn prefix_starts_with_parenthesis(prefix: &PrefixExpression) -> bool {
+    match *prefix {
+        PrefixExpression::Parenthesis(_) => true,
+        PrefixExpression::Variable(ref variable) => variable_starts_with_parenthesis(variable),
+        PrefixExpression::FunctionCall(ref call) => call_starts_with_parenthesis(call),
+    }
+}
+
+fn variable_starts_with_parenthesis(variable: &Variable) -> bool {
+    match *variable {
+        Variable::Name(_) => false,
+        Variable::ArrayAccess { ref from, .. } | Variable::DotAccess { ref from, .. } => {
+            prefix_starts_with_parenthesis(from)
+        }
+    }
+}
+
+fn call_starts_with_parenthesis(call: &FunctionCall) -> bool {
+    match *call {
+        FunctionCall::Static(ref from, _) | FunctionCall::SelfTaking(ref from, _, _) => {
+            prefix_starts_with_parenthesis(from)
+        }
+    }
+}
+
+/// Whether a space is needed between two characters written one after the other, so that they
+/// aren't read as the same token or as a comment.
+fn needs_space(before: char, after: char) -> bool {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    match (before, after) {
+        (a, b) if is_word(a) && is_word(b) => true,
+        // `a[ [[x]] ]` mustn't start a long string, and `1 ..` mustn't read as a number.
+        ('[', '[') | ('[', '=') | ('-', '-') | ('.', '.') => true,
+        ('.', b) => b.is_ascii_digit(),
+        ('=', '=') | ('<', '=') | ('>', '=') | ('~', '=') | ('<', '<') | ('>', '>') => true,
+        ('/', '/') | (':', ':') => true,
+        _ => false,
+    }
+}
+
+/// The output of the printer, which keeps tokens from running together.
+///
+/// A space is added where two pieces of output would otherwise merge into one token, and in compact
+/// mode spaces and newlines are dropped except where they're needed for that. Literals are written
+/// with `raw`, so nothing inside them is changed.
+struct Output<W> {
+    inner: W,
+    compact: bool,
+    last: Option<char>,
+    /// Set when the last thing written is a number literal, which lua reads on into any `.` after
+    /// it, whatever character the literal ends in.
+    after_number: bool,
+    /// Set in compact mode when whitespace has been dropped since the last character written.
+    dropped_space: bool,
+    /// How many characters have been written since the last newline, counting a tab as one.
+    column: usize,
+}
+
+impl<W: Write> Output<W> {
+    fn separate(&mut self, next: char) -> fmt::Result {
+        let into_number = self.after_number && next == '.';
+        if into_number || self.last.is_some_and(|last| needs_space(last, next)) {
+            self.inner.write_char(' ')?;
+            self.column += 1;
+        }
+        Ok(())
+    }
+
+    /// Writes a literal, such as a string or a number, exactly as it is.
+    fn raw(&mut self, s: &str) -> fmt::Result {
+        if let Some(first) = s.chars().next() {
+            self.separate(first)?;
+            self.inner.write_str(s)?;
+            self.column = match s.rfind('\n') {
+                Some(newline) => s[newline + 1..].chars().count(),
+                None => self.column + s.chars().count(),
+            };
+            self.last = s.chars().next_back();
+            self.after_number = false;
+            self.dropped_space = false;
+        }
+        Ok(())
+    }
+
+    /// Writes a number literal exactly as it is.
+    fn number(&mut self, s: &str) -> fmt::Result {
+        self.raw(s)?;
+        self.after_number = true;
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for Output<W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut first = true;
+        for c in s.chars() {
+            if self.compact && (c == ' ' || c == '\n') {
+                self.dropped_space = true;
+                continue;
+            }
+            if first || self.dropped_space {
+                self.separate(c)?;
+            }
+            self.inner.write_char(c)?;
+            self.column = if c == '\n' { 0 } else { self.column + 1 };
+            self.last = Some(c);
+            self.after_number = false;
+            self.dropped_space = false;
+            first = false;
+        }
+        Ok(())
+    }
+}
+
+/// Escapes a string to be written in `quote`s, so it reads back as the same string. Bytes that
+/// aren't part of valid UTF-8 are written as decimal escapes.
+pub(crate) fn escape(s: &[u8], quote: char) -> String {
+    let mut escaped = String::with_capacity(s.len() + 2);
+    escaped.push(quote);
+    for chunk in s.utf8_chunks() {
+        for c in chunk.valid().chars() {
+            escape_char(c, quote, &mut escaped);
+        }
+        for byte in chunk.invalid() {
+            escaped.push_str(&format!("\\{:03}", byte));
+        }
+    }
+    escaped.push(quote);
+    escaped
+}
+
+fn escape_char(c: char, quote: char, escaped: &mut String) {
+    match c {
+        '\\' => escaped.push_str("\\\\"),
+        '\n' => escaped.push_str("\\n"),
+        '\r' => escaped.push_str("\\r"),
+        '\t' => escaped.push_str("\\t"),
+        c if c == quote => {
+            escaped.push('\\');
+            escaped.push(c);
+        }
+        // Three digits, so a digit after the escape isn't read as part of it.
+        c if c.is_ascii_control() => escaped.push_str(&format!("\\{:03}", c as u32)),
+        c => escaped.push(c),
+    }
+}
+
+struct Printer<'a, W> {
+    out: Output<W>,
+    indent: usize,
+    /// What is written for each level of indentation.
+    unit: String,
+    /// Set until the first line is written, so the output doesn't start with a blank line.
+    fresh: bool,
+    /// Comments not written yet, in source order.
+    comments: &'a [Comment],
+    /// Where the source of the statement being written ends. Comments before it are written
+    /// before the statement's blocks are closed, so they stay inside the statement.
+    limit: usize,
+    /// Set when the current line ends in a line comment, so nothing more can go on it.
+    in_line_comment: bool,
+    /// The width lines are kept to, as in `FormatOptions`.
+    max_width: Option<usize>,
+    quote_style: QuoteStyle,
+    semicolons: SemicolonStyle,
+    float_format: FloatFormat,
+}
+
+impl<'a, W: Write> Printer<'a, W> {
+    fn new(out: W) -> Printer<'a, W> {
+        Printer {
+            out: Output {
+                inner: out,
+                compact: false,
+                last: None,
+                after_number: false,
+                dropped_space: false,
+                column: 0,
+            },
+            indent: 0,
+            unit: "    ".to_string(),
+            fresh: true,
+            comments: &[],
+            limit: usize::MAX,
+            in_line_comment: false,
+            max_width: None,
+            quote_style: QuoteStyle::Preserve,
+            semicolons: SemicolonStyle::Preserve,
+            float_format: FloatFormat::Shortest,
+        }
+    }
+
+    /// Whether what `write` prints fits on the current line within the maximum width. Only the
+    /// first line is measured, as the rest of a nested function goes on lines of its own anyway.
+    fn fits<F>(&self, write: F) -> bool
+    where
+        F: FnOnce(&mut Printer<String>) -> fmt::Result,
+    {
+        let max_width = match self.max_width {
+            Some(max_width) => max_width,
+            None => return true,
+        };
+        let mut printer = Printer::new(String::new());
+        printer.out.last = self.out.last;
+        printer.out.after_number = self.out.after_number;
+        printer.unit = self.unit.clone();
+        printer.indent = self.indent;
+        printer.quote_style = self.quote_style;
+        printer.float_format = self.float_format;
+        printer.fresh = false;
+        // Writing to a string can't fail.
+        let _ = write(&mut printer);
+        let first = printer.out.inner.split('\n').next().unwrap_or("");
+        self.out.column + first.chars().count() <= max_width
+    }
+
+    /// Writes a bracketed list with each item on a line of its own, indented a level further than
+    /// the line the list starts on, and the closing bracket on a line of its own after them.
+    fn broken_list<T, F>(
+        &mut self,
+        brackets: (char, char),
+        items: &[&T],
+        trailing_comma: bool,
+        mut write: F,
+    ) -> fmt::Result
+    where
+        F: FnMut(&mut Self, &T) -> fmt::Result,
+    {
+        self.out.write_char(brackets.0)?;
+        self.indent += 1;
+        for (i, item) in items.iter().enumerate() {
+            self.line()?;
+            write(self, item)?;
+            if trailing_comma || i + 1 < items.len() {
+                self.out.write_char(',')?;
+            }
+        }
+        self.indent -= 1;
+        self.line()?;
+        self.out.write_char(brackets.1)
+    }
+
+    /// Starts a new line at the current indentation.
+    fn line(&mut self) -> fmt::Result {
+        if !self.fresh {
+            self.out.write_char('\n')?;
+        }
+        self.fresh = false;
+        self.in_line_comment = false;
+        for _ in 0..self.indent {
+            self.out.write_str(&self.unit)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the comments starting before `position` in the source. Comments that trailed code
+    /// go at the end of the current line, and the rest go on lines of their own.
+    fn comments_before(&mut self, position: usize) -> fmt::Result {
+        let comments = self.comments;
+        for (i, comment) in comments.iter().enumerate() {
+            if comment.span.start >= position {
+                self.comments = &comments[i..];
+                return Ok(());
+            }
+            if comment.trailing && !self.fresh && !self.in_line_comment {
+                self.out.write_char(' ')?;
+            } else {
+                self.line()?;
+            }
+            match comment.kind {
+                CommentKind::Line => {
+                    self.out.raw(&format!("--{}", comment.text))?;
+                    self.in_line_comment = true;
+                }
+                CommentKind::Block(level) => {
+                    let equals = "=".repeat(level);
+                    self.out.raw(&format!("--[{}[{}]{}]", equals, comment.text, equals))?;
+                }
+            }
+        }
+        self.comments = &[];
+        Ok(())
+    }
+
+    /// Writes a statement while keeping the comments of its blocks inside it.
+    fn spanned_statement(&mut self, statement: &Spanned<Statement>) -> fmt::Result {
+        if statement.span.is_dummy() {
+            return self.statement(&statement.node);
+        }
+        let limit = self.limit;
+        self.limit = statement.span.end;
+        let result = self.statement(&statement.node);
+        self.limit = limit;
+        result
+    }
+
+    fn statements(&mut self, block: &Block) -> fmt::Result {
+        if let Some(ref statements) = block.0 {
+            let mut previous = None;
+            for statement in statements {
+                let keep_semicolons =
+                    !self.out.compact && self.semicolons == SemicolonStyle::Preserve;
+                if statement.node == Statement::Semicolon && !keep_semicolons {
+                    continue;
+                }
+                self.comments_before(statement.span.start)?;
+                self.line()?;
+                let add = self.semicolons == SemicolonStyle::Add;
+                // With semicolons added, the statement before already ends with one.
+                let ambiguous = !add && previous.is_some_and(ends_with_expression);
+                if ambiguous && starts_with_parenthesis(&statement.node) {
+                    self.out.write_char(';')?;
+                }
+                self.spanned_statement(statement)?;
+                if add && !ends_with_block(&statement.node) {
+                    self.out.write_char(';')?;
+                }
+                previous = Some(&statement.node);
+            }
+        }
+        if let Some(ref return_statement) = block.1 {
+            if let Some(ref expressions) = return_statement.0 {
+                self.comments_before(expressions.0.span.start)?;
+            }
+            self.line()?;
+            self.out.write_str("return")?;
+            if let Some(ref expressions) = return_statement.0 {
+                self.out.write_char(' ')?;
+                self.expression_list(expressions)?;
+            }
+            if self.semicolons == SemicolonStyle::Add {
+                self.out.write_char(';')?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes an indented block followed by the keyword closing it on its own line.
+    fn block(&mut self, block: &Block, close: &str) -> fmt::Result {
+        self.indent += 1;
+        self.statements(block)?;
+        let limit = self.limit;
+        self.comments_before(limit)?;
+        self.indent -= 1;
+        self.line()?;
+        self.out.write_str(close)
+    }
+
+    /// Writes a block like `block`, keeping the comments after it in the source before `next`.
+    fn block_before(&mut self, block: &Block, close: &str, next: Span) -> fmt::Result {
+        if next.is_dummy() {
+            return self.block(block, close);
+        }
+        let limit = self.limit;
+        self.limit = next.start;
+        let result = self.block(block, close);
+        self.limit = limit;
+        result
+    }
+
+    fn statement(&mut self, statement: &Statement) -> fmt::Result {
+        match *statement {
+            Statement::Semicolon => self.out.write_char(';'),
+            Statement::Assignment(ref variables, ref expressions) => {
+                self.variable(&variables.first)?;
+                if let Some(ref rest) = variables.rest {
+                    for variable in rest {
+                        self.out.write_str(", ")?;
+                        self.variable(variable)?;
+                    }
+                }
+                self.out.write_str(" = ")?;
+                self.expression_list(expressions)
+            }
+            Statement::FunctionCall(ref call) => self.function_call(call),
+            Statement::Label(ref label) => write!(self.out, "::{}::", label.0),
+            Statement::Break => self.out.write_str("break"),
+            Statement::Goto(ref label) => write!(self.out, "goto {}", label.0),
+            Statement::Do(ref block) => {
+                self.out.write_str("do")?;
+                self.block(block, "end")
+            }
+            Statement::While { ref exp, ref do_ } => {
+                self.out.write_str("while ")?;
+                self.expression(&exp.node)?;
+                self.out.write_str(" do")?;
+                self.block(do_, "end")
+            }
+            Statement::Repeat { ref block, ref until } => {
+                self.out.write_str("repeat")?;
+                self.block_before(block, "until ", until.span)?;
+                self.expression(&until.node)
+            }
+            Statement::If { ref condition, ref then, ref elseifs, ref else_ } => {
+                self.out.write_str("if ")?;
+                self.expression(&condition.node)?;
+                self.out.write_str(" then")?;
+                // Each block's comments are the ones before the next clause, as far as spans
+                // tell where it starts.
+                let else_span = else_.as_ref().and_then(|block| block.0.as_ref()?.first());
+                let mut next_spans = elseifs
+                    .iter()
+                    .map(|clause| clause.0.span)
+                    .chain(else_span.map(|statement| statement.span));
+                self.block_before(then, "", next_spans.next().unwrap_or_default())?;
+                for (condition, block) in elseifs {
+                    self.out.write_str("elseif ")?;
+                    self.expression(&condition.node)?;
+                    self.out.write_str(" then")?;
+                    self.block_before(block, "", next_spans.next().unwrap_or_default())?;
+                }
+                if let Some(ref block) = *else_ {
+                    self.out.write_str("else")?;
+                    self.block(block, "")?;
+                }
+                self.out.write_str("end")
+            }
+            Statement::ForStepping { ref name, ref from, ref to, ref step, ref block } => {
+                write!(self.out, "for {} = ", name.node)?;
+                self.expression(&from.node)?;
+                self.out.write_str(", ")?;
+                self.expression(&to.node)?;
+                if let Some(ref step) = *step {
+                    self.out.write_str(", ")?;
+                    self.expression(&step.node)?;
+                }
+                self.out.write_str(" do")?;
+                self.block(block, "end")
+            }
+            Statement::ForIn { ref name_list, ref in_, ref do_ } => {
+                self.out.write_str("for ")?;
+                self.name_list(name_list)?;
+                self.out.write_str(" in ")?;
+                self.expression_list(in_)?;
+                self.out.write_str(" do")?;
+                self.block(do_, "end")
+            }
+            Statement::Function(ref name, ref body) => {
+                write!(self.out, "function {}", name.first_dot_access.node)?;
+                if let Some(ref rest) = name.rest_dot_access {
+                    for key in rest {
+                        write!(self.out, ".{}", key)?;
+                    }
+                }
+                if let Some(ref self_name) = name.self_name {
+                    write!(self.out, ":{}", self_name)?;
+                }
+                self.function_body(body)
+            }
+            Statement::LocalFunction { ref name, ref body } => {
+                write!(self.out, "local function {}", name.node)?;
+                self.function_body(body)
+            }
+            Statement::LocalVariableBinding(ref names, ref expressions) => {
+                self.out.write_str("local ")?;
+                self.attribute_name(&names.0)?;
+                if let Some(ref rest) = names.1 {
+                    for name in rest {
+                        self.out.write_str(", ")?;
+                        self.attribute_name(name)?;
+                    }
+                }
+                if let Some(ref expressions) = *expressions {
+                    self.out.write_str(" = ")?;
+                    self.expression_list(expressions)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn name_list(&mut self, names: &NameList) -> fmt::Result {
+        self.out.write_str(&names.0.node)?;
+        if let Some(ref rest) = names.1 {
+            for name in rest {
+                write!(self.out, ", {}", name.node)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn attribute_name(&mut self, name: &AttributeName) -> fmt::Result {
+        self.out.write_str(&name.name.node)?;
+        match name.attribute {
+            None => Ok(()),
+            Some(Attribute::Const) => self.out.write_str(" <const>"),
+            Some(Attribute::Close) => self.out.write_str(" <close>"),
+        }
+    }
+
+    fn expression_list(&mut self, expressions: &ExpressionList) -> fmt::Result {
+        self.expression(&expressions.0.node)?;
+        if let Some(ref rest) = expressions.1 {
+            for expression in rest {
+                self.out.write_str(", ")?;
+                self.expression(&expression.node)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn function_body(&mut self, body: &FunctionBody) -> fmt::Result {
+        self.out.write_char('(')?;
+        match body.0.as_deref() {
+            None => {}
+            Some(ParameterList::NameList(names)) => self.name_list(names)?,
+            Some(ParameterList::ExtendedArguments(names)) => {
+                self.name_list(names)?;
+                self.out.write_str(", ...")?;
+            }
+            Some(ParameterList::ExtendedArgumentsVoid) => self.out.write_str("...")?,
+        }
+        self.out.write_char(')')?;
+        self.block(&body.1, "end")
+    }
+
+    fn expression(&mut self, expression: &Expression) -> fmt::Result {
+        match *expression {
+            Expression::Nil => self.out.write_str("nil"),
+            Expression::False => self.out.write_str("false"),
+            Expression::True => self.out.write_str("true"),
+            // The decimal literal for the smallest integer would be read as a float, being too big
+            // to be an integer before it's negated, but hexadecimal integers wrap around.
+            Expression::Integer(_, NumberForm::Written(ref text)) |
+            Expression::Float(_, NumberForm::Written(ref text)) => self.out.number(text),
+            Expression::Integer(i64::MIN, _) => self.out.number("0x8000000000000000"),
+            Expression::Integer(n, _) => self.out.number(&n.to_string()),
+            Expression::Float(n, _) => self.float(n),
+            Expression::String(ref s, form) => self.string(s, form),
+            Expression::ExtendedArgumentAccess => self.out.write_str("..."),
+            // The output still has to parse, and the comment keeps the error from passing unseen.
+            Expression::Error => self.out.write_str("nil --[[error]]"),
+            Expression::FunctionDefine(ref define) => {
+                self.out.write_str("function")?;
+                self.function_body(&define.0)
+            }
+            Expression::PrefixExpression(ref prefix) => self.prefix_expression(prefix),
+            Expression::TableConstructor(ref table) => self.table_constructor(table),
+            Expression::BinaryOperation(op, ref lhs, ref rhs) => {
+                // Operands are parenthesized only where the operators would otherwise group
+                // differently, so the output reads back as the same tree. That's where the
+                // operand's operator has a lower precedence, or the same precedence on the side
+                // the operators don't group towards.
+                let parenthesize_lhs = match lhs.node {
+                    Expression::BinaryOperation(inner, ..) => {
+                        precedence(inner) < precedence(op) ||
+                            precedence(inner) == precedence(op) && is_right_associative(op)
+                    }
+                    ref lhs => is_unary(lhs) && UNARY_PRECEDENCE < precedence(op),
+                };
+                self.operand(&lhs.node, parenthesize_lhs)?;
+                write!(self.out, " {} ", binary_sigil(op))?;
+                let parenthesize_rhs = match rhs.node {
+                    Expression::BinaryOperation(inner, ..) => {
+                        precedence(inner) < precedence(op) ||
+                            precedence(inner) == precedence(op) && !is_right_associative(op)
+                    }
+                    _ => false,
+                };
+                self.operand(&rhs.node, parenthesize_rhs)
+            }
+            Expression::UnaryOperation(op, ref operand) => {
+                self.out.write_str(unary_sigil(op))?;
+                // Keep `- -x` from turning into a `--x` comment.
+                let needs_space = op == UnaryOperation::Not || match operand.node {
+                    Expression::UnaryOperation(UnaryOperation::Negate, _) => {
+                        op == UnaryOperation::Negate
+                    }
+                    Expression::Integer(n, _) => op == UnaryOperation::Negate && n < 0,
+                    Expression::Float(n, _) => op == UnaryOperation::Negate && n.is_sign_negative(),
+                    _ => false,
+                };
+                if needs_space {
+                    self.out.write_char(' ')?;
+                }
+                let parenthesize = match operand.node {
+                    Expression::BinaryOperation(inner, ..) => precedence(inner) < UNARY_PRECEDENCE,
+                    _ => false,
+                };
+                self.operand(&operand.node, parenthesize)
+            }
+        }
+    }
+
+    fn operand(&mut self, expression: &Expression, parenthesize: bool) -> fmt::Result {
+        if parenthesize {
+            self.out.write_char('(')?;
+            self.expression(expression)?;
+            self.out.write_char(')')
+        } else {
+            self.expression(expression)
+        }
+    }
+
+    /// Writes a float so that it reads back as a float, keeping the `.0` of whole numbers.
+    fn float(&mut self, n: f64) -> fmt::Result {
+        if n.is_nan() {
+            self.out.write_str("(0 / 0)")
+        } else if n.is_infinite() {
+            // Too big a literal reads as infinity.
+            self.out.number(if n > 0.0 { "1e999" } else { "-1e999" })
+        } else {
+            let written = match self.float_format {
+                // Debug formatting gives the shortest digits that round trip.
+                FloatFormat::Shortest => format!("{:?}", n),
+                FloatFormat::Significant(digits) => {
+                    let mut written = format_general(n, digits);
+                    if written.bytes().all(|b| b == b'-' || b.is_ascii_digit()) {
+                        written.push_str(".0");
+                    }
+                    written
+                }
+            };
+            self.out.number(&written)
+        }
+    }
+
+    /// Writes a string literal. Short strings are in the quotes they were written in, unless the
+    /// quote style says otherwise. Long strings can only hold UTF-8, so one holding other bytes
+    /// is written as a short string in double quotes.
+    fn string(&mut self, s: &[u8], form: StringForm) -> fmt::Result {
+        match (form, str::from_utf8(s)) {
+            (StringForm::Short(quote), _) => {
+                let quote = match (self.quote_style, quote) {
+                    (QuoteStyle::AlwaysDouble, _) | (QuoteStyle::Preserve, Quote::Double) => '"',
+                    (QuoteStyle::AlwaysSingle, _) | (QuoteStyle::Preserve, Quote::Single) => '\'',
+                };
+                self.out.raw(&escape(s, quote))
+            }
+            (StringForm::Long(_), Err(_)) => self.string(s, StringForm::Short(Quote::Double)),
+            (StringForm::Long(level), Ok(s)) => {
+                let equals = "=".repeat(level);
+                // A newline directly after the opening bracket would be dropped when reading the
+                // string back, so one starting with a newline needs an extra one.
+                let newline = if s.starts_with('\n') || s.starts_with('\r') { "\n" } else { "" };
+                self.out.raw(&format!("[{}[{}{}]{}]", equals, newline, s, equals))
+            }
+        }
+    }
+
+    fn prefix_expression(&mut self, prefix: &PrefixExpression) -> fmt::Result {
+        match *prefix {
+            PrefixExpression::Variable(ref variable) => self.variable(variable),
+            PrefixExpression::FunctionCall(ref call) => self.function_call(call),
+            PrefixExpression::Parenthesis(ref expression) => {
+                self.out.write_char('(')?;
+                self.expression(&expression.node)?;
+                self.out.write_char(')')
+            }
+        }
+    }
+
+    fn variable(&mut self, variable: &Variable) -> fmt::Result {
+        match *variable {
+            Variable::Name(ref name) => self.out.write_str(&name.node),
+            Variable::ArrayAccess { ref from, ref key } => {
+                self.prefix_expression(from)?;
+                self.out.write_char('[')?;
+                self.expression(&key.node)?;
+                self.out.write_char(']')
+            }
+            Variable::DotAccess { ref from, ref key } => {
+                self.prefix_expression(from)?;
+                write!(self.out, ".{}", key)
+            }
+        }
+    }
+
+    fn function_call(&mut self, call: &FunctionCall) -> fmt::Result {
+        match *call {
+            FunctionCall::Static(ref from, ref arguments) => {
+                self.prefix_expression(from)?;
+                self.function_arguments(arguments)
+            }
+            FunctionCall::SelfTaking(ref from, ref name, ref arguments) => {
+                self.prefix_expression(from)?;
+                write!(self.out, ":{}", name)?;
+                self.function_arguments(arguments)
+            }
+        }
+    }
+
+    fn function_arguments(&mut self, arguments: &FunctionArguments) -> fmt::Result {
+        match *arguments {
+            FunctionArguments::Parenthesis(Some(ref expressions))
+                if !self.fits(|printer| printer.function_arguments(arguments)) =>
+            {
+                let expressions: Vec<_> = iter::once(&*expressions.0)
+                    .chain(expressions.1.iter().flatten())
+                    .collect();
+                self.broken_list(('(', ')'), &expressions, false, |printer, expression| {
+                    printer.expression(&expression.node)
+                })
+            }
+            FunctionArguments::Parenthesis(ref expressions) => {
+                self.out.write_char('(')?;
+                if let Some(ref expressions) = *expressions {
+                    self.expression_list(expressions)?;
+                }
+                self.out.write_char(')')
+            }
+            FunctionArguments::TableConstructor(ref table) => self.table_constructor(table),
+            FunctionArguments::String(ref s, form) => self.string(s, form),
+        }
+    }
+
+    fn table_constructor(&mut self, table: &TableConstructor) -> fmt::Result {
+        let fields = match table.0 {
+            Some(ref fields) => fields,
+            None => return self.out.write_str("{}"),
+        };
+        if !self.fits(|printer| printer.table_constructor(table)) {
+            let fields: Vec<_> = iter::once(&*fields.0).chain(fields.1.iter().flatten()).collect();
+            // A separator after the last field is allowed in tables, and eases adding another.
+            return self.broken_list(('{', '}'), &fields, true, Printer::field);
+        }
+        self.out.write_char('{')?;
+        self.field(&fields.0)?;
+        if let Some(ref rest) = fields.1 {
+            for field in rest {
+                self.out.write_str(", ")?;
+                self.field(field)?;
+            }
+        }
+        self.out.write_char('}')
+    }
+
+    fn field(&mut self, field: &Field) -> fmt::Result {
+        match *field {
+            Field::ExpressionForName { ref name, ref equals } => {
+                self.out.write_char('[')?;
+                self.expression(&name.node)?;
+                self.out.write_str("] = ")?;
+                self.expression(&equals.node)
+            }
+            Field::Equals { ref name, ref equals } => {
+                write!(self.out, "{} = ", name)?;
+                self.expression(&equals.node)
+            }
+            Field::ArrayStyle(ref expression) => self.expression(&expression.node),
+        }
+    }
+}
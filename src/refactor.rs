@@ -0,0 +1,368 @@
+//! Refactorings, rewriting part of a chunk without changing what it does.
+//!
+//! Refactorings pick the part to rewrite by its span, such as the selection in an editor, so the
+//! chunk should have been parsed rather than built by hand. Renaming locals is done by
+//! `resolve::rename_local`.
+
+use std::error::Error;
+use std::fmt;
+use std::{iter, mem};
+
+use ast::*;
+use build::{expr, stmt, BlockBuilder};
+use resolve::resolve_names;
+use span::{Span, Spanned};
+use validate::{is_identifier, validate_gotos, GotoError};
+use visit::{self, VisitorMut};
+
+/// A reason a refactoring was refused.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RefactorError {
+    /// No expression in the chunk has the span.
+    NoExpression { span: Span },
+    /// The new name isn't an identifier, such as an empty one or a keyword.
+    InvalidName { name: String },
+    /// The expression is on the right of an `and` or `or`, so it isn't always evaluated.
+    Conditional { span: Span },
+    /// The expression is a call or `...` ending a list, where all of its values are used. A local
+    /// would only hold the first.
+    MultipleValues { span: Span },
+    /// The expression is the condition of a `repeat` loop whose block always returns, so it's
+    /// never evaluated.
+    Unreachable { span: Span },
+    /// The name used at the span would refer to something else after the rewrite, as the new
+    /// local would shadow its binding.
+    Collision { span: Span },
+    /// The `goto` at the span would jump into the scope of the new local.
+    JumpIntoScope { span: Span },
+}
+
+impl fmt::Display for RefactorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RefactorError::NoExpression { span } => {
+                write!(f, "no expression spans bytes {} to {}", span.start, span.end)
+            }
+            RefactorError::InvalidName { ref name } => write!(f, "{:?} is not a valid name", name),
+            RefactorError::Conditional { span } => {
+                write!(f, "the expression at byte {} is only evaluated sometimes", span.start)
+            }
+            RefactorError::MultipleValues { span } => write!(
+                f,
+                "the expression at byte {} gives all of its values, not just one",
+                span.start,
+            ),
+            RefactorError::Unreachable { span } => {
+                write!(f, "the expression at byte {} is never evaluated", span.start)
+            }
+            RefactorError::Collision { span } => write!(
+                f,
+                "the rewrite would change what the name at byte {} refers to",
+                span.start,
+            ),
+            RefactorError::JumpIntoScope { span } => write!(
+                f,
+                "the goto at byte {} would jump into the scope of the new local",
+                span.start,
+            ),
+        }
+    }
+}
+
+impl Error for RefactorError {}
+
+/// Moves the expression at `span` into a new local called `name`, declared just before the
+/// innermost statement evaluating it, and reads the local in its place. Returns the chunk as it
+/// was before, so the rewrite can be undone.
+///
+/// The expression is still evaluated exactly once each time it was before, though earlier than
+/// the rest of its statement. For that, a `while` condition is moved to the start of the loop's
+/// block, which breaks out when it fails, a `repeat` condition gets its local at the end of the
+/// loop's block, and an `elseif` condition gets it in an `else` holding the rest of the chain.
+/// Expressions that aren't always evaluated, or whose other values would be lost, are refused,
+/// as are rewrites that change what some name refers to.
+///
+/// # Example
+/// ```
+/// use buildlua::parser::parse;
+/// use buildlua::refactor::extract_local;
+/// use buildlua::span::Span;
+///
+/// let mut chunk = parse("print(x * 2 + 1)").unwrap();
+/// extract_local(&mut chunk, Span::new(6, 11), "double").unwrap();
+/// assert_eq!(chunk.to_string(), "local double = x * 2\nprint(double + 1)\n");
+/// ```
+pub fn extract_local(chunk: &mut Chunk, span: Span, name: &str) -> Result<Chunk, RefactorError> {
+    if !is_identifier(name) {
+        return Err(RefactorError::InvalidName { name: name.to_string() });
+    }
+    let mut extracted = chunk.clone();
+    let mut extractor = Extractor { span, name, result: None };
+    visit::walk_chunk_mut(&mut extractor, &mut extracted);
+    extractor.result.unwrap_or(Err(RefactorError::NoExpression { span }))?;
+    let before = resolve_names(chunk);
+    let after = resolve_names(&extracted);
+    let mut changed: Vec<Span> = before
+        .bindings
+        .iter()
+        .filter(|&(span, binding)| after.bindings.get(span) != Some(binding))
+        .map(|(&span, _)| span)
+        .collect();
+    changed.sort_by_key(|span| span.start);
+    if let Some(&span) = changed.first() {
+        return Err(RefactorError::Collision { span });
+    }
+    if validate_gotos(chunk).is_ok() {
+        if let Err(errors) = validate_gotos(&extracted) {
+            let span = match errors[0] {
+                GotoError::UndefinedLabel { span, .. } |
+                GotoError::JumpIntoLocalScope { span, .. } => span,
+                GotoError::DuplicateLabel { second_span, .. } => second_span,
+            };
+            return Err(RefactorError::JumpIntoScope { span });
+        }
+    }
+    Ok(mem::replace(chunk, extracted))
+}
+
+/// Whether `outer` covers all of `inner`.
+fn contains(outer: Span, inner: Span) -> bool {
+    outer.start <= inner.start && inner.end <= outer.end
+}
+
+struct Extractor<'a> {
+    span: Span,
+    name: &'a str,
+    /// Set once the expression has been found, with whether it could be extracted.
+    result: Option<Result<(), RefactorError>>,
+}
+
+impl<'a> Extractor<'a> {
+    /// `local name = expression`, with an empty span at the end of the extracted expression
+    /// declaring the name so that it's told apart from the names already in the chunk.
+    fn local(&self, expression: Spanned<Expression>, span: Span) -> Spanned<Statement> {
+        let at_end = Span::new(self.span.end, self.span.end);
+        let declaration = Spanned::new(self.name.to_string(), at_end);
+        let names = AttributeNameList(AttributeName { name: declaration, attribute: None }, None);
+        let expressions = ExpressionList(Box::new(expression), None);
+        Spanned::new(Statement::LocalVariableBinding(names, Some(expressions)), span)
+    }
+
+    /// Takes the expression out of whatever `walk` walks, outside of any blocks in it.
+    fn find<F>(&self, walk: F) -> Option<Result<Spanned<Expression>, RefactorError>>
+    where
+        F: FnOnce(&mut Taker),
+    {
+        let mut taker = Taker {
+            span: self.span,
+            name: self.name,
+            conditional: false,
+            last: false,
+            found: None,
+        };
+        walk(&mut taker);
+        taker.found
+    }
+
+    fn take<F>(&self, walk: F) -> Result<Spanned<Expression>, RefactorError>
+    where
+        F: FnOnce(&mut Taker),
+    {
+        self.find(walk).unwrap_or(Err(RefactorError::NoExpression { span: self.span }))
+    }
+
+    /// Extracts the expression from the statement at `index`, which evaluates it outside of its
+    /// blocks.
+    fn extract(
+        &self,
+        statements: &mut Vec<Spanned<Statement>>,
+        index: usize,
+    ) -> Result<(), RefactorError> {
+        let start = statements[index].span.start;
+        let before = match statements[index].node {
+            Statement::While { ref mut exp, ref mut do_ } => {
+                let expression = self.take(|taker| taker.visit_expression_mut(exp))?;
+                let mut condition = mem::replace(&mut **exp, expr::boolean(true));
+                if let Expression::BinaryOperation(..) = condition.node {
+                    let span = condition.span;
+                    let parenthesized = PrefixExpression::Parenthesis(Box::new(condition));
+                    let parenthesized = Expression::PrefixExpression(Box::new(parenthesized));
+                    condition = Spanned::new(parenthesized, span);
+                }
+                let stop = stmt::if_(
+                    expr::unop(UnaryOperation::Not, condition),
+                    BlockBuilder::new().push(stmt::break_()).build(),
+                    None,
+                );
+                let mut body = vec![self.local(expression, Span::dummy()), stop];
+                body.extend(do_.0.take().into_iter().flatten());
+                do_.0 = Some(body);
+                None
+            }
+            Statement::Repeat { ref mut block, ref mut until } => {
+                let expression = self.take(|taker| taker.visit_expression_mut(until))?;
+                if block.1.is_some() {
+                    return Err(RefactorError::Unreachable { span: self.span });
+                }
+                // The condition can see the block's locals, so the new one can go at its end.
+                block.0.get_or_insert_with(Vec::new).push(self.local(expression, Span::dummy()));
+                None
+            }
+            Statement::If { ref mut condition, ref mut elseifs, ref mut else_, .. } => {
+                match self.find(|taker| taker.visit_expression_mut(condition)) {
+                    Some(found) => Some(found?),
+                    None => {
+                        self.extract_from_elseifs(elseifs, else_)?;
+                        None
+                    }
+                }
+            }
+            _ => Some(self.take(|taker| visit::walk_statement_mut(taker, &mut statements[index]))?),
+        };
+        if let Some(expression) = before {
+            statements.insert(index, self.local(expression, Span::new(start, start)));
+        }
+        Ok(())
+    }
+
+    fn extract_from_elseifs(
+        &self,
+        elseifs: &mut Vec<(Box<Spanned<Expression>>, Box<Block>)>,
+        else_: &mut Option<Box<Block>>,
+    ) -> Result<(), RefactorError> {
+        for index in 0..elseifs.len() {
+            let found = self.find(|taker| taker.visit_expression_mut(&mut elseifs[index].0));
+            if let Some(found) = found {
+                // The condition is only evaluated once the ones before it fail, so the local goes
+                // in an `else` holding the rest of the chain.
+                let expression = found?;
+                let mut rest = elseifs.split_off(index).into_iter();
+                let (condition, then) = rest.next().expect("the elseif was just found");
+                let chain = Spanned::dummy(Statement::If {
+                    condition,
+                    then,
+                    elseifs: rest.collect(),
+                    else_: else_.take(),
+                });
+                let local = self.local(expression, Span::dummy());
+                *else_ = Some(Box::new(Block::from_statements(vec![local, chain])));
+                return Ok(());
+            }
+        }
+        Err(RefactorError::NoExpression { span: self.span })
+    }
+}
+
+impl<'a> VisitorMut for Extractor<'a> {
+    fn visit_block_mut(&mut self, block: &mut Block) {
+        // Blocks nested in a statement are searched before the statement itself, so the local
+        // goes before the innermost statement evaluating the expression.
+        let count = block.0.as_ref().map_or(0, Vec::len);
+        for index in 0..count {
+            if self.result.is_some() {
+                return;
+            }
+            let statements = block.0.as_mut().expect("the block has statements");
+            visit::walk_statement_mut(self, &mut statements[index]);
+            if self.result.is_none() && contains(statements[index].span, self.span) {
+                self.result = Some(self.extract(statements, index));
+            }
+        }
+        if self.result.is_some() {
+            return;
+        }
+        if let Some(ref mut return_statement) = block.1 {
+            visit::walk_return_statement_mut(self, return_statement);
+            if self.result.is_none() {
+                let found = self.find(|taker| taker.visit_return_statement_mut(return_statement));
+                let result = found.map(|found| {
+                    let local = self.local(found?, Span::dummy());
+                    block.0.get_or_insert_with(Vec::new).push(local);
+                    Ok(())
+                });
+                self.result = result;
+            }
+        }
+    }
+}
+
+/// Replaces the expression with a read of the new local, skipping blocks as those have been
+/// searched already.
+struct Taker<'a> {
+    span: Span,
+    name: &'a str,
+    /// Whether the current expression is on the right of an `and` or `or`.
+    conditional: bool,
+    /// Whether the current expression ends a list, where a call or `...` gives all its values.
+    last: bool,
+    found: Option<Result<Spanned<Expression>, RefactorError>>,
+}
+
+impl<'a> Taker<'a> {
+    fn replace(&mut self, expression: &mut Spanned<Expression>, last: bool) {
+        let multiple = match expression.node {
+            Expression::ExtendedArgumentAccess => true,
+            Expression::PrefixExpression(ref prefix) => {
+                matches!(**prefix, PrefixExpression::FunctionCall(_))
+            }
+            _ => false,
+        };
+        self.found = Some(if self.conditional {
+            Err(RefactorError::Conditional { span: self.span })
+        } else if last && multiple {
+            Err(RefactorError::MultipleValues { span: self.span })
+        } else {
+            // Like the declaration, the read gets an empty span of its own.
+            let start = Span::new(self.span.start, self.span.start);
+            let name = Spanned::new(self.name.to_string(), start);
+            let mut read = expr::var(Variable::Name(name));
+            read.span = self.span;
+            Ok(mem::replace(expression, read))
+        });
+    }
+}
+
+impl<'a> VisitorMut for Taker<'a> {
+    fn visit_block_mut(&mut self, _: &mut Block) {}
+
+    fn visit_expression_mut(&mut self, expression: &mut Spanned<Expression>) {
+        let last = mem::replace(&mut self.last, false);
+        if self.found.is_some() {
+            return;
+        }
+        if expression.span == self.span {
+            return self.replace(expression, last);
+        }
+        match expression.node {
+            Expression::BinaryOperation(op, ref mut lhs, ref mut rhs)
+                if op == BinaryOperation::And || op == BinaryOperation::Or =>
+            {
+                self.visit_expression_mut(lhs);
+                let conditional = mem::replace(&mut self.conditional, true);
+                self.visit_expression_mut(rhs);
+                self.conditional = conditional;
+            }
+            _ => visit::walk_expression_mut(self, expression),
+        }
+    }
+
+    fn visit_expression_list_mut(&mut self, expressions: &mut ExpressionList) {
+        let count = 1 + expressions.1.as_ref().map_or(0, Vec::len);
+        let all = iter::once(&mut *expressions.0).chain(expressions.1.iter_mut().flatten());
+        for (index, expression) in all.enumerate() {
+            self.last = index + 1 == count;
+            self.visit_expression_mut(expression);
+        }
+    }
+
+    fn visit_table_constructor_mut(&mut self, table: &mut TableConstructor) {
+        if let Some(ref mut fields) = table.0 {
+            let count = 1 + fields.1.as_ref().map_or(0, Vec::len);
+            let all = iter::once(&mut *fields.0).chain(fields.1.iter_mut().flatten());
+            for (index, field) in all.enumerate() {
+                self.last = index + 1 == count && matches!(*field, Field::ArrayStyle(_));
+                self.visit_field_mut(field);
+            }
+        }
+    }
+}
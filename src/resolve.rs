@@ -0,0 +1,379 @@
+//! Name resolution, working out what each use of a name refers to.
+//!
+//! The scoping rules are in the lua manual at http://www.lua.org/manual/5.2/manual.html#3.5. A
+//! local is visible from the statement after its declaration to the end of the innermost block
+//! declaring it, and the condition of a `repeat` loop can see the locals of the loop's block.
+//!
+//! `rename_local` builds on the resolution to rename a local along with its uses, checking that
+//! every name still refers to what it did before.
+
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+
+use ast::*;
+use span::{Span, Spanned};
+use validate::is_identifier;
+use visit::{self, Visitor, VisitorMut};
+
+/// What a name refers to. Locals and upvalues hold the span of the name declaring them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Binding {
+    /// A local of the function the name is used in.
+    Local(Span),
+    /// A local of an enclosing function.
+    Upvalue(Span),
+    /// A name without a local declaration, referring to a field of the global environment.
+    Global,
+}
+
+/// The bindings of every use of a name in a chunk, keyed by the span of the use.
+///
+/// Nodes are told apart by their spans, so the chunk should have been parsed rather than built by
+/// hand.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Resolution {
+    pub bindings: HashMap<Span, Binding>,
+}
+
+impl Resolution {
+    /// The binding of the name used at `span`, if a name is used there.
+    pub fn binding(&self, span: Span) -> Option<Binding> {
+        self.bindings.get(&span).cloned()
+    }
+}
+
+/// Resolves every variable name read or assigned in the chunk.
+pub fn resolve_names(chunk: &Chunk) -> Resolution {
+    // The chunk is itself the body of a function.
+    let mut resolver = Resolver {
+        functions: vec![Vec::new()],
+        method: None,
+        resolution: Resolution::default(),
+    };
+    visit::walk_chunk(&mut resolver, chunk);
+    resolver.resolution
+}
+
+/// The locals declared by a block, as names and the spans declaring them.
+type Scope = Vec<(String, Span)>;
+
+struct Resolver {
+    /// The scopes of each function enclosing the current position, innermost last.
+    functions: Vec<Vec<Scope>>,
+    /// Set when the next function body is a method, holding the span declaring its `self`.
+    method: Option<Span>,
+    resolution: Resolution,
+}
+
+impl Resolver {
+    fn declare(&mut self, name: &Spanned<String>) {
+        let scopes = self.functions.last_mut().expect("resolving outside of any function");
+        let scope = scopes.last_mut().expect("declaring outside of any block");
+        scope.push((name.node.clone(), name.span));
+    }
+
+    fn declare_all(&mut self, names: &NameList) {
+        self.declare(&names.0);
+        if let Some(ref rest) = names.1 {
+            for name in rest {
+                self.declare(name);
+            }
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Binding {
+        for (depth, scopes) in self.functions.iter().rev().enumerate() {
+            let declared = scopes
+                .iter()
+                .rev()
+                .flat_map(|scope| scope.iter().rev())
+                .find(|(declared, _)| declared == name);
+            if let Some(&(_, span)) = declared {
+                return if depth == 0 { Binding::Local(span) } else { Binding::Upvalue(span) };
+            }
+        }
+        Binding::Global
+    }
+
+    fn use_name(&mut self, name: &Spanned<String>) {
+        let binding = self.lookup(&name.node);
+        self.resolution.bindings.insert(name.span, binding);
+    }
+
+    fn push_scope(&mut self) {
+        self.functions.last_mut().unwrap().push(Vec::new());
+    }
+
+    fn pop_scope(&mut self) {
+        self.functions.last_mut().unwrap().pop();
+    }
+}
+
+impl Visitor for Resolver {
+    fn visit_block(&mut self, block: &Block) {
+        self.push_scope();
+        visit::walk_block(self, block);
+        self.pop_scope();
+    }
+
+    fn visit_statement(&mut self, statement: &Spanned<Statement>) {
+        match statement.node {
+            Statement::LocalVariableBinding(ref names, ref expressions) => {
+                // The new locals aren't visible in their own initializers.
+                if let Some(ref expressions) = *expressions {
+                    self.visit_expression_list(expressions);
+                }
+                self.declare(&names.0.name);
+                if let Some(ref rest) = names.1 {
+                    for name in rest {
+                        self.declare(&name.name);
+                    }
+                }
+            }
+            Statement::LocalFunction { ref name, ref body } => {
+                // Unlike other locals, a local function can see itself, so it can recurse.
+                self.declare(name);
+                self.visit_function_body(body);
+            }
+            Statement::ForStepping { ref name, ref from, ref to, ref step, ref block } => {
+                self.visit_expression(from);
+                self.visit_expression(to);
+                if let Some(ref step) = *step {
+                    self.visit_expression(step);
+                }
+                self.push_scope();
+                self.declare(name);
+                self.visit_block(block);
+                self.pop_scope();
+            }
+            Statement::ForIn { ref name_list, ref in_, ref do_ } => {
+                self.visit_expression_list(in_);
+                self.push_scope();
+                self.declare_all(name_list);
+                self.visit_block(do_);
+                self.pop_scope();
+            }
+            Statement::Repeat { ref block, ref until } => {
+                // The block's scope stays open for the condition.
+                self.push_scope();
+                visit::walk_block(self, block);
+                self.visit_expression(until);
+                self.pop_scope();
+            }
+            Statement::Function(ref name, ref body) => {
+                self.use_name(&name.first_dot_access);
+                if name.self_name.is_some() {
+                    self.method = Some(statement.span);
+                }
+                self.visit_function_body(body);
+            }
+            _ => visit::walk_statement(self, statement),
+        }
+    }
+
+    fn visit_variable(&mut self, variable: &Variable) {
+        match *variable {
+            Variable::Name(ref name) => self.use_name(name),
+            _ => visit::walk_variable(self, variable),
+        }
+    }
+
+    fn visit_function_body(&mut self, body: &FunctionBody) {
+        self.functions.push(vec![Vec::new()]);
+        if let Some(span) = self.method.take() {
+            self.declare(&Spanned::new("self".to_string(), span));
+        }
+        match body.0.as_deref() {
+            Some(ParameterList::NameList(names)) |
+            Some(ParameterList::ExtendedArguments(names)) => self.declare_all(names),
+            Some(ParameterList::ExtendedArgumentsVoid) | None => {}
+        }
+        self.visit_block(&body.1);
+        self.functions.pop();
+    }
+}
+
+/// Every use of a name in the chunk, in source order, along with the span of the use.
+///
+/// Uses are variables read or assigned by name, including the table a dotted access starts from,
+/// and the first part of the name of a `function` statement. Declarations of locals and
+/// parameters aren't uses.
+pub fn collect_names(chunk: &Chunk) -> Vec<(String, Span)> {
+    let mut collector = NameCollector(Vec::new());
+    visit::walk_chunk(&mut collector, chunk);
+    collector.0
+}
+
+struct NameCollector(Vec<(String, Span)>);
+
+impl Visitor for NameCollector {
+    fn visit_statement(&mut self, statement: &Spanned<Statement>) {
+        if let Statement::Function(ref name, _) = statement.node {
+            self.0.push((name.first_dot_access.node.clone(), name.first_dot_access.span));
+        }
+        visit::walk_statement(self, statement);
+    }
+
+    fn visit_variable(&mut self, variable: &Variable) {
+        if let Variable::Name(ref name) = *variable {
+            self.0.push((name.node.clone(), name.span));
+        }
+        visit::walk_variable(self, variable);
+    }
+}
+
+/// Identifies a local by the span of the name declaring it, as held by `Binding::Local` and
+/// `Binding::Upvalue`.
+pub type BindingId = Span;
+
+/// A reason `rename_local` refused to rename a local.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RenameError {
+    /// No local is declared by a name at the span. The `self` of a method is declared without a
+    /// name, so it can't be renamed either.
+    NotALocal { span: Span },
+    /// The new name isn't an identifier, such as an empty one or a keyword.
+    InvalidName { name: String },
+    /// The name used at the span would refer to something else after the rename, as the renamed
+    /// local would shadow its binding or be shadowed by another local.
+    Collision { span: Span },
+}
+
+impl fmt::Display for RenameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RenameError::NotALocal { span } => {
+                write!(f, "no local is declared at byte {}", span.start)
+            }
+            RenameError::InvalidName { ref name } => write!(f, "{:?} is not a valid name", name),
+            RenameError::Collision { span } => write!(
+                f,
+                "renaming would change what the name at byte {} refers to",
+                span.start,
+            ),
+        }
+    }
+}
+
+impl Error for RenameError {}
+
+/// Renames the local declared at `binding` to `new_name`, along with every use of it, leaving
+/// other locals and globals of either name alone. The chunk is only changed if every use of a
+/// name in it still refers to the same binding afterwards.
+///
+/// # Example
+/// ```
+/// use buildlua::parser::parse;
+/// use buildlua::resolve::rename_local;
+/// use buildlua::span::Span;
+///
+/// let mut chunk = parse("local x = 1 do local x = 2 end return x").unwrap();
+/// rename_local(&mut chunk, Span::new(6, 7), "count").unwrap();
+/// assert_eq!(chunk.to_string(), "local count = 1\ndo\n    local x = 2\nend\nreturn count\n");
+/// ```
+pub fn rename_local(
+    chunk: &mut Chunk,
+    binding: BindingId,
+    new_name: &str,
+) -> Result<(), RenameError> {
+    if !is_identifier(new_name) {
+        return Err(RenameError::InvalidName { name: new_name.to_string() });
+    }
+    let before = resolve_names(chunk);
+    let uses: HashSet<Span> = before
+        .bindings
+        .iter()
+        .filter(|&(_, &bound)| {
+            bound == Binding::Local(binding) || bound == Binding::Upvalue(binding)
+        })
+        .map(|(&span, _)| span)
+        .collect();
+    let mut renamed = chunk.clone();
+    let mut renamer = Renamer { declaration: binding, uses, new_name, declared: false };
+    visit::walk_chunk_mut(&mut renamer, &mut renamed);
+    if !renamer.declared {
+        return Err(RenameError::NotALocal { span: binding });
+    }
+    let after = resolve_names(&renamed);
+    let mut changed: Vec<Span> = before
+        .bindings
+        .iter()
+        .filter(|&(span, bound)| after.bindings.get(span) != Some(bound))
+        .map(|(&span, _)| span)
+        .collect();
+    changed.sort_by_key(|span| span.start);
+    match changed.first() {
+        Some(&span) => Err(RenameError::Collision { span }),
+        None => {
+            *chunk = renamed;
+            Ok(())
+        }
+    }
+}
+
+struct Renamer<'a> {
+    declaration: Span,
+    /// The spans of every use of the local.
+    uses: HashSet<Span>,
+    new_name: &'a str,
+    /// Set once the name declaring the local has been renamed.
+    declared: bool,
+}
+
+impl<'a> Renamer<'a> {
+    fn declaration(&mut self, name: &mut Spanned<String>) {
+        if name.span == self.declaration {
+            self.declared = true;
+            name.node = self.new_name.to_string();
+        }
+    }
+
+    fn declarations(&mut self, names: &mut NameList) {
+        self.declaration(&mut names.0);
+        for name in names.1.iter_mut().flatten() {
+            self.declaration(name);
+        }
+    }
+
+    fn use_(&mut self, name: &mut Spanned<String>) {
+        if self.uses.contains(&name.span) {
+            name.node = self.new_name.to_string();
+        }
+    }
+}
+
+impl<'a> VisitorMut for Renamer<'a> {
+    fn visit_statement_mut(&mut self, statement: &mut Spanned<Statement>) {
+        match statement.node {
+            Statement::LocalVariableBinding(ref mut names, _) => {
+                self.declaration(&mut names.0.name);
+                for name in names.1.iter_mut().flatten() {
+                    self.declaration(&mut name.name);
+                }
+            }
+            Statement::LocalFunction { ref mut name, .. } |
+            Statement::ForStepping { ref mut name, .. } => self.declaration(name),
+            Statement::ForIn { ref mut name_list, .. } => self.declarations(name_list),
+            Statement::Function(ref mut name, _) => self.use_(&mut name.first_dot_access),
+            _ => {}
+        }
+        visit::walk_statement_mut(self, statement);
+    }
+
+    fn visit_variable_mut(&mut self, variable: &mut Variable) {
+        if let Variable::Name(ref mut name) = *variable {
+            self.use_(name);
+        }
+        visit::walk_variable_mut(self, variable);
+    }
+
+    fn visit_function_body_mut(&mut self, body: &mut FunctionBody) {
+        match body.0.as_deref_mut() {
+            Some(ParameterList::NameList(names)) |
+            Some(ParameterList::ExtendedArguments(names)) => self.declarations(names),
+            Some(ParameterList::ExtendedArgumentsVoid) | None => {}
+        }
+        visit::walk_function_body_mut(self, body);
+    }
+}
@@ -0,0 +1,175 @@
+//! Locations of syntax in the source code.
+
+use core::fmt;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// A range of bytes in the source code, from `start` up to but not including `end`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Span {
+        Span { start, end }
+    }
+
+    /// The span given to nodes that weren't parsed from source, such as ones built by hand.
+    pub fn dummy() -> Span {
+        Span { start: 0, end: 0 }
+    }
+
+    pub fn is_dummy(&self) -> bool {
+        *self == Span::dummy()
+    }
+
+    /// The smallest span covering both `self` and `other`.
+    pub fn to(self, other: Span) -> Span {
+        Span { start: self.start.min(other.start), end: self.end.max(other.end) }
+    }
+}
+
+/// A node along with the span of source code it was parsed from.
+///
+/// Spans are ignored when comparing nodes, so a parsed tree is equal to the same tree built by
+/// hand.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Spanned<T> {
+    pub node: T,
+    pub span: Span,
+}
+
+impl<T> Spanned<T> {
+    pub fn new(node: T, span: Span) -> Spanned<T> {
+        Spanned { node, span }
+    }
+
+    /// Wraps a node that wasn't parsed from source, giving it a dummy span.
+    pub fn dummy(node: T) -> Spanned<T> {
+        Spanned { node, span: Span::dummy() }
+    }
+}
+
+impl<T> From<T> for Spanned<T> {
+    fn from(node: T) -> Spanned<T> {
+        Spanned::dummy(node)
+    }
+}
+
+impl<T: PartialEq> PartialEq for Spanned<T> {
+    fn eq(&self, other: &Spanned<T>) -> bool {
+        self.node == other.node
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Spanned<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.node.fmt(f)?;
+        write!(f, " @ {}..{}", self.span.start, self.span.end)
+    }
+}
+
+/// The starts of the lines of a source string, for turning byte offsets into lines and columns.
+///
+/// Lines are ended by `\n`, `\r\n` or a lone `\r`, so files written on any system are counted
+/// alike, and the line break is left out of the line's text.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone)]
+pub struct LineMap<'a> {
+    source: &'a str,
+    starts: Vec<usize>,
+}
+
+#[cfg(feature = "std")]
+impl<'a> LineMap<'a> {
+    pub fn new(source: &'a str) -> LineMap<'a> {
+        let bytes = source.as_bytes();
+        let mut starts = vec![0];
+        for (i, &byte) in bytes.iter().enumerate() {
+            // The `\r` of a `\r\n` is left for the `\n` to end the line.
+            if byte == b'\n' || (byte == b'\r' && bytes.get(i + 1) != Some(&b'\n')) {
+                starts.push(i + 1);
+            }
+        }
+        LineMap { source, starts }
+    }
+
+    /// The line and column the span starts at, both counted from 1. Columns count characters
+    /// rather than bytes, so a character written as several bytes of UTF-8 is one column.
+    pub fn location(&self, span: Span) -> (usize, usize) {
+        let offset = span.start.min(self.source.len());
+        let line = match self.starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(next) => next - 1,
+        };
+        let before = &self.source.as_bytes()[self.starts[line]..offset];
+        // Every byte of UTF-8 but the continuation bytes starts a character.
+        let column = before.iter().filter(|&&byte| byte & 0xC0 != 0x80).count();
+        (line + 1, column + 1)
+    }
+
+    /// The text of the line, counted from 1, without its line break.
+    pub fn line(&self, line: usize) -> &'a str {
+        let start = self.starts[line - 1];
+        let end = self.starts.get(line).map_or(self.source.len(), |&next| next);
+        let text = &self.source[start..end];
+        let text = text.strip_suffix('\n').unwrap_or(text);
+        text.strip_suffix('\r').unwrap_or(text)
+    }
+}
+
+/// An error found at some place in the source code.
+#[cfg(feature = "std")]
+pub trait SourceError {
+    /// Where the error was found, or `None` if it was found at the end of the source.
+    fn span(&self) -> Option<Span>;
+
+    /// Writes what went wrong, without saying where.
+    fn write_message(&self, f: &mut fmt::Formatter) -> fmt::Result;
+
+    /// Shows the error by the line and column it was found at in `source`, followed by that line
+    /// of the source with the error marked beneath it.
+    ///
+    /// # Example
+    /// ```text
+    /// 1:11: unexpected '=', expected expression
+    /// local x = = 1
+    ///           ^
+    /// ```
+    fn in_source<'a>(&'a self, source: &'a str) -> InSource<'a, Self>
+    where
+        Self: Sized,
+    {
+        InSource { error: self, lines: LineMap::new(source) }
+    }
+}
+
+/// An error shown along with the source it was found in, made by `SourceError::in_source`.
+#[cfg(feature = "std")]
+pub struct InSource<'a, E: 'a> {
+    error: &'a E,
+    lines: LineMap<'a>,
+}
+
+#[cfg(feature = "std")]
+impl<'a, E: SourceError> fmt::Display for InSource<'a, E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let end = self.lines.source.len();
+        let span = self.error.span().unwrap_or(Span::new(end, end));
+        let (line, column) = self.lines.location(span);
+        write!(f, "{}:{}: ", line, column)?;
+        self.error.write_message(f)?;
+        let text = self.lines.line(line);
+        write!(f, "\n{}\n", text)?;
+        // Tabs are kept so the marker lines up however wide they're shown.
+        for c in text.chars().take(column - 1) {
+            f.write_str(if c == '\t' { "\t" } else { " " })?;
+        }
+        f.write_str("^")
+    }
+}
@@ -0,0 +1,118 @@
+//! Counts of the nodes in a syntax tree, for profiling generated code.
+//!
+//! A node is anything a `Visitor` visits, apart from the chunk's own block, so an empty chunk has
+//! no nodes at all. Return statements count as statements, and function bodies are counted once,
+//! whether they come from a function statement, a local function or a function expression.
+
+use ast::*;
+use span::Spanned;
+use visit::{self, Visitor};
+
+/// The sizes of a chunk, as counted by `stats`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// Statements, including return statements and those in nested blocks and functions.
+    pub statements: usize,
+    pub expressions: usize,
+    /// Function bodies.
+    pub functions: usize,
+    /// The most expressions nested in each other, so `1 + 2` has a depth of 2 and a chunk without
+    /// expressions a depth of 0. Expressions in a function body are nested in the function
+    /// expression, if there is one.
+    pub max_depth: usize,
+    /// Every node, including the ones counted above.
+    pub total_nodes: usize,
+}
+
+/// Counts the nodes of the chunk.
+///
+/// # Example
+/// ```
+/// use buildlua::parser::parse;
+/// use buildlua::stats::stats;
+///
+/// let stats = stats(&parse("local x = 1 + 2 return x").unwrap());
+/// assert_eq!(stats.statements, 2);
+/// assert_eq!(stats.expressions, 4);
+/// assert_eq!(stats.max_depth, 2);
+/// ```
+pub fn stats(chunk: &Chunk) -> Stats {
+    let mut counter = Counter { stats: Stats::default(), depth: 0 };
+    visit::walk_block(&mut counter, &chunk.0);
+    counter.stats
+}
+
+struct Counter {
+    stats: Stats,
+    /// How many expressions the node being visited is in.
+    depth: usize,
+}
+
+impl Visitor for Counter {
+    fn visit_block(&mut self, block: &Block) {
+        self.stats.total_nodes += 1;
+        visit::walk_block(self, block);
+    }
+
+    fn visit_statement(&mut self, statement: &Spanned<Statement>) {
+        self.stats.statements += 1;
+        self.stats.total_nodes += 1;
+        visit::walk_statement(self, statement);
+    }
+
+    fn visit_return_statement(&mut self, return_statement: &ReturnStatement) {
+        self.stats.statements += 1;
+        self.stats.total_nodes += 1;
+        visit::walk_return_statement(self, return_statement);
+    }
+
+    fn visit_expression(&mut self, expression: &Spanned<Expression>) {
+        self.stats.expressions += 1;
+        self.stats.total_nodes += 1;
+        self.depth += 1;
+        self.stats.max_depth = self.stats.max_depth.max(self.depth);
+        visit::walk_expression(self, expression);
+        self.depth -= 1;
+    }
+
+    fn visit_expression_list(&mut self, expressions: &ExpressionList) {
+        self.stats.total_nodes += 1;
+        visit::walk_expression_list(self, expressions);
+    }
+
+    fn visit_prefix_expression(&mut self, prefix: &PrefixExpression) {
+        self.stats.total_nodes += 1;
+        visit::walk_prefix_expression(self, prefix);
+    }
+
+    fn visit_variable(&mut self, variable: &Variable) {
+        self.stats.total_nodes += 1;
+        visit::walk_variable(self, variable);
+    }
+
+    fn visit_function_call(&mut self, call: &FunctionCall) {
+        self.stats.total_nodes += 1;
+        visit::walk_function_call(self, call);
+    }
+
+    fn visit_function_arguments(&mut self, arguments: &FunctionArguments) {
+        self.stats.total_nodes += 1;
+        visit::walk_function_arguments(self, arguments);
+    }
+
+    fn visit_function_body(&mut self, body: &FunctionBody) {
+        self.stats.functions += 1;
+        self.stats.total_nodes += 1;
+        visit::walk_function_body(self, body);
+    }
+
+    fn visit_table_constructor(&mut self, table: &TableConstructor) {
+        self.stats.total_nodes += 1;
+        visit::walk_table_constructor(self, table);
+    }
+
+    fn visit_field(&mut self, field: &Field) {
+        self.stats.total_nodes += 1;
+        visit::walk_field(self, field);
+    }
+}
@@ -0,0 +1,740 @@
+//! A standard library for the interpreter, installing native functions as its globals.
+//!
+//! The functions follow the lua manual at http://www.lua.org/manual/5.2/manual.html#6, for the
+//! parts of the basic, string, math and table libraries that don't need the operating system,
+//! along with `math.maxinteger`, `math.mininteger` and `math.type` from lua 5.3.
+//! Strings in the interpreter are UTF-8, so string functions working on bytes replace any
+//! characters they cut in half.
+
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::io::{self, Write};
+use std::iter;
+use std::rc::Rc;
+use std::slice;
+use std::str;
+
+use interp::{self, Function, Interpreter, RuntimeError, Table, Value};
+use print::escape;
+use span::Span;
+
+/// The longest string `string.rep` builds, past which it fails rather than trying to allocate.
+const STRING_LIMIT: usize = i32::MAX as usize;
+
+/// A library of functions that `open_with` can install.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Library {
+    /// The global functions, such as `print`, `pairs` and `tostring`.
+    Base,
+    /// The `string` table, which is also the `__index` of every string's metatable.
+    String,
+    /// The `math` table.
+    Math,
+    /// The `table` table.
+    Table,
+}
+
+impl Library {
+    pub const ALL: [Library; 4] = [Library::Base, Library::String, Library::Math, Library::Table];
+}
+
+/// Options changing what the standard library installs.
+#[derive(Clone)]
+pub struct StdlibConfig {
+    pub libraries: Vec<Library>,
+    /// Where `print` writes.
+    pub output: Rc<RefCell<dyn Write>>,
+}
+
+impl Default for StdlibConfig {
+    /// Every library, printing to standard output.
+    fn default() -> StdlibConfig {
+        let output = Rc::new(RefCell::new(io::stdout()));
+        StdlibConfig { libraries: Library::ALL.to_vec(), output }
+    }
+}
+
+/// Installs every library into the interpreter, printing to standard output.
+pub fn open(interpreter: &mut Interpreter) {
+    open_with(interpreter, &StdlibConfig::default())
+}
+
+/// Installs the libraries the options choose into the interpreter.
+pub fn open_with(interpreter: &mut Interpreter, config: &StdlibConfig) {
+    for &library in &config.libraries {
+        match library {
+            Library::Base => open_base(interpreter, config.output.clone()),
+            Library::String => {
+                let string = Rc::new(RefCell::new(string_library()));
+                let mut metatable = Table::new();
+                metatable.set("__index".into(), Value::Table(string.clone()));
+                interpreter.set_string_metatable(Some(Rc::new(RefCell::new(metatable))));
+                interpreter.set_global("string", Value::Table(string));
+            }
+            Library::Math => interpreter.set_global("math", math_library().into()),
+            Library::Table => interpreter.set_global("table", table_library().into()),
+        }
+    }
+}
+
+/// The arguments a native function was called with, along with what it needs to raise errors
+/// about them.
+struct Arguments {
+    values: Vec<Value>,
+    function: &'static str,
+    span: Span,
+}
+
+impl Arguments {
+    /// The argument at `position`, counted from 1, which is `nil` if it wasn't given.
+    fn get(&self, position: usize) -> Value {
+        self.values.get(position - 1).cloned().unwrap_or(Value::Nil)
+    }
+
+    fn error(&self, position: usize, message: &str) -> RuntimeError {
+        interp::bad_argument(position, self.function, message, self.span)
+    }
+
+    fn expected(&self, position: usize, what: &str) -> RuntimeError {
+        let got = self.values.get(position - 1).map_or("no value", Value::type_name);
+        self.error(position, &format!("{} expected, got {}", what, got))
+    }
+
+    fn any(&self, position: usize) -> Result<Value, RuntimeError> {
+        match self.values.get(position - 1) {
+            Some(value) => Ok(value.clone()),
+            None => Err(self.error(position, "value expected")),
+        }
+    }
+
+    /// A number, or a string that can be read as one.
+    fn number(&self, position: usize) -> Result<f64, RuntimeError> {
+        interp::to_number(&self.get(position)).ok_or_else(|| self.expected(position, "number"))
+    }
+
+    /// An integer or a float, or a string that can be read as one.
+    fn numeric(&self, position: usize) -> Result<Value, RuntimeError> {
+        interp::to_numeric(&self.get(position)).ok_or_else(|| self.expected(position, "number"))
+    }
+
+    /// A number cut down to an integer, as lua's indices and counts are.
+    fn integer(&self, position: usize) -> Result<i64, RuntimeError> {
+        match self.numeric(position)? {
+            Value::Integer(n) => Ok(n),
+            n => Ok(interp::to_number(&n).unwrap() as i64),
+        }
+    }
+
+    /// A number equal to an integer, as the integer conversions of `string.format` need.
+    fn exact_integer(&self, position: usize) -> Result<i64, RuntimeError> {
+        match self.numeric(position)? {
+            Value::Integer(n) => Ok(n),
+            n => interp::to_number(&n)
+                .and_then(interp::float_to_integer)
+                .ok_or_else(|| self.error(position, "not a number in proper range")),
+        }
+    }
+
+    fn optional_integer(&self, position: usize, default: i64) -> Result<i64, RuntimeError> {
+        match self.get(position) {
+            Value::Nil => Ok(default),
+            _ => self.integer(position),
+        }
+    }
+
+    /// A string, or a number written as one.
+    fn string(&self, position: usize) -> Result<Rc<[u8]>, RuntimeError> {
+        match self.get(position) {
+            Value::Str(s) => Ok(s),
+            n @ (Value::Integer(_) | Value::Number(_)) => Ok(n.to_string().into_bytes().into()),
+            _ => Err(self.expected(position, "string")),
+        }
+    }
+
+    fn table(&self, position: usize) -> Result<Rc<RefCell<Table>>, RuntimeError> {
+        match self.get(position) {
+            Value::Table(table) => Ok(table),
+            _ => Err(self.expected(position, "table")),
+        }
+    }
+}
+
+/// A native function, naming itself `name` in errors about its arguments.
+fn native<F>(name: &'static str, function: F) -> Value
+where
+    F: Fn(&mut Interpreter, &Arguments) -> Result<Vec<Value>, RuntimeError> + 'static,
+{
+    Function::native(move |interpreter, values, span| {
+        function(interpreter, &Arguments { values, function: name, span })
+    })
+    .into()
+}
+
+/// Adds a native function to a library.
+fn set<F>(library: &mut Table, name: &'static str, function: F)
+where
+    F: Fn(&mut Interpreter, &Arguments) -> Result<Vec<Value>, RuntimeError> + 'static,
+{
+    library.set(name.into(), native(name, function));
+}
+
+/// Converts a value to a string as `tostring` does, using a `__tostring` metamethod.
+fn tostring(
+    interpreter: &mut Interpreter,
+    value: &Value,
+    span: Span,
+) -> Result<Rc<[u8]>, RuntimeError> {
+    match interpreter.metamethod(value, "__tostring") {
+        Value::Nil => match *value {
+            Value::Str(ref s) => Ok(s.clone()),
+            _ => Ok(value.to_string().into_bytes().into()),
+        },
+        handler => match interpreter.call_value(&handler, vec![value.clone()], span)?.first() {
+            Some(Value::Str(s)) => Ok(s.clone()),
+            _ => Err(RuntimeError::new("'__tostring' must return a string", span)),
+        },
+    }
+}
+
+fn open_base(interpreter: &mut Interpreter, output: Rc<RefCell<dyn Write>>) {
+    let globals = interpreter.globals();
+    let mut base = globals.borrow_mut();
+    base.set("_G".into(), Value::Table(globals.clone()));
+    base.set("_VERSION".into(), "Lua 5.2".into());
+    set(&mut base, "assert", |_, args| {
+        if args.any(1)?.is_truthy() {
+            Ok(args.values.clone())
+        } else {
+            let message = match args.get(2) {
+                Value::Nil => "assertion failed!".to_string(),
+                message => message.to_string(),
+            };
+            Err(RuntimeError::new(message, args.span))
+        }
+    });
+    set(&mut base, "error", |_, args| {
+        let message = match args.get(1) {
+            message @ (Value::Str(_) | Value::Integer(_) | Value::Number(_)) => message.to_string(),
+            other => format!("(error object is a {} value)", other.type_name()),
+        };
+        Err(RuntimeError::new(message, args.span))
+    });
+    let iterator = native("ipairs", |_, args| {
+        let i = args.integer(2)? + 1;
+        match args.table(1)?.borrow().get(&Value::Integer(i)) {
+            Value::Nil => Ok(vec![Value::Nil]),
+            value => Ok(vec![Value::Integer(i), value]),
+        }
+    });
+    set(&mut base, "ipairs", move |_, args| {
+        Ok(vec![iterator.clone(), Value::Table(args.table(1)?), Value::Integer(0)])
+    });
+    // `pairs` gives the same function as `next`.
+    let next = native("next", |_, args| {
+        let table = args.table(1)?;
+        let entry = table.borrow().next(&args.get(2));
+        match entry.map_err(|message| RuntimeError::new(message, args.span))? {
+            Some((key, value)) => Ok(vec![key, value]),
+            None => Ok(vec![Value::Nil]),
+        }
+    });
+    base.set("next".into(), next.clone());
+    set(&mut base, "pairs", move |_, args| {
+        Ok(vec![next.clone(), Value::Table(args.table(1)?), Value::Nil])
+    });
+    set(&mut base, "pcall", |interpreter, args| {
+        let function = args.any(1)?;
+        match interpreter.call_value(&function, args.values[1..].to_vec(), args.span) {
+            Ok(mut values) => {
+                values.insert(0, Value::Bool(true));
+                Ok(values)
+            }
+            Err(error) => Ok(vec![Value::Bool(false), error.message.into()]),
+        }
+    });
+    set(&mut base, "print", move |interpreter, args| {
+        let mut line = Vec::new();
+        for (i, value) in args.values.iter().enumerate() {
+            if i > 0 {
+                line.push(b'\t');
+            }
+            line.extend_from_slice(&tostring(interpreter, value, args.span)?);
+        }
+        line.push(b'\n');
+        output
+            .borrow_mut()
+            .write_all(&line)
+            .map_err(|error| RuntimeError::new(format!("cannot print: {}", error), args.span))?;
+        Ok(Vec::new())
+    });
+    set(&mut base, "rawequal", |_, args| Ok(vec![Value::Bool(args.any(1)? == args.any(2)?)]));
+    set(&mut base, "rawget", |_, args| {
+        let table = args.table(1)?;
+        let value = table.borrow().get(&args.any(2)?);
+        Ok(vec![value])
+    });
+    set(&mut base, "rawlen", |_, args| match args.get(1) {
+        Value::Table(table) => Ok(vec![Value::Integer(table.borrow().border() as i64)]),
+        Value::Str(s) => Ok(vec![Value::Integer(s.len() as i64)]),
+        _ => Err(args.error(1, "table or string expected")),
+    });
+    set(&mut base, "rawset", |_, args| {
+        let table = args.table(1)?;
+        let key = args.any(2)?;
+        interp::check_key(&key).map_err(|message| RuntimeError::new(message, args.span))?;
+        table.borrow_mut().set(key, args.any(3)?);
+        Ok(vec![Value::Table(table)])
+    });
+    set(&mut base, "select", |_, args| {
+        let count = args.values.len() - 1;
+        if args.get(1) == Value::from("#") {
+            return Ok(vec![Value::Integer(count as i64)]);
+        }
+        let n = args.integer(1)?;
+        let start = match n {
+            n if n < 0 && n.unsigned_abs() as usize <= count => count - n.unsigned_abs() as usize,
+            n if n > 0 => (n as usize - 1).min(count),
+            _ => return Err(args.error(1, "index out of range")),
+        };
+        Ok(args.values[1 + start..].to_vec())
+    });
+    set(&mut base, "tonumber", |_, args| {
+        let value = args.any(1)?;
+        let n = match args.get(2) {
+            Value::Nil => interp::to_numeric(&value),
+            _ => {
+                let base = args.integer(2)?;
+                if !(2..=36).contains(&base) {
+                    return Err(args.error(2, "base out of range"));
+                }
+                let s = args.string(1)?;
+                str::from_utf8(&s)
+                    .ok()
+                    .and_then(|s| parse_integer(s, base as u32))
+                    .map(Value::Integer)
+            }
+        };
+        Ok(vec![n.unwrap_or(Value::Nil)])
+    });
+    set(&mut base, "tostring", |interpreter, args| {
+        let value = args.any(1)?;
+        Ok(vec![Value::Str(tostring(interpreter, &value, args.span)?)])
+    });
+    set(&mut base, "type", |_, args| Ok(vec![args.any(1)?.type_name().into()]));
+}
+
+/// Reads a string as an integer in the given base, allowing a sign and surrounding whitespace.
+/// Like integer arithmetic, this wraps around on overflow.
+fn parse_integer(s: &str, base: u32) -> Option<i64> {
+    let s = s.trim();
+    let (negative, digits) = match s.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, s),
+    };
+    if digits.is_empty() {
+        return None;
+    }
+    let mut n: i64 = 0;
+    for c in digits.chars() {
+        n = n.wrapping_mul(base as i64).wrapping_add(c.to_digit(base)? as i64);
+    }
+    Some(if negative { n.wrapping_neg() } else { n })
+}
+
+/// Turns a position in a string of `len` bytes, which counts from the end if it is negative,
+/// into one counting from 1.
+fn string_position(position: i64, len: usize) -> i64 {
+    if position >= 0 {
+        position
+    } else if position.unsigned_abs() as usize > len {
+        0
+    } else {
+        len as i64 + position + 1
+    }
+}
+
+/// The bytes of `s` from `start` to `end`, both counted from 1, as `string.sub` picks them.
+fn substring(s: &[u8], start: i64, end: i64) -> &[u8] {
+    let len = s.len();
+    let start = string_position(start, len).max(1);
+    let end = string_position(end, len).min(len as i64);
+    if start > end { &[] } else { &s[start as usize - 1..end as usize] }
+}
+
+fn string_library() -> Table {
+    let mut string = Table::new();
+    set(&mut string, "byte", |_, args| {
+        let s = args.string(1)?;
+        let start = args.optional_integer(2, 1)?;
+        let end = args.optional_integer(3, start)?;
+        let bytes = substring(&s, start, end);
+        Ok(bytes.iter().map(|&byte| Value::Integer(byte as i64)).collect())
+    });
+    set(&mut string, "char", |_, args| {
+        let mut bytes = Vec::with_capacity(args.values.len());
+        for position in 1..=args.values.len() {
+            match args.integer(position)? {
+                byte @ 0..=255 => bytes.push(byte as u8),
+                _ => return Err(args.error(position, "value out of range")),
+            }
+        }
+        Ok(vec![bytes.into()])
+    });
+    set(&mut string, "format", |interpreter, args| Ok(vec![format(interpreter, args)?.into()]));
+    set(&mut string, "len", |_, args| Ok(vec![Value::Integer(args.string(1)?.len() as i64)]));
+    // Like lua in the C locale, only ASCII letters change case.
+    set(&mut string, "lower", |_, args| Ok(vec![args.string(1)?.to_ascii_lowercase().into()]));
+    set(&mut string, "rep", |_, args| {
+        let s = args.string(1)?;
+        let n = args.integer(2)?.max(0) as usize;
+        let separator = match args.get(3) {
+            Value::Nil => Rc::from(&b""[..]),
+            _ => args.string(3)?,
+        };
+        match n.checked_mul(s.len() + separator.len()) {
+            Some(len) if len <= STRING_LIMIT => {}
+            _ => return Err(RuntimeError::new("resulting string too large", args.span)),
+        }
+        Ok(vec![vec![&*s; n].join(&*separator).into()])
+    });
+    set(&mut string, "reverse", |_, args| {
+        let mut bytes = args.string(1)?.to_vec();
+        bytes.reverse();
+        Ok(vec![bytes.into()])
+    });
+    set(&mut string, "sub", |_, args| {
+        let s = args.string(1)?;
+        let start = args.optional_integer(2, 1)?;
+        let end = args.optional_integer(3, -1)?;
+        Ok(vec![substring(&s, start, end).into()])
+    });
+    set(&mut string, "upper", |_, args| Ok(vec![args.string(1)?.to_ascii_uppercase().into()]));
+    string
+}
+
+/// A conversion of `string.format`, such as `%-5d`.
+#[derive(Default)]
+struct FormatSpec {
+    left: bool,
+    zero: bool,
+    plus: bool,
+    space: bool,
+    width: usize,
+    precision: Option<usize>,
+}
+
+impl FormatSpec {
+    /// Pads a converted value to the width, putting zeros between the sign and the digits of a
+    /// number if the spec asks for them.
+    /// The width counts bytes, as in C.
+    fn pad(&self, sign: &str, body: &[u8], numeric: bool) -> Vec<u8> {
+        let padding = self.width.saturating_sub(sign.len() + body.len());
+        let (before, zeros, after) = if self.left {
+            (0, 0, padding)
+        } else if self.zero && numeric {
+            (0, padding, 0)
+        } else {
+            (padding, 0, 0)
+        };
+        let mut padded = vec![b' '; before];
+        padded.extend_from_slice(sign.as_bytes());
+        padded.resize(padded.len() + zeros, b'0');
+        padded.extend_from_slice(body);
+        padded.resize(padded.len() + after, b' ');
+        padded
+    }
+
+    fn sign(&self, negative: bool) -> &'static str {
+        if negative {
+            "-"
+        } else if self.plus {
+            "+"
+        } else if self.space {
+            " "
+        } else {
+            ""
+        }
+    }
+}
+
+/// Formats the arguments after the first by the format string that is the first, following
+/// `sprintf` for the conversions `%d %i %u %c %x %X %o %e %E %f %F %g %G %q %s` and `%%`.
+/// The pattern and the strings are bytes, which are copied as they are.
+fn format(interpreter: &mut Interpreter, args: &Arguments) -> Result<Vec<u8>, RuntimeError> {
+    let pattern = args.string(1)?;
+    let mut out = Vec::with_capacity(pattern.len());
+    let mut bytes = pattern.iter().copied().peekable();
+    let mut position = 1;
+    while let Some(byte) = bytes.next() {
+        if byte != b'%' {
+            out.push(byte);
+            continue;
+        }
+        if bytes.next_if_eq(&b'%').is_some() {
+            out.push(b'%');
+            continue;
+        }
+        let mut spec = FormatSpec::default();
+        while let Some(flag) = bytes.next_if(|byte| b"-0+ ".contains(byte)) {
+            match flag {
+                b'-' => spec.left = true,
+                b'0' => spec.zero = true,
+                b'+' => spec.plus = true,
+                _ => spec.space = true,
+            }
+        }
+        let digits = |bytes: &mut iter::Peekable<iter::Copied<slice::Iter<u8>>>| {
+            let mut n = 0;
+            let mut count = 0;
+            while let Some(digit) = bytes.next_if(u8::is_ascii_digit) {
+                n = n * 10 + (digit - b'0') as usize;
+                count += 1;
+            }
+            if count > 2 {
+                let message = "invalid format (width or precision too long)";
+                return Err(RuntimeError::new(message, args.span));
+            }
+            Ok(n)
+        };
+        spec.width = digits(&mut bytes)?;
+        if bytes.next_if_eq(&b'.').is_some() {
+            spec.precision = Some(digits(&mut bytes)?);
+        }
+        position += 1;
+        let conversion = bytes.next().map_or('%', char::from);
+        let converted = match conversion {
+            'd' | 'i' | 'u' => {
+                let n = args.exact_integer(position)?;
+                let mut digits = n.unsigned_abs().to_string();
+                if let Some(precision) = spec.precision {
+                    digits = format!("{:0>1$}", digits, precision);
+                }
+                spec.pad(spec.sign(n < 0), digits.as_bytes(), spec.precision.is_none())
+            }
+            'c' => spec.pad("", &[args.integer(position)? as u8], false),
+            'x' | 'X' | 'o' => {
+                let n = args.exact_integer(position)? as u64;
+                let digits = match conversion {
+                    'x' => format!("{:x}", n),
+                    'X' => format!("{:X}", n),
+                    _ => format!("{:o}", n),
+                };
+                spec.pad("", digits.as_bytes(), true)
+            }
+            'e' | 'E' | 'f' | 'F' | 'g' | 'G' => {
+                let n = args.number(position)?;
+                let precision = spec.precision.unwrap_or(6);
+                let body = match conversion.to_ascii_lowercase() {
+                    'e' => interp::format_scientific(n.abs(), precision),
+                    'f' => interp::format_fixed(n.abs(), precision),
+                    _ => interp::format_general(n.abs(), precision),
+                };
+                let body = if conversion.is_ascii_uppercase() { body.to_uppercase() } else { body };
+                let sign = spec.sign(n.is_sign_negative() && !n.is_nan());
+                spec.pad(sign, body.as_bytes(), n.is_finite())
+            }
+            'q' => escape(&args.string(position)?, '"').into_bytes(),
+            's' => {
+                let s = tostring(interpreter, &args.any(position)?, args.span)?;
+                let len = spec.precision.map_or(s.len(), |precision| precision.min(s.len()));
+                spec.pad("", &s[..len], false)
+            }
+            other => {
+                let message = format!("invalid option '%{}' to 'format'", other);
+                return Err(RuntimeError::new(message, args.span));
+            }
+        };
+        out.extend_from_slice(&converted);
+    }
+    Ok(out)
+}
+
+fn math_library() -> Table {
+    let mut math = Table::new();
+    math.set("huge".into(), Value::Number(f64::INFINITY));
+    math.set("pi".into(), Value::Number(std::f64::consts::PI));
+    math.set("maxinteger".into(), Value::Integer(i64::MAX));
+    math.set("mininteger".into(), Value::Integer(i64::MIN));
+    set(&mut math, "abs", |_, args| match args.numeric(1)? {
+        Value::Integer(n) => Ok(vec![Value::Integer(n.wrapping_abs())]),
+        n => Ok(vec![Value::Number(interp::to_number(&n).unwrap().abs())]),
+    });
+    set(&mut math, "ceil", |_, args| Ok(vec![rounded(args.numeric(1)?, f64::ceil)]));
+    set(&mut math, "cos", |_, args| Ok(vec![Value::Number(args.number(1)?.cos())]));
+    set(&mut math, "exp", |_, args| Ok(vec![Value::Number(args.number(1)?.exp())]));
+    set(&mut math, "floor", |_, args| Ok(vec![rounded(args.numeric(1)?, f64::floor)]));
+    set(&mut math, "fmod", |_, args| {
+        // Rust's remainder truncates like C's `fmod`, unlike lua's `%`.
+        match (args.numeric(1)?, args.numeric(2)?) {
+            (Value::Integer(_), Value::Integer(0)) => Err(args.error(2, "zero")),
+            (Value::Integer(a), Value::Integer(b)) => Ok(vec![Value::Integer(a.wrapping_rem(b))]),
+            (a, b) => {
+                let (a, b) = (interp::to_number(&a).unwrap(), interp::to_number(&b).unwrap());
+                Ok(vec![Value::Number(a % b)])
+            }
+        }
+    });
+    set(&mut math, "log", |_, args| {
+        let x = args.number(1)?;
+        let log = match args.get(2) {
+            Value::Nil => x.ln(),
+            _ => x.log(args.number(2)?),
+        };
+        Ok(vec![Value::Number(log)])
+    });
+    set(&mut math, "max", |_, args| extremum(args, Ordering::Greater));
+    set(&mut math, "min", |_, args| extremum(args, Ordering::Less));
+    set(&mut math, "sin", |_, args| Ok(vec![Value::Number(args.number(1)?.sin())]));
+    set(&mut math, "sqrt", |_, args| Ok(vec![Value::Number(args.number(1)?.sqrt())]));
+    set(&mut math, "tan", |_, args| Ok(vec![Value::Number(args.number(1)?.tan())]));
+    set(&mut math, "type", |_, args| match args.any(1)? {
+        Value::Integer(_) => Ok(vec!["integer".into()]),
+        Value::Number(_) => Ok(vec!["float".into()]),
+        _ => Ok(vec![Value::Nil]),
+    });
+    math
+}
+
+/// Rounds a number to an integer, which stays a float if it's too big for one.
+fn rounded(n: Value, round: fn(f64) -> f64) -> Value {
+    match n {
+        Value::Integer(_) => n,
+        n => {
+            let n = round(interp::to_number(&n).unwrap());
+            interp::float_to_integer(n).map_or(Value::Number(n), Value::Integer)
+        }
+    }
+}
+
+/// The first of the arguments to come before all of the others when ordered `towards`.
+fn extremum(args: &Arguments, towards: Ordering) -> Result<Vec<Value>, RuntimeError> {
+    let mut best = args.numeric(1)?;
+    for position in 2..=args.values.len() {
+        let n = args.numeric(position)?;
+        if interp::compare(&n, &best, args.span)? == Some(towards) {
+            best = n;
+        }
+    }
+    Ok(vec![best])
+}
+
+fn table_library() -> Table {
+    let mut table = Table::new();
+    set(&mut table, "concat", |_, args| {
+        let list = args.table(1)?;
+        let list = list.borrow();
+        let separator = match args.get(2) {
+            Value::Nil => Rc::from(&b""[..]),
+            _ => args.string(2)?,
+        };
+        let start = args.optional_integer(3, 1)?;
+        let end = args.optional_integer(4, list.border() as i64)?;
+        let mut pieces = Vec::new();
+        for i in start..=end {
+            match list.get(&Value::Integer(i)) {
+                Value::Str(s) => pieces.push(s.to_vec()),
+                value @ (Value::Integer(_) | Value::Number(_)) => {
+                    pieces.push(value.to_string().into_bytes())
+                }
+                _ => {
+                    let message = format!("invalid value (at index {}) in table for 'concat'", i);
+                    return Err(RuntimeError::new(message, args.span));
+                }
+            }
+        }
+        Ok(vec![pieces.join(&*separator).into()])
+    });
+    set(&mut table, "insert", |_, args| {
+        let list = args.table(1)?;
+        let mut list = list.borrow_mut();
+        let end = list.border() as i64 + 1;
+        let (position, value) = match args.values.len() {
+            2 => (end, args.get(2)),
+            3 => {
+                let position = args.integer(2)?;
+                if position < 1 || position > end {
+                    return Err(args.error(2, "position out of bounds"));
+                }
+                (position, args.get(3))
+            }
+            _ => return Err(RuntimeError::new("wrong number of arguments to 'insert'", args.span)),
+        };
+        for i in (position..end).rev() {
+            let moved = list.get(&Value::Integer(i));
+            list.set(Value::Integer(i + 1), moved);
+        }
+        list.set(Value::Integer(position), value);
+        Ok(Vec::new())
+    });
+    set(&mut table, "remove", |_, args| {
+        let list = args.table(1)?;
+        let mut list = list.borrow_mut();
+        let size = list.border() as i64;
+        let mut position = args.optional_integer(2, size)?;
+        if position != size && (position < 1 || position > size + 1) {
+            return Err(args.error(2, "position out of bounds"));
+        }
+        let removed = list.get(&Value::Integer(position));
+        while position < size {
+            let moved = list.get(&Value::Integer(position + 1));
+            list.set(Value::Integer(position), moved);
+            position += 1;
+        }
+        list.set(Value::Integer(position), Value::Nil);
+        Ok(vec![removed])
+    });
+    set(&mut table, "sort", |interpreter, args| {
+        let list = args.table(1)?;
+        let comparator = args.get(2);
+        let len = list.borrow().border();
+        let values = (1..=len).map(|i| list.borrow().get(&Value::Integer(i as i64))).collect();
+        let mut less = |a: &Value, b: &Value| match comparator {
+            Value::Nil => Ok(interp::compare(a, b, args.span)? == Some(Ordering::Less)),
+            _ => {
+                let arguments = vec![a.clone(), b.clone()];
+                let values = interpreter.call_value(&comparator, arguments, args.span)?;
+                Ok(values.first().is_some_and(Value::is_truthy))
+            }
+        };
+        let sorted = merge_sort(values, &mut less)?;
+        let mut list = list.borrow_mut();
+        for (i, value) in sorted.into_iter().enumerate() {
+            list.set(Value::Integer(i as i64 + 1), value);
+        }
+        Ok(Vec::new())
+    });
+    set(&mut table, "unpack", |_, args| {
+        let list = args.table(1)?;
+        let list = list.borrow();
+        let start = args.optional_integer(2, 1)?;
+        let end = args.optional_integer(3, list.border() as i64)?;
+        if end.saturating_sub(start) >= interp::CALL_LIMIT as i64 * 1000 {
+            return Err(RuntimeError::new("too many results to unpack", args.span));
+        }
+        Ok((start..=end).map(|i| list.get(&Value::Integer(i))).collect())
+    });
+    table
+}
+
+/// Sorts the values by a comparison that can fail, such as a function written in lua. Unlike
+/// the sorts of the standard library, this is fine with comparisons that aren't consistent.
+fn merge_sort<F>(mut values: Vec<Value>, less: &mut F) -> Result<Vec<Value>, RuntimeError>
+where
+    F: FnMut(&Value, &Value) -> Result<bool, RuntimeError>,
+{
+    if values.len() <= 1 {
+        return Ok(values);
+    }
+    let right = values.split_off(values.len() / 2);
+    let (left, right) = (merge_sort(values, less)?, merge_sort(right, less)?);
+    let mut merged = Vec::with_capacity(left.len() + right.len());
+    let (mut left, mut right) = (left.into_iter().peekable(), right.into_iter().peekable());
+    while let (Some(a), Some(b)) = (left.peek(), right.peek()) {
+        // Taking from the left unless the right is less keeps equal values in order.
+        let next = if less(b, a)? { right.next() } else { left.next() };
+        merged.extend(next);
+    }
+    merged.extend(left);
+    merged.extend(right);
+    Ok(merged)
+}
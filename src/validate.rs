@@ -0,0 +1,513 @@
+//! Checks of rules that the grammar alone doesn't enforce.
+//!
+//! The rules for `goto` are in the lua manual at http://www.lua.org/manual/5.2/manual.html#3.3.4.
+//! A label is visible in the whole block defining it, including nested blocks but not nested
+//! functions, and a `goto` may jump to any visible label as long as it doesn't jump into the scope
+//! of a local variable. A block can't define two labels with the same name, but a nested block can
+//! define one hiding a label outside it.
+//!
+//! A vararg expression `...` can only be used directly inside a function taking extended
+//! arguments, as described at http://www.lua.org/manual/5.2/manual.html#3.4.10. The main chunk
+//! is such a function, but a function nested in one isn't unless it takes them itself.
+//!
+//! A `break` ends the innermost loop around it, so it can only be used inside the block of a
+//! `while`, `repeat` or `for` loop. A function defined in a loop isn't inside it, so a `break` in
+//! the function must be in a loop of its own.
+//!
+//! A syntax tree built by hand rather than parsed can also break rules that the grammar keeps but
+//! the types don't, such as every name being an identifier. `validate` checks those, so that the
+//! printed tree reads back as itself.
+
+use std::error::Error;
+use std::fmt;
+use std::iter;
+use std::mem;
+
+use ast::*;
+use lexer::Token;
+use span::{Span, Spanned};
+use visit::{self, Visitor};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GotoError {
+    /// A `goto` to a label that isn't visible from it.
+    UndefinedLabel { name: String, span: Span },
+    /// A label with the same name as another label in its block. A label in a nested block may
+    /// have the name of one outside it, hiding it there.
+    DuplicateLabel { name: String, first_span: Span, second_span: Span },
+    /// A forward `goto` skipping over the declaration of a local that is still in scope at the
+    /// label.
+    JumpIntoLocalScope { name: String, span: Span, local: Span },
+}
+
+impl fmt::Display for GotoError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            GotoError::UndefinedLabel { ref name, span } => {
+                write!(f, "no visible label {:?} for goto at byte {}", name, span.start)
+            }
+            GotoError::DuplicateLabel { ref name, first_span, second_span } => write!(
+                f,
+                "label {:?} at byte {} is already defined at byte {}",
+                name, second_span.start, first_span.start
+            ),
+            GotoError::JumpIntoLocalScope { ref name, span, local } => write!(
+                f,
+                "goto {:?} at byte {} jumps into the scope of the local at byte {}",
+                name, span.start, local.start
+            ),
+        }
+    }
+}
+
+impl Error for GotoError {}
+
+/// Checks that every `goto` in the chunk jumps to a visible label without entering the scope of a
+/// local, and that no block defines a label twice.
+pub fn validate_gotos(chunk: &Chunk) -> Result<(), Vec<GotoError>> {
+    let mut checker = GotoChecker { scopes: Vec::new(), errors: Vec::new() };
+    visit::walk_chunk(&mut checker, chunk);
+    if checker.errors.is_empty() { Ok(()) } else { Err(checker.errors) }
+}
+
+struct LabelDefinition {
+    name: String,
+    index: usize,
+    span: Span,
+    /// Whether only labels and semicolons follow the label in its block. Such a label is treated
+    /// as outside the scope of the block's locals.
+    at_end: bool,
+}
+
+/// The labels and locals of a block being checked.
+struct Scope {
+    labels: Vec<LabelDefinition>,
+    /// The index and span of each statement declaring locals.
+    locals: Vec<(usize, Span)>,
+    /// The index of the statement being checked.
+    position: usize,
+}
+
+struct GotoChecker {
+    /// The blocks enclosing the statement being checked, innermost last, up to the nearest
+    /// function.
+    scopes: Vec<Scope>,
+    errors: Vec<GotoError>,
+}
+
+fn is_void(statement: &Statement) -> bool {
+    matches!(*statement, Statement::Label(_) | Statement::Semicolon)
+}
+
+impl GotoChecker {
+    fn scope(&mut self, block: &Block) -> Scope {
+        let statements = block.0.as_deref().unwrap_or(&[]);
+        let mut scope = Scope { labels: Vec::new(), locals: Vec::new(), position: 0 };
+        for (index, statement) in statements.iter().enumerate() {
+            match statement.node {
+                Statement::Label(ref label) => {
+                    // Only this block's labels count, as nested ones may hide outer ones.
+                    let first = scope.labels.iter().find(|defined| defined.name == label.0);
+                    if let Some(first) = first {
+                        self.errors.push(GotoError::DuplicateLabel {
+                            name: label.0.clone(),
+                            first_span: first.span,
+                            second_span: statement.span,
+                        });
+                        continue;
+                    }
+                    let at_end = block.1.is_none() &&
+                        statements[index + 1..].iter().all(|statement| is_void(&statement.node));
+                    scope.labels.push(LabelDefinition {
+                        name: label.0.clone(),
+                        index,
+                        span: statement.span,
+                        at_end,
+                    });
+                }
+                Statement::LocalVariableBinding(..) | Statement::LocalFunction { .. } => {
+                    scope.locals.push((index, statement.span));
+                }
+                _ => {}
+            }
+        }
+        scope
+    }
+
+    fn check_goto(&mut self, name: &str, span: Span) {
+        for scope in self.scopes.iter().rev() {
+            let label = match scope.labels.iter().find(|label| label.name == name) {
+                Some(label) => label,
+                None => continue,
+            };
+            if label.index > scope.position && !label.at_end {
+                let skipped = scope
+                    .locals
+                    .iter()
+                    .find(|&&(index, _)| index > scope.position && index < label.index);
+                if let Some(&(_, local)) = skipped {
+                    self.errors.push(GotoError::JumpIntoLocalScope {
+                        name: name.to_string(),
+                        span,
+                        local,
+                    });
+                }
+            }
+            return;
+        }
+        self.errors.push(GotoError::UndefinedLabel { name: name.to_string(), span });
+    }
+}
+
+impl Visitor for GotoChecker {
+    fn visit_block(&mut self, block: &Block) {
+        let scope = self.scope(block);
+        self.scopes.push(scope);
+        if let Some(ref statements) = block.0 {
+            for (index, statement) in statements.iter().enumerate() {
+                self.scopes.last_mut().unwrap().position = index;
+                self.visit_statement(statement);
+            }
+        }
+        if let Some(ref return_statement) = block.1 {
+            self.visit_return_statement(return_statement);
+        }
+        self.scopes.pop();
+    }
+
+    fn visit_statement(&mut self, statement: &Spanned<Statement>) {
+        if let Statement::Goto(ref label) = statement.node {
+            self.check_goto(&label.0, statement.span);
+        }
+        visit::walk_statement(self, statement);
+    }
+
+    fn visit_function_body(&mut self, body: &FunctionBody) {
+        // Labels of the enclosing function aren't visible inside this one.
+        let scopes = mem::take(&mut self.scopes);
+        visit::walk_function_body(self, body);
+        self.scopes = scopes;
+    }
+}
+
+/// A vararg expression `...` in a function that doesn't take extended arguments.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VarargError {
+    pub span: Span,
+}
+
+impl fmt::Display for VarargError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "cannot use '...' at byte {} outside a vararg function", self.span.start)
+    }
+}
+
+impl Error for VarargError {}
+
+/// Checks that every `...` in the chunk is inside a function taking extended arguments.
+pub fn validate_varargs(chunk: &Chunk) -> Result<(), Vec<VarargError>> {
+    let mut checker = VarargChecker { vararg: true, errors: Vec::new() };
+    visit::walk_chunk(&mut checker, chunk);
+    if checker.errors.is_empty() { Ok(()) } else { Err(checker.errors) }
+}
+
+struct VarargChecker {
+    /// Whether the innermost enclosing function takes extended arguments.
+    vararg: bool,
+    errors: Vec<VarargError>,
+}
+
+impl Visitor for VarargChecker {
+    fn visit_expression(&mut self, expression: &Spanned<Expression>) {
+        if let Expression::ExtendedArgumentAccess = expression.node {
+            if !self.vararg {
+                self.errors.push(VarargError { span: expression.span });
+            }
+        }
+        visit::walk_expression(self, expression);
+    }
+
+    fn visit_function_body(&mut self, body: &FunctionBody) {
+        let vararg = matches!(
+            body.0.as_deref(),
+            Some(ParameterList::ExtendedArguments(_)) | Some(ParameterList::ExtendedArgumentsVoid)
+        );
+        let outer = mem::replace(&mut self.vararg, vararg);
+        visit::walk_function_body(self, body);
+        self.vararg = outer;
+    }
+}
+
+/// A `break` that isn't inside a loop of its function.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BreakOutsideLoop {
+    pub span: Span,
+}
+
+impl fmt::Display for BreakOutsideLoop {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "break at byte {} is outside a loop", self.span.start)
+    }
+}
+
+impl Error for BreakOutsideLoop {}
+
+/// Checks that every `break` in the chunk is inside a loop, without a function between them.
+pub fn validate_breaks(chunk: &Chunk) -> Result<(), Vec<BreakOutsideLoop>> {
+    let mut checker = BreakChecker { loops: 0, errors: Vec::new() };
+    visit::walk_chunk(&mut checker, chunk);
+    if checker.errors.is_empty() { Ok(()) } else { Err(checker.errors) }
+}
+
+struct BreakChecker {
+    /// How many loops enclose the statement being checked, up to the nearest function.
+    loops: usize,
+    errors: Vec<BreakOutsideLoop>,
+}
+
+impl Visitor for BreakChecker {
+    fn visit_statement(&mut self, statement: &Spanned<Statement>) {
+        match statement.node {
+            Statement::Break if self.loops == 0 => {
+                self.errors.push(BreakOutsideLoop { span: statement.span });
+            }
+            Statement::While { .. } |
+            Statement::Repeat { .. } |
+            Statement::ForStepping { .. } |
+            Statement::ForIn { .. } => {
+                self.loops += 1;
+                visit::walk_statement(self, statement);
+                self.loops -= 1;
+                return;
+            }
+            _ => {}
+        }
+        visit::walk_statement(self, statement);
+    }
+
+    fn visit_function_body(&mut self, body: &FunctionBody) {
+        // A loop around the function doesn't enclose the statements inside it.
+        let loops = mem::replace(&mut self.loops, 0);
+        visit::walk_function_body(self, body);
+        self.loops = loops;
+    }
+}
+
+/// A part of a syntax tree that no lua source parses into.
+///
+/// Parts without a span of their own, such as the key of a dot access, are reported with the span
+/// of the statement or expression holding them.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValidationError {
+    /// A name that isn't an identifier, such as an empty one, one with a space or a keyword.
+    InvalidName { name: String, span: Span },
+    /// An optional list that is present but empty. The parser leaves such lists out, so the tree
+    /// doesn't equal the one read back from its printed form.
+    EmptyList { span: Span },
+    /// A long string containing the closing bracket of its own level, which would end it early.
+    UnclosedLongString { level: usize, span: Span },
+    /// An `Expression::Error` left where `parser::parse_recovering` found no expression.
+    MissingExpression { span: Span },
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ValidationError::InvalidName { ref name, span } => {
+                write!(f, "{:?} at byte {} is not a valid name", name, span.start)
+            }
+            ValidationError::EmptyList { span } => {
+                write!(f, "empty list at byte {} should be left out", span.start)
+            }
+            ValidationError::UnclosedLongString { level, span } => write!(
+                f,
+                "long string at byte {} contains its closing bracket of level {}",
+                span.start, level
+            ),
+            ValidationError::MissingExpression { span } => {
+                write!(f, "missing expression at byte {}", span.start)
+            }
+        }
+    }
+}
+
+impl Error for ValidationError {}
+
+/// Checks that the chunk is one the parser could have produced: every name is an identifier, no
+/// optional list is present but empty, no long string contains its own closing bracket and no
+/// expression is missing.
+///
+/// # Example
+/// ```
+/// use buildlua::ast::*;
+/// use buildlua::span::{Span, Spanned};
+/// use buildlua::validate::{validate, ValidationError};
+///
+/// let goto = Spanned::dummy(Statement::Goto(Label("end".to_string())));
+/// let errors = validate(&Chunk(Block(Some(vec![goto]), None), Vec::new())).unwrap_err();
+/// assert_eq!(errors, vec![ValidationError::InvalidName {
+///     name: "end".to_string(),
+///     span: Span::dummy(),
+/// }]);
+/// ```
+pub fn validate(chunk: &Chunk) -> Result<(), Vec<ValidationError>> {
+    let mut checker = Checker { span: Span::dummy(), errors: Vec::new() };
+    visit::walk_chunk(&mut checker, chunk);
+    if checker.errors.is_empty() { Ok(()) } else { Err(checker.errors) }
+}
+
+/// Whether `name` is an identifier: letters, digits and underscores not starting with a digit,
+/// and not a keyword.
+pub(crate) fn is_identifier(name: &str) -> bool {
+    let mut bytes = name.bytes();
+    let starts = match bytes.next() {
+        Some(first) => first.is_ascii_alphabetic() || first == b'_',
+        None => false,
+    };
+    starts &&
+        bytes.all(|byte| byte.is_ascii_alphanumeric() || byte == b'_') &&
+        Token::keyword(name).is_none()
+}
+
+struct Checker {
+    /// The span of the innermost statement or expression being checked.
+    span: Span,
+    errors: Vec<ValidationError>,
+}
+
+impl Checker {
+    fn name(&mut self, name: &str, span: Span) {
+        if !is_identifier(name) {
+            self.errors.push(ValidationError::InvalidName { name: name.to_string(), span });
+        }
+    }
+
+    fn names(&mut self, list: &NameList) {
+        for name in iter::once(&list.0).chain(list.1.iter().flatten()) {
+            self.name(&name.node, name.span);
+        }
+        self.rest(&list.1, list.0.span);
+    }
+
+    /// Reports the rest of a list at `span` if it's present but empty.
+    fn rest<T>(&mut self, rest: &Option<Vec<T>>, span: Span) {
+        if rest.as_ref().is_some_and(|rest| rest.is_empty()) {
+            self.errors.push(ValidationError::EmptyList { span });
+        }
+    }
+
+    fn string(&mut self, s: &[u8], form: StringForm) {
+        if let StringForm::Long(level) = form {
+            let closing = format!("]{}]", "=".repeat(level));
+            if s.windows(closing.len()).any(|window| window == closing.as_bytes()) {
+                let span = self.span;
+                self.errors.push(ValidationError::UnclosedLongString { level, span });
+            }
+        }
+    }
+}
+
+impl Visitor for Checker {
+    fn visit_block(&mut self, block: &Block) {
+        self.rest(&block.0, self.span);
+        visit::walk_block(self, block);
+    }
+
+    fn visit_statement(&mut self, statement: &Spanned<Statement>) {
+        let outer = mem::replace(&mut self.span, statement.span);
+        match statement.node {
+            Statement::Assignment(ref variables, _) => self.rest(&variables.rest, statement.span),
+            Statement::Label(ref label) | Statement::Goto(ref label) => {
+                self.name(&label.0, statement.span)
+            }
+            Statement::ForStepping { ref name, .. } => self.name(&name.node, name.span),
+            Statement::ForIn { ref name_list, .. } => self.names(name_list),
+            Statement::Function(ref name, _) => {
+                let first = &name.first_dot_access;
+                self.name(&first.node, first.span);
+                for key in name.rest_dot_access.iter().flatten() {
+                    self.name(key, first.span);
+                }
+                self.rest(&name.rest_dot_access, first.span);
+                if let Some(ref self_name) = name.self_name {
+                    self.name(self_name, first.span);
+                }
+            }
+            Statement::LocalFunction { ref name, .. } => self.name(&name.node, name.span),
+            Statement::LocalVariableBinding(ref list, _) => {
+                for declared in iter::once(&list.0).chain(list.1.iter().flatten()) {
+                    self.name(&declared.name.node, declared.name.span);
+                }
+                self.rest(&list.1, list.0.name.span);
+            }
+            _ => {}
+        }
+        visit::walk_statement(self, statement);
+        self.span = outer;
+    }
+
+    fn visit_expression(&mut self, expression: &Spanned<Expression>) {
+        let outer = mem::replace(&mut self.span, expression.span);
+        match expression.node {
+            Expression::String(ref s, form) => self.string(s, form),
+            Expression::Error => {
+                self.errors.push(ValidationError::MissingExpression { span: expression.span })
+            }
+            _ => {}
+        }
+        visit::walk_expression(self, expression);
+        self.span = outer;
+    }
+
+    fn visit_expression_list(&mut self, expressions: &ExpressionList) {
+        self.rest(&expressions.1, expressions.0.span);
+        visit::walk_expression_list(self, expressions);
+    }
+
+    fn visit_variable(&mut self, variable: &Variable) {
+        match *variable {
+            Variable::Name(ref name) => self.name(&name.node, name.span),
+            Variable::DotAccess { ref key, .. } => self.name(key, self.span),
+            Variable::ArrayAccess { .. } => {}
+        }
+        visit::walk_variable(self, variable);
+    }
+
+    fn visit_function_call(&mut self, call: &FunctionCall) {
+        if let FunctionCall::SelfTaking(_, ref method, _) = *call {
+            self.name(method, self.span);
+        }
+        visit::walk_function_call(self, call);
+    }
+
+    fn visit_function_arguments(&mut self, arguments: &FunctionArguments) {
+        if let FunctionArguments::String(ref s, form) = *arguments {
+            self.string(s, form);
+        }
+        visit::walk_function_arguments(self, arguments);
+    }
+
+    fn visit_function_body(&mut self, body: &FunctionBody) {
+        match body.0.as_deref() {
+            Some(ParameterList::NameList(list)) | Some(ParameterList::ExtendedArguments(list)) => {
+                self.names(list)
+            }
+            Some(ParameterList::ExtendedArgumentsVoid) | None => {}
+        }
+        visit::walk_function_body(self, body);
+    }
+
+    fn visit_table_constructor(&mut self, table: &TableConstructor) {
+        if let Some(ref list) = table.0 {
+            self.rest(&list.1, self.span);
+        }
+        visit::walk_table_constructor(self, table);
+    }
+
+    fn visit_field(&mut self, field: &Field) {
+        if let Field::Equals { ref name, .. } = *field {
+            self.name(name, self.span);
+        }
+        visit::walk_field(self, field);
+    }
+}
@@ -0,0 +1,830 @@
+//! Traversal of the abstract syntax tree.
+//!
+//! Each `visit_*` method of `Visitor` defaults to calling the matching `walk_*` function, which
+//! visits the children of the node. Overriding a method lets a visitor look at just the nodes it
+//! cares about, and calling the `walk_*` function from the override keeps the traversal going.
+//!
+//! `VisitorMut` is the same with mutable references, for rewriting the tree in place. Its methods
+//! and walk functions end in `_mut`.
+//!
+//! `TryVisitor` is the same again with methods returning a `Result`, for traversals that can
+//! fail. Its methods start with `try_`, and the walk stops at the first error, which is returned
+//! from `try_walk_chunk`.
+//!
+//! `fold_chunk` goes through every expression with a closure instead, for gathering a result
+//! without writing a visitor.
+
+use ast::*;
+use span::Spanned;
+
+pub trait Visitor {
+    fn visit_block(&mut self, block: &Block) {
+        walk_block(self, block)
+    }
+
+    fn visit_statement(&mut self, statement: &Spanned<Statement>) {
+        walk_statement(self, statement)
+    }
+
+    fn visit_return_statement(&mut self, return_statement: &ReturnStatement) {
+        walk_return_statement(self, return_statement)
+    }
+
+    fn visit_expression(&mut self, expression: &Spanned<Expression>) {
+        walk_expression(self, expression)
+    }
+
+    fn visit_expression_list(&mut self, expressions: &ExpressionList) {
+        walk_expression_list(self, expressions)
+    }
+
+    fn visit_prefix_expression(&mut self, prefix: &PrefixExpression) {
+        walk_prefix_expression(self, prefix)
+    }
+
+    fn visit_variable(&mut self, variable: &Variable) {
+        walk_variable(self, variable)
+    }
+
+    fn visit_function_call(&mut self, call: &FunctionCall) {
+        walk_function_call(self, call)
+    }
+
+    fn visit_function_arguments(&mut self, arguments: &FunctionArguments) {
+        walk_function_arguments(self, arguments)
+    }
+
+    fn visit_function_body(&mut self, body: &FunctionBody) {
+        walk_function_body(self, body)
+    }
+
+    fn visit_table_constructor(&mut self, table: &TableConstructor) {
+        walk_table_constructor(self, table)
+    }
+
+    fn visit_field(&mut self, field: &Field) {
+        walk_field(self, field)
+    }
+}
+
+/// Visits every node of a chunk, starting with its block.
+pub fn walk_chunk<V: Visitor + ?Sized>(visitor: &mut V, chunk: &Chunk) {
+    visitor.visit_block(&chunk.0);
+}
+
+pub fn walk_block<V: Visitor + ?Sized>(visitor: &mut V, block: &Block) {
+    if let Some(ref statements) = block.0 {
+        for statement in statements {
+            visitor.visit_statement(statement);
+        }
+    }
+    if let Some(ref return_statement) = block.1 {
+        visitor.visit_return_statement(return_statement);
+    }
+}
+
+pub fn walk_statement<V: Visitor + ?Sized>(visitor: &mut V, statement: &Spanned<Statement>) {
+    match statement.node {
+        Statement::Semicolon | Statement::Label(_) | Statement::Break | Statement::Goto(_) => {}
+        Statement::Assignment(ref variables, ref expressions) => {
+            visitor.visit_variable(&variables.first);
+            if let Some(ref rest) = variables.rest {
+                for variable in rest {
+                    visitor.visit_variable(variable);
+                }
+            }
+            visitor.visit_expression_list(expressions);
+        }
+        Statement::FunctionCall(ref call) => visitor.visit_function_call(call),
+        Statement::Do(ref block) => visitor.visit_block(block),
+        Statement::While { ref exp, ref do_ } => {
+            visitor.visit_expression(exp);
+            visitor.visit_block(do_);
+        }
+        Statement::Repeat { ref block, ref until } => {
+            visitor.visit_block(block);
+            visitor.visit_expression(until);
+        }
+        Statement::If { ref condition, ref then, ref elseifs, ref else_ } => {
+            visitor.visit_expression(condition);
+            visitor.visit_block(then);
+            for (condition, block) in elseifs {
+                visitor.visit_expression(condition);
+                visitor.visit_block(block);
+            }
+            if let Some(ref block) = *else_ {
+                visitor.visit_block(block);
+            }
+        }
+        Statement::ForStepping { ref from, ref to, ref step, ref block, .. } => {
+            visitor.visit_expression(from);
+            visitor.visit_expression(to);
+            if let Some(ref step) = *step {
+                visitor.visit_expression(step);
+            }
+            visitor.visit_block(block);
+        }
+        Statement::ForIn { ref in_, ref do_, .. } => {
+            visitor.visit_expression_list(in_);
+            visitor.visit_block(do_);
+        }
+        Statement::Function(_, ref body) | Statement::LocalFunction { ref body, .. } => {
+            visitor.visit_function_body(body)
+        }
+        Statement::LocalVariableBinding(_, ref expressions) => {
+            if let Some(ref expressions) = *expressions {
+                visitor.visit_expression_list(expressions);
+            }
+        }
+    }
+}
+
+pub fn walk_return_statement<V: Visitor + ?Sized>(
+    visitor: &mut V,
+    return_statement: &ReturnStatement,
+) {
+    if let Some(ref expressions) = return_statement.0 {
+        visitor.visit_expression_list(expressions);
+    }
+}
+
+pub fn walk_expression<V: Visitor + ?Sized>(visitor: &mut V, expression: &Spanned<Expression>) {
+    match expression.node {
+        Expression::Nil |
+        Expression::False |
+        Expression::True |
+        Expression::Integer(..) |
+        Expression::Float(..) |
+        Expression::String(..) |
+        Expression::ExtendedArgumentAccess |
+        Expression::Error => {}
+        Expression::FunctionDefine(ref define) => visitor.visit_function_body(&define.0),
+        Expression::PrefixExpression(ref prefix) => visitor.visit_prefix_expression(prefix),
+        Expression::TableConstructor(ref table) => visitor.visit_table_constructor(table),
+        Expression::BinaryOperation(_, ref lhs, ref rhs) => {
+            visitor.visit_expression(lhs);
+            visitor.visit_expression(rhs);
+        }
+        Expression::UnaryOperation(_, ref operand) => visitor.visit_expression(operand),
+    }
+}
+
+pub fn walk_expression_list<V: Visitor + ?Sized>(visitor: &mut V, expressions: &ExpressionList) {
+    visitor.visit_expression(&expressions.0);
+    if let Some(ref rest) = expressions.1 {
+        for expression in rest {
+            visitor.visit_expression(expression);
+        }
+    }
+}
+
+pub fn walk_prefix_expression<V: Visitor + ?Sized>(visitor: &mut V, prefix: &PrefixExpression) {
+    match *prefix {
+        PrefixExpression::Variable(ref variable) => visitor.visit_variable(variable),
+        PrefixExpression::FunctionCall(ref call) => visitor.visit_function_call(call),
+        PrefixExpression::Parenthesis(ref expression) => visitor.visit_expression(expression),
+    }
+}
+
+pub fn walk_variable<V: Visitor + ?Sized>(visitor: &mut V, variable: &Variable) {
+    match *variable {
+        Variable::Name(_) => {}
+        Variable::ArrayAccess { ref from, ref key } => {
+            visitor.visit_prefix_expression(from);
+            visitor.visit_expression(key);
+        }
+        Variable::DotAccess { ref from, .. } => visitor.visit_prefix_expression(from),
+    }
+}
+
+pub fn walk_function_call<V: Visitor + ?Sized>(visitor: &mut V, call: &FunctionCall) {
+    match *call {
+        FunctionCall::Static(ref from, ref arguments) |
+        FunctionCall::SelfTaking(ref from, _, ref arguments) => {
+            visitor.visit_prefix_expression(from);
+            visitor.visit_function_arguments(arguments);
+        }
+    }
+}
+
+pub fn walk_function_arguments<V: Visitor + ?Sized>(
+    visitor: &mut V,
+    arguments: &FunctionArguments,
+) {
+    match *arguments {
+        FunctionArguments::Parenthesis(ref expressions) => {
+            if let Some(ref expressions) = *expressions {
+                visitor.visit_expression_list(expressions);
+            }
+        }
+        FunctionArguments::TableConstructor(ref table) => visitor.visit_table_constructor(table),
+        FunctionArguments::String(..) => {}
+    }
+}
+
+pub fn walk_function_body<V: Visitor + ?Sized>(visitor: &mut V, body: &FunctionBody) {
+    visitor.visit_block(&body.1);
+}
+
+pub fn walk_table_constructor<V: Visitor + ?Sized>(visitor: &mut V, table: &TableConstructor) {
+    if let Some(ref fields) = table.0 {
+        visitor.visit_field(&fields.0);
+        if let Some(ref rest) = fields.1 {
+            for field in rest {
+                visitor.visit_field(field);
+            }
+        }
+    }
+}
+
+pub fn walk_field<V: Visitor + ?Sized>(visitor: &mut V, field: &Field) {
+    match *field {
+        Field::ExpressionForName { ref name, ref equals } => {
+            visitor.visit_expression(name);
+            visitor.visit_expression(equals);
+        }
+        Field::Equals { ref equals, .. } => visitor.visit_expression(equals),
+        Field::ArrayStyle(ref expression) => visitor.visit_expression(expression),
+    }
+}
+
+/// Combines every expression of the chunk into a result, starting from `init` and calling `f`
+/// with the result so far and each expression in turn. An expression comes before the
+/// expressions inside it, and otherwise they're in the order they're written in.
+///
+/// # Example
+/// ```
+/// use buildlua::ast::Expression;
+/// use buildlua::parser::parse;
+/// use buildlua::visit::fold_chunk;
+///
+/// let chunk = parse("local x = 1 + f(2, {3}) return x * 4.5").unwrap();
+/// let sum = fold_chunk(&chunk, 0.0, |sum, expression| match *expression {
+///     Expression::Integer(n, _) => sum + n as f64,
+///     Expression::Float(n, _) => sum + n,
+///     _ => sum,
+/// });
+/// assert_eq!(sum, 10.5);
+/// ```
+pub fn fold_chunk<T, F: FnMut(T, &Expression) -> T>(chunk: &Chunk, init: T, f: F) -> T {
+    let mut folder = Folder { result: Some(init), f };
+    walk_chunk(&mut folder, chunk);
+    folder.result.expect("the result is put back after each expression")
+}
+
+struct Folder<T, F> {
+    /// The result so far, only taken out while `f` is being called.
+    result: Option<T>,
+    f: F,
+}
+
+impl<T, F: FnMut(T, &Expression) -> T> Visitor for Folder<T, F> {
+    fn visit_expression(&mut self, expression: &Spanned<Expression>) {
+        let result = self.result.take().expect("the result is put back after each expression");
+        self.result = Some((self.f)(result, &expression.node));
+        walk_expression(self, expression);
+    }
+}
+
+pub trait VisitorMut {
+    fn visit_block_mut(&mut self, block: &mut Block) {
+        walk_block_mut(self, block)
+    }
+
+    fn visit_statement_mut(&mut self, statement: &mut Spanned<Statement>) {
+        walk_statement_mut(self, statement)
+    }
+
+    fn visit_return_statement_mut(&mut self, return_statement: &mut ReturnStatement) {
+        walk_return_statement_mut(self, return_statement)
+    }
+
+    fn visit_expression_mut(&mut self, expression: &mut Spanned<Expression>) {
+        walk_expression_mut(self, expression)
+    }
+
+    fn visit_expression_list_mut(&mut self, expressions: &mut ExpressionList) {
+        walk_expression_list_mut(self, expressions)
+    }
+
+    fn visit_prefix_expression_mut(&mut self, prefix: &mut PrefixExpression) {
+        walk_prefix_expression_mut(self, prefix)
+    }
+
+    fn visit_variable_mut(&mut self, variable: &mut Variable) {
+        walk_variable_mut(self, variable)
+    }
+
+    fn visit_function_call_mut(&mut self, call: &mut FunctionCall) {
+        walk_function_call_mut(self, call)
+    }
+
+    fn visit_function_arguments_mut(&mut self, arguments: &mut FunctionArguments) {
+        walk_function_arguments_mut(self, arguments)
+    }
+
+    fn visit_function_body_mut(&mut self, body: &mut FunctionBody) {
+        walk_function_body_mut(self, body)
+    }
+
+    fn visit_table_constructor_mut(&mut self, table: &mut TableConstructor) {
+        walk_table_constructor_mut(self, table)
+    }
+
+    fn visit_field_mut(&mut self, field: &mut Field) {
+        walk_field_mut(self, field)
+    }
+}
+
+/// Visits every node of a chunk mutably, starting with its block.
+pub fn walk_chunk_mut<V: VisitorMut + ?Sized>(visitor: &mut V, chunk: &mut Chunk) {
+    visitor.visit_block_mut(&mut chunk.0);
+}
+
+pub fn walk_block_mut<V: VisitorMut + ?Sized>(visitor: &mut V, block: &mut Block) {
+    if let Some(ref mut statements) = block.0 {
+        for statement in statements {
+            visitor.visit_statement_mut(statement);
+        }
+    }
+    if let Some(ref mut return_statement) = block.1 {
+        visitor.visit_return_statement_mut(return_statement);
+    }
+}
+
+pub fn walk_statement_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    statement: &mut Spanned<Statement>,
+) {
+    match statement.node {
+        Statement::Semicolon | Statement::Label(_) | Statement::Break | Statement::Goto(_) => {}
+        Statement::Assignment(ref mut variables, ref mut expressions) => {
+            visitor.visit_variable_mut(&mut variables.first);
+            if let Some(ref mut rest) = variables.rest {
+                for variable in rest {
+                    visitor.visit_variable_mut(variable);
+                }
+            }
+            visitor.visit_expression_list_mut(expressions);
+        }
+        Statement::FunctionCall(ref mut call) => visitor.visit_function_call_mut(call),
+        Statement::Do(ref mut block) => visitor.visit_block_mut(block),
+        Statement::While { ref mut exp, ref mut do_ } => {
+            visitor.visit_expression_mut(exp);
+            visitor.visit_block_mut(do_);
+        }
+        Statement::Repeat { ref mut block, ref mut until } => {
+            visitor.visit_block_mut(block);
+            visitor.visit_expression_mut(until);
+        }
+        Statement::If { ref mut condition, ref mut then, ref mut elseifs, ref mut else_ } => {
+            visitor.visit_expression_mut(condition);
+            visitor.visit_block_mut(then);
+            for (condition, block) in elseifs {
+                visitor.visit_expression_mut(condition);
+                visitor.visit_block_mut(block);
+            }
+            if let Some(ref mut block) = *else_ {
+                visitor.visit_block_mut(block);
+            }
+        }
+        Statement::ForStepping { ref mut from, ref mut to, ref mut step, ref mut block, .. } => {
+            visitor.visit_expression_mut(from);
+            visitor.visit_expression_mut(to);
+            if let Some(ref mut step) = *step {
+                visitor.visit_expression_mut(step);
+            }
+            visitor.visit_block_mut(block);
+        }
+        Statement::ForIn { ref mut in_, ref mut do_, .. } => {
+            visitor.visit_expression_list_mut(in_);
+            visitor.visit_block_mut(do_);
+        }
+        Statement::Function(_, ref mut body) | Statement::LocalFunction { ref mut body, .. } => {
+            visitor.visit_function_body_mut(body)
+        }
+        Statement::LocalVariableBinding(_, ref mut expressions) => {
+            if let Some(ref mut expressions) = *expressions {
+                visitor.visit_expression_list_mut(expressions);
+            }
+        }
+    }
+}
+
+pub fn walk_return_statement_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    return_statement: &mut ReturnStatement,
+) {
+    if let Some(ref mut expressions) = return_statement.0 {
+        visitor.visit_expression_list_mut(expressions);
+    }
+}
+
+pub fn walk_expression_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    expression: &mut Spanned<Expression>,
+) {
+    match expression.node {
+        Expression::Nil |
+        Expression::False |
+        Expression::True |
+        Expression::Integer(..) |
+        Expression::Float(..) |
+        Expression::String(..) |
+        Expression::ExtendedArgumentAccess |
+        Expression::Error => {}
+        Expression::FunctionDefine(ref mut define) => {
+            visitor.visit_function_body_mut(&mut define.0)
+        }
+        Expression::PrefixExpression(ref mut prefix) => visitor.visit_prefix_expression_mut(prefix),
+        Expression::TableConstructor(ref mut table) => visitor.visit_table_constructor_mut(table),
+        Expression::BinaryOperation(_, ref mut lhs, ref mut rhs) => {
+            visitor.visit_expression_mut(lhs);
+            visitor.visit_expression_mut(rhs);
+        }
+        Expression::UnaryOperation(_, ref mut operand) => visitor.visit_expression_mut(operand),
+    }
+}
+
+pub fn walk_expression_list_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    expressions: &mut ExpressionList,
+) {
+    visitor.visit_expression_mut(&mut expressions.0);
+    if let Some(ref mut rest) = expressions.1 {
+        for expression in rest {
+            visitor.visit_expression_mut(expression);
+        }
+    }
+}
+
+pub fn walk_prefix_expression_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    prefix: &mut PrefixExpression,
+) {
+    match *prefix {
+        PrefixExpression::Variable(ref mut variable) => visitor.visit_variable_mut(variable),
+        PrefixExpression::FunctionCall(ref mut call) => visitor.visit_function_call_mut(call),
+        PrefixExpression::Parenthesis(ref mut expression) => {
+            visitor.visit_expression_mut(expression)
+        }
+    }
+}
+
+pub fn walk_variable_mut<V: VisitorMut + ?Sized>(visitor: &mut V, variable: &mut Variable) {
+    match *variable {
+        Variable::Name(_) => {}
+        Variable::ArrayAccess { ref mut from, ref mut key } => {
+            visitor.visit_prefix_expression_mut(from);
+            visitor.visit_expression_mut(key);
+        }
+        Variable::DotAccess { ref mut from, .. } => visitor.visit_prefix_expression_mut(from),
+    }
+}
+
+pub fn walk_function_call_mut<V: VisitorMut + ?Sized>(visitor: &mut V, call: &mut FunctionCall) {
+    match *call {
+        FunctionCall::Static(ref mut from, ref mut arguments) |
+        FunctionCall::SelfTaking(ref mut from, _, ref mut arguments) => {
+            visitor.visit_prefix_expression_mut(from);
+            visitor.visit_function_arguments_mut(arguments);
+        }
+    }
+}
+
+pub fn walk_function_arguments_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    arguments: &mut FunctionArguments,
+) {
+    match *arguments {
+        FunctionArguments::Parenthesis(ref mut expressions) => {
+            if let Some(ref mut expressions) = *expressions {
+                visitor.visit_expression_list_mut(expressions);
+            }
+        }
+        FunctionArguments::TableConstructor(ref mut table) => {
+            visitor.visit_table_constructor_mut(table)
+        }
+        FunctionArguments::String(..) => {}
+    }
+}
+
+pub fn walk_function_body_mut<V: VisitorMut + ?Sized>(visitor: &mut V, body: &mut FunctionBody) {
+    visitor.visit_block_mut(&mut body.1);
+}
+
+pub fn walk_table_constructor_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    table: &mut TableConstructor,
+) {
+    if let Some(ref mut fields) = table.0 {
+        visitor.visit_field_mut(&mut fields.0);
+        if let Some(ref mut rest) = fields.1 {
+            for field in rest {
+                visitor.visit_field_mut(field);
+            }
+        }
+    }
+}
+
+pub fn walk_field_mut<V: VisitorMut + ?Sized>(visitor: &mut V, field: &mut Field) {
+    match *field {
+        Field::ExpressionForName { ref mut name, ref mut equals } => {
+            visitor.visit_expression_mut(name);
+            visitor.visit_expression_mut(equals);
+        }
+        Field::Equals { ref mut equals, .. } => visitor.visit_expression_mut(equals),
+        Field::ArrayStyle(ref mut expression) => visitor.visit_expression_mut(expression),
+    }
+}
+
+pub trait TryVisitor {
+    type Error;
+
+    fn try_visit_block(&mut self, block: &Block) -> Result<(), Self::Error> {
+        try_walk_block(self, block)
+    }
+
+    fn try_visit_statement(&mut self, statement: &Spanned<Statement>) -> Result<(), Self::Error> {
+        try_walk_statement(self, statement)
+    }
+
+    fn try_visit_return_statement(
+        &mut self,
+        return_statement: &ReturnStatement,
+    ) -> Result<(), Self::Error> {
+        try_walk_return_statement(self, return_statement)
+    }
+
+    fn try_visit_expression(
+        &mut self,
+        expression: &Spanned<Expression>,
+    ) -> Result<(), Self::Error> {
+        try_walk_expression(self, expression)
+    }
+
+    fn try_visit_expression_list(
+        &mut self,
+        expressions: &ExpressionList,
+    ) -> Result<(), Self::Error> {
+        try_walk_expression_list(self, expressions)
+    }
+
+    fn try_visit_prefix_expression(
+        &mut self,
+        prefix: &PrefixExpression,
+    ) -> Result<(), Self::Error> {
+        try_walk_prefix_expression(self, prefix)
+    }
+
+    fn try_visit_variable(&mut self, variable: &Variable) -> Result<(), Self::Error> {
+        try_walk_variable(self, variable)
+    }
+
+    fn try_visit_function_call(&mut self, call: &FunctionCall) -> Result<(), Self::Error> {
+        try_walk_function_call(self, call)
+    }
+
+    fn try_visit_function_arguments(
+        &mut self,
+        arguments: &FunctionArguments,
+    ) -> Result<(), Self::Error> {
+        try_walk_function_arguments(self, arguments)
+    }
+
+    fn try_visit_function_body(&mut self, body: &FunctionBody) -> Result<(), Self::Error> {
+        try_walk_function_body(self, body)
+    }
+
+    fn try_visit_table_constructor(
+        &mut self,
+        table: &TableConstructor,
+    ) -> Result<(), Self::Error> {
+        try_walk_table_constructor(self, table)
+    }
+
+    fn try_visit_field(&mut self, field: &Field) -> Result<(), Self::Error> {
+        try_walk_field(self, field)
+    }
+}
+
+/// Visits every node of a chunk, starting with its block, until a visitor method returns an
+/// error. Nothing after that node is visited, and the error is returned.
+pub fn try_walk_chunk<V: TryVisitor + ?Sized>(
+    visitor: &mut V,
+    chunk: &Chunk,
+) -> Result<(), V::Error> {
+    visitor.try_visit_block(&chunk.0)
+}
+
+pub fn try_walk_block<V: TryVisitor + ?Sized>(
+    visitor: &mut V,
+    block: &Block,
+) -> Result<(), V::Error> {
+    if let Some(ref statements) = block.0 {
+        for statement in statements {
+            visitor.try_visit_statement(statement)?;
+        }
+    }
+    if let Some(ref return_statement) = block.1 {
+        visitor.try_visit_return_statement(return_statement)?;
+    }
+    Ok(())
+}
+
+pub fn try_walk_statement<V: TryVisitor + ?Sized>(
+    visitor: &mut V,
+    statement: &Spanned<Statement>,
+) -> Result<(), V::Error> {
+    match statement.node {
+        Statement::Semicolon | Statement::Label(_) | Statement::Break | Statement::Goto(_) => {
+            Ok(())
+        }
+        Statement::Assignment(ref variables, ref expressions) => {
+            visitor.try_visit_variable(&variables.first)?;
+            if let Some(ref rest) = variables.rest {
+                for variable in rest {
+                    visitor.try_visit_variable(variable)?;
+                }
+            }
+            visitor.try_visit_expression_list(expressions)
+        }
+        Statement::FunctionCall(ref call) => visitor.try_visit_function_call(call),
+        Statement::Do(ref block) => visitor.try_visit_block(block),
+        Statement::While { ref exp, ref do_ } => {
+            visitor.try_visit_expression(exp)?;
+            visitor.try_visit_block(do_)
+        }
+        Statement::Repeat { ref block, ref until } => {
+            visitor.try_visit_block(block)?;
+            visitor.try_visit_expression(until)
+        }
+        Statement::If { ref condition, ref then, ref elseifs, ref else_ } => {
+            visitor.try_visit_expression(condition)?;
+            visitor.try_visit_block(then)?;
+            for (condition, block) in elseifs {
+                visitor.try_visit_expression(condition)?;
+                visitor.try_visit_block(block)?;
+            }
+            if let Some(ref block) = *else_ {
+                visitor.try_visit_block(block)?;
+            }
+            Ok(())
+        }
+        Statement::ForStepping { ref from, ref to, ref step, ref block, .. } => {
+            visitor.try_visit_expression(from)?;
+            visitor.try_visit_expression(to)?;
+            if let Some(ref step) = *step {
+                visitor.try_visit_expression(step)?;
+            }
+            visitor.try_visit_block(block)
+        }
+        Statement::ForIn { ref in_, ref do_, .. } => {
+            visitor.try_visit_expression_list(in_)?;
+            visitor.try_visit_block(do_)
+        }
+        Statement::Function(_, ref body) | Statement::LocalFunction { ref body, .. } => {
+            visitor.try_visit_function_body(body)
+        }
+        Statement::LocalVariableBinding(_, ref expressions) => match *expressions {
+            Some(ref expressions) => visitor.try_visit_expression_list(expressions),
+            None => Ok(()),
+        },
+    }
+}
+
+pub fn try_walk_return_statement<V: TryVisitor + ?Sized>(
+    visitor: &mut V,
+    return_statement: &ReturnStatement,
+) -> Result<(), V::Error> {
+    match return_statement.0 {
+        Some(ref expressions) => visitor.try_visit_expression_list(expressions),
+        None => Ok(()),
+    }
+}
+
+pub fn try_walk_expression<V: TryVisitor + ?Sized>(
+    visitor: &mut V,
+    expression: &Spanned<Expression>,
+) -> Result<(), V::Error> {
+    match expression.node {
+        Expression::Nil |
+        Expression::False |
+        Expression::True |
+        Expression::Integer(..) |
+        Expression::Float(..) |
+        Expression::String(..) |
+        Expression::ExtendedArgumentAccess |
+        Expression::Error => Ok(()),
+        Expression::FunctionDefine(ref define) => visitor.try_visit_function_body(&define.0),
+        Expression::PrefixExpression(ref prefix) => visitor.try_visit_prefix_expression(prefix),
+        Expression::TableConstructor(ref table) => visitor.try_visit_table_constructor(table),
+        Expression::BinaryOperation(_, ref lhs, ref rhs) => {
+            visitor.try_visit_expression(lhs)?;
+            visitor.try_visit_expression(rhs)
+        }
+        Expression::UnaryOperation(_, ref operand) => visitor.try_visit_expression(operand),
+    }
+}
+
+pub fn try_walk_expression_list<V: TryVisitor + ?Sized>(
+    visitor: &mut V,
+    expressions: &ExpressionList,
+) -> Result<(), V::Error> {
+    visitor.try_visit_expression(&expressions.0)?;
+    if let Some(ref rest) = expressions.1 {
+        for expression in rest {
+            visitor.try_visit_expression(expression)?;
+        }
+    }
+    Ok(())
+}
+
+pub fn try_walk_prefix_expression<V: TryVisitor + ?Sized>(
+    visitor: &mut V,
+    prefix: &PrefixExpression,
+) -> Result<(), V::Error> {
+    match *prefix {
+        PrefixExpression::Variable(ref variable) => visitor.try_visit_variable(variable),
+        PrefixExpression::FunctionCall(ref call) => visitor.try_visit_function_call(call),
+        PrefixExpression::Parenthesis(ref expression) => visitor.try_visit_expression(expression),
+    }
+}
+
+pub fn try_walk_variable<V: TryVisitor + ?Sized>(
+    visitor: &mut V,
+    variable: &Variable,
+) -> Result<(), V::Error> {
+    match *variable {
+        Variable::Name(_) => Ok(()),
+        Variable::ArrayAccess { ref from, ref key } => {
+            visitor.try_visit_prefix_expression(from)?;
+            visitor.try_visit_expression(key)
+        }
+        Variable::DotAccess { ref from, .. } => visitor.try_visit_prefix_expression(from),
+    }
+}
+
+pub fn try_walk_function_call<V: TryVisitor + ?Sized>(
+    visitor: &mut V,
+    call: &FunctionCall,
+) -> Result<(), V::Error> {
+    match *call {
+        FunctionCall::Static(ref from, ref arguments) |
+        FunctionCall::SelfTaking(ref from, _, ref arguments) => {
+            visitor.try_visit_prefix_expression(from)?;
+            visitor.try_visit_function_arguments(arguments)
+        }
+    }
+}
+
+pub fn try_walk_function_arguments<V: TryVisitor + ?Sized>(
+    visitor: &mut V,
+    arguments: &FunctionArguments,
+) -> Result<(), V::Error> {
+    match *arguments {
+        FunctionArguments::Parenthesis(Some(ref expressions)) => {
+            visitor.try_visit_expression_list(expressions)
+        }
+        FunctionArguments::TableConstructor(ref table) => {
+            visitor.try_visit_table_constructor(table)
+        }
+        FunctionArguments::Parenthesis(None) | FunctionArguments::String(..) => Ok(()),
+    }
+}
+
+pub fn try_walk_function_body<V: TryVisitor + ?Sized>(
+    visitor: &mut V,
+    body: &FunctionBody,
+) -> Result<(), V::Error> {
+    visitor.try_visit_block(&body.1)
+}
+
+pub fn try_walk_table_constructor<V: TryVisitor + ?Sized>(
+    visitor: &mut V,
+    table: &TableConstructor,
+) -> Result<(), V::Error> {
+    if let Some(ref fields) = table.0 {
+        visitor.try_visit_field(&fields.0)?;
+        if let Some(ref rest) = fields.1 {
+            for field in rest {
+                visitor.try_visit_field(field)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn try_walk_field<V: TryVisitor + ?Sized>(
+    visitor: &mut V,
+    field: &Field,
+) -> Result<(), V::Error> {
+    match *field {
+        Field::ExpressionForName { ref name, ref equals } => {
+            visitor.try_visit_expression(name)?;
+            visitor.try_visit_expression(equals)
+        }
+        Field::Equals { ref equals, .. } => visitor.try_visit_expression(equals),
+        Field::ArrayStyle(ref expression) => visitor.try_visit_expression(expression),
+    }
+}
@@ -0,0 +1,71 @@
+//! Checks that parsing a borrowed tree allocates less than parsing an owned one.
+//!
+//! The global allocator counts the allocations made on each thread, so the tests running at the
+//! same time don't count each other's.
+
+#![cfg(feature = "std")]
+
+extern crate buildlua;
+
+mod common;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use buildlua::borrowed::BorrowedChunk;
+use buildlua::parser::parse;
+
+struct Counting;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+/// The number of allocations `f` makes.
+fn allocations<T>(f: impl FnOnce() -> T) -> usize {
+    let before = ALLOCATIONS.with(Cell::get);
+    let result = f();
+    let count = ALLOCATIONS.with(Cell::get) - before;
+    drop(result);
+    count
+}
+
+/// Asserts that parsing `src` into a borrowed tree makes fewer allocations than into an owned one,
+/// returning both counts.
+fn assert_fewer(src: &str) -> (usize, usize) {
+    let owned = allocations(|| parse(src).unwrap());
+    let borrowed = allocations(|| BorrowedChunk::parse(src).unwrap());
+    assert!(borrowed < owned, "{} borrowed allocations against {} owned", borrowed, owned);
+    (borrowed, owned)
+}
+
+#[test]
+fn corpus() {
+    for (path, src) in common::corpus() {
+        println!("{}", path.display());
+        assert_fewer(&src);
+    }
+}
+
+#[test]
+fn names_and_comments_dont_allocate() {
+    // Every name, number and comment is a string of its own in the owned tree.
+    let src = "local alpha, beta = gamma.delta, 0x10 -- a comment\nepsilon(alpha, beta)\n";
+    let (borrowed, owned) = assert_fewer(src);
+    let names = "alpha beta gamma delta epsilon alpha beta".split(' ').count();
+    assert!(owned - borrowed >= names + 2, "{} borrowed against {} owned", borrowed, owned);
+}
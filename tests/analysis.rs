@@ -0,0 +1,73 @@
+//! Checks the control flow analyses on parsed chunks.
+
+#![cfg(feature = "std")]
+
+extern crate buildlua;
+
+use buildlua::analysis::{find_unreachable, is_tail_call, tail_calls};
+use buildlua::parser::parse;
+
+/// Whether the return statement ending `src` is a tail call.
+fn returns_tail_call(src: &str) -> bool {
+    let chunk = parse(src).unwrap_or_else(|error| panic!("failed to parse the source: {}", error));
+    is_tail_call((chunk.0).1.as_ref().expect("the source doesn't end with a return"))
+}
+
+#[test]
+fn single_calls_are_tail_calls() {
+    assert!(returns_tail_call("return f(x)"));
+    assert!(returns_tail_call("return f()"));
+    assert!(returns_tail_call("return o:m(1, 2)"));
+    assert!(returns_tail_call("return t.f 'a'"));
+}
+
+#[test]
+fn other_returns_are_not_tail_calls() {
+    assert!(!returns_tail_call("return"));
+    assert!(!returns_tail_call("return x"));
+    assert!(!returns_tail_call("return f(x) + 1"));
+    assert!(!returns_tail_call("return f(), g()"));
+    assert!(!returns_tail_call("return x, f()"));
+    // The parentheses cut the call's values down to one.
+    assert!(!returns_tail_call("return (f(x))"));
+}
+
+#[test]
+fn tail_calls_in_nested_functions() {
+    let src = "
+        local function loop(n) if n > 0 then return loop(n - 1) end return n end
+        return g(function() return h() end)
+    ";
+    let chunk = parse(src).unwrap();
+    let calls: Vec<_> = tail_calls(&chunk).iter().map(|span| &src[span.start..span.end]).collect();
+    assert_eq!(calls, vec!["loop(n - 1)", "g(function() return h() end)", "h()"]);
+}
+
+/// The source of each statement `find_unreachable` flags in `src`.
+fn unreachable(src: &str) -> Vec<&str> {
+    let chunk = parse(src).unwrap_or_else(|error| panic!("failed to parse the source: {}", error));
+    find_unreachable(&chunk).iter().map(|span| &src[span.start..span.end]).collect()
+}
+
+#[test]
+fn dead_code_after_a_return() {
+    assert_eq!(unreachable("do return end print(1) x = 2"), vec!["print(1)", "x = 2"]);
+    let src = "function f() if x then return 1 else return 2 end print(3) end";
+    assert_eq!(unreachable(src), vec!["print(3)"]);
+}
+
+#[test]
+fn dead_code_after_break_and_goto() {
+    assert_eq!(unreachable("while x do break y() end"), vec!["y()"]);
+    assert_eq!(unreachable("goto skip y() ::skip:: z()"), vec!["y()"]);
+}
+
+#[test]
+fn a_return_in_one_branch_leaves_the_rest_reachable() {
+    assert_eq!(unreachable("if x then return end print(1)"), Vec::<&str>::new());
+    let src = "if x then return elseif y then break end print(1)";
+    assert_eq!(unreachable(src), Vec::<&str>::new());
+    // Jumping to a label in the block carries on past its end.
+    assert_eq!(unreachable("do goto a ::a:: end print(1)"), Vec::<&str>::new());
+    assert_eq!(unreachable("while x do if y then break end end print(1)"), Vec::<&str>::new());
+}
@@ -0,0 +1,33 @@
+//! Checks that trees survive being copied into an arena and back.
+
+#![cfg(feature = "std")]
+
+extern crate buildlua;
+
+mod common;
+
+use buildlua::arena::Arena;
+use buildlua::parser::parse;
+
+#[test]
+fn corpus() {
+    for (path, src) in common::corpus() {
+        println!("{}", path.display());
+        let chunk = parse(&src).expect("failed to parse a corpus file");
+        let arena = Arena::from_chunk(&chunk);
+        assert_eq!(arena.to_chunk(), chunk);
+    }
+}
+
+#[test]
+fn spans_are_kept() {
+    let src = "local x = 1\nprint(x + 2)";
+    let chunk = parse(src).unwrap();
+    let arena = Arena::from_chunk(&chunk);
+    let statements = &arena[arena.root()].statements;
+    let spans: Vec<_> = arena.items(*statements).map(|id| arena[id].span).collect();
+    let back = arena.to_chunk();
+    let expected: Vec<_> = back.0.0.as_ref().unwrap().iter().map(|statement| statement.span).collect();
+    assert_eq!(spans, expected);
+    assert_eq!(&src[spans[1].start..spans[1].end], "print(x + 2)");
+}
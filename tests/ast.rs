@@ -0,0 +1,145 @@
+//! Checks building syntax trees by hand and what the derived traits of the nodes give.
+
+extern crate buildlua;
+
+use buildlua::ast::*;
+
+fn binary(op: BinaryOperation, lhs: Expression, rhs: Expression) -> Expression {
+    Expression::BinaryOperation(op, Box::new(lhs.into()), Box::new(rhs.into()))
+}
+
+fn variable(name: &str) -> Box<PrefixExpression> {
+    let variable = Variable::Name(name.to_string().into());
+    Box::new(PrefixExpression::Variable(Box::new(variable)))
+}
+
+fn name(name: &str) -> Expression {
+    Expression::PrefixExpression(variable(name))
+}
+
+fn unary(op: UnaryOperation, operand: Expression) -> Expression {
+    Expression::UnaryOperation(op, Box::new(operand.into()))
+}
+
+/// `1 + 2 * 3`, with the multiplication nested in the addition.
+fn one_plus_two_times_three() -> Expression {
+    let product = binary(BinaryOperation::Times, 2.into(), 3.into());
+    binary(BinaryOperation::Plus, 1.into(), product)
+}
+
+#[test]
+fn binary_operations_nest_by_precedence() {
+    let sum = one_plus_two_times_three();
+    let (lhs, rhs) = match sum {
+        Expression::BinaryOperation(BinaryOperation::Plus, ref lhs, ref rhs) => (lhs, rhs),
+        ref other => panic!("{:?}", other),
+    };
+    assert_eq!(lhs.node, Expression::from(1));
+    match rhs.node {
+        Expression::BinaryOperation(BinaryOperation::Times, ref lhs, ref rhs) => {
+            assert_eq!(lhs.node, Expression::from(2));
+            assert_eq!(rhs.node, Expression::from(3));
+        }
+        ref other => panic!("{:?}", other),
+    }
+}
+
+#[test]
+fn unary_operations_hold_their_operand() {
+    match unary(UnaryOperation::Length, name("t")) {
+        Expression::UnaryOperation(UnaryOperation::Length, ref operand) => {
+            assert_eq!(operand.node, name("t"));
+        }
+        ref other => panic!("{:?}", other),
+    }
+
+    let parenthesized = PrefixExpression::Parenthesis(Box::new(name("a").into()));
+    let parenthesized = Expression::PrefixExpression(Box::new(parenthesized));
+    match unary(UnaryOperation::Negate, parenthesized) {
+        Expression::UnaryOperation(UnaryOperation::Negate, ref operand) => match operand.node {
+            Expression::PrefixExpression(ref prefix) => match **prefix {
+                PrefixExpression::Parenthesis(ref inner) => assert_eq!(inner.node, name("a")),
+                ref other => panic!("{:?}", other),
+            },
+            ref other => panic!("{:?}", other),
+        },
+        ref other => panic!("{:?}", other),
+    }
+}
+
+#[test]
+fn if_statements_hold_their_elseifs_and_else() {
+    let call = |f: &str| {
+        let arguments = Box::new(FunctionArguments::Parenthesis(None));
+        let call = FunctionCall::Static(variable(f), arguments);
+        Block::from_statements(vec![Statement::FunctionCall(Box::new(call)).into()])
+    };
+    // if a then f() elseif b then g() else h() end
+    let statement = Statement::If {
+        condition: Box::new(name("a").into()),
+        then: Box::new(call("f")),
+        elseifs: vec![(Box::new(name("b").into()), Box::new(call("g")))],
+        else_: Some(Box::new(call("h"))),
+    };
+    match statement {
+        Statement::If { ref elseifs, ref else_, .. } => {
+            assert_eq!(elseifs.len(), 1);
+            assert_eq!(elseifs[0].0.node, name("b"));
+            assert_eq!(**else_.as_ref().unwrap(), call("h"));
+        }
+        ref other => panic!("{:?}", other),
+    }
+}
+
+#[test]
+fn debug_output_shows_nested_nodes() {
+    let debug = format!("{:?}", one_plus_two_times_three());
+    assert_eq!(
+        debug,
+        "BinaryOperation(Plus, Integer(1, Plain) @ 0..0, BinaryOperation(Times, Integer(2, Plain) \
+         @ 0..0, Integer(3, Plain) @ 0..0) @ 0..0)"
+    );
+}
+
+/// `x = 1 + 2 * 3`
+fn assignment() -> Chunk {
+    let x = Variable::Name("x".to_string().into());
+    let variables = VariableList { first: Box::new(x), rest: None };
+    let values = ExpressionList(Box::new(one_plus_two_times_three().into()), None);
+    let statement = Statement::Assignment(Box::new(variables), Box::new(values));
+    Chunk(Block::from_statements(vec![statement.into()]), Vec::new())
+}
+
+#[test]
+fn clones_are_independent() {
+    let original = assignment();
+    let mut copy = original.clone();
+    assert_eq!(copy, original);
+    for statement in copy.0.statements_mut() {
+        statement.node = Statement::Break;
+    }
+    assert_ne!(copy, original);
+    assert_eq!(original, assignment());
+    assert!(matches!(original.0.statements().next().unwrap().node, Statement::Assignment(..)));
+}
+
+#[test]
+fn trees_built_separately_compare_by_their_nodes() {
+    assert_eq!(assignment(), assignment());
+    assert_eq!(one_plus_two_times_three(), one_plus_two_times_three());
+
+    let other_operator = binary(
+        BinaryOperation::Minus,
+        1.into(),
+        binary(BinaryOperation::Times, 2.into(), 3.into()),
+    );
+    assert_ne!(one_plus_two_times_three(), other_operator);
+    let swapped = binary(
+        BinaryOperation::Plus,
+        binary(BinaryOperation::Times, 2.into(), 3.into()),
+        1.into(),
+    );
+    assert_ne!(one_plus_two_times_three(), swapped);
+    // Integers and floats are different literals even when they're the same number.
+    assert_ne!(Expression::from(1), Expression::from(1.0));
+}
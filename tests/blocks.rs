@@ -0,0 +1,54 @@
+//! Checks the iterators over a block's statements.
+
+#![cfg(feature = "std")]
+
+extern crate buildlua;
+
+use buildlua::ast::*;
+use buildlua::parser::parse;
+use buildlua::span::Spanned;
+
+#[test]
+fn empty_block() {
+    let mut block: Block = Block(None, None);
+    assert_eq!(block.statements().count(), 0);
+    assert_eq!(block.statements_mut().count(), 0);
+    assert!(block.is_empty());
+    assert!(Block::<Owned>(Some(Vec::new()), None).is_empty());
+}
+
+#[test]
+fn three_statements() {
+    let Chunk(mut block, _) = parse("local a = 1\nf(a)\n;\nreturn a").unwrap();
+    let statements: Vec<_> = block.statements().map(|statement| &statement.node).collect();
+    assert_eq!(statements.len(), 3);
+    assert!(matches!(statements[0], Statement::LocalVariableBinding(..)));
+    assert!(matches!(statements[1], Statement::FunctionCall(_)));
+    assert_eq!(*statements[2], Statement::Semicolon);
+    assert!(!block.is_empty());
+    for statement in block.statements_mut() {
+        statement.node = Statement::Semicolon;
+    }
+    assert!(block.statements().all(|statement| statement.node == Statement::Semicolon));
+}
+
+#[test]
+fn return_only() {
+    let Chunk(block, _) = parse("return").unwrap();
+    assert_eq!(block.statements().count(), 0);
+    assert!(!block.is_empty());
+}
+
+#[test]
+fn constructors() {
+    let empty = Block::empty();
+    assert_eq!(empty.statements().count(), 0);
+    assert_eq!(empty, Block(None, None));
+    assert_eq!(Block::from_statements(Vec::new()), Block(None, None));
+
+    let Chunk(block, _) = parse("f()\ng()").unwrap();
+    let statements: Vec<_> = block.statements().cloned().collect();
+    let chunk = Chunk::new(Block::from_statements(statements));
+    assert_eq!(chunk, parse("f() g()").unwrap());
+    assert_eq!(NameList::single(Spanned::dummy("a".to_string())).1, None);
+}
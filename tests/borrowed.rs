@@ -0,0 +1,104 @@
+//! Checks that the borrowed tree points into the source and converts back to the parsed one.
+
+#![cfg(feature = "std")]
+
+extern crate buildlua;
+
+mod common;
+
+use std::borrow::Cow;
+
+use buildlua::borrowed::*;
+use buildlua::parser::parse;
+
+/// Whether the bytes lie within `src`.
+fn within(src: &str, bytes: &[u8]) -> bool {
+    let range = src.as_bytes().as_ptr_range();
+    let end = bytes.as_ptr() as usize + bytes.len();
+    range.contains(&bytes.as_ptr()) && end <= range.end as usize
+}
+
+/// Whether the text is borrowed from somewhere within `src`.
+fn aliases(src: &str, text: &Text) -> bool {
+    matches!(*text, Cow::Borrowed(text) if within(src, text.as_bytes()))
+}
+
+/// Whether the bytes of a string are borrowed from somewhere within `src`.
+fn aliases_bytes(src: &str, bytes: &Bytes) -> bool {
+    matches!(*bytes, Cow::Borrowed(bytes) if within(src, bytes))
+}
+
+fn statements<'c, 'a>(block: &'c Block<'a>) -> Vec<&'c Statement<'a>> {
+    block.0.iter().flatten().map(|statement| &statement.node).collect()
+}
+
+#[test]
+fn converts_back_to_the_parsed_chunk() {
+    for (path, src) in common::corpus() {
+        let borrowed = BorrowedChunk::parse(&src).unwrap();
+        assert_eq!(borrowed.to_chunk(), parse(&src).unwrap(), "{}", path.display());
+        for comment in &borrowed.1 {
+            assert!(aliases(&src, &comment.text), "{}: {:?}", path.display(), comment.text);
+        }
+    }
+}
+
+#[test]
+fn names_and_strings_alias_the_source() {
+    let src = "local greeting = 'hello' -- says hello\nobj:method(0x10, [[long]])\n";
+    let chunk = BorrowedChunk::parse(src).unwrap();
+    let statements = statements(&chunk.0);
+
+    let (names, values) = match *statements[0] {
+        Statement::LocalVariableBinding(ref names, Some(ref values)) => (names, values),
+        ref other => panic!("{:?}", other),
+    };
+    assert!(aliases(src, &names.0.name.node));
+    assert_eq!(names.0.name.node, "greeting");
+    match values.0.node {
+        Expression::String(ref s, _) => assert!(aliases_bytes(src, s) && **s == *b"hello"),
+        ref other => panic!("{:?}", other),
+    }
+    assert!(aliases(src, &chunk.1[0].text));
+    assert_eq!(chunk.1[0].text, " says hello");
+
+    let (method, arguments) = match *statements[1] {
+        Statement::FunctionCall(ref call) => match **call {
+            FunctionCall::SelfTaking(_, ref method, ref arguments) => (method, arguments),
+            ref other => panic!("{:?}", other),
+        },
+        ref other => panic!("{:?}", other),
+    };
+    assert!(aliases(src, method));
+    let arguments = match **arguments {
+        FunctionArguments::Parenthesis(Some(ref arguments)) => arguments,
+        ref other => panic!("{:?}", other),
+    };
+    match arguments.0.node {
+        Expression::Integer(16, NumberForm::Written(ref text)) => {
+            assert!(aliases(src, text) && text == "0x10")
+        }
+        ref other => panic!("{:?}", other),
+    }
+    match arguments.1.as_ref().unwrap()[0].node {
+        Expression::String(ref s, _) => assert!(aliases_bytes(src, s) && **s == *b"long"),
+        ref other => panic!("{:?}", other),
+    }
+}
+
+#[test]
+fn escaped_strings_are_owned() {
+    let src = "return 'tab\\there', 'plain'\n";
+    let chunk = BorrowedChunk::parse(src).unwrap();
+    let values = chunk.0 .1.as_ref().unwrap().0.as_ref().unwrap();
+    match values.0.node {
+        Expression::String(Cow::Owned(ref s), _) => assert_eq!(s, b"tab\there"),
+        ref other => panic!("{:?}", other),
+    }
+    match values.1.as_ref().unwrap()[0].node {
+        Expression::String(ref s, _) => assert!(aliases_bytes(src, s)),
+        ref other => panic!("{:?}", other),
+    }
+    assert_eq!(chunk.to_chunk(), parse(src).unwrap());
+}
+
@@ -0,0 +1,47 @@
+//! Checks that the builder makes the same trees as writing the nodes out by hand.
+
+#![cfg(feature = "std")]
+
+extern crate buildlua;
+
+use buildlua::ast::*;
+use buildlua::build::{expr, stmt, var, BlockBuilder};
+use buildlua::parser::parse;
+use buildlua::span::Spanned;
+
+#[test]
+fn assignment_matches_the_tree_built_by_hand() {
+    let built = BlockBuilder::new()
+        .push(stmt::assign(
+            vec![var::name("x")],
+            vec![expr::binop(BinaryOperation::Plus, expr::integer(1), expr::integer(2))],
+        ))
+        .chunk();
+
+    let sum = Expression::BinaryOperation(
+        BinaryOperation::Plus,
+        Box::new(Spanned::dummy(Expression::Integer(1, NumberForm::Plain))),
+        Box::new(Spanned::dummy(Expression::Integer(2, NumberForm::Plain))),
+    );
+    let assignment = Statement::Assignment(
+        Box::new(VariableList {
+            first: Box::new(Variable::Name(Spanned::dummy("x".to_string()))),
+            rest: None,
+        }),
+        Box::new(ExpressionList(Box::new(Spanned::dummy(sum)), None)),
+    );
+    let by_hand = Chunk(Block(Some(vec![Spanned::dummy(assignment)]), None), Vec::new());
+
+    assert_eq!(built, by_hand);
+    assert_eq!(built, parse("x = 1 + 2").unwrap());
+    assert_eq!(built.to_string(), "x = 1 + 2\n");
+}
+
+#[test]
+fn blocks_with_returns() {
+    let built = BlockBuilder::new()
+        .push(stmt::local(&["a"], vec![expr::string("s")]))
+        .ret(vec![expr::name("a"), expr::nil()])
+        .chunk();
+    assert_eq!(built, parse("local a = \"s\" return a, nil").unwrap());
+}
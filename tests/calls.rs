@@ -0,0 +1,45 @@
+//! Checks that both forms of function call hold their arguments the same way.
+
+#![cfg(feature = "std")]
+
+extern crate buildlua;
+
+use buildlua::ast::*;
+use buildlua::parser::parse;
+
+/// The arguments of a call, whichever form it takes.
+fn arguments(call: &FunctionCall) -> &FunctionArguments {
+    match *call {
+        FunctionCall::Static(_, ref arguments) | FunctionCall::SelfTaking(_, _, ref arguments) => {
+            arguments
+        }
+    }
+}
+
+fn name(name: &str) -> Box<PrefixExpression> {
+    let variable = Variable::Name(name.to_string().into());
+    Box::new(PrefixExpression::Variable(Box::new(variable)))
+}
+
+#[test]
+fn both_forms_box_their_arguments() {
+    let form = StringForm::Short(Quote::Double);
+    let string = || Box::new(FunctionArguments::String(b"x".to_vec(), form));
+    let calls = vec![
+        FunctionCall::Static(name("f"), string()),
+        FunctionCall::SelfTaking(name("object"), "method".to_string(), string()),
+    ];
+    for call in &calls {
+        assert_eq!(*arguments(call), *string());
+    }
+}
+
+#[test]
+fn parsed_calls_match_built_ones() {
+    let chunk = parse("object:method 'x'").unwrap();
+    let statement = &chunk.0 .0.as_ref().unwrap()[0].node;
+    let form = StringForm::Short(Quote::Single);
+    let arguments = Box::new(FunctionArguments::String(b"x".to_vec(), form));
+    let call = FunctionCall::SelfTaking(name("object"), "method".to_string(), arguments);
+    assert_eq!(*statement, Statement::FunctionCall(Box::new(call)));
+}
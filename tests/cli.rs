@@ -0,0 +1,47 @@
+//! Runs the `buildlua` binary on files in the test directory.
+
+#![cfg(feature = "std")]
+
+use std::path::Path;
+use std::process::{Command, Output};
+
+fn buildlua(args: &[&str]) -> Output {
+    let tests = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests");
+    Command::new(env!("CARGO_BIN_EXE_buildlua"))
+        .current_dir(tests)
+        .args(args)
+        .output()
+        .expect("failed to run buildlua")
+}
+
+#[test]
+fn dumps_the_tree() {
+    let output = buildlua(&["corpus/functions.lua"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("Chunk("), "{}", stdout);
+    assert!(stdout.contains("FunctionBody("), "{}", stdout);
+}
+
+#[test]
+fn parse_error() {
+    let output = buildlua(&["fixtures/unexpected_token.lua"]);
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let expected = "error: fixtures/unexpected_token.lua:1:11: \
+                    unexpected '=', expected expression\n\
+                    local x = = 1\n          ^\n";
+    assert_eq!(stderr, expected);
+}
+
+#[test]
+fn missing_file() {
+    let output = buildlua(&["fixtures/missing.lua"]);
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn usage() {
+    assert_eq!(buildlua(&[]).status.code(), Some(2));
+    assert_eq!(buildlua(&["--frobnicate", "corpus/functions.lua"]).status.code(), Some(2));
+}
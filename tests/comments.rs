@@ -0,0 +1,64 @@
+//! Checks how comments are read, especially block comments in long brackets.
+
+#![cfg(feature = "std")]
+
+extern crate buildlua;
+
+use buildlua::ast::{CommentKind, Statement, StringForm};
+use buildlua::lexer::{LexErrorKind, Lexer, Token};
+use buildlua::parser::{parse, ParseError};
+use buildlua::span::Span;
+
+#[test]
+fn block_comment_levels_must_match() {
+    let chunk = parse("--[==[ a ]] b ]=] c ]==] x = 1").unwrap();
+    assert_eq!(chunk.1.len(), 1);
+    assert_eq!(chunk.1[0].text, " a ]] b ]=] c ");
+    assert_eq!(chunk.1[0].kind, CommentKind::Block(2));
+    assert_eq!(chunk.0.statements().count(), 1);
+}
+
+#[test]
+fn brackets_that_dont_open_a_long_bracket_start_a_line_comment() {
+    for source in ["--[= x\ny = 1", "--[ [x]]\ny = 1", "--[==x]==]\ny = 1"] {
+        let chunk = parse(source).unwrap();
+        assert_eq!(chunk.1[0].kind, CommentKind::Line, "{}", source);
+        assert_eq!(chunk.0.statements().count(), 1, "{}", source);
+    }
+}
+
+#[test]
+fn string_after_a_space_isnt_a_comment() {
+    let mut lexer = Lexer::new("x = - -[[s]]");
+    let tokens: Vec<_> = lexer.by_ref().map(Result::unwrap).collect();
+    assert_eq!(tokens.last(), Some(&Token::String(b"s".to_vec(), StringForm::Long(0))));
+    assert!(lexer.into_comments().is_empty());
+}
+
+#[test]
+fn unterminated_block_comment() {
+    match parse("x = 1 --[==[ never ]=] closed ]]") {
+        Err(ParseError::Lex(err)) => {
+            assert_eq!(err.kind, LexErrorKind::UnterminatedComment);
+            assert_eq!((err.span.start, err.span.end), (6, 32));
+        }
+        other => panic!("expected an unterminated comment, got {:?}", other),
+    }
+}
+
+#[test]
+fn comments_lead_into_the_statement_after_them() {
+    let chunk = parse("-- hi\nx = 1").unwrap();
+    let assignment = chunk.0.statements().next().unwrap();
+    assert!(matches!(assignment.node, Statement::Assignment(..)));
+    let leading = chunk.comments_in(Span::new(0, assignment.span.start));
+    assert_eq!(leading.len(), 1);
+    assert_eq!(leading[0].text, " hi");
+    assert_eq!(leading[0].kind, CommentKind::Line);
+    assert!(!leading[0].trailing);
+    assert_eq!(chunk.to_string(), "-- hi\nx = 1\n");
+
+    let chunk = parse("x = 1 -- one\ny = 2").unwrap();
+    assert!(chunk.1[0].trailing);
+    assert_eq!(chunk.to_string(), "x = 1 -- one\ny = 2\n");
+}
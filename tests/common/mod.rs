@@ -0,0 +1,96 @@
+//! Helpers shared by the integration tests.
+
+// Each test uses only some of the helpers.
+#![allow(dead_code)]
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use buildlua::ast::Chunk;
+use buildlua::parser::parse;
+
+/// The path and source of every `.lua` file in `tests/corpus`, in order of their names.
+pub fn corpus() -> Vec<(PathBuf, String)> {
+    let corpus = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("corpus");
+    let mut paths: Vec<_> = fs::read_dir(&corpus)
+        .expect("failed to read the corpus")
+        .map(|entry| entry.expect("failed to read the corpus").path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "lua"))
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty(), "the corpus is empty");
+    paths
+        .into_iter()
+        .map(|path| {
+            let src = fs::read_to_string(&path).expect("failed to read a corpus file");
+            (path, src)
+        })
+        .collect()
+}
+
+/// The files in `tests/fixtures/<dir>` that `filter` keeps, in order of their names.
+pub fn fixtures<F: Fn(&Path) -> bool>(dir: &str, filter: F) -> Vec<PathBuf> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("fixtures").join(dir);
+    let mut paths: Vec<_> = fs::read_dir(&dir)
+        .expect("failed to read the fixtures")
+        .map(|entry| entry.expect("failed to read the fixtures").path())
+        .filter(|path| filter(path))
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty(), "there are no fixtures");
+    paths
+}
+
+/// Parses every fixture and compares what `output` gives for it with the file of the same name
+/// but with `extension` instead, the `kind` of file that is. Running the tests with `BLESS=1` set
+/// writes the output to those files instead of comparing it, to update them after an intended
+/// change. `verb` says what was done to a fixture whose output differs.
+pub fn check_snapshots<F>(
+    fixtures: Vec<PathBuf>,
+    extension: &str,
+    kind: &str,
+    verb: &str,
+    output: F,
+) where
+    F: Fn(&Chunk) -> String,
+{
+    let bless = env::var_os("BLESS").is_some();
+    let mut mismatched = Vec::new();
+    for path in fixtures {
+        let src = fs::read_to_string(&path).expect("failed to read a fixture");
+        let chunk = parse(&src)
+            .unwrap_or_else(|error| panic!("failed to parse {}: {}", path.display(), error));
+        let found = output(&chunk);
+        let expected_path = path.with_extension(extension);
+        if bless {
+            fs::write(&expected_path, &found)
+                .unwrap_or_else(|error| panic!("failed to write a {}: {}", kind, error));
+            continue;
+        }
+        match fs::read_to_string(&expected_path) {
+            Ok(ref expected) if *expected == found => {}
+            Ok(expected) => {
+                mismatched.push(format!(
+                    "{} {} differently:\n--- expected\n{}--- found\n{}",
+                    path.display(),
+                    verb,
+                    expected,
+                    found,
+                ));
+            }
+            Err(_) => mismatched.push(format!("{} has no {}", path.display(), kind)),
+        }
+    }
+    assert!(
+        mismatched.is_empty(),
+        "{}\nrun the tests with BLESS=1 to update the {}s",
+        mismatched.join("\n"),
+        kind,
+    );
+}
+
+/// Parses `src`, which has to parse.
+pub fn chunk(src: &str) -> Chunk {
+    parse(src).unwrap_or_else(|error| panic!("failed to parse the source: {}", error))
+}
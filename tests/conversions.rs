@@ -0,0 +1,29 @@
+//! Checks the conversions of rust values into literal expressions.
+
+#![cfg(feature = "std")]
+
+extern crate buildlua;
+
+use buildlua::ast::*;
+
+#[test]
+fn numbers() {
+    assert_eq!(Expression::from(3.0), Expression::Float(3.0, NumberForm::Plain));
+    assert_eq!(Expression::from(3), Expression::Integer(3, NumberForm::Plain));
+    assert_eq!(Expression::from(3.0).to_string(), "3.0");
+}
+
+#[test]
+fn booleans() {
+    assert_eq!(Expression::from(true), Expression::True);
+    assert_eq!(Expression::from(false), Expression::False);
+}
+
+#[test]
+fn strings() {
+    let expected = Expression::String(b"x".to_vec(), StringForm::Short(Quote::Double));
+    assert_eq!(Expression::from("x"), expected);
+    assert_eq!(Expression::from("x".to_string()), expected);
+    // The string is a literal, not the name `x`.
+    assert_eq!(Expression::from("x").to_string(), "\"x\"");
+}
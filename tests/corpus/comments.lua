@@ -0,0 +1,11 @@
+-- a leading comment
+local x = 1 -- a trailing comment
+--[[ a block
+comment ]]
+local   y =   2   --   spaced out
+--[==[ another ]==]
+if x then
+    -- inside the if
+    x = y
+end
+-- at the end
@@ -0,0 +1,23 @@
+local function fact(n)
+    if n <= 1 then
+        return 1
+    end
+    return n * fact(n - 1)
+end
+function global(a, b, ...)
+    return select("#", ...)
+end
+function a.b.c:method()
+    return self
+end
+local anonymous = function(...) return ... end
+local varargs = function(a, ...) end
+print "string call"
+print [[long string call]]
+print {table = "call"}
+object:method(1, 2)
+object.field.call()
+object["index"]:method "x"
+local called = (anonymous)(1)
+;(print)("after semicolon")
+f()()
@@ -0,0 +1,9 @@
+local numbers = {1, 0x10, 0xff, 3.5, 1e10, 0.5e-3, 0x1p4, 9007199254740993, 1e999}
+local strings = {"double", 'single', "it's", 'say "hi"', "escape \n\t\\", [[long]], [==[with ]] inside]==]}
+local long = [[
+first line dropped
+second line]]
+local constants = {nil, true, false, ...}
+local escapes = {"\a\b\f\v\0", "\65\066\x41\x7e", "skip \z
+    whitespace", 'mixed "quotes" and \'', "line\
+break", "caf\xc3\xa9"}
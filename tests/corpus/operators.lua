@@ -0,0 +1,15 @@
+local a = 1 + 2 * 3 - 4 / 5 % 6 // 7
+local b = (1 + 2) * 3
+local c = 2 ^ 3 ^ 2
+local d = (2 ^ 3) ^ 2
+local e = -2 ^ 2
+local f = (-2) ^ 2
+local g = "a" .. "b" .. "c"
+local h = ("a" .. "b") .. "c"
+local i = a < b and b <= c or c > d and d >= e
+local j = a == b or not (a ~= b)
+local k = 1 | 2 ~ 3 & 4 << 5 >> 6
+local l = ~a + #"abc" - - -a
+local m = a - (b - c)
+local n = a .. b + c
+local o = not a == b
@@ -0,0 +1,38 @@
+local x, y <const>, z <close> = 1, 2
+x, y = y, x
+do
+    local inner = x
+end
+while x < 10 do
+    x = x + 1
+    if x == 5 then
+        break
+    end
+end
+repeat
+    local done = x > 20
+    x = x * 2
+until done
+if x then
+    print("x")
+elseif y then
+    print("y")
+elseif z then
+    print("z")
+else
+    print("none")
+end
+for i = 1, 10 do
+    print(i)
+end
+for i = 10, 1, -1 do
+    print(i)
+end
+for key, value in pairs({}) do
+    print(key, value)
+end
+goto skip
+print("skipped")
+::skip::
+;;;
+return x, y
@@ -0,0 +1,8 @@
+local empty = {}
+local array = {1, 2, 3;}
+local record = {a = 1, b = 2; c = 3}
+local mixed = {1, key = "value", [10] = "ten", ["quoted"] = true, {nested = {}}}
+local keyed = {[ [[long]] ] = 1}
+record.a = record["b"]
+record.nested.deeper[1] = nil
+mixed[#mixed + 1] = function() end
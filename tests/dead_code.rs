@@ -0,0 +1,52 @@
+//! Checks which statements `eliminate_dead_code` removes.
+
+#![cfg(feature = "std")]
+
+extern crate buildlua;
+
+use buildlua::opt::{eliminate_dead_code, fold_constants};
+use buildlua::parser::parse;
+
+fn eliminated(src: &str) -> String {
+    let mut chunk = parse(src).unwrap_or_else(|error| panic!("failed to parse: {}", error));
+    eliminate_dead_code(&mut chunk);
+    chunk.to_string()
+}
+
+#[test]
+fn code_after_a_return_is_removed() {
+    let src = "function f() do return 1 end print(2) return 3 end";
+    assert_eq!(eliminated(src), "function f()\n    do\n        return 1\n    end\nend\n");
+    assert_eq!(eliminated("while x do break y() end"), "while x do\n    break\nend\n");
+}
+
+#[test]
+fn a_label_a_goto_needs_survives() {
+    let src = "goto done print(1) ::done:: print(2)";
+    assert_eq!(eliminated(src), "goto done\n::done::\nprint(2)\n");
+}
+
+#[test]
+fn labels_nothing_jumps_to_go_with_the_dead_code() {
+    assert_eq!(eliminated("do return end ::unused:: print(1)"), "do\n    return\nend\n");
+    // The only goto to `b` is dead, so the code after `b` is too.
+    let src = "goto a goto b ::b:: print(1) ::a:: print(2)";
+    assert_eq!(eliminated(src), "goto a\n::a::\nprint(2)\n");
+}
+
+#[test]
+fn branches_on_literals_are_resolved() {
+    assert_eq!(eliminated("if false then a() end"), "");
+    assert_eq!(eliminated("if nil then a() elseif x then b() end"), "if x then\n    b()\nend\n");
+    let src = "if x then a() elseif 1 then b() else c() end";
+    assert_eq!(eliminated(src), "if x then\n    a()\nelse\n    b()\nend\n");
+    assert_eq!(eliminated("if false then a() else local y = 1 end"), "do\n    local y = 1\nend\n");
+}
+
+#[test]
+fn folded_conditions_are_resolved() {
+    let mut chunk = parse("if 1 > 2 then a() end if 2 > 1 then b() end").unwrap();
+    fold_constants(&mut chunk);
+    eliminate_dead_code(&mut chunk);
+    assert_eq!(chunk.to_string(), "do\n    b()\nend\n");
+}
@@ -0,0 +1,309 @@
+//! Checks that desugared chunks run the same as the originals.
+
+#![cfg(feature = "std")]
+
+extern crate buildlua;
+
+use buildlua::ast::{Chunk, PrefixExpression, Statement, Variable};
+use buildlua::interp::{eval, Interpreter, Value};
+use buildlua::opt::{desugar_function_statements, desugar_generic_for, desugar_numeric_for};
+use buildlua::parser::parse;
+use buildlua::span::Spanned;
+use buildlua::stdlib;
+use buildlua::visit::{self, Visitor};
+
+struct ForCounter(usize);
+
+impl Visitor for ForCounter {
+    fn visit_statement(&mut self, statement: &Spanned<Statement>) {
+        if let Statement::ForStepping { .. } | Statement::ForIn { .. } = statement.node {
+            self.0 += 1;
+        }
+        visit::walk_statement(self, statement);
+    }
+}
+
+fn for_loops(chunk: &Chunk) -> usize {
+    let mut counter = ForCounter(0);
+    visit::walk_chunk(&mut counter, chunk);
+    counter.0
+}
+
+/// Runs a chunk with the standard library, which desugared loops call, giving what it returns or
+/// the message of its error.
+fn run(chunk: &Chunk) -> Result<Vec<Value>, String> {
+    let mut interpreter = Interpreter::new();
+    stdlib::open(&mut interpreter);
+    interpreter.eval(chunk).map_err(|error| error.message)
+}
+
+/// Runs `src` before and after desugaring both kinds of loop, checking that both give the same
+/// values or fail with the same error, and returns what they give.
+fn try_both(src: &str) -> Result<Vec<Value>, String> {
+    let mut chunk = parse(src).unwrap_or_else(|error| panic!("failed to parse: {}", error));
+    let expected = run(&chunk);
+    desugar_numeric_for(&mut chunk);
+    desugar_generic_for(&mut chunk);
+    assert_eq!(for_loops(&chunk), 0, "a loop wasn't desugared:\n{}", chunk);
+    // The desugared chunk has to read back from its printed form too.
+    let printed = chunk.to_string();
+    let reparsed = parse(&printed).unwrap_or_else(|error| panic!("{}\n{}", error, printed));
+    for chunk in &[chunk, reparsed] {
+        assert_eq!(run(chunk), expected, "the desugared chunk ran differently:\n{}", printed);
+    }
+    expected
+}
+
+/// The values of `src`, which has to run, before and after desugaring.
+fn run_both(src: &str) -> Vec<Value> {
+    try_both(src).unwrap_or_else(|error| panic!("failed to run: {}", error))
+}
+
+/// The values the loop variable takes in a loop with the given bounds, joined by spaces.
+fn loop_values(bounds: &str) -> Value {
+    let src = format!("local s = '' for i = {} do s = s .. ' ' .. i end return s", bounds);
+    run_both(&src).remove(0)
+}
+
+#[test]
+fn positive_steps() {
+    assert_eq!(loop_values("1, 3"), Value::from(" 1 2 3"));
+    assert_eq!(loop_values("1, 10, 3"), Value::from(" 1 4 7 10"));
+    assert_eq!(loop_values("3, 1"), Value::from(""));
+}
+
+#[test]
+fn negative_steps() {
+    assert_eq!(loop_values("3, 1, -1"), Value::from(" 3 2 1"));
+    assert_eq!(loop_values("10, 1, -4"), Value::from(" 10 6 2"));
+    assert_eq!(loop_values("1, 3, -1"), Value::from(""));
+}
+
+#[test]
+fn fractional_steps() {
+    assert_eq!(loop_values("0, 1, 0.25"), Value::from(" 0.0 0.25 0.5 0.75 1.0"));
+    assert_eq!(loop_values("1, 0, -0.5"), Value::from(" 1.0 0.5 0.0"));
+}
+
+#[test]
+fn integer_loops_stop_at_the_bounds() {
+    let max = i64::MAX;
+    let values = |values: &[i64]| {
+        Value::from(values.iter().map(|value| format!(" {}", value)).collect::<String>())
+    };
+    let expected = values(&[max - 2, max - 1, max]);
+    assert_eq!(loop_values("math.maxinteger - 2, math.maxinteger"), expected);
+    let expected = values(&[i64::MIN + 2, i64::MIN + 1, i64::MIN]);
+    assert_eq!(loop_values("math.mininteger + 2, math.mininteger, -1"), expected);
+    // The distance between the bounds is too large for an integer.
+    let expected = values(&[i64::MIN, -1, max - 1]);
+    assert_eq!(loop_values("math.mininteger, math.maxinteger, math.maxinteger"), expected);
+    let expected = values(&[max, -1]);
+    assert_eq!(loop_values("math.maxinteger, math.mininteger, math.mininteger"), expected);
+    let src = "
+        local function count(from, to, step)
+            local n = 0
+            for i = from, to, step do n = n + 1 end
+            return n
+        end
+        return count(math.maxinteger - 2, math.maxinteger, 1),
+            count(math.mininteger + 2, math.mininteger, -1)
+    ";
+    assert_eq!(run_both(src), vec![Value::Integer(3), Value::Integer(3)]);
+}
+
+#[test]
+fn float_limits_of_integer_loops() {
+    assert_eq!(loop_values("1, 3.5"), Value::from(" 1 2 3"));
+    assert_eq!(loop_values("3, 0.5, -1"), Value::from(" 3 2 1"));
+    let max = i64::MAX;
+    let expected = Value::from(format!(" {} {}", max - 1, max));
+    assert_eq!(loop_values("math.maxinteger - 1, 1e300"), expected);
+    let expected = Value::from(format!(" {} {}", i64::MIN + 1, i64::MIN));
+    assert_eq!(loop_values("math.mininteger + 1, -math.huge, -1"), expected);
+    assert_eq!(loop_values("1, 0/0"), Value::from(""));
+}
+
+#[test]
+fn bounds_that_arent_numbers() {
+    assert_eq!(loop_values("'1', '3'"), Value::from(" 1 2 3"));
+    let error = |bounds: &str| {
+        let src = format!("local t = {{}} for i = {} do end", bounds);
+        try_both(&src).unwrap_err()
+    };
+    assert_eq!(error("1, t"), "'for' limit must be a number");
+    assert_eq!(error("1, 'x'"), "'for' limit must be a number");
+    assert_eq!(error("nil, 2"), "'for' initial value must be a number");
+    assert_eq!(error("1, 2, {}"), "'for' step must be a number");
+    assert_eq!(error("1, 2, #t"), "'for' step is zero");
+}
+
+#[test]
+fn steps_known_only_at_runtime() {
+    let src = "
+        local function collect(from, to, step)
+            local s = ''
+            for i = from, to, step do s = s .. ' ' .. i end
+            return s
+        end
+        return collect(1, 3, 1), collect(3, 1, -1), collect(1, 2, 0.5)
+    ";
+    let expected = vec![Value::from(" 1 2 3"), Value::from(" 3 2 1"), Value::from(" 1.0 1.5 2.0")];
+    assert_eq!(run_both(src), expected);
+}
+
+#[test]
+fn nested_loops_break_and_closures() {
+    let src = "
+        local sum, fs = 0, {}
+        for i = 1, 4 do
+            for j = i, 1, -1 do
+                if j == 2 then break end
+                sum = sum + i * j
+            end
+            fs[i] = function() return i end
+        end
+        return sum, fs[1](), fs[4]()
+    ";
+    let expected = vec![Value::Number(38.0), Value::Number(1.0), Value::Number(4.0)];
+    assert_eq!(run_both(src), expected);
+}
+
+#[test]
+fn hidden_names_dont_shadow_the_chunk() {
+    let src = "
+        local for_var, for_step = 'outer', 'names'
+        local s = ''
+        for i = 1, 2 do s = s .. for_var .. for_step .. i end
+        return s
+    ";
+    assert_eq!(run_both(src), vec![Value::from("outernames1outernames2")]);
+}
+
+#[test]
+fn loop_variables_named_like_hidden_locals() {
+    let src = "local n = 0 for for_var = 1, 3 do n = n + 1 end return n";
+    assert_eq!(run_both(src), vec![Value::Integer(3)]);
+}
+
+#[test]
+fn generic_for_with_a_pairs_like_iterator() {
+    // Without a standard library, `ipairs` is written out, stopping at the first nil value.
+    let src = "
+        local function next_item(t, i)
+            i = i + 1
+            local v = t[i]
+            if v ~= nil then return i, v end
+        end
+        local function items(t) return next_item, t, 0 end
+        local s = ''
+        for i, v in items({'a', 'b', 'c'}) do
+            if v == 'c' then break end
+            s = s .. i .. v
+        end
+        local count = 0
+        for _ in items({}) do count = count + 1 end
+        return s, count
+    ";
+    assert_eq!(run_both(src), vec![Value::from("1a2b"), Value::Number(0.0)]);
+}
+
+#[test]
+fn generic_for_variables_named_like_hidden_locals() {
+    let src = "
+        local function it(_, i) if i < 3 then return i + 1 end end
+        local n = 0
+        for for_control in it, nil, 0 do n = n + 1 end
+        return n
+    ";
+    assert_eq!(run_both(src), vec![Value::Integer(3)]);
+}
+
+#[test]
+fn generic_for_keeps_each_iteration_separate() {
+    let src = "
+        local function range(n)
+            return function(_, i) if i < n then return i + 1 end end, nil, 0
+        end
+        local fs = {}
+        for i in range(3) do
+            fs[i] = function() return i end
+            if i == 2 then
+                for j in range(2) do fs[i] = function() return i * 10 + j end end
+            end
+        end
+        return fs[1](), fs[2](), fs[3]()
+    ";
+    let expected = vec![Value::Number(1.0), Value::Number(22.0), Value::Number(3.0)];
+    assert_eq!(run_both(src), expected);
+}
+
+/// Runs `src` before and after desugaring its function statements, checking that both give the
+/// same values, and returns the desugared chunk as printed.
+fn desugar_functions(src: &str) -> String {
+    let mut chunk = parse(src).unwrap_or_else(|error| panic!("failed to parse: {}", error));
+    let expected = eval(&chunk).unwrap_or_else(|error| panic!("failed to run: {}", error));
+    desugar_function_statements(&mut chunk);
+    let printed = chunk.to_string();
+    let actual = eval(&chunk).unwrap_or_else(|error| panic!("{}\n{}", error, printed));
+    assert_eq!(actual, expected, "the desugared chunk gave different values:\n{}", printed);
+    printed
+}
+
+/// The variable that the function statement in `src` assigns to.
+fn function_variable(src: &str) -> Variable {
+    let chunk = parse(src).unwrap_or_else(|error| panic!("failed to parse: {}", error));
+    let statement = chunk.0.statements().next().expect("the source has no statement");
+    match statement.node {
+        Statement::Function(ref name, _) => name.to_variable(),
+        ref statement => panic!("expected a function statement, found {:?}", statement),
+    }
+}
+
+#[test]
+fn function_names_as_variables() {
+    let variable = function_variable("function f() end");
+    assert!(matches!(variable, Variable::Name(ref name) if name.node == "f"));
+    match function_variable("function a.b:c() end") {
+        Variable::DotAccess { ref from, ref key } => {
+            assert_eq!(key, "c");
+            let from = match **from {
+                PrefixExpression::Variable(ref from) => from,
+                ref from => panic!("expected a variable, found {:?}", from),
+            };
+            assert!(matches!(**from, Variable::DotAccess { ref key, .. } if key == "b"));
+        }
+        ref variable => panic!("expected a dot access, found {:?}", variable),
+    }
+}
+
+#[test]
+fn function_statements() {
+    let src = "
+        local t = {inner = {}}
+        function t.inner.add(a, b) return a + b end
+        function double(x) return x * 2 end
+        return t.inner.add(1, 2), double(4)
+    ";
+    let printed = desugar_functions(src);
+    assert!(printed.contains("t.inner.add = function(a, b)"), "{}", printed);
+    assert!(printed.contains("double = function(x)"), "{}", printed);
+}
+
+#[test]
+fn methods_take_self() {
+    let src = "
+        local counter = {count = 0}
+        function counter:add(n, ...) self.count = self.count + n + #{...} end
+        function counter:get() return self.count end
+        function counter.helpers() end
+        counter:add(2, 'a', 'b')
+        counter.add(counter, 1)
+        return counter:get()
+    ";
+    let printed = desugar_functions(src);
+    assert!(printed.contains("counter.add = function(self, n, ...)"), "{}", printed);
+    assert!(printed.contains("counter.get = function(self)"), "{}", printed);
+    assert!(printed.contains("counter.helpers = function()"), "{}", printed);
+    assert!(!printed.contains("function counter"), "{}", printed);
+}
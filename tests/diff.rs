@@ -0,0 +1,61 @@
+//! Checks that `diff` finds where two trees differ.
+
+#![cfg(feature = "std")]
+
+extern crate buildlua;
+
+use buildlua::diff::diff;
+use buildlua::parser::parse;
+
+/// The differences between two sources, written out.
+fn differences(a: &str, b: &str) -> Vec<String> {
+    diff(&parse(a).unwrap(), &parse(b).unwrap()).iter().map(ToString::to_string).collect()
+}
+
+#[test]
+fn equal_trees() {
+    assert!(differences("local x = 1\nif x then print(x) end", "local  x=1 if x then print(x) end")
+        .is_empty());
+}
+
+#[test]
+fn different_operator() {
+    let a = "local x = 1\nif x + 1 > 2 then print(x) end";
+    let b = "local x = 1\nif x - 1 > 2 then print(x) end";
+    let expected = ["statement 2, condition, left, operator: Plus vs Minus"];
+    assert_eq!(differences(a, b), expected);
+    let d = &diff(&parse(a).unwrap(), &parse(b).unwrap())[0];
+    assert_eq!(d.path, ["statement 2", "condition", "left", "operator"]);
+}
+
+#[test]
+fn different_literal() {
+    let a = "local t = {1, 2, name = 'a'}\nreturn t";
+    let b = "local t = {1, 3, name = 'a'}\nreturn t";
+    assert_eq!(differences(a, b), ["statement 1, value 1, field 2, value: 2 vs 3"]);
+    let expected = ["statement 1, value 1, field 3, value: 'a' vs 'b'"];
+    assert_eq!(differences(a, "local t = {1, 2, name = 'b'}\nreturn t"), expected);
+}
+
+#[test]
+fn nodes_only_one_tree_has() {
+    let a = "f(1)\nreturn 1";
+    let b = "f(1, x)\ng()";
+    let expected = [
+        "statement 1, argument 2: nothing vs x",
+        "statement 2: nothing vs g()",
+        "return: return 1 vs nothing",
+    ];
+    assert_eq!(differences(a, b), expected);
+}
+
+#[test]
+fn different_kinds_of_node() {
+    let a = "function f(a, ...) while a do a = a.b end end";
+    let b = "function f(a) while a do a = a[1] end end";
+    let expected = [
+        "statement 1, body, varargs: ... vs nothing",
+        "statement 1, body, statement 1, block, statement 1, value 1: a.b vs a[1]",
+    ];
+    assert_eq!(differences(a, b), expected);
+}
@@ -0,0 +1,64 @@
+//! Checks that printed expressions get parentheses exactly where the tree needs them.
+
+#![cfg(feature = "std")]
+
+extern crate buildlua;
+
+use buildlua::ast::BinaryOperation::*;
+use buildlua::ast::UnaryOperation::*;
+use buildlua::ast::*;
+use buildlua::build::expr;
+use buildlua::span::Spanned;
+
+fn name(name: &str) -> Spanned<Expression> {
+    expr::name(name)
+}
+
+fn printed(expression: Spanned<Expression>) -> String {
+    expression.node.to_string()
+}
+
+#[test]
+fn lower_precedence_operands_are_parenthesized() {
+    let sum = expr::binop(Plus, name("a"), name("b"));
+    assert_eq!(printed(expr::binop(Times, sum, name("c"))), "(a + b) * c");
+    let product = expr::binop(Times, name("a"), name("b"));
+    assert_eq!(printed(expr::binop(Plus, product, name("c"))), "a * b + c");
+}
+
+#[test]
+fn left_associative_operators() {
+    let left = expr::binop(Minus, expr::binop(Minus, name("a"), name("b")), name("c"));
+    assert_eq!(printed(left), "a - b - c");
+    let right = expr::binop(Minus, name("a"), expr::binop(Minus, name("b"), name("c")));
+    assert_eq!(printed(right), "a - (b - c)");
+    let (one, two, three) = (expr::integer(1), expr::integer(2), expr::integer(3));
+    let sum = expr::binop(Plus, expr::binop(Plus, one, two), three);
+    assert_eq!(printed(sum), "1 + 2 + 3");
+}
+
+#[test]
+fn right_associative_operators() {
+    let right = expr::binop(Exponent, name("a"), expr::binop(Exponent, name("b"), name("c")));
+    assert_eq!(printed(right), "a ^ b ^ c");
+    let left = expr::binop(Exponent, expr::binop(Exponent, name("a"), name("b")), name("c"));
+    assert_eq!(printed(left), "(a ^ b) ^ c");
+    let left = expr::binop(Concatanate, expr::binop(Concatanate, name("a"), name("b")), name("c"));
+    assert_eq!(printed(left), "(a .. b) .. c");
+}
+
+#[test]
+fn unary_operators() {
+    let negated_power = expr::unop(Negate, expr::binop(Exponent, name("a"), name("b")));
+    assert_eq!(printed(negated_power), "-a ^ b");
+    let power_of_negation = expr::binop(Exponent, expr::unop(Negate, name("a")), name("b"));
+    assert_eq!(printed(power_of_negation), "(-a) ^ b");
+    let negated_conjunction = expr::unop(Not, expr::binop(And, name("a"), name("b")));
+    assert_eq!(printed(negated_conjunction), "not (a and b)");
+}
+
+#[test]
+fn parentheses_in_the_source_are_kept() {
+    let chunk = buildlua::parser::parse("x = (1 + 2) * 3").unwrap();
+    assert_eq!(chunk.to_string(), "x = (1 + 2) * 3\n");
+}
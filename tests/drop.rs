@@ -0,0 +1,59 @@
+//! Checks that very deeply nested trees can be dropped without overflowing the stack.
+
+#![cfg(feature = "std")]
+
+extern crate buildlua;
+
+use std::thread;
+
+use buildlua::ast::*;
+use buildlua::build::{expr, stmt, BlockBuilder};
+
+const DEPTH: usize = 100_000;
+
+/// Runs `f` on a thread with a small stack, so that recursing once per level would overflow.
+fn with_small_stack(f: impl FnOnce() + Send + 'static) {
+    thread::Builder::new().stack_size(256 * 1024).spawn(f).unwrap().join().unwrap();
+}
+
+#[test]
+fn nested_concatenation() {
+    with_small_stack(|| {
+        let mut expression = expr::string("end");
+        for _ in 0..DEPTH {
+            expression = expr::binop(BinaryOperation::Concatanate, expr::string("a"), expression);
+        }
+        drop(expression);
+    });
+}
+
+#[test]
+fn nested_parentheses_and_operators() {
+    with_small_stack(|| {
+        let mut expression = expr::name("x");
+        for depth in 0..DEPTH {
+            expression = match depth % 3 {
+                0 => expr::unop(UnaryOperation::Negate, expression),
+                1 => expr::binop(BinaryOperation::Plus, expression, expr::integer(1)),
+                _ => expr::call(expr::name("f"), vec![expression]),
+            };
+        }
+        drop(expression);
+    });
+}
+
+#[test]
+fn nested_blocks() {
+    with_small_stack(|| {
+        let mut block = BlockBuilder::new().build();
+        for depth in 0..DEPTH {
+            let statement = match depth % 3 {
+                0 => stmt::do_(block),
+                1 => stmt::while_(expr::boolean(true), block),
+                _ => stmt::if_(expr::name("x"), block, None),
+            };
+            block = BlockBuilder::new().push(statement).build();
+        }
+        drop(block);
+    });
+}
@@ -0,0 +1,82 @@
+//! Checks the exports of the syntax tree in other notations.
+
+#![cfg(feature = "std")]
+
+extern crate buildlua;
+
+use buildlua::export::{to_dot, to_sexpr};
+use buildlua::parser::parse;
+
+/// The label of each node and the labels of the nodes beneath it, read back from DOT output.
+fn read_dot(dot: &str) -> Vec<(String, Vec<String>)> {
+    let mut labels = Vec::new();
+    let mut nodes: Vec<(String, Vec<String>)> = Vec::new();
+    for line in dot.lines().map(str::trim) {
+        if let Some(rest) = line.strip_prefix('n') {
+            if let Some((_, label)) = rest.split_once(" [label=\"") {
+                let label = label.trim_end_matches("\"];").replace("\\\"", "\"");
+                labels.push(label.clone());
+                nodes.push((label, Vec::new()));
+            } else if let Some((parent, child)) = rest.split_once(" -> n") {
+                let parent: usize = parent.parse().unwrap();
+                let child: usize = child.trim_end_matches(';').parse().unwrap();
+                nodes[parent].1.push(labels[child].clone());
+            }
+        }
+    }
+    nodes
+}
+
+#[test]
+fn operator_with_numbers() {
+    let dot = to_dot(&parse("return 1 + 2").unwrap());
+    assert_eq!(dot.matches("digraph").count(), 1);
+    assert!(dot.starts_with("digraph ast {\n"));
+    assert!(dot.ends_with("}\n"));
+    assert_eq!(dot.matches('{').count(), dot.matches('}').count());
+    let nodes = read_dot(&dot);
+    let plus = nodes.iter().find(|node| node.0 == "+").unwrap();
+    assert_eq!(plus.1, ["num 1", "num 2"]);
+    assert_eq!(nodes[0], ("block".to_string(), vec!["return".to_string()]));
+}
+
+#[test]
+fn quotes_in_labels_are_escaped() {
+    let dot = to_dot(&parse("print('a\"b')").unwrap());
+    assert!(dot.contains("    n3 [label=\"str 'a\\\"b'\"];\n"));
+    let nodes = read_dot(&dot);
+    assert_eq!(nodes[1].1, ["name print", "str 'a\"b'"]);
+}
+
+#[test]
+fn statements_and_blocks() {
+    let dot = to_dot(&parse("while x do local y <const> = x.z end").unwrap());
+    let nodes = read_dot(&dot);
+    let labels: Vec<_> = nodes.iter().map(|node| &*node.0).collect();
+    let expected = [
+        "block", "while", "name x", "block", "local", "names", "name y <const>", "values", "dot z",
+        "name x",
+    ];
+    assert_eq!(labels, expected);
+    assert_eq!(nodes[1].1, ["name x", "block"]);
+}
+
+#[test]
+fn sexpr_of_expressions() {
+    let sexpr = |source| to_sexpr(&parse(source).unwrap());
+    assert_eq!(sexpr("return 1 + 2"), "(block (return (binop + (num 1) (num 2))))");
+    let expected = "(block (return (unop - (name a)) (call (dot b (name t)) (str 's') nil ...)))";
+    assert_eq!(sexpr("return -a, t.b('s', nil, ...)"), expected);
+    let expected = "(block (assign (targets (index (name t) (num 1))) (values (table (num 2.0) \
+                    (field x true) (field (name k) (function (parameters (name a) ...) block))))))";
+    assert_eq!(sexpr("t[1] = {2.0, x = true, [k] = function(a, ...) end}"), expected);
+}
+
+#[test]
+fn sexpr_of_nested_blocks() {
+    let source = "for i = 1, 3 do if i > 1 then break else do f() end end end";
+    let expected = "(block (for i (num 1) (num 3) (block (if (binop > (name i) (num 1)) \
+                    (block break) (else (block (do (block (call (name f))))))))))";
+    assert_eq!(to_sexpr(&parse(source).unwrap()), expected);
+    assert_eq!(to_sexpr(&parse("").unwrap()), "block");
+}
@@ -0,0 +1,37 @@
+//! Checks what the lexer skips at the very start of a file.
+
+#![cfg(feature = "std")]
+
+extern crate buildlua;
+
+use buildlua::ast::*;
+use buildlua::parser::parse;
+
+#[test]
+fn shebang_line() {
+    let chunk = parse("#!/usr/bin/env lua\nprint(1)").unwrap();
+    assert_eq!(chunk, parse("print(1)").unwrap());
+    assert_eq!(chunk.0.0.as_ref().unwrap()[0].span.start, 19);
+    assert!(parse("#!/usr/bin/env lua").unwrap().0.0.is_none());
+}
+
+#[test]
+fn hash_later_is_the_length_operator() {
+    let chunk = parse("#!/usr/bin/env lua\nreturn #t").unwrap();
+    let returned = chunk.0.1.as_ref().unwrap().0.as_ref().unwrap();
+    assert!(matches!(
+        returned.0.node,
+        Expression::UnaryOperation(UnaryOperation::Length, _)
+    ));
+    assert!(parse("x = 1\n#!/usr/bin/env lua").is_err());
+    assert!(parse(" #!/usr/bin/env lua").is_err());
+}
+
+#[test]
+fn byte_order_mark() {
+    let chunk = parse("\u{FEFF}x = 1").unwrap();
+    assert_eq!(chunk, parse("x = 1").unwrap());
+    assert_eq!(chunk.0.0.as_ref().unwrap()[0].span.start, 3);
+    assert_eq!(parse("\u{FEFF}#!/usr/bin/env lua\nx = 1").unwrap(), chunk);
+    assert!(parse("x = 1\n\u{FEFF}y = 2").is_err());
+}
@@ -0,0 +1,11 @@
+-- Adds up the numbers from 1 to n.
+local function sum(n)
+    local total = 0
+    for i = 1, n do
+        total = total + i
+    end
+    return total
+end
+function M.greet(name, ...)
+    print("hello, " .. name, ...)
+end
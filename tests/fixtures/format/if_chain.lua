@@ -0,0 +1,10 @@
+local function classify(n)
+if n<0 then return "negative"
+elseif n==0 then return "zero" elseif n<10 then
+-- Small enough to count on fingers.
+return "small"
+else
+if n%2==0 then return "big and even" end
+return "big"
+end
+end
@@ -0,0 +1,12 @@
+local config = {
+    name = 'buildlua',
+    version = {major = 0, minor = 1},
+    ["key with spaces"] = true,
+    'first',
+    'second',
+    callback = function(x)
+        return x * 2
+    end,
+}
+local empty = {}
+local short = {1, 2, 3}
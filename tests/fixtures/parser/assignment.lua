@@ -0,0 +1,4 @@
+a, b, c = 1, 2
+a.b, a[1], a["c"] = f()
+local x, y <const>, z <close> = 1, ...
+t[i], t[j] = t[j], t[i]
@@ -0,0 +1,7 @@
+f()
+f "string"
+f {1, 2}
+obj:method(1, 2)
+obj.field:method "s"
+a.b.c:d(e)(f):g {}
+f(...)(g)
@@ -0,0 +1,11 @@
+x = 1 + 2 * 3 - 4 / 5
+x = a .. b .. c
+x = 2 ^ 3 ^ 2
+x = -a ^ 2
+x = not a == b
+x = a or b and c
+x = a < b == c
+x = 1 << 2 + 3 & 4 | 5 ~ 6
+x = #t + 1
+x = (a + b) * c
+x = a // b % c
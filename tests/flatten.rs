@@ -0,0 +1,47 @@
+//! Checks which `do` blocks `flatten_blocks` inlines into the block around them.
+
+#![cfg(feature = "std")]
+
+extern crate buildlua;
+
+use buildlua::interp::eval;
+use buildlua::opt::flatten_blocks;
+use buildlua::parser::parse;
+
+/// Flattens `src`, checking that it returns the same values afterwards, and gives it printed.
+fn flattened(src: &str) -> String {
+    let mut chunk = parse(src).unwrap_or_else(|error| panic!("failed to parse: {}", error));
+    let expected = eval(&chunk).unwrap_or_else(|error| panic!("failed to run: {}", error));
+    flatten_blocks(&mut chunk);
+    let actual = eval(&chunk).unwrap_or_else(|error| panic!("{}\n{}", error, chunk));
+    assert_eq!(actual, expected, "the flattened chunk gave different values:\n{}", chunk);
+    chunk.to_string()
+}
+
+#[test]
+fn local_free_blocks_are_inlined() {
+    let src = "x = 1 do x = x + 1 y = x end return y";
+    assert_eq!(flattened(src), "x = 1\nx = x + 1\ny = x\nreturn y\n");
+    assert_eq!(flattened("do do x = 1 end end do end return x"), "x = 1\nreturn x\n");
+}
+
+#[test]
+fn blocks_declaring_locals_are_kept() {
+    let src = "local x = 1 do local x = 2 end return x";
+    assert_eq!(flattened(src), "local x = 1\ndo\n    local x = 2\nend\nreturn x\n");
+    let src = "do local function f() end end";
+    assert_eq!(flattened(src), "do\n    local function f()\n    end\nend\n");
+}
+
+#[test]
+fn blocks_with_labels_or_returns_are_kept() {
+    let src = "do ::a:: end ::a:: do return 1 end";
+    assert_eq!(flattened(src), "do\n    ::a::\nend\n::a::\ndo\n    return 1\nend\n");
+}
+
+#[test]
+fn nested_blocks_are_flattened_inside_kept_ones() {
+    let src = "while x do do x = false end end do local y do y = 1 end end";
+    let expected = "while x do\n    x = false\nend\ndo\n    local y\n    y = 1\nend\n";
+    assert_eq!(flattened(src), expected);
+}
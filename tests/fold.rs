@@ -0,0 +1,109 @@
+//! Checks that `fold_constants` evaluates operations on literals, and leaves alone the ones that
+//! aren't constant or would fail at runtime.
+
+#![cfg(feature = "std")]
+
+extern crate buildlua;
+
+use buildlua::opt::fold_constants;
+use buildlua::parser::parse;
+
+/// The expression `src` returns, folded and printed.
+fn folded(src: &str) -> String {
+    let mut chunk = parse(&format!("return {}", src)).unwrap();
+    fold_constants(&mut chunk);
+    let printed = chunk.to_string();
+    printed["return ".len()..].trim_end().to_string()
+}
+
+#[test]
+fn nested_operations_fold_inside_out() {
+    assert_eq!(folded("2 * (3 + 4)"), "14");
+    let mut chunk = parse("x = 2 * (3 + 4)").unwrap();
+    fold_constants(&mut chunk);
+    assert_eq!(chunk, parse("x = 14").unwrap());
+}
+
+#[test]
+fn arithmetic() {
+    assert_eq!(folded("1 + 2"), "3");
+    assert_eq!(folded("10 - 2.5"), "7.5");
+    assert_eq!(folded("6 * 7"), "42");
+    assert_eq!(folded("7 / 2"), "3.5");
+    assert_eq!(folded("7 // 2"), "3");
+    assert_eq!(folded("-7 // 2"), "-4");
+    assert_eq!(folded("-7 % 3"), "2");
+    assert_eq!(folded("2 ^ 10"), "1024.0");
+    assert_eq!(folded("-(3)"), "-3");
+    // Integers wrap around, and the smallest one is printed so that it reads back as an integer.
+    assert_eq!(folded("9223372036854775807 + 1"), "0x8000000000000000");
+}
+
+#[test]
+fn comparison() {
+    assert_eq!(folded("1 < 2"), "true");
+    assert_eq!(folded("2 <= 1"), "false");
+    assert_eq!(folded("3 > 2.5"), "true");
+    assert_eq!(folded("'a' >= 'b'"), "false");
+    assert_eq!(folded("1 == 1.0"), "true");
+    assert_eq!(folded("'1' ~= 1"), "true");
+    assert_eq!(folded("nil == false"), "false");
+    // Integers and floats compare by their exact values, which a float can't always hold.
+    assert_eq!(folded("9007199254740993 == 9007199254740992.0"), "false");
+    assert_eq!(folded("9007199254740992.0 < 9007199254740993"), "true");
+    assert_eq!(folded("9007199254740993 <= 9007199254740992.0"), "false");
+}
+
+#[test]
+fn concatenation() {
+    assert_eq!(folded("'a' .. 'b'"), "\"ab\"");
+    assert_eq!(folded("'n' .. 1"), "\"n1\"");
+    assert_eq!(folded("'a' .. 'b' .. 'c'"), "\"abc\"");
+}
+
+#[test]
+fn logical_operators_and_not() {
+    assert_eq!(folded("true and 1"), "1");
+    assert_eq!(folded("nil and f()"), "nil");
+    assert_eq!(folded("false or 'x'"), "'x'");
+    assert_eq!(folded("1 or f()"), "1");
+    // A call on the right is kept to a single value.
+    assert_eq!(folded("true and f()"), "(f())");
+    assert_eq!(folded("not nil"), "true");
+    assert_eq!(folded("not 0"), "false");
+    assert_eq!(folded("not not true"), "true");
+}
+
+#[test]
+fn bitwise() {
+    assert_eq!(folded("6 & 3"), "2");
+    assert_eq!(folded("6 | 3"), "7");
+    assert_eq!(folded("6 ~ 3"), "5");
+    assert_eq!(folded("~0"), "-1");
+    assert_eq!(folded("1 << 4"), "16");
+    assert_eq!(folded("256 >> 4"), "16");
+    assert_eq!(folded("1 << 64"), "0");
+}
+
+#[test]
+fn division_by_zero_is_left_alone() {
+    assert_eq!(folded("1 // 0"), "1 // 0");
+    assert_eq!(folded("1 % 0"), "1 % 0");
+    assert_eq!(folded("1 / 0"), "1 / 0");
+    assert_eq!(folded("1.5 // 0"), "1.5 // 0");
+}
+
+#[test]
+fn operands_that_arent_constant_are_left_alone() {
+    assert_eq!(folded("x + 1"), "x + 1");
+    assert_eq!(folded("f() * 2"), "f() * 2");
+    assert_eq!(folded("#t"), "#t");
+    assert_eq!(folded("x and 1"), "x and 1");
+    assert_eq!(folded("-x"), "-x");
+    // Operations that would raise an error at runtime are left for it to raise.
+    assert_eq!(folded("'a' + 1"), "'a' + 1");
+    assert_eq!(folded("1 < 'a'"), "1 < 'a'");
+    assert_eq!(folded("1.5 & 1"), "1.5 & 1");
+    // Only the constant part of an expression is folded.
+    assert_eq!(folded("x + (2 * 3)"), "x + (6)");
+}
@@ -0,0 +1,43 @@
+//! Checks what `fold_chunk` goes through.
+
+#![cfg(feature = "std")]
+
+extern crate buildlua;
+
+mod common;
+
+use buildlua::ast::Expression;
+use buildlua::visit::fold_chunk;
+use common::chunk;
+
+#[test]
+fn sums_number_literals() {
+    let src = "
+        local t = {1, x = 2, [3] = 4.5}
+        for i = 5, 6 do print(i + 7) end
+        local function f(a) return a * -8 end
+        while f(9) > 10 do end
+        return t[11] or 0.5
+    ";
+    let sum = fold_chunk(&chunk(src), 0.0, |sum, expression| match *expression {
+        Expression::Integer(n, _) => sum + n as f64,
+        Expression::Float(n, _) => sum + n,
+        _ => sum,
+    });
+    assert_eq!(sum, 1.0 + 2.0 + 3.0 + 4.5 + 5.0 + 6.0 + 7.0 + 8.0 + 9.0 + 10.0 + 11.0 + 0.5);
+}
+
+#[test]
+fn outer_expressions_come_first() {
+    let src = "return (1 + 2) * 3, -4";
+    let order = fold_chunk(&chunk(src), Vec::new(), |mut order, expression| {
+        order.push(expression.to_string());
+        order
+    });
+    assert_eq!(order, ["(1 + 2) * 3", "(1 + 2)", "1 + 2", "1", "2", "3", "-4", "4"]);
+}
+
+#[test]
+fn chunks_without_expressions_give_the_initial_result() {
+    assert_eq!(fold_chunk(&chunk("do end ::a:: goto a"), 7, |count, _| count + 1), 7);
+}
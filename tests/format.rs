@@ -0,0 +1,197 @@
+//! Checks that `format` indents nested blocks, breaks long lines, and writes strings, semicolons
+//! and floats as its options say.
+
+#![cfg(feature = "std")]
+
+extern crate buildlua;
+
+use std::iter;
+
+use buildlua::ast::{Expression, UnaryOperation};
+use buildlua::build::{expr, BlockBuilder};
+use buildlua::parser::parse;
+use buildlua::print::{format, FloatFormat, FormatOptions, Indent, QuoteStyle, SemicolonStyle};
+
+const SOURCE: &str = "function f(n) if n then return 1 end end";
+
+fn formatted(indent: Indent) -> String {
+    let chunk = parse(SOURCE).unwrap();
+    format(&chunk, &FormatOptions { indent, ..FormatOptions::default() })
+}
+
+#[test]
+fn two_spaces() {
+    let expected = "function f(n)\n  if n then\n    return 1\n  end\nend\n";
+    assert_eq!(formatted(Indent::Spaces(2)), expected);
+}
+
+#[test]
+fn tabs() {
+    let expected = "function f(n)\n\tif n then\n\t\treturn 1\n\tend\nend\n";
+    assert_eq!(formatted(Indent::Tab), expected);
+}
+
+#[test]
+fn default_matches_display() {
+    let chunk = parse(SOURCE).unwrap();
+    assert_eq!(format(&chunk, &FormatOptions::default()), chunk.to_string());
+}
+
+fn wrapped(source: &str, max_width: usize) -> String {
+    let chunk = parse(source).unwrap();
+    let options = FormatOptions { max_width: Some(max_width), ..FormatOptions::default() };
+    let formatted = format(&chunk, &options);
+    assert_eq!(parse(&formatted).unwrap(), chunk);
+    formatted
+}
+
+#[test]
+fn short_call_stays_on_one_line() {
+    assert_eq!(wrapped("print(a, b, c)", 14), "print(a, b, c)\n");
+}
+
+#[test]
+fn long_call_wraps() {
+    let expected = "print(\n    a,\n    b,\n    c\n)\n";
+    assert_eq!(wrapped("print(a, b, c)", 13), expected);
+}
+
+#[test]
+fn long_table_wraps_with_a_trailing_comma() {
+    let source = "local t = {1, 2, name = 'x', [k] = v}";
+    let expected = "local t = {\n    1,\n    2,\n    name = 'x',\n    [k] = v,\n}\n";
+    assert_eq!(wrapped(source, 30), expected);
+}
+
+#[test]
+fn nested_lists_wrap_a_level_further() {
+    let source = "if x then f(g(aaaa, bbbb), {cccc, dddd, eeee}) end";
+    let expected = [
+        "if x then",
+        "    f(",
+        "        g(aaaa, bbbb),",
+        "        {",
+        "            cccc,",
+        "            dddd,",
+        "            eeee,",
+        "        }",
+        "    )",
+        "end",
+        "",
+    ];
+    assert_eq!(wrapped(source, 22), expected.join("\n"));
+}
+
+#[test]
+fn functions_in_arguments_measure_their_first_line() {
+    let source = "call(function(x) return x end)";
+    let expected = "call(function(x)\n    return x\nend)\n";
+    assert_eq!(wrapped(source, 16), expected);
+}
+
+fn quoted(source: &str, quote_style: QuoteStyle) -> String {
+    let chunk = parse(source).unwrap();
+    format(&chunk, &FormatOptions { quote_style, ..FormatOptions::default() })
+}
+
+#[test]
+fn preserved_quotes() {
+    let source = "print('a\"b', \"c'd\", 'e', \"f\")\n";
+    assert_eq!(quoted(source, QuoteStyle::Preserve), source);
+    assert_eq!(parse(source).unwrap().to_string(), source);
+}
+
+#[test]
+fn normalized_to_double_quotes() {
+    let expected = "print(\"a\\\"b\", \"c\")\n";
+    assert_eq!(quoted("print('a\"b', 'c')", QuoteStyle::AlwaysDouble), expected);
+}
+
+#[test]
+fn normalized_to_single_quotes() {
+    let expected = "print('a\"b', 'c\\'d', [[e]])\n";
+    assert_eq!(quoted("print('a\"b', \"c'd\", [[e]])", QuoteStyle::AlwaysSingle), expected);
+}
+
+const STATEMENTS: &str =
+    "local a = 1; f(a);; (g)(a)\nwhile a do a = a - 1 end ::top:: goto top; return a";
+
+fn semicolons(semicolons: SemicolonStyle) -> String {
+    let chunk = parse(STATEMENTS).unwrap();
+    let formatted = format(&chunk, &FormatOptions { semicolons, ..FormatOptions::default() });
+    assert!(!formatted.contains(";;"));
+    formatted
+}
+
+#[test]
+fn preserved_semicolons() {
+    let expected = [
+        "local a = 1", ";", "f(a)", ";", ";", "(g)(a)", "while a do", "    a = a - 1", "end",
+        "::top::", "goto top", ";", "return a", "",
+    ];
+    assert_eq!(semicolons(SemicolonStyle::Preserve), expected.join("\n"));
+}
+
+#[test]
+fn removed_semicolons() {
+    // The one before `(g)(a)` is still needed, or it would call the result of `f(a)`.
+    let expected = [
+        "local a = 1", "f(a)", ";(g)(a)", "while a do", "    a = a - 1", "end", "::top::",
+        "goto top", "return a", "",
+    ];
+    assert_eq!(semicolons(SemicolonStyle::Remove), expected.join("\n"));
+}
+
+#[test]
+fn added_semicolons() {
+    let expected = [
+        "local a = 1;", "f(a);", "(g)(a);", "while a do", "    a = a - 1;", "end", "::top::",
+        "goto top;", "return a;", "",
+    ];
+    let formatted = semicolons(SemicolonStyle::Add);
+    assert_eq!(formatted, expected.join("\n"));
+    // Taking the added ones out again gives the same program.
+    let options = FormatOptions { semicolons: SemicolonStyle::Remove, ..FormatOptions::default() };
+    let removed = format(&parse(&formatted).unwrap(), &options);
+    assert_eq!(removed, semicolons(SemicolonStyle::Remove));
+}
+
+fn floats(float_format: FloatFormat) -> String {
+    let values = [0.1, 1e300, 5e-324, -2.5, 3.0, 1.0 / 3.0];
+    let values = values.iter().map(|&n| expr::float(n)).collect();
+    let chunk = BlockBuilder::new().ret(values).chunk();
+    format(&chunk, &FormatOptions { float_format, ..FormatOptions::default() })
+}
+
+/// The floats `source` returns as literals.
+fn returned_floats(source: &str) -> Vec<f64> {
+    let chunk = parse(source).unwrap();
+    let list = (chunk.0).1.as_ref().unwrap().0.as_ref().unwrap();
+    iter::once(&*list.0)
+        .chain(list.1.iter().flatten())
+        .map(|value| match value.node {
+            Expression::Float(n, _) => n,
+            Expression::UnaryOperation(UnaryOperation::Negate, ref operand) => match operand.node {
+                Expression::Float(n, _) => -n,
+                ref other => panic!("{:?} is not a float", other),
+            },
+            ref other => panic!("{:?} is not a float", other),
+        })
+        .collect()
+}
+
+#[test]
+fn shortest_floats_read_back_exactly() {
+    let formatted = floats(FloatFormat::Shortest);
+    assert_eq!(formatted, "return 0.1, 1e300, 5e-324, -2.5, 3.0, 0.3333333333333333\n");
+    let bits: Vec<u64> = returned_floats(&formatted).iter().map(|n| n.to_bits()).collect();
+    let expected = [0.1f64, 1e300, 5e-324, -2.5, 3.0, 1.0 / 3.0];
+    assert_eq!(bits, expected.iter().map(|n| n.to_bits()).collect::<Vec<u64>>());
+}
+
+#[test]
+fn floats_with_significant_digits() {
+    let formatted = floats(FloatFormat::Significant(6));
+    assert_eq!(formatted, "return 0.1, 1e+300, 4.94066e-324, -2.5, 3.0, 0.333333\n");
+    assert_eq!(returned_floats(&formatted).len(), 6);
+}
@@ -0,0 +1,66 @@
+//! Checks how the parser splits the name of a function statement into its parts.
+
+#![cfg(feature = "std")]
+
+extern crate buildlua;
+
+use buildlua::ast::*;
+use buildlua::parser::parse;
+use buildlua::span::Span;
+
+/// The name of the function statement that is the whole of `src`.
+fn function_name(src: &str) -> FunctionName {
+    let chunk = parse(src).unwrap_or_else(|error| panic!("failed to parse the source: {}", error));
+    let statement = chunk.0.statements().next().expect("the source has no statement");
+    match statement.node {
+        Statement::Function(ref name, _) => name.clone(),
+        ref statement => panic!("expected a function statement, found {:?}", statement),
+    }
+}
+
+#[test]
+fn plain_names() {
+    let name = function_name("function f() end");
+    assert_eq!(name.first_dot_access.node, "f");
+    assert_eq!(name.first_dot_access.span, Span::new(9, 10));
+    assert_eq!(name.rest_dot_access, None);
+    assert_eq!(name.self_name, None);
+}
+
+#[test]
+fn dotted_names() {
+    let name = function_name("function a.b() end");
+    assert_eq!(name.first_dot_access.node, "a");
+    assert_eq!(name.rest_dot_access, Some(vec!["b".to_string()]));
+    assert_eq!(name.self_name, None);
+    let name = function_name("function a.b.c.d() end");
+    assert_eq!(name.rest_dot_access, Some(vec!["b".to_string(), "c".to_string(), "d".to_string()]));
+    assert_eq!(name.self_name, None);
+}
+
+#[test]
+fn method_names() {
+    let name = function_name("function a.b:c() end");
+    assert_eq!(name.first_dot_access.node, "a");
+    assert_eq!(name.rest_dot_access, Some(vec!["b".to_string()]));
+    assert_eq!(name.self_name, Some("c".to_string()));
+    let name = function_name("function a:c() end");
+    assert_eq!(name.first_dot_access.node, "a");
+    assert_eq!(name.rest_dot_access, None);
+    assert_eq!(name.self_name, Some("c".to_string()));
+}
+
+#[test]
+fn methods_end_the_name() {
+    assert!(parse("function a:b.c() end").is_err());
+    assert!(parse("function a:b:c() end").is_err());
+    assert!(parse("function a.() end").is_err());
+}
+
+#[test]
+fn names_print_back() {
+    for src in ["function f() end", "function a.b.c() end", "function a.b:c() end"] {
+        let printed = parse(src).unwrap().to_string();
+        assert_eq!(function_name(&printed), function_name(src));
+    }
+}
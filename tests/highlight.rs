@@ -0,0 +1,101 @@
+//! Checks how `semantic_tokens` classifies the tokens of source.
+
+#![cfg(feature = "std")]
+
+extern crate buildlua;
+
+use buildlua::highlight::semantic_tokens;
+use buildlua::highlight::SemanticKind::{self, *};
+
+/// The text and kind of each classified token of `src`.
+fn classified(src: &str) -> Vec<(&str, SemanticKind)> {
+    semantic_tokens(src)
+        .into_iter()
+        .map(|token| (&src[token.span.start..token.span.end], token.kind))
+        .collect()
+}
+
+/// The kind of each classified token of `src` with the text `text`.
+fn kinds_of(src: &str, text: &str) -> Vec<SemanticKind> {
+    classified(src).into_iter().filter(|&(token, _)| token == text).map(|(_, kind)| kind).collect()
+}
+
+#[test]
+fn locals_and_globals() {
+    assert_eq!(classified("local x = 1; print(x)"), [
+        ("local", Keyword),
+        ("x", Local),
+        ("1", Number),
+        ("print", Global),
+        ("x", Local),
+    ]);
+}
+
+#[test]
+fn shadowing_follows_resolution() {
+    let src = "print(x) local x = 'a' do local print = x print(x) end print(x)";
+    assert_eq!(kinds_of(src, "print"), [Global, Local, Local, Global]);
+    assert_eq!(kinds_of(src, "x"), [Global, Local, Local, Local, Local]);
+}
+
+#[test]
+fn parameters_keep_their_kind() {
+    let src = "
+        local function f(a, ...) return function() return a end end
+        function t:m(b) return self, b end
+    ";
+    assert_eq!(kinds_of(src, "a"), [Parameter, Parameter]);
+    assert_eq!(kinds_of(src, "b"), [Parameter, Parameter]);
+    assert_eq!(kinds_of(src, "self"), [Parameter]);
+    assert_eq!(kinds_of(src, "t"), [Global]);
+    assert_eq!(kinds_of(src, "m"), [Field]);
+}
+
+#[test]
+fn functions() {
+    let src = "
+        local function f() end
+        local g, h = function() end, 1
+        function k() end
+        f() g() h() k()
+    ";
+    assert_eq!(kinds_of(src, "f"), [Function, Function]);
+    assert_eq!(kinds_of(src, "g"), [Function, Function]);
+    assert_eq!(kinds_of(src, "h"), [Local, Local]);
+    // A global function is only a function where it's declared.
+    assert_eq!(kinds_of(src, "k"), [Function, Global]);
+}
+
+#[test]
+fn fields_labels_and_attributes() {
+    let src = "local t <const> = {key = 1} ::top:: t.key = t:method() goto top";
+    assert_eq!(kinds_of(src, "key"), [Field, Field]);
+    assert_eq!(kinds_of(src, "method"), [Field]);
+    assert_eq!(kinds_of(src, "top"), [Label, Label]);
+    assert_eq!(kinds_of(src, "const"), [Keyword]);
+    assert_eq!(kinds_of(src, "t"), [Local, Local, Local]);
+}
+
+#[test]
+fn literals_and_comments() {
+    let src = "-- note\nx = 0x10 .. [[long]] .. 'short' --[[ block ]] .. nil";
+    assert_eq!(classified(src), [
+        ("-- note", Comment),
+        ("x", Global),
+        ("0x10", Number),
+        ("[[long]]", String),
+        ("'short'", String),
+        ("--[[ block ]]", Comment),
+        ("nil", Keyword),
+    ]);
+}
+
+#[test]
+fn source_with_errors_is_still_classified() {
+    assert_eq!(classified("local x = ; print(x"), [
+        ("local", Keyword),
+        ("x", Local),
+        ("print", Global),
+        ("x", Local),
+    ]);
+}
@@ -0,0 +1,106 @@
+//! Checks that identifiers are interned into symbols as the parser meets them.
+
+#![cfg(feature = "std")]
+
+extern crate buildlua;
+
+mod common;
+
+use buildlua::ast::{Expression, Label, PrefixExpression, Statement, Variable};
+use buildlua::intern::{Interned, InternedChunk, StringInterner, Symbol};
+use buildlua::parser::{parse, parse_interned, ParserConfig};
+
+fn statements(chunk: &InternedChunk) -> Vec<&Statement<Interned>> {
+    chunk.0.statements().map(|statement| &statement.node).collect()
+}
+
+/// The variable an expression reads.
+fn variable(expression: &Expression<Interned>) -> &Variable<Interned> {
+    match *expression {
+        Expression::PrefixExpression(ref prefix) => match **prefix {
+            PrefixExpression::Variable(ref variable) => variable,
+            ref other => panic!("expected a variable, found {:?}", other),
+        },
+        ref other => panic!("expected a variable, found {:?}", other),
+    }
+}
+
+/// The symbol of a variable that is a plain name.
+fn name(variable: &Variable<Interned>) -> Symbol {
+    match *variable {
+        Variable::Name(ref name) => name.node,
+        ref other => panic!("expected a name, found {:?}", other),
+    }
+}
+
+#[test]
+fn same_names_share_a_symbol() {
+    let src = "local key, other = key.key, other\n";
+    let mut interner = StringInterner::new();
+    let chunk = parse_interned(src, &ParserConfig::default(), &mut interner).unwrap();
+    assert_eq!(interner.resolve_chunk(&chunk), parse(src).unwrap());
+
+    let (names, values) = match *statements(&chunk)[0] {
+        Statement::LocalVariableBinding(ref names, Some(ref values)) => (names, values),
+        ref other => panic!("{:?}", other),
+    };
+    let key = names.0.name.node;
+    let other = names.1.as_ref().unwrap()[0].name.node;
+    assert_eq!(interner.resolve(key), "key");
+    assert_eq!(interner.resolve(other), "other");
+    assert_ne!(key, other);
+    match *variable(&values.0.node) {
+        Variable::DotAccess { ref from, key: field } => {
+            assert_eq!(field, key);
+            match **from {
+                PrefixExpression::Variable(ref from) => assert_eq!(name(from), key),
+                ref from => panic!("expected a variable, found {:?}", from),
+            }
+        }
+        ref other => panic!("expected a dot access, found {:?}", other),
+    }
+    assert_eq!(name(variable(&values.1.as_ref().unwrap()[0].node)), other);
+    assert_eq!(interner.len(), 2);
+}
+
+#[test]
+fn keywords_and_strings_are_not_names() {
+    let src = "local s <const> = 'name' goto done ::done::";
+    let mut interner = StringInterner::new();
+    let chunk = parse_interned(src, &ParserConfig::default(), &mut interner).unwrap();
+    assert_eq!(interner.get("name"), None);
+    assert_eq!(interner.get("local"), None);
+    assert_eq!(interner.get("const"), None);
+    let labels: Vec<_> = statements(&chunk)[1..]
+        .iter()
+        .map(|statement| match **statement {
+            Statement::Goto(Label(label)) | Statement::Label(Label(label)) => label,
+            ref other => panic!("{:?}", other),
+        })
+        .collect();
+    assert_eq!(labels[0], labels[1]);
+    assert_eq!(interner.get("done"), Some(labels[0]));
+}
+
+#[test]
+fn interners_are_shared_between_files() {
+    let mut interner = StringInterner::new();
+    let config = ParserConfig::default();
+    for (path, src) in common::corpus() {
+        let chunk = parse_interned(&src, &config, &mut interner).unwrap();
+        assert_eq!(interner.resolve_chunk(&chunk), parse(&src).unwrap(), "{}", path.display());
+    }
+    // Parsing the files again interns nothing new.
+    let before = interner.len();
+    for (_, src) in common::corpus() {
+        parse_interned(&src, &config, &mut interner).unwrap();
+    }
+    assert_eq!(interner.len(), before);
+}
+
+#[test]
+fn failed_parses_give_errors() {
+    let mut interner = StringInterner::new();
+    assert!(parse_interned("x = ", &ParserConfig::default(), &mut interner).is_err());
+    assert!(parse_interned("x = '", &ParserConfig::default(), &mut interner).is_err());
+}
@@ -0,0 +1,337 @@
+//! Runs small programs in the interpreter and checks the values they return.
+
+#![cfg(feature = "std")]
+
+extern crate buildlua;
+
+use buildlua::interp::{eval, Value};
+use buildlua::parser::parse;
+
+fn run(src: &str) -> Vec<Value> {
+    let chunk = parse(src).unwrap_or_else(|error| panic!("failed to parse the source: {}", error));
+    eval(&chunk).unwrap_or_else(|error| panic!("failed to run the source: {}", error))
+}
+
+#[test]
+fn factorial() {
+    let src = "
+        local n = 1
+        for i = 1, 10 do
+            n = n * i
+        end
+        return n
+    ";
+    assert_eq!(run(src), vec![Value::Number(3628800.0)]);
+}
+
+#[test]
+fn fibonacci() {
+    let src = "
+        local function fib(n)
+            local a, b = 0, 1
+            for _ = 1, n do
+                a, b = b, a + b
+            end
+            return a
+        end
+        return fib(30), fib(1), fib(0)
+    ";
+    assert_eq!(run(src), vec![Value::Number(832040.0), Value::Number(1.0), Value::Number(0.0)]);
+}
+
+#[test]
+fn recursion() {
+    let src = "
+        function fact(n)
+            if n <= 1 then return 1 else return n * fact(n - 1) end
+        end
+        return fact(5)
+    ";
+    assert_eq!(run(src), vec![Value::Number(120.0)]);
+}
+
+#[test]
+fn multiple_returns() {
+    let src = "
+        local function pair() return 1, 2 end
+        local a, b, c = pair()
+        local d, e = pair(), 10
+        return a, b, c, d, e, pair()
+    ";
+    let (one, two) = (Value::Number(1.0), Value::Number(2.0));
+    assert_eq!(
+        run(src),
+        vec![one.clone(), two.clone(), Value::Nil, one.clone(), Value::Number(10.0), one, two]
+    );
+}
+
+#[test]
+fn closures_share_locals() {
+    let src = "
+        local function counter()
+            local n = 0
+            return function() n = n + 1 return n end
+        end
+        local c = counter()
+        c() c()
+        return c()
+    ";
+    assert_eq!(run(src), vec![Value::Number(3.0)]);
+}
+
+#[test]
+fn scoping() {
+    let src = "
+        local x = 1
+        do local x = 2 end
+        local i = 0
+        repeat local y = i i = i + 1 until y >= 3
+        while true do x = x + 1 if x > 5 then break end end
+        return x, i
+    ";
+    assert_eq!(run(src), vec![Value::Number(6.0), Value::Number(4.0)]);
+    // Jumping out of a loop's block takes its locals out of scope too.
+    let src = "repeat local x = 1 goto done until true ::done:: return x";
+    assert_eq!(run(src), vec![Value::Nil]);
+    let src = "while true do local x = 1 goto done end ::done:: return x";
+    assert_eq!(run(src), vec![Value::Nil]);
+}
+
+#[test]
+fn short_circuit() {
+    let src = "
+        local calls = 0
+        local function f() calls = calls + 1 return true end
+        local a = false and f()
+        local b = 1 or f()
+        local c = nil or 'x'
+        return a, b, c, calls
+    ";
+    assert_eq!(
+        run(src),
+        vec![Value::Bool(false), Value::Number(1.0), Value::from("x"), Value::Number(0.0)]
+    );
+}
+
+#[test]
+fn runtime_error() {
+    let chunk = parse("local t = nil\nreturn t.x").unwrap();
+    let error = eval(&chunk).unwrap_err();
+    assert_eq!(error.message, "attempt to index a nil value");
+}
+
+#[test]
+fn short_circuit_skips_the_right_operand() {
+    // Calling `error_fn` would fail, as it calls a nil value.
+    let src = "
+        local function error_fn() return undefined() end
+        return false and error_fn(), nil and error_fn(), 1 or error_fn()
+    ";
+    assert_eq!(run(src), vec![Value::Bool(false), Value::Nil, Value::Number(1.0)]);
+}
+
+#[test]
+fn logical_operators_give_an_operand() {
+    assert_eq!(run("return nil or 5"), vec![Value::Number(5.0)]);
+    assert_eq!(run("return false or nil"), vec![Value::Nil]);
+    assert_eq!(run("return 1 and 'x'"), vec![Value::from("x")]);
+}
+
+#[test]
+fn only_nil_and_false_are_falsy() {
+    let src = "
+        local function truthy(v) if v then return true else return false end end
+        return truthy(0), truthy(''), truthy({}), truthy(nil), truthy(false), not 0
+    ";
+    let (t, f) = (Value::Bool(true), Value::Bool(false));
+    assert_eq!(run(src), vec![t.clone(), t.clone(), t, f.clone(), f.clone(), f]);
+}
+
+/// The values `src` returns as they debug print, which tells integers from floats.
+fn run_exactly(src: &str) -> String {
+    format!("{:?}", run(src))
+}
+
+#[test]
+fn integers_wrap_around() {
+    let src = "
+        local max, min = 9223372036854775807, -9223372036854775807 - 1
+        return max + 1 == min, min - 1 == max, max * 2, -min
+    ";
+    let t = Value::Bool(true);
+    assert_eq!(run(src), vec![t.clone(), t, Value::Integer(-2), Value::Integer(i64::MIN)]);
+}
+
+#[test]
+fn mixing_integers_and_floats_gives_floats() {
+    assert_eq!(
+        run_exactly("return 1 + 2, 1 + 2.0, 2 * 0.5, -3"),
+        "[Integer(3), Number(3.0), Number(1.0), Integer(-3)]",
+    );
+    assert_eq!(run("return 1 == 1.0, 2 < 2.5, 3 > 2.5"), vec![Value::Bool(true); 3]);
+}
+
+#[test]
+fn division_gives_a_float() {
+    assert_eq!(run("return 3 / 2 == 1.5"), vec![Value::Bool(true)]);
+    assert_eq!(
+        run_exactly("return 3 / 2, 4 / 2, 2 ^ 2"),
+        "[Number(1.5), Number(2.0), Number(4.0)]",
+    );
+}
+
+#[test]
+fn floor_division_and_modulo_keep_integers() {
+    assert_eq!(
+        run_exactly("return 7 // 2, -7 // 2, 7 % 3, -7 % 3"),
+        "[Integer(3), Integer(-4), Integer(1), Integer(2)]",
+    );
+    assert_eq!(
+        run_exactly("return 7.0 // 2, 7 % 2.5, 1 // 0.0"),
+        "[Number(3.0), Number(2.0), Number(inf)]",
+    );
+    for (src, message) in &[("return 1 // 0", "'n//0'"), ("return 1 % 0", "'n%0'")] {
+        let error = eval(&parse(src).unwrap()).unwrap_err();
+        assert_eq!(error.message, format!("attempt to perform {}", message));
+    }
+}
+
+#[test]
+fn integer_valued_float_keys_are_integers() {
+    let src = "
+        local t = {}
+        t[1.0], t[2] = 'a', 'b'
+        t[2^53] = 'c'
+        return t[1], t[2.0], #t, t[9007199254740992]
+    ";
+    assert_eq!(run_exactly(src), r#"[Str("a"), Str("b"), Integer(2), Str("c")]"#);
+}
+
+#[test]
+fn numbers_concatenate_by_their_type() {
+    assert_eq!(run("return 1 .. '', 1.0 .. '', 2^53 .. ''"), vec![
+        Value::from("1"),
+        Value::from("1.0"),
+        Value::from("9.007199254741e+15"),
+    ]);
+}
+
+#[test]
+fn returning_a_call_gives_all_its_values() {
+    let src = "
+        local function pair() return 1, 2 end
+        local function forward() return pair() end
+        return forward()
+    ";
+    assert_eq!(run_exactly(src), "[Integer(1), Integer(2)]");
+    // Parentheses keep only the first value.
+    let src = "local function pair() return 1, 2 end return (pair())";
+    assert_eq!(run_exactly(src), "[Integer(1)]");
+}
+
+#[test]
+fn calls_before_the_last_give_one_value() {
+    let src = "
+        local function pair() return 1, 2 end
+        x, y, z = pair(), 10
+        return x, y, z
+    ";
+    assert_eq!(run_exactly(src), "[Integer(1), Integer(10), Nil]");
+    let src = "
+        local function none() end
+        x, y = none(), 10
+        return x, y
+    ";
+    assert_eq!(run_exactly(src), "[Nil, Integer(10)]");
+}
+
+#[test]
+fn tables_collect_all_values_of_the_last_call() {
+    let src = "
+        local function three() return 1, 2, 3 end
+        local all, first = {three()}, {three(), 10}
+        return #all, all[3], #first, first[1], first[2]
+    ";
+    assert_eq!(run_exactly(src), "[Integer(3), Integer(3), Integer(2), Integer(1), Integer(10)]");
+}
+
+#[test]
+fn arguments_expand_only_the_last_call() {
+    let src = "
+        local function pair() return 1, 2 end
+        local function count(...) return #{...} end
+        return count(pair()), count(pair(), pair()), count((pair())), count(pair(), 5)
+    ";
+    assert_eq!(run_exactly(src), "[Integer(2), Integer(3), Integer(1), Integer(2)]");
+    let src = "
+        local function varargs(...) return ..., 'last' end
+        local function all(...) return ... end
+        return varargs(1, 2), all(3, 4)
+    ";
+    assert_eq!(run_exactly(src), "[Integer(1), Integer(3), Integer(4)]");
+    let src = "local function varargs(...) return ..., 'last' end return varargs(1, 2)";
+    assert_eq!(run_exactly(src), "[Integer(1), Str(\"last\")]");
+}
+
+#[test]
+fn length_of_strings_counts_bytes() {
+    let src = "return #'', #'hello', #'h\u{e9}llo'";
+    assert_eq!(run_exactly(src), "[Integer(0), Integer(5), Integer(6)]");
+}
+
+#[test]
+fn length_of_tables_is_a_border() {
+    let src = "return #{1, 2, 3}, #{}, #{x = 1}";
+    assert_eq!(run_exactly(src), "[Integer(3), Integer(0), Integer(0)]");
+    let src = "
+        local shrunk = {1, 2, 3}
+        shrunk[3] = nil
+        local filled = {}
+        filled[2] = 'b'
+        filled[3] = 'c'
+        filled[1] = 'a'
+        local holed = {1, 2, 3}
+        holed[2] = nil
+        return #shrunk, #filled, holed[#holed + 1] == nil and holed[#holed] ~= nil
+    ";
+    assert_eq!(run_exactly(src), "[Integer(2), Integer(3), Bool(true)]");
+}
+
+#[test]
+fn length_of_other_values_is_an_error() {
+    let chunk = parse("return #5").unwrap();
+    let error = eval(&chunk).unwrap_err();
+    assert_eq!(error.to_string(), "attempt to get length of a number value at byte 7");
+}
+
+#[test]
+fn array_fields_count_up_around_keyed_ones() {
+    let src = "
+        local t = {1, 2, x = 3, [10] = 4}
+        return t[1], t[2], t.x, t[10], #t, t[3]
+    ";
+    let expected = "[Integer(1), Integer(2), Integer(3), Integer(4), Integer(2), Nil]";
+    assert_eq!(run_exactly(src), expected);
+    let src = "
+        local t = {'a', x = 'b', 'c', [4.0] = 'd', ['y'] = 'e', 'f'}
+        return t[1], t[2], t[3], t[4], t.x, t.y
+    ";
+    let expected = "[Str(\"a\"), Str(\"c\"), Str(\"f\"), Str(\"d\"), Str(\"b\"), Str(\"e\")]";
+    assert_eq!(run_exactly(src), expected);
+}
+
+#[test]
+fn only_a_last_array_field_expands() {
+    let src = "
+        local function three() return 1, 2, 3 end
+        local last, keyed_after, keyed_before = {0, three()}, {three(), x = 1}, {x = 1, three()}
+        return #last, last[4], #keyed_after, #keyed_before, keyed_before[3]
+    ";
+    assert_eq!(run_exactly(src), "[Integer(4), Integer(3), Integer(1), Integer(3), Integer(3)]");
+    let src = "
+        local function none() end
+        local empty, padded = {none()}, {none(), 2}
+        return #empty, padded[1], padded[2]
+    ";
+    assert_eq!(run_exactly(src), "[Integer(0), Nil, Integer(2)]");
+}
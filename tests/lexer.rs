@@ -0,0 +1,105 @@
+//! Checks the tokens the lexer splits source code into.
+
+#![cfg(feature = "std")]
+
+extern crate buildlua;
+
+use buildlua::ast::{Expression, StringForm};
+use buildlua::lexer::{LexError, LexErrorKind, Lexer, Token};
+use buildlua::parser::parse;
+
+fn tokens(src: &str) -> Vec<Token> {
+    Lexer::new(src).collect::<Result<_, _>>().unwrap()
+}
+
+fn error(src: &str) -> LexError {
+    Lexer::new(src).find_map(Result::err).unwrap()
+}
+
+fn name(name: &str) -> Token {
+    Token::Name(name.to_string())
+}
+
+#[test]
+fn local_assignment() {
+    assert_eq!(
+        tokens("local x = 1 + 2"),
+        vec![
+            Token::Local,
+            name("x"),
+            Token::Equal,
+            Token::Integer(1),
+            Token::Plus,
+            Token::Integer(2),
+        ]
+    );
+}
+
+#[test]
+fn line_comments_are_skipped() {
+    assert_eq!(tokens("x -- the rest of the line\ny"), vec![name("x"), name("y")]);
+    assert_eq!(tokens("-- only a comment"), vec![]);
+    assert_eq!(tokens("x --"), vec![name("x")]);
+}
+
+#[test]
+fn block_comments_are_skipped() {
+    assert_eq!(tokens("x --[[ spans\ntwo lines ]] y"), vec![name("x"), name("y")]);
+    assert_eq!(tokens("--[==[ ]] ]==] z"), vec![name("z")]);
+    let mut lexer = Lexer::new("a --[[ note ]] b");
+    assert_eq!(lexer.by_ref().count(), 2);
+    assert_eq!(lexer.into_comments()[0].text, " note ");
+    assert_eq!(error("--[[ never closed").kind, LexErrorKind::UnterminatedComment);
+}
+
+#[test]
+fn integers_and_floats() {
+    assert_eq!(tokens("3"), vec![Token::Integer(3)]);
+    assert_eq!(tokens("3.0"), vec![Token::Float(3.0)]);
+    assert_eq!(tokens("3e2"), vec![Token::Float(300.0)]);
+    assert_eq!(tokens(".5 5. 1E-1"), vec![Token::Float(0.5), Token::Float(5.0), Token::Float(0.1)]);
+    // Too large for an integer.
+    assert_eq!(tokens("9223372036854775808"), vec![Token::Float(9223372036854775808.0)]);
+
+    let returned = |src: &str| {
+        let chunk = parse(&format!("return {}", src)).unwrap();
+        chunk.0 .1.as_ref().unwrap().0.as_ref().unwrap().0.node.clone()
+    };
+    assert!(matches!(returned("3"), Expression::Integer(3, _)));
+    assert!(matches!(returned("3.0"), Expression::Float(n, _) if n == 3.0));
+    assert!(matches!(returned("3e2"), Expression::Float(n, _) if n == 300.0));
+}
+
+#[test]
+fn hexadecimal_numbers() {
+    assert_eq!(tokens("0xff"), vec![Token::Integer(255)]);
+    assert_eq!(tokens("0XA0"), vec![Token::Integer(160)]);
+    assert_eq!(tokens("0x1p4"), vec![Token::Float(16.0)]);
+    assert_eq!(tokens("0x.8"), vec![Token::Float(0.5)]);
+    // Hexadecimal integers wrap around rather than becoming floats.
+    assert_eq!(tokens("0xffffffffffffffff"), vec![Token::Integer(-1)]);
+    let bare = error("0x");
+    assert_eq!(bare.kind, LexErrorKind::MalformedNumber);
+    assert_eq!(bare.span.start, 0);
+    assert_eq!(error("0xg").kind, LexErrorKind::MalformedNumber);
+}
+
+#[test]
+fn long_strings() {
+    assert_eq!(tokens("[[abc]]"), vec![Token::String(b"abc".to_vec(), StringForm::Long(0))]);
+    assert_eq!(
+        tokens("[==[a]=]b]==]"),
+        vec![Token::String(b"a]=]b".to_vec(), StringForm::Long(2))]
+    );
+    // A newline straight after the opening bracket isn't part of the string.
+    assert_eq!(tokens("[[\nx\n]]"), vec![Token::String(b"x\n".to_vec(), StringForm::Long(0))]);
+    assert_eq!(error("[[abc").kind, LexErrorKind::UnterminatedString);
+    assert_eq!(error("[==[abc]=]").kind, LexErrorKind::UnterminatedString);
+}
+
+#[test]
+fn division_and_floor_division() {
+    assert_eq!(tokens("a/b"), vec![name("a"), Token::Slash, name("b")]);
+    assert_eq!(tokens("a//b"), vec![name("a"), Token::DoubleSlash, name("b")]);
+    assert_eq!(tokens("a/ /b"), vec![name("a"), Token::Slash, Token::Slash, name("b")]);
+}
@@ -0,0 +1,64 @@
+//! Checks that `\r\n` and lone `\r` line breaks are treated like `\n`.
+
+#![cfg(feature = "std")]
+
+extern crate buildlua;
+
+use buildlua::lexer::{Lexer, Token};
+use buildlua::parser::parse;
+use buildlua::span::{LineMap, SourceError, Span};
+
+#[test]
+fn line_comment_ends_at_crlf() {
+    let chunk = parse("-- one\r\nx = 1\r\n").unwrap();
+    assert_eq!(chunk.1.len(), 1);
+    assert_eq!(chunk.1[0].text, " one");
+    assert_eq!(chunk.1[0].span, Span::new(0, 6));
+    assert_eq!(chunk.0.statements().count(), 1);
+}
+
+#[test]
+fn line_comment_ends_at_lone_cr() {
+    let chunk = parse("-- one\rx = 1 -- two\rreturn x").unwrap();
+    let comments: Vec<_> = chunk.1.iter().map(|comment| &*comment.text).collect();
+    assert_eq!(comments, [" one", " two"]);
+    assert_eq!(chunk.0.statements().count(), 1);
+    assert!(chunk.0.1.is_some());
+}
+
+#[test]
+fn long_strings_read_line_breaks_as_newlines() {
+    let string = |src: &str| match Lexer::new(src).next() {
+        Some(Ok(Token::String(s, _))) => s,
+        other => panic!("expected a string, found {:?}", other),
+    };
+    assert_eq!(string("[[a\r\nb\n\rc\rd\ne]]"), b"a\nb\nc\nd\ne");
+    assert_eq!(string("[[a\r\rb\n\nc]]"), b"a\n\nb\n\nc");
+    // The line break right after the opening bracket is still dropped.
+    assert_eq!(string("[==[\r\nx\r\n]==]"), b"x\n");
+}
+
+#[test]
+fn shebang_ends_at_cr() {
+    let mut lexer = Lexer::new("#!/usr/bin/lua\rx");
+    assert_eq!(lexer.next(), Some(Ok(Token::Name("x".to_string()))));
+}
+
+#[test]
+fn mixed_line_endings() {
+    let source = "a\r\nb\rc\nd\r\r\ne";
+    let lines = LineMap::new(source);
+    let offsets = [0, 3, 5, 7, 11];
+    let found: Vec<_> = offsets.iter().map(|&i| lines.location(Span::new(i, i + 1))).collect();
+    assert_eq!(found, [(1, 1), (2, 1), (3, 1), (4, 1), (6, 1)]);
+    let texts: Vec<_> = (1..=6).map(|line| lines.line(line)).collect();
+    assert_eq!(texts, ["a", "b", "c", "d", "", "e"]);
+}
+
+#[test]
+fn errors_after_windows_line_endings() {
+    let source = "local a = 1\r\nlocal b = 2\r\nlocal = 3\r\n";
+    let err = parse(source).unwrap_err();
+    let expected = "3:7: unexpected '=', expected name\nlocal = 3\n      ^";
+    assert_eq!(err.in_source(source).to_string(), expected);
+}
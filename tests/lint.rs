@@ -0,0 +1,89 @@
+//! Checks what the lints find in small snippets.
+
+#![cfg(feature = "std")]
+
+extern crate buildlua;
+
+use std::collections::HashSet;
+
+use buildlua::lint::{find_undefined_globals, find_unused_locals, LocalKind};
+use buildlua::parser::parse;
+
+/// The name, start and kind of each unused local in `src`.
+fn unused(src: &str) -> Vec<(String, usize, LocalKind)> {
+    let chunk = parse(src).unwrap_or_else(|error| panic!("failed to parse the source: {}", error));
+    find_unused_locals(&chunk)
+        .into_iter()
+        .map(|local| (local.name, local.span.start, local.kind))
+        .collect()
+}
+
+fn expected(locals: &[(&str, usize, LocalKind)]) -> Vec<(String, usize, LocalKind)> {
+    locals.iter().map(|&(name, start, kind)| (name.to_string(), start, kind)).collect()
+}
+
+#[test]
+fn unused_locals() {
+    let src = "local a, b = 1, 2\nprint(a)";
+    assert_eq!(unused(src), expected(&[("b", 9, LocalKind::Variable)]));
+    let src = "local function f(x, y) return y end";
+    assert_eq!(
+        unused(src),
+        expected(&[("f", 15, LocalKind::Function), ("x", 17, LocalKind::Parameter)])
+    );
+}
+
+#[test]
+fn used_locals() {
+    assert_eq!(unused("local a = 1\nreturn function() return a end"), expected(&[]));
+    assert_eq!(unused("local t = {}\nt.x = 1"), expected(&[]));
+    assert_eq!(unused("local function f(n) return f(n) end\nf(1)"), expected(&[]));
+}
+
+#[test]
+fn shadowed_locals() {
+    let src = "local x = 1\nlocal x = 2\nprint(x)";
+    assert_eq!(unused(src), expected(&[("x", 6, LocalKind::Variable)]));
+}
+
+#[test]
+fn written_but_never_read() {
+    let src = "local x = 1\nx = 2\nx = x";
+    assert_eq!(unused(src), expected(&[]));
+    let src = "local x = 1\nx = 2\nfunction f() x = 3 end";
+    assert_eq!(unused(src), expected(&[("x", 6, LocalKind::Variable)]));
+}
+
+#[test]
+fn underscore_is_ignored() {
+    assert_eq!(unused("local _ = f()\nfunction g(_, y) return y end"), expected(&[]));
+}
+
+/// The names and starts of the undefined globals `src` reads, knowing only `print` and `string`.
+fn undefined(src: &str) -> Vec<(String, usize)> {
+    let chunk = parse(src).unwrap_or_else(|error| panic!("failed to parse the source: {}", error));
+    let known: HashSet<String> = ["print", "string"].iter().map(|name| name.to_string()).collect();
+    find_undefined_globals(&chunk, &known)
+        .into_iter()
+        .map(|(name, span)| (name, span.start))
+        .collect()
+}
+
+#[test]
+fn misspelt_globals() {
+    assert_eq!(undefined("print(\"x\")"), vec![]);
+    assert_eq!(undefined("prnt(\"x\")"), vec![("prnt".to_string(), 0)]);
+    assert_eq!(undefined("string.formt('%d', x)"), vec![("x".to_string(), 19)]);
+}
+
+#[test]
+fn locals_arent_globals() {
+    let src = "local prnt = print\nlocal function f(a, b) return prnt(a, b) end\nf(1)";
+    assert_eq!(undefined(src), vec![]);
+}
+
+#[test]
+fn assigned_globals_are_only_reported_when_read() {
+    let src = "x = 1\nfunction g() end\nfunction t.f() end\nprint(x)";
+    assert_eq!(undefined(src), vec![("t".to_string(), 32), ("x".to_string(), 48)]);
+}
@@ -0,0 +1,39 @@
+//! Checks that errors are shown at the line and column they were found at.
+
+#![cfg(feature = "std")]
+
+extern crate buildlua;
+
+use buildlua::parser::parse;
+use buildlua::span::{LineMap, SourceError, Span};
+
+#[test]
+fn lines_and_columns() {
+    let lines = LineMap::new("a\nbc\r\n\ndé=x");
+    assert_eq!(lines.location(Span::new(0, 1)), (1, 1));
+    assert_eq!(lines.location(Span::new(3, 4)), (2, 2));
+    assert_eq!(lines.location(Span::new(6, 6)), (3, 1));
+    // The é takes two bytes but one column.
+    assert_eq!(lines.location(Span::new(10, 11)), (4, 3));
+    assert_eq!(lines.line(2), "bc");
+    assert_eq!(lines.line(4), "dé=x");
+}
+
+#[test]
+fn error_on_the_third_line() {
+    let source = "local a = 1\nlocal b = 2\nlocal c = a +\n";
+    let error = parse(source).unwrap_err();
+    // The source ends on the line after, so the error is at the start of the fourth line.
+    let shown = error.in_source(source).to_string();
+    assert_eq!(shown.lines().next(), Some("4:1: unexpected end of input, expected expression"));
+    let source = "local a = 1\nlocal b = 2\nlocal ñ = a\n";
+    let expected = "3:7: unexpected character 'ñ'\nlocal ñ = a\n      ^";
+    assert_eq!(parse(source).unwrap_err().in_source(source).to_string(), expected);
+}
+
+#[test]
+fn tabs_are_kept_under_the_line() {
+    let source = "if x then\n\treturn = 1\nend";
+    let expected = "2:9: unexpected '=', expected expression\n\treturn = 1\n\t       ^";
+    assert_eq!(parse(source).unwrap_err().in_source(source).to_string(), expected);
+}
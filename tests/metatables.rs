@@ -0,0 +1,88 @@
+//! Runs programs using metatables in the interpreter.
+
+#![cfg(feature = "std")]
+
+extern crate buildlua;
+
+use buildlua::interp::{eval, RuntimeError, Value};
+use buildlua::parser::parse;
+
+fn try_run(src: &str) -> Result<Vec<Value>, RuntimeError> {
+    let chunk = parse(src).unwrap_or_else(|error| panic!("failed to parse the source: {}", error));
+    eval(&chunk)
+}
+
+fn run(src: &str) -> Vec<Value> {
+    try_run(src).unwrap_or_else(|error| panic!("failed to run the source: {}", error))
+}
+
+#[test]
+fn index_falls_through_to_a_prototype() {
+    let src = "
+        local Animal = {}
+        Animal.__index = Animal
+        function Animal.new(sound) return setmetatable({sound = sound}, Animal) end
+        function Animal:speak() return self.sound .. '!' end
+        local Dog = setmetatable({}, {__index = Animal})
+        Dog.legs = 4
+        local dog = Animal.new('woof')
+        return dog:speak(), dog.missing, Dog.new, Dog.legs
+    ";
+    let values = run(src);
+    assert_eq!(values[0], Value::from("woof!"));
+    assert_eq!(values[1], Value::Nil);
+    assert_eq!(values[2].type_name(), "function");
+    assert_eq!(values[3], Value::Number(4.0));
+}
+
+#[test]
+fn index_function() {
+    let src = "
+        local calls = 0
+        local t = setmetatable({present = 1}, {__index = function(t, key)
+            calls = calls + 1
+            return key .. '?'
+        end})
+        return t.present, t.absent, t[2], calls
+    ";
+    let expected = vec![1.0.into(), "absent?".into(), "2?".into(), 2.0.into()];
+    assert_eq!(run(src), expected);
+}
+
+#[test]
+fn newindex() {
+    let src = "
+        local log, store = {}, {}
+        local t = setmetatable({existing = 0}, {__newindex = function(t, key, value)
+            log[#log + 1] = key
+        end})
+        t.a = 1
+        t.existing = 2
+        local proxy = setmetatable({}, {__newindex = store})
+        proxy.b = 3
+        return log[1], log[2], t.a, t.existing, store.b
+    ";
+    let expected = vec!["a".into(), Value::Nil, Value::Nil, 2.0.into(), 3.0.into()];
+    assert_eq!(run(src), expected);
+}
+
+#[test]
+fn getmetatable() {
+    let src = "
+        local mt = {}
+        local t = setmetatable({}, mt)
+        local protected = setmetatable({}, {__metatable = 'locked'})
+        return getmetatable(t) == mt, getmetatable(protected), getmetatable(1)
+    ";
+    assert_eq!(run(src), vec![Value::Bool(true), "locked".into(), Value::Nil]);
+    let error = try_run("setmetatable(setmetatable({}, {__metatable = 1}), {})").unwrap_err();
+    assert_eq!(error.message, "cannot change a protected metatable");
+    let error = try_run("setmetatable(1, {})").unwrap_err();
+    assert_eq!(error.message, "bad argument #1 to 'setmetatable' (table expected, got number)");
+}
+
+#[test]
+fn index_loops_are_caught() {
+    let error = try_run("local t = {} setmetatable(t, {__index = t}) return t.x").unwrap_err();
+    assert_eq!(error.message, "'__index' chain too long; possibly a loop");
+}
@@ -0,0 +1,89 @@
+//! Checks that minified code is short and parses back to the same tree.
+
+#![cfg(feature = "std")]
+
+extern crate buildlua;
+
+mod common;
+
+use buildlua::ast::*;
+use buildlua::parser::parse;
+use buildlua::print::minify;
+use buildlua::visit::{self, VisitorMut};
+
+/// Drops semicolons, which `minify` leaves out unless they're needed to tell statements apart.
+struct Semicolons;
+
+impl VisitorMut for Semicolons {
+    fn visit_block_mut(&mut self, block: &mut Block) {
+        if let Some(ref mut statements) = block.0 {
+            statements.retain(|statement| statement.node != Statement::Semicolon);
+        }
+        if block.0.as_ref().is_some_and(Vec::is_empty) {
+            block.0 = None;
+        }
+        visit::walk_block_mut(self, block);
+    }
+}
+
+fn without_semicolons(src: &str) -> Block {
+    let mut chunk = parse(src).unwrap();
+    visit::walk_chunk_mut(&mut Semicolons, &mut chunk);
+    chunk.0.clone()
+}
+
+/// Minifies `src`, checking that the result parses back to the tree `src` parses to.
+fn minified(src: &str) -> String {
+    let minified = minify(&parse(src).unwrap());
+    assert_eq!(without_semicolons(&minified), without_semicolons(src), "{}", minified);
+    minified
+}
+
+#[test]
+fn programs_parse_back_the_same() {
+    let src = "-- a comment that goes\nlocal t = { 1, 2, x = 3 };\n\nfor i = 1, #t do\n    \
+               print(t[i] .. \"!\")\nend\n\nlocal function f(a, ...)\n    return a + 1, ...\n\
+               end\nif f(1) == 2 then goto done end\n::done::\n";
+    let printed = minified(src);
+    assert!(printed.len() < src.len(), "{}", printed);
+    assert!(!printed.contains("comment"));
+    assert!(!printed.contains('\n'));
+}
+
+#[test]
+fn the_corpus_parses_back_the_same() {
+    for (_, src) in common::corpus() {
+        minified(&src);
+    }
+}
+
+#[test]
+fn concatenation_next_to_a_number_keeps_its_space() {
+    // `1..2` would lex as a malformed number.
+    assert_eq!(minified("x = 1 .. 2"), "x=1 .. 2");
+    assert_eq!(minified("x = a .. b"), "x=a..b");
+    assert_eq!(minified("x = 1.5 .. 'a'"), "x=1.5 ..'a'");
+    assert_eq!(minified("x = 0xA .. 'b'"), "x=0xA ..'b'");
+    assert_eq!(minified("x = 1e5 .. a1.b"), "x=1e5 ..a1.b");
+}
+
+#[test]
+fn words_next_to_words_keep_their_space() {
+    assert_eq!(minified("if not x then return end"), "if not x then return end");
+    assert_eq!(minified("local a = b and c or d"), "local a=b and c or d");
+    assert_eq!(minified("while true do break end"), "while true do break end");
+    assert_eq!(minified("x = f 'a'"), "x=f'a'");
+}
+
+#[test]
+fn operators_that_would_merge_keep_their_space() {
+    assert_eq!(minified("x = 1 - -y"), "x=1- -y");
+    // `~~` is two operators, since lua has no operator of its own written that way.
+    assert_eq!(minified("x = a ~ ~b"), "x=a~~b");
+}
+
+#[test]
+fn semicolons_that_tell_statements_apart_are_kept() {
+    assert_eq!(minified("local x = f\n;(g)()"), "local x=f;(g)()");
+    assert_eq!(minified("x = 1; y = 2;"), "x=1 y=2");
+}
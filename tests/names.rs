@@ -0,0 +1,51 @@
+//! Checks which uses of names `collect_names` finds.
+
+#![cfg(feature = "std")]
+
+extern crate buildlua;
+
+use buildlua::parser::parse;
+use buildlua::resolve::collect_names;
+
+/// The names used in `src` along with the source text at each span, which should be the name.
+fn names(src: &str) -> Vec<(String, usize)> {
+    let chunk = parse(src).unwrap_or_else(|error| panic!("failed to parse the source: {}", error));
+    collect_names(&chunk)
+        .into_iter()
+        .map(|(name, span)| {
+            assert_eq!(&src[span.start..span.end], name);
+            (name, span.start)
+        })
+        .collect()
+}
+
+fn expected(names: &[(&str, usize)]) -> Vec<(String, usize)> {
+    names.iter().map(|&(name, start)| (name.to_string(), start)).collect()
+}
+
+#[test]
+fn nested_functions() {
+    let src = "local a = b
+function t.m:f(x)
+    local function g() return x + a.y[z] end
+    return g(c.d)
+end";
+    assert_eq!(
+        names(src),
+        expected(&[
+            ("b", 10),
+            ("t", 21),
+            ("x", 60),
+            ("a", 64),
+            ("z", 68),
+            ("g", 86),
+            ("c", 88),
+        ])
+    );
+}
+
+#[test]
+fn assignments_and_calls() {
+    let src = "x, y.z = f(...), w:m()";
+    assert_eq!(names(src), expected(&[("x", 0), ("y", 3), ("f", 9), ("w", 17)]));
+}
@@ -0,0 +1,28 @@
+//! Builds a chunk by hand without the standard library, to check that the syntax tree doesn't
+//! need it.
+
+#![no_std]
+
+extern crate alloc;
+extern crate buildlua;
+
+use core::fmt;
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+use buildlua::ast::*;
+use buildlua::span::Spanned;
+
+/// The chunk `return "hello"`.
+pub fn chunk() -> Chunk {
+    let hello = Expression::String(b"hello".to_vec(), StringForm::Short(Quote::Double));
+    let expressions = ExpressionList(Box::new(Spanned::dummy(hello)), None);
+    let block = Block(None, Some(Box::new(ReturnStatement(Some(Box::new(expressions))))));
+    Chunk(block, Vec::new())
+}
+
+/// Writes the chunk's debug form, which only needs `core::fmt`.
+pub fn debug(chunk: &Chunk, out: &mut impl fmt::Write) -> fmt::Result {
+    write!(out, "{:?}", chunk)
+}
@@ -0,0 +1,70 @@
+//! Checks that operators print as their sigils and read back from them.
+
+#![cfg(feature = "std")]
+
+extern crate buildlua;
+
+use buildlua::ast::{BinaryOperation, UnaryOperation};
+
+const BINARY: [BinaryOperation; 21] = [
+    BinaryOperation::Plus,
+    BinaryOperation::Minus,
+    BinaryOperation::Times,
+    BinaryOperation::Devide,
+    BinaryOperation::FloorDivide,
+    BinaryOperation::Exponent,
+    BinaryOperation::Modulo,
+    BinaryOperation::Concatanate,
+    BinaryOperation::LessThan,
+    BinaryOperation::LessThanOrEqual,
+    BinaryOperation::GreaterThan,
+    BinaryOperation::GreaterThanOrEqual,
+    BinaryOperation::Equal,
+    BinaryOperation::NotEqual,
+    BinaryOperation::BitwiseAnd,
+    BinaryOperation::BitwiseOr,
+    BinaryOperation::BitwiseXor,
+    BinaryOperation::ShiftLeft,
+    BinaryOperation::ShiftRight,
+    BinaryOperation::And,
+    BinaryOperation::Or,
+];
+
+const UNARY: [UnaryOperation; 4] = [
+    UnaryOperation::Negate,
+    UnaryOperation::Not,
+    UnaryOperation::Length,
+    UnaryOperation::BitwiseNot,
+];
+
+#[test]
+fn sigils() {
+    assert_eq!(BinaryOperation::Concatanate.to_string(), "..");
+    assert_eq!(BinaryOperation::And.to_string(), "and");
+    assert_eq!(UnaryOperation::Length.to_string(), "#");
+    assert_eq!(UnaryOperation::Not.to_string(), "not");
+    assert_eq!("~=".parse::<BinaryOperation>(), Ok(BinaryOperation::NotEqual));
+    assert_eq!("~".parse::<BinaryOperation>(), Ok(BinaryOperation::BitwiseXor));
+    assert_eq!("~".parse::<UnaryOperation>(), Ok(UnaryOperation::BitwiseNot));
+    assert_eq!("-".parse::<UnaryOperation>(), Ok(UnaryOperation::Negate));
+}
+
+#[test]
+fn every_operator_round_trips() {
+    for &op in BINARY.iter() {
+        assert_eq!(op.to_string().parse(), Ok(op));
+    }
+    for &op in UNARY.iter() {
+        assert_eq!(op.to_string().parse(), Ok(op));
+    }
+}
+
+#[test]
+fn unknown_operators_are_errors() {
+    for s in &["", "+=", "!", "&&", "not x", " +", "x", "#", "--"] {
+        assert!(s.parse::<BinaryOperation>().is_err(), "{:?}", s);
+    }
+    for s in &["", "+", "!", "..", "- -", "and"] {
+        assert!(s.parse::<UnaryOperation>().is_err(), "{:?}", s);
+    }
+}
@@ -0,0 +1,52 @@
+//! Checks that parsing files in parallel gives what parsing them one at a time does.
+
+#![cfg(feature = "rayon")]
+
+extern crate buildlua;
+
+mod common;
+
+use buildlua::parser::{parse, parse_many};
+
+#[test]
+fn matches_sequential_parsing() {
+    let corpus = common::corpus();
+    let mut names = Vec::new();
+    let mut sources = Vec::new();
+    // The corpus is repeated, along with sources that fail to parse and parts of every file, to
+    // give the threads a batch large enough to share out.
+    for round in 0..20 {
+        for (path, src) in &corpus {
+            let name = path.file_name().unwrap().to_string_lossy();
+            let cut = src.len() * round / 20;
+            let cut = (cut..=src.len()).find(|&cut| src.is_char_boundary(cut)).unwrap();
+            names.push(format!("{}#{}", name, round));
+            sources.push(if round == 0 { src.to_string() } else { src[..cut].to_string() });
+        }
+        names.push(format!("broken#{}", round));
+        sources.push(format!("local x = {} +", round));
+    }
+    let batch: Vec<(&str, &str)> =
+        names.iter().map(|name| &**name).zip(sources.iter().map(|src| &**src)).collect();
+    let parsed = parse_many(&batch);
+    assert_eq!(parsed.len(), batch.len());
+    for (&(name, src), (parsed_name, result)) in batch.iter().zip(parsed) {
+        assert_eq!(parsed_name, name);
+        assert_eq!(result, parse(src), "{} parsed differently", name);
+    }
+    assert!(batch.iter().any(|&(_, src)| parse(src).is_err()));
+}
+
+#[test]
+fn parses_empty_batches() {
+    assert!(parse_many(&[]).is_empty());
+}
+
+#[test]
+fn deep_nesting_fits_on_the_threads() {
+    // Inside the recursion limit, but deeper than the stack of a test thread holds without
+    // optimizations.
+    let src = format!("{}{}", "do ".repeat(190), "end ".repeat(190));
+    let parsed = parse_many(&[("deep.lua", &src)]);
+    assert!(parsed[0].1.is_ok());
+}
@@ -0,0 +1,22 @@
+//! Checks the trees the parser builds against golden files of them.
+//!
+//! Every `.lua` file in `tests/fixtures/parser` is parsed and written with `to_sexpr_pretty`, then
+//! compared with the file of the same name ending in `.sexpr` instead. Running the tests with
+//! `BLESS=1` set writes the output to those files instead of comparing it, to update them after an
+//! intended change.
+
+#![cfg(feature = "std")]
+
+extern crate buildlua;
+
+mod common;
+
+use buildlua::export::to_sexpr_pretty;
+
+#[test]
+fn parser_golden_files() {
+    let fixtures = common::fixtures("parser", |path| {
+        path.extension().is_some_and(|extension| extension == "lua")
+    });
+    common::check_snapshots(fixtures, "sexpr", "golden file", "parsed", to_sexpr_pretty);
+}
@@ -0,0 +1,174 @@
+//! Checks the trees the parser builds for each kind of syntax, and the errors it gives.
+
+#![cfg(feature = "std")]
+
+extern crate buildlua;
+
+use buildlua::ast::*;
+use buildlua::lexer::Token;
+use buildlua::parser::{parse, ParseError};
+use buildlua::span::Span;
+
+/// The statements of a chunk parsed from `src`.
+fn statements(src: &str) -> Vec<Statement> {
+    parse(src).unwrap().0.statements().map(|statement| statement.node.clone()).collect()
+}
+
+/// The expression of `return <src>`.
+fn expression(src: &str) -> Expression {
+    let chunk = parse(&format!("return {}", src)).unwrap();
+    let values = chunk.0 .1.as_ref().unwrap().0.as_ref().unwrap();
+    values.0.node.clone()
+}
+
+fn is_name(expression: &Expression, expected: &str) -> bool {
+    match *expression {
+        Expression::PrefixExpression(ref prefix) => match **prefix {
+            PrefixExpression::Variable(ref variable) => {
+                matches!(**variable, Variable::Name(ref name) if name.node == expected)
+            }
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+#[test]
+fn assignments() {
+    match statements("a, b.c = 1, f()")[0] {
+        Statement::Assignment(ref variables, ref values) => {
+            assert!(matches!(*variables.first, Variable::Name(ref name) if name.node == "a"));
+            let rest = variables.rest.as_ref().unwrap();
+            assert_eq!(rest.len(), 1);
+            assert!(matches!(rest[0], Variable::DotAccess { ref key, .. } if key == "c"));
+            assert_eq!(values.0.node, Expression::from(1));
+            let rest = values.1.as_ref().unwrap();
+            assert!(matches!(rest[0].node, Expression::PrefixExpression(_)));
+        }
+        ref other => panic!("{:?}", other),
+    }
+}
+
+#[test]
+fn numeric_for_loops() {
+    match statements("for i = 10, 1, -1 do print(i) end")[0] {
+        Statement::ForStepping { ref name, ref from, ref to, ref step, ref block } => {
+            assert_eq!(name.node, "i");
+            assert_eq!(from.node, Expression::from(10));
+            assert_eq!(to.node, Expression::from(1));
+            let step = &step.as_ref().unwrap().node;
+            assert!(matches!(*step, Expression::UnaryOperation(UnaryOperation::Negate, _)));
+            assert_eq!(block.statements().count(), 1);
+        }
+        ref other => panic!("{:?}", other),
+    }
+    match statements("for i = 1, n do end")[0] {
+        Statement::ForStepping { ref to, ref step, ref block, .. } => {
+            assert!(is_name(&to.node, "n"));
+            assert!(step.is_none());
+            assert!(block.is_empty());
+        }
+        ref other => panic!("{:?}", other),
+    }
+}
+
+#[test]
+fn generic_for_loops() {
+    match statements("for k, v in pairs(t) do end")[0] {
+        Statement::ForIn { ref name_list, ref in_, .. } => {
+            assert_eq!(name_list.0.node, "k");
+            assert_eq!(name_list.1.as_ref().unwrap()[0].node, "v");
+            assert!(in_.1.is_none());
+        }
+        ref other => panic!("{:?}", other),
+    }
+}
+
+#[test]
+fn vararg_functions() {
+    match statements("function f(a, ...) return ... end")[0] {
+        Statement::Function(ref name, ref body) => {
+            assert_eq!(name.first_dot_access.node, "f");
+            match **body.0.as_ref().unwrap() {
+                ParameterList::ExtendedArguments(ref names) => {
+                    assert_eq!(names.0.node, "a");
+                    assert!(names.1.is_none());
+                }
+                ref other => panic!("{:?}", other),
+            }
+            let returned = body.1 .1.as_ref().unwrap().0.as_ref().unwrap();
+            assert_eq!(returned.0.node, Expression::ExtendedArgumentAccess);
+        }
+        ref other => panic!("{:?}", other),
+    }
+    match expression("function(...) end") {
+        Expression::FunctionDefine(ref define) => {
+            assert_eq!(define.0 .0.as_deref(), Some(&ParameterList::ExtendedArgumentsVoid));
+        }
+        ref other => panic!("{:?}", other),
+    }
+}
+
+#[test]
+fn nodes_have_the_spans_they_were_parsed_from() {
+    let chunk = parse("x = 1").unwrap();
+    let statement = chunk.0.statements().next().unwrap();
+    assert_eq!(statement.span, Span::new(0, 5));
+    match statement.node {
+        Statement::Assignment(ref variables, ref values) => {
+            let x = match *variables.first {
+                Variable::Name(ref name) => name,
+                ref other => panic!("{:?}", other),
+            };
+            assert_eq!(x.span, Span::new(0, 1));
+            assert_eq!(values.0.span, Span::new(4, 5));
+        }
+        ref other => panic!("{:?}", other),
+    }
+}
+
+#[test]
+fn errors_point_at_the_unexpected_token() {
+    match parse("local = 1").unwrap_err() {
+        ParseError::UnexpectedToken { found, expected, span } => {
+            assert_eq!(found, Token::Equal);
+            assert_eq!(span, Span::new(6, 7));
+            assert!(expected.contains(&"name"), "{:?}", expected);
+        }
+        other => panic!("{:?}", other),
+    }
+}
+
+/// The names and attributes declared by the `local` statement `src`.
+fn attributes(src: &str) -> Vec<(String, Option<Attribute>)> {
+    match statements(src)[0] {
+        Statement::LocalVariableBinding(ref names, _) => std::iter::once(&names.0)
+            .chain(names.1.iter().flatten())
+            .map(|name| (name.name.node.clone(), name.attribute))
+            .collect(),
+        ref other => panic!("{:?}", other),
+    }
+}
+
+#[test]
+fn local_attributes() {
+    assert_eq!(attributes("local x <const> = 1"), vec![("x".to_string(), Some(Attribute::Const))]);
+    assert_eq!(attributes("local f <close> = g"), vec![("f".to_string(), Some(Attribute::Close))]);
+    assert_eq!(
+        attributes("local a, b<const>, c <close> = 1"),
+        vec![
+            ("a".to_string(), None),
+            ("b".to_string(), Some(Attribute::Const)),
+            ("c".to_string(), Some(Attribute::Close)),
+        ]
+    );
+    match parse("local x <bad> = 1").unwrap_err() {
+        ParseError::UnexpectedToken { found, expected, span } => {
+            assert_eq!(found, Token::Name("bad".to_string()));
+            assert_eq!(expected, vec!["'const'", "'close'"]);
+            assert_eq!(span, Span::new(9, 12));
+        }
+        other => panic!("{:?}", other),
+    }
+    assert!(parse("local x <const = 1").is_err());
+}
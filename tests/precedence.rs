@@ -0,0 +1,143 @@
+//! Checks that operators group by their binding powers and associativity.
+
+#![cfg(feature = "std")]
+
+extern crate buildlua;
+
+use buildlua::ast::BinaryOperation::*;
+use buildlua::ast::UnaryOperation::*;
+use buildlua::ast::*;
+use buildlua::build::{expr, stmt, var, BlockBuilder};
+use buildlua::parser::parse;
+use buildlua::span::Spanned;
+
+/// Asserts that `x = src` assigns the expected expression.
+fn assert_parses_as(src: &str, expected: Spanned<Expression>) {
+    let chunk = parse(&format!("x = {}", src)).unwrap();
+    let assignment = stmt::assign(vec![var::name("x")], vec![expected]);
+    assert_eq!(chunk, BlockBuilder::new().push(assignment).chunk(), "{}", src);
+}
+
+fn n(value: i64) -> Spanned<Expression> {
+    expr::integer(value)
+}
+
+fn name(name: &str) -> Spanned<Expression> {
+    expr::name(name)
+}
+
+#[test]
+fn exponentiation_is_right_associative() {
+    assert_parses_as("2^2^3", expr::binop(Exponent, n(2), expr::binop(Exponent, n(2), n(3))));
+    assert_parses_as("2^3^2", expr::binop(Exponent, n(2), expr::binop(Exponent, n(3), n(2))));
+}
+
+#[test]
+fn concatenation_is_right_associative() {
+    let expected = expr::binop(
+        Concatanate,
+        name("a"),
+        expr::binop(Concatanate, name("b"), name("c")),
+    );
+    assert_parses_as("a..b..c", expected);
+    let strings = expr::binop(
+        Concatanate,
+        expr::string("a"),
+        expr::binop(Concatanate, expr::string("b"), expr::string("c")),
+    );
+    assert_parses_as("\"a\"..\"b\"..\"c\"", strings);
+}
+
+#[test]
+fn other_operators_are_left_associative() {
+    assert_parses_as("1 - 2 - 3", expr::binop(Minus, expr::binop(Minus, n(1), n(2)), n(3)));
+    assert_parses_as("1 // 2 % 3", expr::binop(Modulo, expr::binop(FloorDivide, n(1), n(2)), n(3)));
+    let expected = expr::binop(Or, expr::binop(Or, name("a"), name("b")), name("c"));
+    assert_parses_as("a or b or c", expected);
+}
+
+#[test]
+fn exponentiation_binds_tighter_than_unary_operators() {
+    assert_parses_as("-2^2", expr::unop(Negate, expr::binop(Exponent, n(2), n(2))));
+    let expected = expr::binop(Exponent, n(2), expr::unop(Negate, n(3)));
+    assert_parses_as("2^-3", expected);
+}
+
+#[test]
+fn unary_operators_bind_tighter_than_other_binary_operators() {
+    assert_parses_as("-1 + 2", expr::binop(Plus, expr::unop(Negate, n(1)), n(2)));
+    let expected = expr::binop(Equal, expr::unop(Not, name("a")), name("b"));
+    assert_parses_as("not a == b", expected);
+}
+
+#[test]
+fn precedence_levels() {
+    let expected = expr::binop(
+        Or,
+        expr::binop(
+            And,
+            expr::binop(LessThan, name("a"), expr::binop(Plus, name("b"), name("c"))),
+            name("d"),
+        ),
+        expr::binop(Equal, name("e"), expr::binop(BitwiseOr, name("f"), name("g"))),
+    );
+    assert_parses_as("a < b + c and d or e == f | g", expected);
+    let expected = expr::binop(
+        BitwiseOr,
+        name("a"),
+        expr::binop(
+            BitwiseXor,
+            name("b"),
+            expr::binop(
+                BitwiseAnd,
+                name("c"),
+                expr::binop(ShiftLeft, name("d"), expr::binop(Concatanate, name("e"), name("f"))),
+            ),
+        ),
+    );
+    assert_parses_as("a | b ~ c & d << e .. f", expected);
+    let expected = expr::binop(Plus, name("a"), expr::binop(Times, name("b"), name("c")));
+    assert_parses_as("a + b * c", expected);
+}
+
+#[test]
+fn precedence_table() {
+    use buildlua::ast::{is_right_associative, precedence, UNARY_PRECEDENCE};
+
+    assert!(precedence(Times) > precedence(Plus));
+    assert!(precedence(Plus) > precedence(Concatanate));
+    assert!(precedence(Exponent) > UNARY_PRECEDENCE);
+    assert!(UNARY_PRECEDENCE > precedence(Modulo));
+    assert!(precedence(And) > precedence(Or));
+    assert_eq!(precedence(LessThan), precedence(NotEqual));
+    assert!(is_right_associative(Exponent));
+    assert!(is_right_associative(Concatanate));
+    assert!(!is_right_associative(Plus));
+    assert!(!is_right_associative(Minus));
+}
+
+#[test]
+fn bitwise_operators() {
+    let expected = expr::binop(BitwiseOr, expr::binop(BitwiseAnd, name("a"), name("b")), name("c"));
+    assert_parses_as("a & b | c", expected);
+    let expected = expr::binop(BitwiseOr, name("a"), expr::binop(BitwiseAnd, name("b"), name("c")));
+    assert_parses_as("a | b & c", expected);
+    assert_parses_as("x << 2", expr::binop(ShiftLeft, name("x"), n(2)));
+    let expected = expr::binop(ShiftLeft, name("x"), expr::binop(Plus, n(2), n(1)));
+    assert_parses_as("x << 2 + 1", expected);
+    // `~` is exclusive or between operands and bitwise not before one.
+    assert_parses_as("a ~ b", expr::binop(BitwiseXor, name("a"), name("b")));
+    assert_parses_as("~a", expr::unop(BitwiseNot, name("a")));
+    let expected = expr::binop(BitwiseXor, name("a"), expr::unop(BitwiseNot, name("b")));
+    assert_parses_as("a ~ ~b", expected);
+    let expected = expr::binop(BitwiseXor, expr::unop(BitwiseNot, name("a")), name("b"));
+    assert_parses_as("~a ~ b", expected);
+}
+
+#[test]
+fn floor_division() {
+    assert_parses_as("7 // 2", expr::binop(FloorDivide, n(7), n(2)));
+    assert_parses_as("7 / 2", expr::binop(Devide, n(7), n(2)));
+    let expected = expr::binop(Plus, expr::binop(FloorDivide, n(7), n(2)), n(1));
+    assert_parses_as("7 // 2 + 1", expected);
+}
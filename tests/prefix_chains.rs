@@ -0,0 +1,87 @@
+//! Checks that chains of indexing and calls nest from the left, innermost first.
+
+#![cfg(feature = "std")]
+
+extern crate buildlua;
+
+use std::iter;
+
+use buildlua::ast::*;
+use buildlua::parser::parse;
+
+/// The arguments of a call as written between parentheses, or `{}` or `""` for the other forms.
+fn arguments(arguments: &FunctionArguments) -> String {
+    match *arguments {
+        FunctionArguments::Parenthesis(None) => "()".to_string(),
+        FunctionArguments::Parenthesis(Some(ref list)) => {
+            let values = iter::once(&*list.0).chain(list.1.iter().flatten());
+            let values: Vec<_> = values.map(|value| value.node.to_string()).collect();
+            format!("({})", values.join(", "))
+        }
+        FunctionArguments::TableConstructor(_) => "{}".to_string(),
+        FunctionArguments::String(..) => "\"\"".to_string(),
+    }
+}
+
+/// The steps of a prefix expression's chain, from the innermost name outward, such as `a`, `.b`,
+/// `[d]`, `:e(f)` and `()`.
+fn chain(prefix: &PrefixExpression) -> Vec<String> {
+    let mut steps = Vec::new();
+    let mut prefix = prefix;
+    loop {
+        let from = match *prefix {
+            PrefixExpression::Variable(ref variable) => match **variable {
+                Variable::Name(ref name) => {
+                    steps.push(name.node.clone());
+                    break;
+                }
+                Variable::DotAccess { ref from, ref key } => {
+                    steps.push(format!(".{}", key));
+                    from
+                }
+                Variable::ArrayAccess { ref from, ref key } => {
+                    steps.push(format!("[{}]", key.node));
+                    from
+                }
+            },
+            PrefixExpression::FunctionCall(ref call) => match **call {
+                FunctionCall::Static(ref from, ref args) => {
+                    steps.push(arguments(args));
+                    from
+                }
+                FunctionCall::SelfTaking(ref from, ref method, ref args) => {
+                    steps.push(format!(":{}{}", method, arguments(args)));
+                    from
+                }
+            },
+            PrefixExpression::Parenthesis(ref inner) => {
+                panic!("unexpected parenthesised expression {}", inner.node)
+            }
+        };
+        prefix = from;
+    }
+    steps.reverse();
+    steps
+}
+
+/// The chain of the expression returned by `src`.
+fn returned_chain(src: &str) -> Vec<String> {
+    let chunk = parse(src).unwrap_or_else(|error| panic!("failed to parse the source: {}", error));
+    let values = chunk.0.1.as_ref().and_then(|ret| ret.0.as_ref()).expect("nothing is returned");
+    match values.0.node {
+        Expression::PrefixExpression(ref prefix) => chain(prefix),
+        ref expression => panic!("expected a prefix expression, found {:?}", expression),
+    }
+}
+
+#[test]
+fn indexing_and_calls_nest_from_the_left() {
+    let steps = returned_chain("return a.b.c[d]:e(f).g");
+    assert_eq!(steps, ["a", ".b", ".c", "[d]", ":e(f)", ".g"]);
+}
+
+#[test]
+fn calls_of_calls() {
+    assert_eq!(returned_chain("return f()(1, 2)[3]"), ["f", "()", "(1, 2)", "[3]"]);
+    assert_eq!(returned_chain("return a:b 's' {}.c"), ["a", ":b\"\"", "{}", ".c"]);
+}
@@ -0,0 +1,56 @@
+//! Checks that printing a chunk writes out its keywords and operators as lua source.
+
+#![cfg(feature = "std")]
+
+extern crate buildlua;
+
+use buildlua::parser::parse;
+
+#[test]
+fn statements_print_their_keywords() {
+    let src = "local function f(a, ...)\n\
+               while a do break end\n\
+               repeat a = a - 1 until a < 0\n\
+               if a then return ... elseif not a then goto done else do end end\n\
+               for i = 1, 2 do end\n\
+               for k in next, {} do end\n\
+               ::done::\n\
+               end\n";
+    let printed = parse(src).unwrap().to_string();
+    for keyword in [
+        "local function f(a, ...)",
+        "while a do",
+        "break",
+        "repeat",
+        "until a < 0",
+        "if a then",
+        "return ...",
+        "elseif not a then",
+        "goto done",
+        "else",
+        "do",
+        "for i = 1, 2 do",
+        "for k in next, {} do",
+        "::done::",
+        "end",
+    ] {
+        assert!(printed.contains(keyword), "{:?} isn't in\n{}", keyword, printed);
+    }
+    assert_eq!(parse(&printed).unwrap(), parse(src).unwrap());
+}
+
+#[test]
+fn expressions_print_their_operators() {
+    let src = "x = a + b - c * d / e // f % g ^ h .. i\n\
+               y = a == b or a ~= b and a < b or a <= b or a > b or a >= b\n\
+               z = a & b | c ~ d << e >> f\n\
+               w = -a + #b + ~c\n";
+    let printed = parse(src).unwrap().to_string();
+    for operator in [
+        " + ", " - ", " * ", " / ", " // ", " % ", " ^ ", " .. ", " == ", " or ", " ~= ", " and ",
+        " < ", " <= ", " > ", " >= ", " & ", " | ", " ~ ", " << ", " >> ", "-a", "#b", "~c",
+    ] {
+        assert!(printed.contains(operator), "{:?} isn't in\n{}", operator, printed);
+    }
+    assert_eq!(printed, src);
+}
@@ -0,0 +1,44 @@
+//! Checks where `propagate_constants` replaces locals with their literals.
+
+#![cfg(feature = "std")]
+
+extern crate buildlua;
+
+use buildlua::opt::propagate_constants;
+use buildlua::parser::parse;
+
+fn propagated(src: &str) -> String {
+    let mut chunk = parse(src).unwrap_or_else(|error| panic!("failed to parse: {}", error));
+    propagate_constants(&mut chunk);
+    chunk.to_string()
+}
+
+#[test]
+fn literals_replace_reads() {
+    let src = "local x = 5 y = x + 1";
+    assert_eq!(propagated(src), "local x = 5\ny = 5 + 1\n");
+    let src = "local a, b, c = 'a', true, f() return a, b, c";
+    assert_eq!(propagated(src), "local a, b, c = 'a', true, f()\nreturn 'a', true, c\n");
+    // A closure that only reads the local can have the literal too.
+    let src = "local n = 2 function g() return n * n end";
+    assert_eq!(propagated(src), "local n = 2\nfunction g()\n    return 2 * 2\nend\n");
+}
+
+#[test]
+fn reassigned_locals_are_left_alone() {
+    let src = "local x = 5 x = x + 1 return x";
+    assert_eq!(propagated(src), "local x = 5\nx = x + 1\nreturn x\n");
+    let src = "local x = 5 function g() x = 6 end return x";
+    assert_eq!(propagated(src), "local x = 5\nfunction g()\n    x = 6\nend\nreturn x\n");
+    let src = "local f = nil function f() end return f";
+    assert_eq!(propagated(src), "local f = nil\nfunction f()\nend\nreturn f\n");
+}
+
+#[test]
+fn shadowing_locals_are_told_apart() {
+    let src = "local x = 1 do local x = g() y = x end z = x";
+    assert_eq!(propagated(src), "local x = 1\ndo\n    local x = g()\n    y = x\nend\nz = 1\n");
+    // The initializer of a local sees the one it shadows.
+    let src = "local x = 1 local x = x + 1 return x";
+    assert_eq!(propagated(src), "local x = 1\nlocal x = 1 + 1\nreturn x\n");
+}
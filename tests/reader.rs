@@ -0,0 +1,44 @@
+//! Checks parsing source code from a reader.
+
+#![cfg(feature = "std")]
+
+extern crate buildlua;
+
+use std::io::{self, Cursor, Read};
+
+use buildlua::parser::{parse, parse_reader, ParseError, ReadError};
+
+#[test]
+fn reads_a_cursor() {
+    let source = "local x = 1\nprint(x)\n";
+    let chunk = parse_reader(Cursor::new(source.as_bytes().to_vec())).unwrap();
+    assert_eq!(chunk, parse(source).unwrap());
+}
+
+#[test]
+fn parse_errors() {
+    let err = parse_reader(Cursor::new(b"local = 1".to_vec())).unwrap_err();
+    assert!(matches!(err, ReadError::Parse(ParseError::UnexpectedToken { .. })));
+}
+
+/// A reader that fails as soon as it's read.
+struct Broken;
+
+impl Read for Broken {
+    fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+        Err(io::Error::other("the disk fell out"))
+    }
+}
+
+#[test]
+fn io_errors() {
+    let err = parse_reader(Broken).unwrap_err();
+    assert!(matches!(err, ReadError::Io(ref err) if err.to_string() == "the disk fell out"));
+    assert_eq!(err.to_string(), "couldn't read the source: the disk fell out");
+}
+
+#[test]
+fn invalid_utf8_is_an_io_error() {
+    let err = parse_reader(Cursor::new(vec![b'x', b'=', 0xff])).unwrap_err();
+    assert!(matches!(err, ReadError::Io(ref err) if err.kind() == io::ErrorKind::InvalidData));
+}
@@ -0,0 +1,129 @@
+//! Checks that `parse_recovering` goes on past errors, keeping the rest of the tree.
+
+#![cfg(feature = "std")]
+
+extern crate buildlua;
+
+mod common;
+
+use buildlua::ast::*;
+use buildlua::interp::eval;
+use buildlua::parser::{parse, parse_recovering, ParseError};
+use buildlua::print::{format, FormatOptions};
+use buildlua::validate::{validate, ValidationError};
+
+/// The chunk and errors of `src`, with the statements that aren't semicolons printed.
+fn recover(src: &str) -> (Vec<String>, Vec<ParseError>) {
+    let (chunk, errors) = parse_recovering(src);
+    let statements = chunk
+        .0
+        .statements()
+        .filter(|statement| statement.node != Statement::Semicolon)
+        .map(|statement| {
+            let block = Block(Some(vec![statement.clone()]), None);
+            format(&Chunk::new(block), &FormatOptions::default()).trim_end().to_string()
+        })
+        .collect();
+    (statements, errors)
+}
+
+#[test]
+fn missing_expressions_are_left_as_errors() {
+    let (statements, errors) = recover("x = ; y = 1");
+    assert_eq!(statements, ["x = nil --[[error]]", "y = 1"]);
+    assert_eq!(errors.len(), 1);
+    assert!(matches!(errors[0], ParseError::UnexpectedToken { .. }), "{:?}", errors[0]);
+    let (chunk, _) = parse_recovering("x = ; y = 1");
+    let statement = chunk.0.statements().next().unwrap();
+    match statement.node {
+        Statement::Assignment(_, ref values) => assert_eq!(values.0.node, Expression::Error),
+        ref statement => panic!("expected an assignment, found {:?}", statement),
+    }
+}
+
+#[test]
+fn missing_operands_and_arguments() {
+    let (statements, errors) = recover("x = 1 + ; y = f(, 2)");
+    assert_eq!(statements, ["x = 1 + nil --[[error]]", "y = f(nil --[[error]], 2)"]);
+    assert_eq!(errors.len(), 2);
+}
+
+#[test]
+fn missing_words_are_treated_as_there() {
+    let (statements, errors) = recover("if x y = 1 end\nwhile z do f()");
+    assert_eq!(statements, ["if x then\n    y = 1\nend", "while z do\n    f()\nend"]);
+    assert_eq!(errors.len(), 2);
+    assert_eq!(errors[1], ParseError::UnexpectedEof { expected: vec!["'end'"] });
+}
+
+#[test]
+fn broken_statements_are_skipped() {
+    let (statements, errors) = recover("x = 1 ) ) y = 2 local = 3 z = 4");
+    assert_eq!(statements, ["x = 1", "y = 2", "z = 4"]);
+    assert_eq!(errors.len(), 2);
+}
+
+#[test]
+fn stray_block_ends_are_skipped() {
+    let (statements, errors) = recover("x = 1 end y = 2 until");
+    assert_eq!(statements, ["x = 1", "y = 2"]);
+    assert_eq!(errors.len(), 2);
+}
+
+#[test]
+fn errors_in_nested_blocks_keep_the_block() {
+    let (statements, errors) = recover("function f() local = 1 return 2 end x = 3");
+    assert_eq!(statements, ["function f()\n    return 2\nend", "x = 3"]);
+    assert_eq!(errors.len(), 1);
+}
+
+#[test]
+fn lexing_stops_at_a_malformed_token() {
+    let (statements, errors) = recover("x = 1 y = 'unfinished");
+    assert_eq!(statements, ["x = 1", "y = nil --[[error]]"]);
+    assert_eq!(errors.len(), 2);
+    assert!(matches!(errors[1], ParseError::Lex(_)), "{:?}", errors[1]);
+}
+
+#[test]
+fn missing_expressions_are_invalid() {
+    let (chunk, _) = parse_recovering("x = ");
+    let errors = validate(&chunk).unwrap_err();
+    assert!(matches!(errors[..], [ValidationError::MissingExpression { .. }]), "{:?}", errors);
+    // Running it is an error too, rather than giving a value.
+    let (chunk, _) = parse_recovering("return 1 +");
+    let error = eval(&chunk).unwrap_err();
+    assert_eq!(error.to_string(), "attempt to evaluate a missing expression at byte 10");
+}
+
+#[test]
+fn valid_source_parses_the_same() {
+    for (path, src) in common::corpus() {
+        let (chunk, errors) = parse_recovering(&src);
+        assert_eq!(errors, Vec::new(), "{} has errors", path.display());
+        assert_eq!(chunk, parse(&src).unwrap(), "{} parsed differently", path.display());
+    }
+}
+
+#[test]
+fn every_prefix_of_valid_source_recovers() {
+    for (path, src) in common::corpus() {
+        for end in (0..src.len()).filter(|&end| src.is_char_boundary(end)) {
+            let prefix = &src[..end];
+            let (chunk, errors) = parse_recovering(prefix);
+            match parse(prefix) {
+                Ok(parsed) => {
+                    assert_eq!(errors, Vec::new(), "{} up to {}", path.display(), end);
+                    assert_eq!(chunk, parsed, "{} up to {}", path.display(), end);
+                }
+                Err(error) => {
+                    assert!(!errors.is_empty(), "{} up to {} has no errors", path.display(), end);
+                    // Lexing errors are reported by both, wherever they are.
+                    if !matches!(error, ParseError::Lex(_)) {
+                        assert_eq!(errors[0], error, "{} up to {}", path.display(), end);
+                    }
+                }
+            }
+        }
+    }
+}
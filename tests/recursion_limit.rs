@@ -0,0 +1,71 @@
+//! Checks that deeply nested input is refused with an error rather than overflowing the stack.
+
+#![cfg(feature = "std")]
+
+extern crate buildlua;
+
+use std::thread;
+
+use buildlua::parser::{parse, parse_with, ParseError, ParserConfig};
+
+/// Runs `f` with the stack of a main thread rather than the smaller one of a test thread, which
+/// the default limit doesn't fit in without optimizations.
+fn with_main_stack(f: impl FnOnce() + Send + 'static) {
+    thread::Builder::new().stack_size(8 << 20).spawn(f).unwrap().join().unwrap();
+}
+
+fn parentheses(depth: usize) -> String {
+    format!("x = {}1{}", "(".repeat(depth), ")".repeat(depth))
+}
+
+fn is_recursion_limit(result: Result<buildlua::ast::Chunk, ParseError>) -> bool {
+    matches!(result, Err(ParseError::RecursionLimit { .. }))
+}
+
+#[test]
+fn deep_parentheses_hit_the_limit() {
+    with_main_stack(|| {
+        assert!(is_recursion_limit(parse(&parentheses(10_000))));
+    });
+}
+
+#[test]
+fn deep_blocks_hit_the_limit() {
+    with_main_stack(|| {
+        let src = format!("{}{}", "do ".repeat(10_000), "end ".repeat(10_000));
+        assert!(is_recursion_limit(parse(&src)));
+    });
+}
+
+#[test]
+fn deep_unary_operators_hit_the_limit() {
+    with_main_stack(|| {
+        assert!(is_recursion_limit(parse(&format!("x = {}1", "- ".repeat(10_000)))));
+    });
+}
+
+#[test]
+fn reasonable_nesting_parses() {
+    with_main_stack(|| {
+        parse(&parentheses(150)).unwrap();
+        let src = format!("{}{}", "do ".repeat(150), "end ".repeat(150));
+        parse(&src).unwrap();
+        parse(&format!("x = 1{}", " .. 1".repeat(150))).unwrap();
+    });
+}
+
+#[test]
+fn the_limit_can_be_changed() {
+    let config = ParserConfig { recursion_limit: 10 };
+    assert!(is_recursion_limit(parse_with(&parentheses(10), &config)));
+    parse_with(&parentheses(5), &config).unwrap();
+}
+
+#[test]
+fn the_error_points_at_the_nesting() {
+    let config = ParserConfig { recursion_limit: 3 };
+    match parse_with("x = ((((1))))", &config) {
+        Err(ParseError::RecursionLimit { limit: 3, span }) => assert_eq!(span.start, 6),
+        other => panic!("expected a recursion limit error, got {:?}", other),
+    }
+}
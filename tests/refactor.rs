@@ -0,0 +1,142 @@
+//! Checks that `extract_local` moves expressions into locals without changing what the chunk
+//! does.
+
+#![cfg(feature = "std")]
+
+extern crate buildlua;
+
+mod common;
+
+use buildlua::ast::Chunk;
+use buildlua::interp::eval;
+use buildlua::refactor::{extract_local, RefactorError};
+use buildlua::span::Span;
+use common::chunk;
+
+/// The span of the first appearance of `expression` in `src`.
+fn span_of(src: &str, expression: &str) -> Span {
+    let start = src.find(expression).expect("the expression isn't in the source");
+    Span::new(start, start + expression.len())
+}
+
+/// Extracts the first appearance of `expression` in `src` into a local called `name`, checking
+/// that the result returns the same values as the source.
+fn extract(src: &str, expression: &str, name: &str) -> Result<Chunk, RefactorError> {
+    let mut extracted = chunk(src);
+    let before = extract_local(&mut extracted, span_of(src, expression), name)?;
+    assert_eq!(before, chunk(src));
+    let expected = eval(&before).expect("failed to run the source");
+    let found = eval(&extracted).expect("failed to run the extracted chunk");
+    assert_eq!(format!("{:?}", found), format!("{:?}", expected));
+    Ok(extracted)
+}
+
+/// Counts the calls to `f`, which returns its argument.
+const COUNTED: &str = "local calls = 0 local function f(x) calls = calls + 1 return x end ";
+
+#[test]
+fn extracts_before_the_statement() {
+    let src = "local x = 3 local y = x * 2 + 1 return y";
+    let expected = chunk("local x = 3 local double = x * 2 local y = double + 1 return y");
+    assert_eq!(extract(src, "x * 2", "double"), Ok(expected));
+}
+
+#[test]
+fn extracts_from_nested_blocks_and_returns() {
+    let src = "local t = {} for i = 1, 3 do t[i] = i * i end return #t + 1";
+    let expected = chunk("local t = {} for i = 1, 3 do local s = i * i t[i] = s end return #t + 1");
+    assert_eq!(extract(src, "i * i", "s"), Ok(expected));
+    let src = "local t = {1, 2} return #t + 1, 0";
+    let expected = chunk("local t = {1, 2} local n = #t + 1 return n, 0");
+    assert_eq!(extract(src, "#t + 1", "n"), Ok(expected));
+}
+
+#[test]
+fn while_conditions_are_evaluated_every_time() {
+    let src = format!("{}local i = 0 while i < f(3) do i = i + 1 end return i, calls", COUNTED);
+    let expected = chunk(&format!(
+        "{}local i = 0
+        while true do
+            local limit = f(3)
+            if not (i < limit) then break end
+            i = i + 1
+        end
+        return i, calls",
+        COUNTED,
+    ));
+    assert_eq!(extract(&src, "f(3)", "limit"), Ok(expected));
+}
+
+#[test]
+fn repeat_conditions_are_evaluated_at_the_end_of_the_block() {
+    let src = format!("{}local i = 0 repeat i = i + 1 until i >= f(3) return i, calls", COUNTED);
+    let expected = chunk(&format!(
+        "{}local i = 0 repeat i = i + 1 local limit = f(3) until i >= limit return i, calls",
+        COUNTED,
+    ));
+    assert_eq!(extract(&src, "f(3)", "limit"), Ok(expected));
+}
+
+#[test]
+fn elseif_conditions_are_evaluated_after_the_ones_before() {
+    let src = format!(
+        "{}local x
+        if f(1) == 2 then x = 1 elseif f(2) == 2 then x = 2 else x = 3 end
+        return x, calls",
+        COUNTED,
+    );
+    let expected = chunk(&format!(
+        "{}local x
+        if f(1) == 2 then x = 1 else
+            local y = f(2)
+            if y == 2 then x = 2 else x = 3 end
+        end
+        return x, calls",
+        COUNTED,
+    ));
+    assert_eq!(extract(&src, "f(2)", "y"), Ok(expected));
+}
+
+#[test]
+fn conditional_expressions_are_refused() {
+    let src = format!("{}local x = false and f(1) return x, calls", COUNTED);
+    let span = span_of(&src, "f(1)");
+    assert_eq!(extract(&src, "f(1)", "y"), Err(RefactorError::Conditional { span }));
+}
+
+#[test]
+fn calls_giving_every_value_are_refused() {
+    let src = "local f = function() return 1, 2 end return f()";
+    let span = span_of(src, "f()");
+    assert_eq!(extract(src, "f()", "v"), Err(RefactorError::MultipleValues { span }));
+    // Elsewhere in a list, the call gives one value anyway.
+    let src = "local f = function() return 1, 2 end return f(), 3";
+    let expected = chunk("local f = function() return 1, 2 end local v = f() return v, 3");
+    assert_eq!(extract(src, "f()", "v"), Ok(expected));
+}
+
+#[test]
+fn shadowing_names_is_refused() {
+    // The new local would hide the `x` read after it.
+    let src = "local x = 1 local y = x + 2 return x";
+    let span = Span::new(src.len() - 1, src.len());
+    assert_eq!(extract(src, "x + 2", "x"), Err(RefactorError::Collision { span }));
+}
+
+#[test]
+fn jumping_into_the_new_scope_is_refused() {
+    let src = "local x = 1 goto skip x = x + 2 ::skip:: x = 3 return x";
+    let span = span_of(src, "goto skip");
+    assert_eq!(extract(src, "x + 2", "y"), Err(RefactorError::JumpIntoScope { span }));
+}
+
+#[test]
+fn refuses_what_isnt_an_expression() {
+    let src = "local x = 1 + 2";
+    let span = Span::new(6, 11);
+    let mut extracted = chunk(src);
+    assert_eq!(extract_local(&mut extracted, span, "y"), Err(RefactorError::NoExpression { span }));
+    let error = RefactorError::InvalidName { name: "end".to_string() };
+    assert_eq!(extract_local(&mut extracted, span_of(src, "1 + 2"), "end"), Err(error));
+    assert_eq!(extracted, chunk(src));
+}
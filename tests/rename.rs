@@ -0,0 +1,106 @@
+//! Checks that `rename_local` renames exactly one local, and refuses renames that would change
+//! what a name refers to.
+
+#![cfg(feature = "std")]
+
+extern crate buildlua;
+
+mod common;
+
+use buildlua::resolve::{rename_local, RenameError};
+use buildlua::span::Span;
+use common::chunk;
+
+/// The span of the `n`th appearance of `name` as a whole word in `src`, counting from 0.
+fn nth(src: &str, name: &str, n: usize) -> Span {
+    let is_word = |byte: u8| byte.is_ascii_alphanumeric() || byte == b'_';
+    let (start, _) = src
+        .match_indices(name)
+        .filter(|&(start, _)| {
+            let before = start.checked_sub(1).map(|before| src.as_bytes()[before]);
+            let after = src.as_bytes().get(start + name.len()).cloned();
+            !before.is_some_and(is_word) && !after.is_some_and(is_word)
+        })
+        .nth(n)
+        .expect("the name doesn't appear that often");
+    Span::new(start, start + name.len())
+}
+
+/// Renames the local declared by the `n`th appearance of `name`, returning the renamed source.
+fn rename(src: &str, name: &str, n: usize, new_name: &str) -> Result<String, RenameError> {
+    let mut chunk = chunk(src);
+    rename_local(&mut chunk, nth(src, name, n), new_name)?;
+    Ok(chunk.to_string())
+}
+
+#[test]
+fn renames_the_local_and_its_uses() {
+    let src = "local x = g(x) do local x = 2 end function x.f() return x end return x + y";
+    let expected = chunk(
+        "local z = g(x) do local x = 2 end function z.f() return z end return z + y",
+    );
+    assert_eq!(rename(src, "x", 0, "z"), Ok(expected.to_string()));
+}
+
+#[test]
+fn renames_parameters_and_loop_variables() {
+    let src = "local function f(a, b) for i = a, b do print(i) end return a end";
+    let expected =
+        chunk("local function f(first, b) for i = first, b do print(i) end return first end");
+    assert_eq!(rename(src, "a", 0, "first"), Ok(expected.to_string()));
+    let expected = chunk("local function f(a, b) for n = a, b do print(n) end return a end");
+    assert_eq!(rename(src, "i", 0, "n"), Ok(expected.to_string()));
+}
+
+#[test]
+fn renaming_to_an_unused_global_is_fine() {
+    let src = "local x = 1 return x";
+    let expected = chunk("local print = 1 return print");
+    assert_eq!(rename(src, "x", 0, "print"), Ok(expected.to_string()));
+}
+
+#[test]
+fn rejects_capturing_another_binding() {
+    // Renamed to `y`, the local would shadow the global read in its scope.
+    let src = "local x = 1 return x + y";
+    assert_eq!(rename(src, "x", 0, "y"), Err(RenameError::Collision { span: nth(src, "y", 0) }));
+    // And here the outer local.
+    let src = "local y = 1 do local x = 2 return x, y end";
+    assert_eq!(rename(src, "x", 0, "y"), Err(RenameError::Collision { span: nth(src, "y", 1) }));
+}
+
+#[test]
+fn rejects_being_shadowed() {
+    // Renamed to `y`, the outer local would be shadowed by the inner one where it's used.
+    let src = "local x = 1 do local y = 2 return x + y end";
+    assert_eq!(rename(src, "x", 0, "y"), Err(RenameError::Collision { span: nth(src, "x", 1) }));
+}
+
+#[test]
+fn rejected_renames_leave_the_chunk_alone() {
+    let src = "local x = 1 return x + y";
+    let mut renamed = chunk(src);
+    assert!(rename_local(&mut renamed, nth(src, "x", 0), "y").is_err());
+    assert_eq!(renamed, chunk(src));
+}
+
+#[test]
+fn rejects_what_isnt_a_local() {
+    let src = "local x = 1 function t:m() return self end return x";
+    // A use rather than the declaration.
+    let span = nth(src, "x", 1);
+    assert_eq!(rename(src, "x", 1, "y"), Err(RenameError::NotALocal { span }));
+    // The implicit `self` of a method.
+    let span = Span::new(src.find("function").unwrap(), src.find(" return x").unwrap());
+    let mut renamed = chunk(src);
+    assert_eq!(rename_local(&mut renamed, span, "this"), Err(RenameError::NotALocal { span }));
+}
+
+#[test]
+fn rejects_invalid_names() {
+    let src = "local x = 1";
+    for &name in &["", "end", "1x", "a b"] {
+        let error = RenameError::InvalidName { name: name.to_string() };
+        assert_eq!(rename(src, "x", 0, name), Err(error));
+    }
+}
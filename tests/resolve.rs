@@ -0,0 +1,92 @@
+//! Checks what `resolve_names` binds each use of a name to.
+
+#![cfg(feature = "std")]
+
+extern crate buildlua;
+
+use buildlua::parser::parse;
+use buildlua::resolve::{resolve_names, Binding};
+use buildlua::span::Span;
+
+/// The binding of the use of a name starting at byte `start` of `src`.
+fn binding_at(src: &str, start: usize) -> Binding {
+    let chunk = parse(src).unwrap_or_else(|error| panic!("failed to parse the source: {}", error));
+    let resolution = resolve_names(&chunk);
+    let found = resolution.bindings.iter().find(|&(span, _)| span.start == start);
+    match found {
+        Some((_, &binding)) => binding,
+        None => panic!("no name is used at byte {} of {:?}", start, src),
+    }
+}
+
+/// The span of the `n`th appearance of `name` in `src`, counting from 0.
+fn nth(src: &str, name: &str, n: usize) -> Span {
+    let (start, _) = src.match_indices(name).nth(n).expect("the name doesn't appear that often");
+    Span::new(start, start + name.len())
+}
+
+#[test]
+fn repeat_conditions_see_the_block_locals() {
+    let src = "repeat local done = f() until done";
+    let declared = nth(src, "done", 0);
+    assert_eq!(binding_at(src, nth(src, "done", 1).start), Binding::Local(declared));
+    // Without the local, the condition reads the global.
+    let src = "repeat done = f() until done";
+    assert_eq!(binding_at(src, nth(src, "done", 1).start), Binding::Global);
+}
+
+#[test]
+fn repeat_conditions_dont_see_nested_block_locals() {
+    let src = "repeat do local done = f() end until done";
+    assert_eq!(binding_at(src, nth(src, "done", 1).start), Binding::Global);
+}
+
+#[test]
+fn repeat_block_locals_end_with_the_loop() {
+    let src = "local done repeat local done = f() until done return done";
+    let (outer, inner) = (nth(src, "done", 0), nth(src, "done", 1));
+    assert_eq!(binding_at(src, nth(src, "done", 2).start), Binding::Local(inner));
+    assert_eq!(binding_at(src, nth(src, "done", 3).start), Binding::Local(outer));
+}
+
+#[test]
+fn functions_in_repeat_conditions_see_block_locals_as_upvalues() {
+    let src = "repeat local done = f() until (function() return done end)()";
+    let declared = nth(src, "done", 0);
+    assert_eq!(binding_at(src, nth(src, "done", 1).start), Binding::Upvalue(declared));
+}
+
+#[test]
+fn inner_locals_shadow_outer_ones() {
+    let src = "local v = 1 do local v = 2 print(v) end print(v)";
+    let (outer, inner) = (nth(src, "v", 0), nth(src, "v", 1));
+    assert_eq!(binding_at(src, nth(src, "v", 2).start), Binding::Local(inner));
+    assert_eq!(binding_at(src, nth(src, "v", 3).start), Binding::Local(outer));
+    // A local isn't visible in its own initializer, which reads the one it shadows.
+    let src = "local v = 1 local v = v + 1";
+    assert_eq!(binding_at(src, nth(src, "v", 2).start), Binding::Local(nth(src, "v", 0)));
+}
+
+#[test]
+fn names_without_locals_read_globals() {
+    let src = "print(undeclared)";
+    assert_eq!(binding_at(src, 0), Binding::Global);
+    assert_eq!(binding_at(src, nth(src, "undeclared", 0).start), Binding::Global);
+    // A local declared later doesn't reach back.
+    let src = "print(later) local later = 1";
+    assert_eq!(binding_at(src, nth(src, "later", 0).start), Binding::Global);
+    // Nor does one from a block that has ended.
+    let src = "do local gone = 1 end print(gone)";
+    assert_eq!(binding_at(src, nth(src, "gone", 1).start), Binding::Global);
+}
+
+#[test]
+fn parameters_are_locals_of_their_function() {
+    let src = "function f(p, q) return p + q end return p";
+    assert_eq!(binding_at(src, nth(src, "p", 1).start), Binding::Local(nth(src, "p", 0)));
+    assert_eq!(binding_at(src, nth(src, "q", 1).start), Binding::Local(nth(src, "q", 0)));
+    assert_eq!(binding_at(src, nth(src, "p", 2).start), Binding::Global);
+    // Functions inside see them as upvalues.
+    let src = "local function f(p) return function() return p end end";
+    assert_eq!(binding_at(src, nth(src, "p", 1).start), Binding::Upvalue(nth(src, "p", 0)));
+}
@@ -0,0 +1,66 @@
+//! Checks that the parser and the printer agree, by reading back what the printer writes.
+//!
+//! Every `.lua` file in `tests/corpus` is parsed, printed and parsed again, and the two trees have
+//! to be equal. Spans are ignored by the comparison, so the printed layout doesn't matter.
+
+#![cfg(feature = "std")]
+
+extern crate buildlua;
+
+mod common;
+
+use buildlua::parser::parse;
+
+/// Asserts that `src` parses to the same tree as its own printed form.
+fn assert_roundtrip(src: &str) {
+    let chunk = parse(src).unwrap_or_else(|error| panic!("failed to parse the source: {}", error));
+    let printed = chunk.to_string();
+    let reparsed = parse(&printed).unwrap_or_else(|error| {
+        panic!("failed to parse the printed source: {}\n{}", error, printed)
+    });
+    assert_eq!(chunk, reparsed, "the printed source parsed differently:\n{}", printed);
+}
+
+#[test]
+fn corpus() {
+    for (path, src) in common::corpus() {
+        println!("{}", path.display());
+        assert_roundtrip(&src);
+    }
+}
+
+#[test]
+fn minimal_parentheses() {
+    assert_roundtrip("x = a - (b - c) .. (d .. e) .. f");
+    assert_roundtrip("x = (-a) ^ b ^ (c ^ d) + -(e + f)");
+    assert_roundtrip("x = not (a and b) or c and (d or e)");
+}
+
+#[test]
+fn numbers_keep_their_form() {
+    let src = "x = {0x1F, 1E3, .5, 100., 3.0, 255, 0XaBp-2}\n";
+    assert_eq!(parse(src).unwrap().to_string(), src);
+    assert_roundtrip(src);
+}
+
+#[test]
+fn numbers_keep_their_value() {
+    use buildlua::ast::{Expression, NumberForm};
+    let chunk = parse("return 0x1F, 1E3").unwrap();
+    let values = chunk.0.1.as_ref().unwrap().0.as_ref().unwrap();
+    assert_eq!(values.0.node, Expression::Integer(31, NumberForm::Written("0x1F".to_string())));
+    let expected = Expression::Float(1000.0, NumberForm::Written("1E3".to_string()));
+    assert_eq!(values.1.as_ref().unwrap()[0].node, expected);
+}
+
+#[test]
+fn strings_that_are_not_utf8() {
+    let src = "x = \"\\255\", 'caf\\xc3\\xa9', \"a\\xff\\u{e9}\"\n";
+    assert_eq!(parse(src).unwrap().to_string(), "x = \"\\255\", 'café', \"a\\255é\"\n");
+    assert_roundtrip(src);
+
+    // A long string built by hand can't hold the bytes, so it's printed as a short one.
+    use buildlua::ast::{Expression, StringForm};
+    let long = Expression::String(vec![b'[', 0xff], StringForm::Long(0));
+    assert_eq!(long.to_string(), "\"[\\255\"");
+}
@@ -0,0 +1,31 @@
+//! Checks that syntax trees round trip through JSON.
+
+#![cfg(feature = "serde")]
+
+extern crate buildlua;
+extern crate serde_json;
+
+use buildlua::ast::*;
+use buildlua::parser::parse;
+use serde_json::Value;
+
+#[test]
+fn chunks_round_trip_through_json() {
+    let src = "-- counts up\nlocal n <const> = 1 + 2.5\nfor i = 1, n do print(i, 'x') end\n";
+    let chunk = parse(src).unwrap();
+    let json = serde_json::to_string(&chunk).unwrap();
+    let read: Chunk = serde_json::from_str(&json).unwrap();
+    assert_eq!(read, chunk);
+    assert_eq!(read.1.len(), 1);
+    assert_eq!(read.1[0].span, chunk.1[0].span);
+}
+
+#[test]
+fn numbers_are_json_numbers() {
+    let integer = serde_json::to_value(Expression::from(3)).unwrap();
+    assert_eq!(integer["Integer"][0], Value::from(3));
+    assert!(integer["Integer"][0].is_i64());
+    let float = serde_json::to_value(Expression::from(2.5)).unwrap();
+    assert_eq!(float["Float"][0], Value::from(2.5));
+    assert!(float["Float"][0].is_f64());
+}
@@ -0,0 +1,58 @@
+//! Checks which identities `simplify` drops, and that it leaves alone the ones lua's semantics
+//! don't allow.
+
+#![cfg(feature = "std")]
+
+extern crate buildlua;
+
+use buildlua::opt::simplify;
+use buildlua::parser::parse;
+
+/// The expression `src` returns, simplified and printed.
+fn simplified(src: &str) -> String {
+    let mut chunk = parse(&format!("return {}", src)).unwrap();
+    simplify(&mut chunk);
+    let printed = chunk.to_string();
+    printed["return ".len()..].trim_end().to_string()
+}
+
+#[test]
+fn adding_zero_to_integers() {
+    assert_eq!(simplified("(7 // 2 + 1) + 0"), "(7 // 2 + 1)");
+    assert_eq!(simplified("0 + #'abc'"), "3");
+    assert_eq!(simplified("(1 - 2) - 0"), "(1 - 2)");
+    assert_eq!(simplified("1.5 - 0"), "1.5");
+}
+
+#[test]
+fn multiplying_numbers_by_one() {
+    assert_eq!(simplified("(2 ^ 3) * 1"), "(2 ^ 3)");
+    assert_eq!(simplified("1 * -2.5"), "-2.5");
+}
+
+#[test]
+fn logical_identities_on_booleans() {
+    assert_eq!(simplified("(a < b) and true"), "(a < b)");
+    assert_eq!(simplified("not x or false"), "not x");
+    assert_eq!(simplified("x == 1 and true or false"), "x == 1");
+}
+
+#[test]
+fn length_of_a_literal() {
+    assert_eq!(simplified("#'hello'"), "5");
+    assert_eq!(simplified("#[[\\n]]"), "2");
+}
+
+#[test]
+fn operands_that_could_be_anything_are_kept() {
+    // A string would be converted, a table could have metamethods, and infinity times zero is
+    // NaN, so none of these are identities.
+    assert_eq!(simplified("x + 0"), "x + 0");
+    assert_eq!(simplified("x * 1"), "x * 1");
+    assert_eq!(simplified("x * 0"), "x * 0");
+    assert_eq!(simplified("2.5 * 0"), "2.5 * 0");
+    assert_eq!(simplified("-0.0 + 0"), "-0.0 + 0");
+    assert_eq!(simplified("x and true"), "x and true");
+    assert_eq!(simplified("f() or false"), "f() or false");
+    assert_eq!(simplified("#t"), "#t");
+}
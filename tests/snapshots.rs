@@ -0,0 +1,25 @@
+//! Checks the formatter's output against snapshots of it.
+//!
+//! Every `.lua` file in `tests/fixtures/format` is formatted and compared with the file of the same
+//! name ending in `.expected.lua` instead. Running the tests with `BLESS=1` set writes the output
+//! to those files instead of comparing it, to update them after an intended change.
+
+#![cfg(feature = "std")]
+
+extern crate buildlua;
+
+mod common;
+
+use buildlua::print::{format, FormatOptions};
+
+#[test]
+fn formatter_snapshots() {
+    let fixtures = common::fixtures("format", |path| {
+        let name = path.file_name().unwrap().to_string_lossy();
+        name.ends_with(".lua") && !name.ends_with(".expected.lua")
+    });
+    let options = FormatOptions { max_width: Some(80), ..FormatOptions::default() };
+    common::check_snapshots(fixtures, "expected.lua", "snapshot", "formatted", |chunk| {
+        format(chunk, &options)
+    });
+}
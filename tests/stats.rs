@@ -0,0 +1,56 @@
+//! Checks the node counts of small chunks.
+
+#![cfg(feature = "std")]
+
+extern crate buildlua;
+
+use buildlua::parser::parse;
+use buildlua::stats::{stats, Stats};
+
+fn stats_of(src: &str) -> Stats {
+    stats(&parse(src).unwrap_or_else(|error| panic!("failed to parse the source: {}", error)))
+}
+
+#[test]
+fn counts_a_small_program() {
+    let src = "
+        local function f(a)
+            return a * (a + 1)
+        end
+        print(f(2))
+    ";
+    let expected = Stats {
+        // The local function, its return and the call.
+        statements: 3,
+        // `a * (a + 1)`, `a`, `(a + 1)`, `a + 1`, `a`, `1`, `f(2)` and `2`.
+        expressions: 8,
+        functions: 1,
+        // `a` in `a + 1`, in the parentheses, in the multiplication.
+        max_depth: 4,
+        // The above, the function's block, three expression lists, six prefix expressions, four
+        // variables, two calls and their arguments.
+        total_nodes: 30,
+    };
+    assert_eq!(stats_of(src), expected);
+}
+
+#[test]
+fn counts_every_kind_of_function() {
+    let src = "function a() end local function b() end local c = function() end";
+    assert_eq!(stats_of(src).functions, 3);
+}
+
+#[test]
+fn function_expressions_nest_their_bodies() {
+    assert_eq!(stats_of("local f = function() return 1 end").max_depth, 2);
+    assert_eq!(stats_of("local function f() return 1 end").max_depth, 1);
+}
+
+#[test]
+fn empty_chunks_have_no_nodes() {
+    assert_eq!(stats_of(""), Stats::default());
+    assert_eq!(
+        Stats::default(),
+        Stats { statements: 0, expressions: 0, functions: 0, max_depth: 0, total_nodes: 0 },
+    );
+}
@@ -0,0 +1,199 @@
+//! Runs programs using the standard library, capturing what they print.
+
+#![cfg(feature = "std")]
+
+extern crate buildlua;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use buildlua::interp::{Interpreter, RuntimeError, Value};
+use buildlua::parser::parse;
+use buildlua::stdlib::{open_with, Library, StdlibConfig};
+
+/// Runs `src` with the given libraries, giving what it returns and what it printed.
+fn run_with(src: &str, libraries: &[Library]) -> (Result<Vec<Value>, RuntimeError>, String) {
+    let chunk = parse(src).unwrap_or_else(|error| panic!("failed to parse the source: {}", error));
+    let output = Rc::new(RefCell::new(Vec::new()));
+    let mut interpreter = Interpreter::new();
+    let config = StdlibConfig { libraries: libraries.to_vec(), output: output.clone() };
+    open_with(&mut interpreter, &config);
+    let result = interpreter.eval(&chunk);
+    let printed = String::from_utf8(output.borrow().clone()).unwrap();
+    (result, printed)
+}
+
+/// What `src` prints with every library.
+fn printed(src: &str) -> String {
+    let (result, printed) = run_with(src, &Library::ALL);
+    result.unwrap_or_else(|error| panic!("failed to run the source: {}", error));
+    printed
+}
+
+fn assert_prints(src: &str, expected: &str) {
+    assert_eq!(printed(src), expected, "{}", src);
+}
+
+fn error(src: &str) -> String {
+    run_with(src, &Library::ALL).0.unwrap_err().message
+}
+
+#[test]
+fn print_and_type() {
+    assert_prints("print(type({}))", "table\n");
+    assert_prints("print(1, nil, true, 'x', 2.5)", "1\tnil\ttrue\tx\t2.5\n");
+    assert_prints(
+        "print(type(print), type(nil), type(2), type('')) print()",
+        "function\tnil\tnumber\tstring\n\n",
+    );
+}
+
+#[test]
+fn tostring_and_tonumber() {
+    assert_prints("print(tostring(1), tostring(1e100), tostring(-0.5))", "1\t1e+100\t-0.5\n");
+    assert_prints("print(tonumber('0x10'), tonumber(' 12 '), tonumber('z'))", "16\t12\tnil\n");
+    assert_prints(
+        "print(tonumber('ff', 16), tonumber('777', 8), tonumber('9', 8))",
+        "255\t511\tnil\n",
+    );
+    let src = "print(tostring(setmetatable({}, {__tostring = function() return 'obj' end})))";
+    assert_prints(src, "obj\n");
+}
+
+#[test]
+fn pairs_loop() {
+    let src = "
+        local t = {10, 20, 30, x = 'a', y = 'b'}
+        for k, v in pairs(t) do print(k, v) end
+        for i, v in ipairs({'p', 'q', nil, 's'}) do print(i, v) end
+    ";
+    assert_prints(src, "1\t10\n2\t20\n3\t30\nx\ta\ny\tb\n1\tp\n2\tq\n");
+}
+
+#[test]
+fn clearing_fields_while_iterating() {
+    let src = "
+        local t = {1, 2, 3, a = 1, b = 2, c = 3}
+        for k in pairs(t) do t[k] = nil end
+        print(next(t))
+    ";
+    assert_prints(src, "nil\n");
+}
+
+#[test]
+fn select_and_pcall() {
+    assert_prints("print(select('#', 1, nil, 3), select(2, 'a', 'b', 'c'))", "3\tb\tc\n");
+    assert_prints("print(select(-1, 'a', 'b'))", "b\n");
+    assert_prints("print(pcall(error, 'oops'))", "false\toops\n");
+    assert_prints("print(pcall(function(a) return a * 2 end, 4))", "true\t8\n");
+    assert_eq!(error("assert(false, 'no')"), "no");
+    assert_eq!(error("assert(nil)"), "assertion failed!");
+}
+
+#[test]
+fn strings() {
+    assert_prints(
+        "print(string.upper('abc'), ('Hi'):lower(), #string.rep('ab', 3, ','))",
+        "ABC\thi\t8\n",
+    );
+    assert_prints(
+        "print(('hello'):sub(2, -2), ('hello'):sub(-3), ('x'):byte())",
+        "ell\tllo\t120\n",
+    );
+    assert_prints(
+        "print(string.char(72, 105), string.reverse('abc'), string.len('abc'))",
+        "Hi\tcba\t3\n",
+    );
+    assert_eq!(error("string.rep('ab', math.maxinteger)"), "resulting string too large");
+    let src = "print(pcall(string.rep, 'x', math.maxinteger, ','))";
+    assert_prints(src, "false\tresulting string too large\n");
+}
+
+#[test]
+fn strings_hold_any_bytes() {
+    let values = |src: &str| run_with(src, &Library::ALL).0.unwrap();
+    let bytes = |bytes: &[u8]| Value::from(bytes);
+    let src = "return #'\\xff', #string.char(255, 0)";
+    assert_eq!(values(src), [Value::Integer(1), Value::Integer(2)]);
+    let src = "return string.byte(string.reverse('\\xfe\\xff'), 1, 2)";
+    assert_eq!(values(src), [Value::Integer(255), Value::Integer(254)]);
+    assert_eq!(values("return string.sub('\\xc3\\xa9x', 2)"), [bytes(b"\xa9x")]);
+    let src = "return ('\\xff'):upper() == '\\xff', string.format('%s%c', '\\xff', 254)";
+    assert_eq!(values(src), [Value::Bool(true), bytes(b"\xff\xfe")]);
+    assert_eq!(values("return '\\xff' .. 1"), [bytes(b"\xff1")]);
+}
+
+#[test]
+fn format() {
+    let src = "print(string.format('%d|%5d|%-5d|%05d|%x|%X|%o', 42, 42, 42, -42, 255, 255, 8))";
+    assert_prints(src, "42|   42|42   |-0042|ff|FF|10\n");
+    let src = "print(string.format('%.2f|%e|%g|%g|%g', 3.14159, 1234.5, 0.0001, 1e20, 100))";
+    assert_prints(src, "3.14|1.234500e+03|0.0001|1e+20|100\n");
+    let src = "print(string.format('%s=%q %.2s %%', 'k', 'a\"b\\n', 'xyz'))";
+    assert_prints(src, "k=\"a\\\"b\\n\" xy %\n");
+    assert_eq!(
+        error("string.format('%d', 1.5)"),
+        "bad argument #2 to 'format' (not a number in proper range)",
+    );
+}
+
+#[test]
+fn math() {
+    assert_prints(
+        "print(math.floor(3.7), math.ceil(3.2), math.abs(-2), math.max(1, 5, 3))",
+        "3\t4\t2\t5\n",
+    );
+    assert_prints(
+        "print(math.min(4, 2), math.fmod(-7, 3), math.sqrt(16), math.huge)",
+        "2\t-1\t4.0\tinf\n",
+    );
+    let expected = "bad argument #1 to 'floor' (number expected, got string)";
+    assert_eq!(error("math.floor('x')"), expected);
+}
+
+#[test]
+fn integers() {
+    assert_prints(
+        "print(math.maxinteger + 1 == math.mininteger, math.mininteger - 1 == math.maxinteger)",
+        "true\ttrue\n",
+    );
+    assert_prints(
+        "print(math.type(1), math.type(1.0), math.type('1'), 7 // 2, 3 / 2, math.floor(2.5))",
+        "integer\tfloat\tnil\t3\t1.5\t2\n",
+    );
+    assert_prints(
+        "print(string.format('%d %x', math.maxinteger, -1), tonumber('10'), tonumber('1e1'))",
+        "9223372036854775807 ffffffffffffffff\t10\t10.0\n",
+    );
+    // An integer loop stops at the largest integer rather than wrapping around to the smallest.
+    assert_prints(
+        "for i = math.maxinteger - 1, math.maxinteger do print(i) end",
+        "9223372036854775806\n9223372036854775807\n",
+    );
+}
+
+#[test]
+fn tables() {
+    let src = "
+        local t = {3, 1, 2}
+        table.insert(t, 4)
+        table.insert(t, 1, 0)
+        print(table.concat(t, ','))
+        print(table.remove(t), table.remove(t, 1), table.concat(t, ','))
+        table.sort(t)
+        print(table.concat(t, ' '))
+        table.sort(t, function(a, b) return a > b end)
+        print(table.unpack(t))
+    ";
+    assert_prints(src, "0,3,1,2,4\n4\t0\t3,1,2\n1 2 3\n3\t2\t1\n");
+}
+
+#[test]
+fn only_chosen_libraries_are_installed() {
+    let src = "print(type(string), type(math), type(table))";
+    let (result, printed) = run_with(src, &[Library::Base, Library::Math]);
+    result.unwrap();
+    assert_eq!(printed, "nil\ttable\tnil\n");
+    let (result, _) = run_with("print(1)", &[Library::Math]);
+    assert_eq!(result.unwrap_err().message, "attempt to call a nil value");
+}
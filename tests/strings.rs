@@ -0,0 +1,84 @@
+//! Checks how the lexer decodes the escape sequences of short strings.
+
+#![cfg(feature = "std")]
+
+extern crate buildlua;
+
+use buildlua::ast::StringForm;
+use buildlua::lexer::{LexErrorKind, Lexer, Token};
+
+/// Lexes `src`, which has to be a single string literal, into the bytes it stands for.
+fn decode(src: &str) -> Result<Vec<u8>, LexErrorKind> {
+    let token = Lexer::new(src).next_spanned().expect("no token in the source");
+    match token.map_err(|err| err.kind)?.node {
+        Token::String(s, StringForm::Short(_)) => Ok(s),
+        other => panic!("expected a short string, found {:?}", other),
+    }
+}
+
+#[test]
+fn character_escapes() {
+    assert_eq!(decode(r#""a\nb\tc""#).unwrap(), b"a\nb\tc");
+    assert_eq!(decode(r#""\\ \" \'""#).unwrap(), b"\\ \" '");
+    assert_eq!(decode(r#""\a\b\f\v\r""#).unwrap(), b"\x07\x08\x0c\x0b\r");
+    assert_eq!(decode("'line\\\nbreak'").unwrap(), b"line\nbreak");
+    assert_eq!(decode("'line\\\r\nbreak'").unwrap(), b"line\nbreak");
+}
+
+#[test]
+fn decimal_escapes() {
+    assert_eq!(decode(r#""\65\066\0671""#).unwrap(), b"ABC1");
+    assert_eq!(decode(r#""\0""#).unwrap(), b"\0");
+    assert_eq!(decode(r#""\255""#).unwrap(), [255]);
+    assert_eq!(decode(r#""\300""#), Err(LexErrorKind::InvalidEscape));
+    assert_eq!(decode(r#""\256""#), Err(LexErrorKind::InvalidEscape));
+}
+
+#[test]
+fn hex_escapes() {
+    assert_eq!(decode(r#""\x41\x7e\x7E""#).unwrap(), b"A~~");
+    assert_eq!(decode(r#""caf\xc3\xa9""#).unwrap(), "café".as_bytes());
+    assert_eq!(decode(r#""\xff\xFE""#).unwrap(), [0xff, 0xfe]);
+    assert_eq!(decode(r#""\x4""#), Err(LexErrorKind::InvalidEscape));
+    assert_eq!(decode(r#""\xg0""#), Err(LexErrorKind::InvalidEscape));
+}
+
+#[test]
+fn bytes_that_are_not_utf8() {
+    // Lua strings hold any bytes, so escapes needn't make valid UTF-8 between them.
+    assert_eq!(decode(r#""\xc3""#).unwrap(), [0xc3]);
+    assert_eq!(decode(r#""a\200\xe9b""#).unwrap(), b"a\xc8\xe9b");
+    assert_eq!(decode(r#""\u{e9}\xa9""#).unwrap(), [0xc3, 0xa9, 0xa9]);
+}
+
+#[test]
+fn skipping_whitespace() {
+    assert_eq!(decode("\"a\\z  \n\t  b\"").unwrap(), b"ab");
+    assert_eq!(decode(r#""a\zb""#).unwrap(), b"ab");
+}
+
+#[test]
+fn invalid_escapes() {
+    assert_eq!(decode(r#""\q""#), Err(LexErrorKind::InvalidEscape));
+    // The error points at the escape, and the lexer carries on after the string.
+    let mut lexer = Lexer::new(r#""ok \q" x"#);
+    let err = lexer.next().unwrap().unwrap_err();
+    assert_eq!((err.span.start, err.span.end), (4, 6));
+    assert_eq!(lexer.next().unwrap(), Ok(Token::Name("x".to_string())));
+}
+
+#[test]
+fn unicode_escapes() {
+    assert_eq!(decode(r#""\u{48}\u{49}""#).unwrap(), b"HI");
+    assert_eq!(decode(r#""\u{e9}\u{263A}\u{1F600}""#).unwrap(), "é☺😀".as_bytes());
+    assert_eq!(decode(r#""\u{0000000041}""#).unwrap(), b"A");
+    // Surrogates and code points past the last one of unicode are encoded all the same.
+    assert_eq!(decode(r#""\u{D800}""#).unwrap(), [0xed, 0xa0, 0x80]);
+    assert_eq!(decode(r#""\u{110000}""#).unwrap(), [0xf4, 0x90, 0x80, 0x80]);
+    assert_eq!(decode(r#""\u{7FFFFFFF}""#).unwrap(), [0xfd, 0xbf, 0xbf, 0xbf, 0xbf, 0xbf]);
+    assert_eq!(decode(r#""\u{80000000}""#), Err(LexErrorKind::InvalidEscape));
+    assert_eq!(decode(r#""\u{FFFFFFFFF}""#), Err(LexErrorKind::InvalidEscape));
+    assert_eq!(decode(r#""\u{}""#), Err(LexErrorKind::InvalidEscape));
+    assert_eq!(decode(r#""\u48""#), Err(LexErrorKind::InvalidEscape));
+    assert_eq!(decode(r#""\u{48""#), Err(LexErrorKind::InvalidEscape));
+}
@@ -0,0 +1,47 @@
+//! Checks looking ahead in a `TokenStream`.
+
+#![cfg(feature = "std")]
+
+extern crate buildlua;
+
+use buildlua::lexer::{LexError, LexErrorKind, Token, TokenStream};
+use buildlua::span::{Span, Spanned};
+
+/// The token of a result that should have lexed.
+fn token(result: Option<&Result<Spanned<Token>, LexError>>) -> Token {
+    result.unwrap().clone().unwrap().node
+}
+
+#[test]
+fn peek_does_not_take() {
+    let mut tokens = TokenStream::new("local x = 1");
+    assert_eq!(token(tokens.peek()), Token::Local);
+    assert_eq!(token(tokens.peek()), Token::Local);
+    let local = tokens.next().unwrap().unwrap();
+    assert_eq!((local.node, local.span), (Token::Local, Span::new(0, 5)));
+    assert_eq!(token(tokens.peek()), Token::Name("x".to_string()));
+}
+
+#[test]
+fn peek_nth_looks_ahead() {
+    let mut tokens = TokenStream::new("local x = 1");
+    assert_eq!(token(tokens.peek_nth(1)), Token::Name("x".to_string()));
+    assert_eq!(token(tokens.peek_nth(3)), Token::Integer(1));
+    assert!(tokens.peek_nth(4).is_none());
+    assert_eq!(token(tokens.peek()), Token::Local);
+    assert_eq!(tokens.count(), 4);
+}
+
+#[test]
+fn next_if_takes_matching_tokens() {
+    let mut tokens = TokenStream::new("a, b $");
+    assert!(tokens.next_if(|token| *token == Token::Comma).is_none());
+    assert!(tokens.next_if(|token| matches!(*token, Token::Name(_))).is_some());
+    let comma = tokens.next_if(|token| *token == Token::Comma).unwrap();
+    assert_eq!(comma.span, Span::new(1, 2));
+    tokens.next();
+    // An error isn't taken, whatever the predicate says.
+    assert!(tokens.next_if(|_| true).is_none());
+    let error = tokens.next().unwrap().unwrap_err();
+    assert_eq!(error.kind, LexErrorKind::UnexpectedCharacter('$'));
+}
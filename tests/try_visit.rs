@@ -0,0 +1,64 @@
+//! Checks that `TryVisitor` stops the walk at the first error.
+
+#![cfg(feature = "std")]
+
+extern crate buildlua;
+
+mod common;
+
+use buildlua::ast::{Expression, Statement};
+use buildlua::span::Spanned;
+use buildlua::visit::{try_walk_chunk, try_walk_expression, try_walk_statement, TryVisitor};
+use common::chunk;
+
+/// Fails on the first `goto`, remembering every statement and expression it saw before that.
+#[derive(Default)]
+struct FirstGoto {
+    visited: Vec<String>,
+}
+
+impl TryVisitor for FirstGoto {
+    type Error = String;
+
+    fn try_visit_statement(&mut self, statement: &Spanned<Statement>) -> Result<(), String> {
+        if let Statement::Goto(ref label) = statement.node {
+            return Err(label.0.clone());
+        }
+        self.visited.push(statement.node.to_string());
+        try_walk_statement(self, statement)
+    }
+
+    fn try_visit_expression(&mut self, expression: &Spanned<Expression>) -> Result<(), String> {
+        self.visited.push(expression.node.to_string());
+        try_walk_expression(self, expression)
+    }
+}
+
+#[test]
+fn stops_at_the_first_error() {
+    let src = "
+        local a = 1
+        while a do
+            goto continue
+            a = 2
+        end
+        goto done
+        return 3
+    ";
+    let mut visitor = FirstGoto::default();
+    assert_eq!(try_walk_chunk(&mut visitor, &chunk(src)), Err("continue".to_string()));
+    let visited = visitor.visited.iter().map(|node| node.trim()).collect::<Vec<_>>();
+    assert_eq!(visited.len(), 4);
+    assert_eq!(visited[0], "local a = 1");
+    assert_eq!(visited[1], "1");
+    assert!(visited[2].starts_with("while a do"));
+    assert_eq!(visited[3], "a");
+}
+
+#[test]
+fn walks_everything_without_errors() {
+    let mut visitor = FirstGoto::default();
+    assert_eq!(try_walk_chunk(&mut visitor, &chunk("f(1, {2}) return 3")), Ok(()));
+    let visited = visitor.visited.iter().map(|node| node.trim()).collect::<Vec<_>>();
+    assert_eq!(&visited[1..], ["1", "{2}", "2", "3"]);
+}
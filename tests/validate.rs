@@ -0,0 +1,216 @@
+//! Checks the rules the validation passes enforce on parsed chunks.
+
+#![cfg(feature = "std")]
+
+extern crate buildlua;
+
+use buildlua::ast::*;
+use buildlua::parser::parse;
+use buildlua::span::{Span, Spanned};
+use buildlua::validate::{
+    validate, validate_breaks, validate_gotos, validate_varargs, BreakOutsideLoop, GotoError,
+    ValidationError, VarargError,
+};
+
+/// The start of each `...` that `validate_varargs` rejects in `src`.
+fn vararg_errors(src: &str) -> Vec<usize> {
+    let chunk = parse(src).unwrap_or_else(|error| panic!("failed to parse the source: {}", error));
+    match validate_varargs(&chunk) {
+        Ok(()) => Vec::new(),
+        Err(errors) => errors.iter().map(|&VarargError { span }| span.start).collect(),
+    }
+}
+
+#[test]
+fn varargs_in_vararg_functions() {
+    assert_eq!(vararg_errors("local a, b = ..."), Vec::<usize>::new());
+    assert_eq!(vararg_errors("function f(...) return ... end"), Vec::<usize>::new());
+    assert_eq!(vararg_errors("function f(a, ...) return {...} end"), Vec::<usize>::new());
+}
+
+#[test]
+fn varargs_in_fixed_argument_functions() {
+    assert_eq!(vararg_errors("function f(a) return ... end"), vec![21]);
+    assert_eq!(vararg_errors("local f = function() print(...) end"), vec![27]);
+}
+
+#[test]
+fn nested_functions_dont_see_outer_varargs() {
+    let src = "function f(...) return function() return ... end end";
+    assert_eq!(vararg_errors(src), vec![41]);
+    // An inner vararg function inside a fixed one is fine.
+    let src = "function f() return function(...) return ... end end";
+    assert_eq!(vararg_errors(src), Vec::<usize>::new());
+}
+
+/// The start of each `break` that `validate_breaks` rejects in `src`.
+fn break_errors(src: &str) -> Vec<usize> {
+    let chunk = parse(src).unwrap_or_else(|error| panic!("failed to parse the source: {}", error));
+    match validate_breaks(&chunk) {
+        Ok(()) => Vec::new(),
+        Err(errors) => errors.iter().map(|&BreakOutsideLoop { span }| span.start).collect(),
+    }
+}
+
+#[test]
+fn breaks_in_loops() {
+    let src = "while x do break end repeat if x then break end until y for i = 1, 2 do break end";
+    assert_eq!(break_errors(src), Vec::<usize>::new());
+    assert_eq!(break_errors("for k in pairs(t) do do break end end"), Vec::<usize>::new());
+}
+
+#[test]
+fn breaks_outside_loops() {
+    assert_eq!(break_errors("break"), vec![0]);
+    assert_eq!(break_errors("if x then break end"), vec![10]);
+}
+
+#[test]
+fn functions_in_loops_dont_see_the_loop() {
+    let src = "while x do local f = function() break end end";
+    assert_eq!(break_errors(src), vec![32]);
+    let src = "function f() for i = 1, 2 do local g = function() while y do break end end end end";
+    assert_eq!(break_errors(src), Vec::<usize>::new());
+}
+
+fn goto_errors(src: &str) -> Vec<GotoError> {
+    let chunk = parse(src).unwrap_or_else(|error| panic!("failed to parse the source: {}", error));
+    validate_gotos(&chunk).err().unwrap_or_default()
+}
+
+#[test]
+fn forward_and_backward_gotos() {
+    assert_eq!(goto_errors("goto done print(1) ::done::"), Vec::new());
+    assert_eq!(goto_errors("::top:: x = x + 1 if x < 3 then goto top end"), Vec::new());
+    // A goto out of nested blocks sees the labels of the blocks around it.
+    let src = "for i = 1, 3 do while true do goto next end ::next:: end";
+    assert_eq!(goto_errors(src), Vec::new());
+    // A label at the end of a block is outside the scope of the block's locals.
+    assert_eq!(goto_errors("do goto e local x = 1 ::e:: end"), Vec::new());
+}
+
+#[test]
+fn undefined_labels() {
+    assert_eq!(goto_errors("goto nowhere"), vec![GotoError::UndefinedLabel {
+        name: "nowhere".to_string(),
+        span: Span::new(0, 12),
+    }]);
+    // Labels in nested blocks and functions aren't visible outside them.
+    assert_eq!(goto_errors("goto a do ::a:: end").len(), 1);
+    assert_eq!(goto_errors("local f = function() goto b end ::b::").len(), 1);
+}
+
+#[test]
+fn gotos_into_the_scope_of_a_local() {
+    let errors = goto_errors("goto skip local x = 1 ::skip:: print(x)");
+    match errors[..] {
+        [GotoError::JumpIntoLocalScope { ref name, .. }] => assert_eq!(name, "skip"),
+        ref other => panic!("{:?}", other),
+    }
+}
+
+#[test]
+fn duplicate_label_in_one_block() {
+    let src = "::a:: print(1) ::a::";
+    assert_eq!(goto_errors(src), vec![GotoError::DuplicateLabel {
+        name: "a".to_string(),
+        first_span: Span::new(0, 5),
+        second_span: Span::new(15, 20),
+    }]);
+    let src = "do ::a:: ::b:: ::a:: end";
+    assert_eq!(goto_errors(src).len(), 1);
+}
+
+#[test]
+fn same_label_in_sibling_blocks() {
+    let src = "do ::a:: end do ::a:: end while x do ::a:: end";
+    assert_eq!(goto_errors(src), Vec::new());
+}
+
+#[test]
+fn nested_labels_hide_outer_ones() {
+    let src = "::a:: do ::a:: goto a end";
+    assert_eq!(goto_errors(src), Vec::new());
+    // A nested function has labels of its own.
+    let src = "::a:: local f = function() ::a:: end";
+    assert_eq!(goto_errors(src), Vec::new());
+}
+
+/// A chunk holding only `statement`.
+fn chunk_of(statement: Statement) -> Chunk {
+    Chunk(Block(Some(vec![Spanned::new(statement, Span::new(0, 10))]), None), Vec::new())
+}
+
+/// A chunk returning only `values`.
+fn returning(values: ExpressionList) -> Chunk {
+    Chunk(Block(None, Some(Box::new(ReturnStatement(Some(Box::new(values)))))), Vec::new())
+}
+
+fn name(name: &str, start: usize) -> Spanned<String> {
+    Spanned::new(name.to_string(), Span::new(start, start + name.len()))
+}
+
+fn number(value: i64, start: usize) -> Spanned<Expression> {
+    Spanned::new(Expression::Integer(value, NumberForm::Plain), Span::new(start, start + 1))
+}
+
+#[test]
+fn parsed_chunks_are_well_formed() {
+    let src = "
+        local a <const>, b = 1, [==[x]]y]==]
+        function t.a.b:c(d, ...) return d.e, self:f'g' end
+        for i = 1, 2 do while a do repeat break until b end end
+        for k, v in pairs({x = 1, [2] = 'y'; 3}) do goto continue ::continue:: end
+        local function f() end
+    ";
+    let chunk = parse(src).unwrap_or_else(|error| panic!("failed to parse the source: {}", error));
+    assert_eq!(validate(&chunk), Ok(()));
+}
+
+#[test]
+fn empty_names() {
+    let binding = AttributeNameList(AttributeName { name: name("", 6), attribute: None }, None);
+    let values = ExpressionList(Box::new(number(1, 9)), None);
+    let chunk = chunk_of(Statement::LocalVariableBinding(binding, Some(values)));
+    assert_eq!(validate(&chunk), Err(vec![ValidationError::InvalidName {
+        name: String::new(),
+        span: Span::new(6, 6),
+    }]));
+}
+
+#[test]
+fn names_that_arent_identifiers() {
+    let assigned = VariableList {
+        first: Box::new(Variable::Name(name("end", 0))),
+        rest: Some(vec![Variable::Name(name("1st", 5)), Variable::Name(name("x y", 10))]),
+    };
+    let values = ExpressionList(Box::new(number(1, 16)), None);
+    let chunk = chunk_of(Statement::Assignment(Box::new(assigned), Box::new(values)));
+    let errors = validate(&chunk).unwrap_err();
+    let names: Vec<_> = errors
+        .iter()
+        .map(|error| match *error {
+            ValidationError::InvalidName { ref name, .. } => name.as_str(),
+            ref error => panic!("unexpected error {:?}", error),
+        })
+        .collect();
+    assert_eq!(names, ["end", "1st", "x y"]);
+}
+
+#[test]
+fn empty_expression_lists() {
+    let values = ExpressionList(Box::new(number(1, 7)), Some(Vec::new()));
+    let chunk = returning(values);
+    assert_eq!(validate(&chunk), Err(vec![ValidationError::EmptyList { span: Span::new(7, 8) }]));
+}
+
+#[test]
+fn long_strings_containing_their_closing_bracket() {
+    let string = Expression::String(b"a]]b".to_vec(), StringForm::Long(0));
+    let values = ExpressionList(Box::new(Spanned::new(string, Span::new(7, 15))), None);
+    let chunk = returning(values);
+    assert_eq!(validate(&chunk), Err(vec![ValidationError::UnclosedLongString {
+        level: 0,
+        span: Span::new(7, 15),
+    }]));
+}
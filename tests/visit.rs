@@ -0,0 +1,61 @@
+//! Checks that visitors reach every node they're meant to and can rewrite them in place.
+
+#![cfg(feature = "std")]
+
+extern crate buildlua;
+
+use buildlua::ast::*;
+use buildlua::parser::parse;
+use buildlua::span::Spanned;
+use buildlua::visit::{self, Visitor, VisitorMut};
+
+/// Counts the calls in a chunk, whether they're statements or expressions.
+#[derive(Default)]
+struct CallCounter {
+    calls: usize,
+}
+
+impl Visitor for CallCounter {
+    fn visit_function_call(&mut self, call: &FunctionCall) {
+        self.calls += 1;
+        visit::walk_function_call(self, call);
+    }
+}
+
+fn calls(src: &str) -> usize {
+    let mut counter = CallCounter::default();
+    visit::walk_chunk(&mut counter, &parse(src).unwrap());
+    counter.calls
+}
+
+#[test]
+fn counts_function_calls() {
+    assert_eq!(calls("x = 1"), 0);
+    assert_eq!(calls("print(1)"), 1);
+    // Calls in arguments, in chains, in methods and inside functions are all reached.
+    assert_eq!(calls("print(f(g()))"), 3);
+    assert_eq!(calls("a.b:c()()"), 2);
+    assert_eq!(calls("local function f() return g() end\nif h() then t = { k() } end"), 3);
+    assert_eq!(calls("for i = f(), 2 do repeat x() until y() end"), 3);
+}
+
+/// Doubles every number literal.
+struct Doubler;
+
+impl VisitorMut for Doubler {
+    fn visit_expression_mut(&mut self, expression: &mut Spanned<Expression>) {
+        visit::walk_expression_mut(self, expression);
+        match expression.node {
+            Expression::Integer(n, _) => expression.node = Expression::from(n * 2),
+            Expression::Float(n, _) => expression.node = Expression::from(n * 2.0),
+            _ => {}
+        }
+    }
+}
+
+#[test]
+fn rewrites_numbers_in_place() {
+    let mut chunk = parse("x = 1 + f(2.5, { 3, k = -4 })\nfor i = 1, 10 do end").unwrap();
+    visit::walk_chunk_mut(&mut Doubler, &mut chunk);
+    assert_eq!(chunk, parse("x = 2 + f(5.0, { 6, k = -8 })\nfor i = 2, 20 do end").unwrap());
+}